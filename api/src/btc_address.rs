@@ -4,8 +4,10 @@ use bitcoin::Network;
 use coin_bitcoin::address::BtcAddress;
 use coin_bitcoin::btcapi::{BtcAddressReq, BtcAddressRes, BtcXpubReq, BtcXpubRes};
 use prost::Message;
+use transport::default_transport;
 
 pub fn get_btc_xpub(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: BtcXpubReq = BtcXpubReq::decode(data).expect("imkey_illegal_param");
 
     let network = match input.network.as_ref() {
@@ -14,13 +16,14 @@ pub fn get_btc_xpub(data: &[u8]) -> Result<Vec<u8>> {
         _ => Network::Testnet,
     };
 
-    let xpub = BtcAddress::get_xpub(network, input.path.as_ref())?;
+    let xpub = BtcAddress::get_xpub(transport.as_ref(), network, input.path.as_ref())?;
 
     let address_message = BtcXpubRes { xpub };
     encode_message(address_message)
 }
 
 pub fn get_btc_address(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: BtcAddressReq = BtcAddressReq::decode(data).expect("imkey_illegal_param");
 
     let network = match input.network.as_ref() {
@@ -29,13 +32,14 @@ pub fn get_btc_address(data: &[u8]) -> Result<Vec<u8>> {
         _ => Network::Testnet,
     };
 
-    let address = BtcAddress::get_address(network, input.path.as_ref())?;
+    let address = BtcAddress::get_address(transport.as_ref(), network, input.path.as_ref())?;
 
     let address_message = BtcAddressRes { address };
     encode_message(address_message)
 }
 
 pub fn get_segwit_address(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: BtcAddressReq = BtcAddressReq::decode(data).expect("imkey_illegal_param");
 
     let network = match input.network.as_ref() {
@@ -44,13 +48,14 @@ pub fn get_segwit_address(data: &[u8]) -> Result<Vec<u8>> {
         _ => Network::Testnet,
     };
 
-    let address = BtcAddress::get_segwit_address(network, input.path.as_ref())?;
+    let address = BtcAddress::get_segwit_address(transport.as_ref(), network, input.path.as_ref())?;
 
     let address_message = BtcAddressRes { address };
     encode_message(address_message)
 }
 
 pub fn display_btc_address(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: BtcAddressReq = BtcAddressReq::decode(data).expect("imkey_illegal_param");
 
     let network = match input.network.as_ref() {
@@ -59,13 +64,14 @@ pub fn display_btc_address(data: &[u8]) -> Result<Vec<u8>> {
         _ => Network::Testnet,
     };
 
-    let address = BtcAddress::display_address(network, input.path.as_ref())?;
+    let address = BtcAddress::display_address(transport.as_ref(), network, input.path.as_ref())?;
 
     let address_message = BtcAddressRes { address };
     encode_message(address_message)
 }
 
 pub fn display_segwit_address(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: BtcAddressReq = BtcAddressReq::decode(data).expect("imkey_illegal_param");
     let network = match input.network.as_ref() {
         "MAINNET" => Network::Bitcoin,
@@ -73,7 +79,94 @@ pub fn display_segwit_address(data: &[u8]) -> Result<Vec<u8>> {
         _ => Network::Testnet,
     };
 
-    let address = BtcAddress::display_segwit_address(network, input.path.as_ref())?;
+    let address =
+        BtcAddress::display_segwit_address(transport.as_ref(), network, input.path.as_ref())?;
+
+    let address_message = BtcAddressRes { address };
+    encode_message(address_message)
+}
+
+pub fn get_native_segwit_xpub(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: BtcXpubReq = BtcXpubReq::decode(data).expect("imkey_illegal_param");
+
+    let network = match input.network.as_ref() {
+        "MAINNET" => Network::Bitcoin,
+        "TESTNET" => Network::Testnet,
+        _ => Network::Testnet,
+    };
+
+    let xpub =
+        BtcAddress::get_native_segwit_xpub(transport.as_ref(), network, input.path.as_ref())?;
+
+    let address_message = BtcXpubRes { xpub };
+    encode_message(address_message)
+}
+
+pub fn get_native_segwit_address(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: BtcAddressReq = BtcAddressReq::decode(data).expect("imkey_illegal_param");
+
+    let network = match input.network.as_ref() {
+        "MAINNET" => Network::Bitcoin,
+        "TESTNET" => Network::Testnet,
+        _ => Network::Testnet,
+    };
+
+    let address =
+        BtcAddress::get_native_segwit_address(transport.as_ref(), network, input.path.as_ref())?;
+
+    let address_message = BtcAddressRes { address };
+    encode_message(address_message)
+}
+
+pub fn display_native_segwit_address(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: BtcAddressReq = BtcAddressReq::decode(data).expect("imkey_illegal_param");
+    let network = match input.network.as_ref() {
+        "MAINNET" => Network::Bitcoin,
+        "TESTNET" => Network::Testnet,
+        _ => Network::Testnet,
+    };
+
+    let address = BtcAddress::display_native_segwit_address(
+        transport.as_ref(),
+        network,
+        input.path.as_ref(),
+    )?;
+
+    let address_message = BtcAddressRes { address };
+    encode_message(address_message)
+}
+
+pub fn get_taproot_address(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: BtcAddressReq = BtcAddressReq::decode(data).expect("imkey_illegal_param");
+
+    let network = match input.network.as_ref() {
+        "MAINNET" => Network::Bitcoin,
+        "TESTNET" => Network::Testnet,
+        _ => Network::Testnet,
+    };
+
+    let address =
+        BtcAddress::get_taproot_address(transport.as_ref(), network, input.path.as_ref())?;
+
+    let address_message = BtcAddressRes { address };
+    encode_message(address_message)
+}
+
+pub fn display_taproot_address(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: BtcAddressReq = BtcAddressReq::decode(data).expect("imkey_illegal_param");
+    let network = match input.network.as_ref() {
+        "MAINNET" => Network::Bitcoin,
+        "TESTNET" => Network::Testnet,
+        _ => Network::Testnet,
+    };
+
+    let address =
+        BtcAddress::display_taproot_address(transport.as_ref(), network, input.path.as_ref())?;
 
     let address_message = BtcAddressRes { address };
     encode_message(address_message)
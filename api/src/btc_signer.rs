@@ -1,12 +1,18 @@
 use crate::error_handling::Result;
 use crate::message_handler::encode_message;
 use bitcoin::{Address, Network};
-use coin_bitcoin::btcapi::{BtcSegwitTxReq, BtcSegwitTxRes, BtcTxReq, BtcTxRes};
-use coin_bitcoin::transaction::{BtcTransaction, Utxo};
+use coin_bitcoin::btcapi::{
+    BtcOutput, BtcPsbtReq, BtcPsbtRes, BtcSegwitTxReq, BtcSegwitTxRes, BtcTxReq, BtcTxRes,
+};
+use coin_bitcoin::psbt::BtcPsbt;
+use coin_bitcoin::taproot::parse_address;
+use coin_bitcoin::transaction::{BtcMultiTransaction, ChangeOutput, DustPolicy, Utxo};
 use prost::Message;
 use std::str::FromStr;
+use transport::default_transport;
 
 pub fn sign_btc_transaction(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: BtcTxReq = BtcTxReq::decode(data).expect("BtcTxInput");
 
     let mut unspents = Vec::new();
@@ -23,13 +29,16 @@ pub fn sign_btc_transaction(data: &[u8]) -> Result<Vec<u8>> {
         unspents.push(new_utxo);
     }
 
-    let btc_tx = BtcTransaction {
-        to: Address::from_str(&input.to).unwrap(),
-        //        change_idx: input.change_address_index as i32,
-        amount: input.amount,
+    let btc_tx = BtcMultiTransaction {
+        outputs: build_outputs(&input.to, input.amount, &input.outputs)?,
         unspents: unspents,
         fee: input.fee,
-        //        extra_data: input.extra_data,
+        change: build_change(
+            &input.change_address,
+            &input.change_path,
+            input.change_address_index,
+        )?,
+        dust_policy: build_dust_policy(input.dust_threshold),
     };
 
     let network = if input.network == "TESTNET".to_string() {
@@ -38,9 +47,9 @@ pub fn sign_btc_transaction(data: &[u8]) -> Result<Vec<u8>> {
         Network::Bitcoin
     };
     let signed = btc_tx.sign_transaction(
+        transport.as_ref(),
         network,
         &input.path_prefix,
-        input.change_address_index as i32,
         &input.extra_data,
     )?;
     let tx_sign_result = BtcTxRes {
@@ -51,6 +60,7 @@ pub fn sign_btc_transaction(data: &[u8]) -> Result<Vec<u8>> {
 }
 
 pub fn sign_segwit_transaction(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: BtcSegwitTxReq = BtcSegwitTxReq::decode(data).expect("BtcTxInput");
 
     let mut unspents = Vec::new();
@@ -67,12 +77,16 @@ pub fn sign_segwit_transaction(data: &[u8]) -> Result<Vec<u8>> {
         unspents.push(new_utxo);
     }
 
-    let btc_tx = BtcTransaction {
-        to: Address::from_str(&input.to).unwrap(),
-        //        change_idx: input.change_address_index as i32,
-        amount: input.amount,
+    let btc_tx = BtcMultiTransaction {
+        outputs: build_outputs(&input.to, input.amount, &input.outputs)?,
         unspents: unspents,
         fee: input.fee,
+        change: build_change(
+            &input.change_address,
+            &input.change_path,
+            input.change_address_index,
+        )?,
+        dust_policy: build_dust_policy(input.dust_threshold),
     };
 
     let network = if input.network == "TESTNET".to_string() {
@@ -81,9 +95,9 @@ pub fn sign_segwit_transaction(data: &[u8]) -> Result<Vec<u8>> {
         Network::Bitcoin
     };
     let signed = btc_tx.sign_segwit_transaction(
+        transport.as_ref(),
         network,
         &input.path_prefix,
-        input.change_address_index as i32,
         &input.extra_data,
     )?;
     let tx_sign_result = BtcSegwitTxRes {
@@ -93,3 +107,166 @@ pub fn sign_segwit_transaction(data: &[u8]) -> Result<Vec<u8>> {
     };
     encode_message(tx_sign_result)
 }
+
+pub fn sign_native_segwit_transaction(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: BtcSegwitTxReq = BtcSegwitTxReq::decode(data).expect("BtcTxInput");
+
+    let mut unspents = Vec::new();
+    for utxo in input.unspents {
+        let new_utxo = Utxo {
+            txhash: utxo.tx_hash,
+            vout: utxo.vout,
+            amount: utxo.amount,
+            address: parse_address(&utxo.address)?,
+            script_pubkey: utxo.script_pub_key,
+            derive_path: utxo.derived_path,
+            sequence: utxo.sequence,
+        };
+        unspents.push(new_utxo);
+    }
+
+    let btc_tx = BtcMultiTransaction {
+        outputs: build_outputs(&input.to, input.amount, &input.outputs)?,
+        unspents: unspents,
+        fee: input.fee,
+        change: build_change(
+            &input.change_address,
+            &input.change_path,
+            input.change_address_index,
+        )?,
+        dust_policy: build_dust_policy(input.dust_threshold),
+    };
+
+    let network = if input.network == "TESTNET".to_string() {
+        Network::Testnet
+    } else {
+        Network::Bitcoin
+    };
+    let signed = btc_tx.sign_native_segwit_transaction(
+        transport.as_ref(),
+        network,
+        &input.path_prefix,
+        &input.extra_data,
+    )?;
+    let tx_sign_result = BtcSegwitTxRes {
+        witness_tx_data: signed.signature,
+        wtx_hash: signed.wtx_id,
+        tx_hash: signed.tx_hash,
+    };
+    encode_message(tx_sign_result)
+}
+
+pub fn sign_taproot_transaction(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: BtcSegwitTxReq = BtcSegwitTxReq::decode(data).expect("BtcTxInput");
+
+    let mut unspents = Vec::new();
+    for utxo in input.unspents {
+        let new_utxo = Utxo {
+            txhash: utxo.tx_hash,
+            vout: utxo.vout,
+            amount: utxo.amount,
+            address: parse_address(&utxo.address)?,
+            script_pubkey: utxo.script_pub_key,
+            derive_path: utxo.derived_path,
+            sequence: utxo.sequence,
+        };
+        unspents.push(new_utxo);
+    }
+
+    let btc_tx = BtcMultiTransaction {
+        outputs: build_outputs(&input.to, input.amount, &input.outputs)?,
+        unspents: unspents,
+        fee: input.fee,
+        change: build_change(
+            &input.change_address,
+            &input.change_path,
+            input.change_address_index,
+        )?,
+        dust_policy: build_dust_policy(input.dust_threshold),
+    };
+
+    let network = if input.network == "TESTNET".to_string() {
+        Network::Testnet
+    } else {
+        Network::Bitcoin
+    };
+    let signed = btc_tx.sign_taproot_transaction(
+        transport.as_ref(),
+        network,
+        &input.path_prefix,
+        &input.extra_data,
+    )?;
+    let tx_sign_result = BtcSegwitTxRes {
+        witness_tx_data: signed.signature,
+        wtx_hash: signed.wtx_id,
+        tx_hash: signed.tx_hash,
+    };
+    encode_message(tx_sign_result)
+}
+
+pub fn sign_psbt(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: BtcPsbtReq = BtcPsbtReq::decode(data).expect("BtcPsbtInput");
+
+    let btc_psbt = BtcPsbt::from_hex(&input.psbt)?;
+    let network = if input.network == "TESTNET".to_string() {
+        Network::Testnet
+    } else {
+        Network::Bitcoin
+    };
+    let signed = BtcPsbt {
+        psbt: btc_psbt.sign_psbt(transport.as_ref(), network, &input.path_prefix)?,
+    };
+    let psbt_sign_result = BtcPsbtRes {
+        psbt: signed.to_hex(),
+    };
+    encode_message(psbt_sign_result)
+}
+
+/**
+ * outputs takes precedence over the single to and amount pair
+ */
+fn build_outputs(
+    to: &str,
+    amount: i64,
+    outputs: &[BtcOutput],
+) -> Result<Vec<coin_bitcoin::transaction::BtcOutput>> {
+    if outputs.is_empty() {
+        return Ok(vec![coin_bitcoin::transaction::BtcOutput {
+            address: parse_address(to)?,
+            amount,
+        }]);
+    }
+    let mut btc_outputs = vec![];
+    for output in outputs {
+        btc_outputs.push(coin_bitcoin::transaction::BtcOutput {
+            address: parse_address(&output.address)?,
+            amount: output.amount,
+        });
+    }
+    Ok(btc_outputs)
+}
+
+fn build_change(
+    change_address: &str,
+    change_path: &str,
+    change_address_index: u32,
+) -> Result<ChangeOutput> {
+    if !change_address.is_empty() {
+        Ok(ChangeOutput::Address(parse_address(change_address)?))
+    } else if !change_path.is_empty() {
+        Ok(ChangeOutput::Path(change_path.to_string()))
+    } else {
+        Ok(ChangeOutput::index(change_address_index as i32))
+    }
+}
+
+fn build_dust_policy(dust_threshold: i64) -> DustPolicy {
+    let mut dust_policy = DustPolicy::default();
+    if dust_threshold > 0 {
+        dust_policy.change_threshold = dust_threshold;
+    }
+    dust_policy
+}
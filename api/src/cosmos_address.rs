@@ -2,11 +2,15 @@ use crate::error_handling::Result;
 use crate::message_handler::encode_message;
 use coin_cosmos::address::CosmosAddress;
 use coin_cosmos::cosmosapi::{CosmosAddressReq, CosmosAddressRes};
+use common::constants;
 use prost::Message;
+use transport::default_transport;
 
 pub fn display_cosmos_address(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: CosmosAddressReq = CosmosAddressReq::decode(data).expect("imkey_illegal_param");
-    let cosmos_address = CosmosAddress::display_address(&input.path)?;
+    let cosmos_address =
+        CosmosAddress::display_address(transport.as_ref(), hrp(&input), &input.path)?;
     let address_message = CosmosAddressRes {
         address: cosmos_address,
     };
@@ -14,10 +18,20 @@ pub fn display_cosmos_address(data: &[u8]) -> Result<Vec<u8>> {
 }
 
 pub fn get_cosmos_address(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: CosmosAddressReq = CosmosAddressReq::decode(data).expect("imkey_illegal_param");
-    let cosmos_address = CosmosAddress::get_address(&input.path)?;
+    let cosmos_address = CosmosAddress::get_address(transport.as_ref(), hrp(&input), &input.path)?;
     let address_message = CosmosAddressRes {
         address: cosmos_address,
     };
     encode_message(address_message)
 }
+
+//the cosmos hub prefix unless the request names another chain's
+fn hrp(input: &CosmosAddressReq) -> &str {
+    if input.hrp.is_empty() {
+        constants::COSMOS_HRP
+    } else {
+        &input.hrp
+    }
+}
@@ -2,17 +2,21 @@ use crate::error_handling::Result;
 use crate::message_handler::encode_message;
 use coin_cosmos::cosmosapi::CosmosTxReq;
 use coin_cosmos::transaction::{Coin, CosmosTransaction, SignData, StdFee};
+use common::error::CoinError;
 use prost::Message;
+use transport::default_transport;
 
 pub fn sign_cosmos_transaction(data: &[u8]) -> Result<Vec<u8>> {
-    // let input: CosmosTxReq = CosmosTxReq::decode(data).expect("imkey_illegal_param");
-    let input: CosmosTxReq = CosmosTxReq::decode(data).unwrap();
+    let transport = default_transport()?;
+    let input: CosmosTxReq = CosmosTxReq::decode(data)?;
 
     // fee
     let mut coins = Vec::new();
 
-    let input_sign_data = input.sign_data.unwrap();
-    let input_fee = &input_sign_data.fee.unwrap();
+    let input_sign_data = input.sign_data.ok_or(CoinError::ImkeySdkIllegalArgument)?;
+    let input_fee = input_sign_data
+        .fee
+        .ok_or(CoinError::ImkeySdkIllegalArgument)?;
     for itme in &input_fee.amount {
         let coin = Coin {
             amount: itme.amount.clone(),
@@ -25,9 +29,7 @@ pub fn sign_cosmos_transaction(data: &[u8]) -> Result<Vec<u8>> {
         gas: input_fee.gas.clone(),
     };
 
-    let msg_witout_slash = input_sign_data.msgs.replace("\\", "");
-
-    let r = serde_json::from_str(&msg_witout_slash).unwrap();
+    let r = serde_json::from_str(&input_sign_data.msgs)?;
 
     //SignData
     let sign_data = SignData {
@@ -45,8 +47,13 @@ pub fn sign_cosmos_transaction(data: &[u8]) -> Result<Vec<u8>> {
         payment_dis: input.payment_dis,
         to_dis: input.to_dis,
         fee_dis: input.fee_dis,
+        from_dis: input.from_dis,
+    };
+    let cosmos_tx_output = if input.sign_mode == "direct" {
+        cosmos_input.sign_direct(transport.as_ref())?
+    } else {
+        cosmos_input.sign(transport.as_ref())?
     };
-    let cosmos_tx_output = cosmos_input.sign()?;
 
     encode_message(cosmos_tx_output)
 }
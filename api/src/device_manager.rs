@@ -3,50 +3,69 @@ use crate::error_handling::Result;
 use crate::message_handler::encode_message;
 use common::applet;
 use common::constants;
+use common::https::{self, TsmConfig};
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use device::device_discovery;
 use device::device_manager;
 use device::deviceapi::{
     AppDeleteReq, AppDownloadReq, AppUpdateReq, AvailableAppBean, BindAcquireReq, BindAcquireRes,
-    BindCheckReq, BindCheckRes, CheckUpdateRes, CosCheckUpdateRes, DeviceConnectReq,
+    BindCheckReq, BindCheckRes, CheckUpdateRes, CosCheckUpdateRes, DeviceConnectReq, DeviceEvent,
     GetBatteryPowerRes, GetBleNameRes, GetBleVersionRes, GetFirmwareVersionRes, GetLifeTimeRes,
-    GetRamSizeRes, GetSdkInfoRes, GetSeidRes, GetSnRes, IsBlStatusRes, SetBleNameReq,
+    GetRamSizeRes, GetSdkInfoRes, GetSeidRes, GetSnRes, ImkeyDeviceInfo, IsBlStatusRes,
+    ListDevicesRes, PollDeviceEventsRes, SetBleNameReq, SetTsmConfigReq,
 };
 use prost::Message;
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-use transport::hid_api::hid_connect;
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use transport::hid_watcher::{self, DeviceWatcher};
+use transport::{default_transport, secure_transport};
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+lazy_static! {
+    //the keys the caller has been told about by poll_device_events
+    static ref DEVICE_WATCHER: Mutex<DeviceWatcher> = Mutex::new(device_discovery::device_watcher());
+}
 
 pub fn app_download(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let request: AppDownloadReq = AppDownloadReq::decode(data).expect("imkey_illegal_param");
-    device_manager::app_download(request.app_name.as_ref())?;
+    device_manager::app_download(transport.as_ref(), request.app_name.as_ref())?;
     encode_message(CommonResponse {
         result: "success".to_string(),
     })
 }
 
 pub fn app_update(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let request: AppUpdateReq = AppUpdateReq::decode(data).expect("imkey_illegal_prarm");
-    device_manager::app_update(request.app_name.as_ref())?;
+    device_manager::app_update(transport.as_ref(), request.app_name.as_ref())?;
     encode_message(CommonResponse {
         result: "success".to_string(),
     })
 }
 
 pub fn app_delete(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let request: AppDeleteReq = AppDeleteReq::decode(data).expect("imkey_illegal_param");
-    device_manager::app_delete(request.app_name.as_ref())?;
+    device_manager::app_delete(transport.as_ref(), request.app_name.as_ref())?;
     encode_message(CommonResponse {
         result: "success".to_string(),
     })
 }
 
 pub fn se_activate() -> Result<Vec<u8>> {
-    device_manager::active_device()?;
+    let transport = default_transport()?;
+    device_manager::active_device(transport.as_ref())?;
     encode_message(CommonResponse {
         result: "success".to_string(),
     })
 }
 
 pub fn check_update() -> Result<Vec<u8>> {
-    let response = device_manager::check_update()?;
+    let transport = default_transport()?;
+    let response = device_manager::check_update(transport.as_ref())?;
 
     let mut available_bean_list: Vec<AvailableAppBean> = Vec::new();
     for (index, value) in response
@@ -93,15 +112,17 @@ pub fn check_update() -> Result<Vec<u8>> {
 }
 
 pub fn se_secure_check() -> Result<Vec<u8>> {
-    device_manager::check_device()?;
+    let transport = default_transport()?;
+    device_manager::check_device(transport.as_ref())?;
     encode_message(CommonResponse {
         result: "success".to_string(),
     })
 }
 
 pub fn bind_check(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let bind_check: BindCheckReq = BindCheckReq::decode(data).expect("imkey_illegal_param");
-    let check_result = device_manager::bind_check(&bind_check.file_path)?;
+    let check_result = device_manager::bind_check(transport.as_ref(), &bind_check.file_path)?;
     let response_msg = BindCheckRes {
         bind_status: check_result,
     };
@@ -109,33 +130,56 @@ pub fn bind_check(data: &[u8]) -> Result<Vec<u8>> {
 }
 
 pub fn bind_display_code() -> Result<Vec<u8>> {
-    device_manager::bind_display_code()?;
+    let transport = default_transport()?;
+    device_manager::bind_display_code(transport.as_ref())?;
     encode_message(CommonResponse {
         result: "success".to_string(),
     })
 }
 
 pub fn bind_acquire(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let bind_acquire: BindAcquireReq = BindAcquireReq::decode(data).expect("imkey_illegal_param");
-    let bind_result = device_manager::bind_acquire(&bind_acquire.bind_code)?;
+    let bind_result = device_manager::bind_acquire(transport.as_ref(), &bind_acquire.bind_code)?;
     let response_msg = BindAcquireRes { bind_result };
     encode_message(response_msg)
 }
 
+pub fn secure_channel_open() -> Result<Vec<u8>> {
+    device_manager::open_secure_channel(secure_transport()?.as_ref())?;
+    encode_message(CommonResponse {
+        result: "success".to_string(),
+    })
+}
+
+pub fn secure_channel_close() -> Result<Vec<u8>> {
+    secure_transport()?.close();
+    encode_message(CommonResponse {
+        result: "success".to_string(),
+    })
+}
+
 pub fn get_seid() -> Result<Vec<u8>> {
-    let seid = device_manager::get_se_id().ok().expect("get_seid_error");
+    let transport = default_transport()?;
+    let seid = device_manager::get_se_id(transport.as_ref())
+        .ok()
+        .expect("get_seid_error");
     let response_msg = GetSeidRes { seid };
     encode_message(response_msg)
 }
 
 pub fn get_sn() -> Result<Vec<u8>> {
-    let sn = device_manager::get_sn().ok().expect("get_sn_error");
+    let transport = default_transport()?;
+    let sn = device_manager::get_sn(transport.as_ref())
+        .ok()
+        .expect("get_sn_error");
     let response_msg = GetSnRes { sn };
     encode_message(response_msg)
 }
 
 pub fn get_ram_size() -> Result<Vec<u8>> {
-    let ram_size = device_manager::get_ram_size()
+    let transport = default_transport()?;
+    let ram_size = device_manager::get_ram_size(transport.as_ref())
         .ok()
         .expect("get_ram_size_error");
     let response_msg = GetRamSizeRes { ram_size };
@@ -143,7 +187,8 @@ pub fn get_ram_size() -> Result<Vec<u8>> {
 }
 
 pub fn get_firmware_version() -> Result<Vec<u8>> {
-    let firmware_version = device_manager::get_firmware_version()
+    let transport = default_transport()?;
+    let firmware_version = device_manager::get_firmware_version(transport.as_ref())
         .ok()
         .expect("get_firmware_version_error");
 
@@ -152,7 +197,8 @@ pub fn get_firmware_version() -> Result<Vec<u8>> {
 }
 
 pub fn get_battery_power() -> Result<Vec<u8>> {
-    let battery_power = device_manager::get_battery_power()
+    let transport = default_transport()?;
+    let battery_power = device_manager::get_battery_power(transport.as_ref())
         .ok()
         .expect("get_battery_power_error");
     let response_msg = GetBatteryPowerRes { battery_power };
@@ -160,7 +206,8 @@ pub fn get_battery_power() -> Result<Vec<u8>> {
 }
 
 pub fn get_life_time() -> Result<Vec<u8>> {
-    let life_time = device_manager::get_life_time()
+    let transport = default_transport()?;
+    let life_time = device_manager::get_life_time(transport.as_ref())
         .ok()
         .expect("get_life_time_error");
     let response_msg = GetLifeTimeRes { life_time };
@@ -168,7 +215,8 @@ pub fn get_life_time() -> Result<Vec<u8>> {
 }
 
 pub fn get_ble_name() -> Result<Vec<u8>> {
-    let ble_name = device_manager::get_ble_name()
+    let transport = default_transport()?;
+    let ble_name = device_manager::get_ble_name(transport.as_ref())
         .ok()
         .expect("get_ble_name_error");
 
@@ -177,9 +225,10 @@ pub fn get_ble_name() -> Result<Vec<u8>> {
 }
 
 pub fn set_ble_name(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let request: SetBleNameReq = SetBleNameReq::decode(data).expect("ble_action");
 
-    device_manager::set_ble_name(request.ble_name)
+    device_manager::set_ble_name(transport.as_ref(), request.ble_name)
         .ok()
         .expect("set_ble_name_error");
     encode_message(CommonResponse {
@@ -187,8 +236,44 @@ pub fn set_ble_name(data: &[u8]) -> Result<Vec<u8>> {
     })
 }
 
+pub fn set_tsm_config(data: &[u8]) -> Result<Vec<u8>> {
+    let request: SetTsmConfigReq = SetTsmConfigReq::decode(data).expect("imkey_illegal_param");
+
+    let mut config = TsmConfig {
+        pinned_certs: request.pinned_certs,
+        pinned_spki_sha256: request.pinned_spki_sha256,
+        ..TsmConfig::default()
+    };
+    if !request.base_url.is_empty() {
+        config.base_url = request.base_url;
+    }
+    if !request.proxy.is_empty() {
+        config.proxy = Some(request.proxy);
+    }
+    config.connect_timeout = millis_or(request.connect_timeout_millis, config.connect_timeout);
+    config.read_timeout = millis_or(request.read_timeout_millis, config.read_timeout);
+    config.write_timeout = millis_or(request.write_timeout_millis, config.write_timeout);
+    config.request_timeout = millis_or(request.request_timeout_millis, config.request_timeout);
+    if let Some(retry) = request.retry {
+        config.retries = retry.retries;
+        config.retry_backoff = Duration::from_millis(retry.backoff_millis);
+    }
+    https::set_tsm_config(config)?;
+    encode_message(CommonResponse {
+        result: "success".to_string(),
+    })
+}
+
+fn millis_or(millis: u64, default: Duration) -> Duration {
+    match millis {
+        0 => default,
+        millis => Duration::from_millis(millis),
+    }
+}
+
 pub fn get_ble_version() -> Result<Vec<u8>> {
-    let ble_version = device_manager::get_ble_version()
+    let transport = default_transport()?;
+    let ble_version = device_manager::get_ble_version(transport.as_ref())
         .ok()
         .expect("get_ble_version_error");
     let response_msg = GetBleVersionRes { ble_version };
@@ -204,7 +289,8 @@ pub fn get_sdk_info() -> Result<Vec<u8>> {
 
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
 pub fn cos_update() -> Result<Vec<u8>> {
-    device_manager::cos_upgrade()?;
+    let transport = default_transport()?;
+    device_manager::cos_upgrade(transport.as_ref())?;
     encode_message(CommonResponse {
         result: "success".to_string(),
     })
@@ -215,16 +301,71 @@ pub fn device_connect(data: &[u8]) -> Result<Vec<u8>> {
     let device_connect_req: DeviceConnectReq =
         DeviceConnectReq::decode(data).expect("imkey_illegal_param");
 
-    hid_connect(&device_connect_req.device_model_name)?;
+    let transport = default_transport()?;
+    device_discovery::connect_device(
+        transport.as_ref(),
+        &device_connect_req.sn,
+        &device_connect_req.seid,
+    )?;
 
     encode_message(CommonResponse {
         result: "success".to_string(),
     })
 }
 
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+pub fn list_devices() -> Result<Vec<u8>> {
+    let devices = device_discovery::list_devices()?
+        .into_iter()
+        .map(|device| ImkeyDeviceInfo {
+            path: device.path,
+            serial_number: device.serial_number,
+            sn: device.sn,
+            seid: device.seid,
+            is_bl_status: device.is_bl_status,
+        })
+        .collect();
+    encode_message(ListDevicesRes { devices })
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+pub fn poll_device_events() -> Result<Vec<u8>> {
+    let events = DEVICE_WATCHER
+        .lock()
+        .unwrap()
+        .poll()?
+        .into_iter()
+        .map(|event| match event {
+            hid_watcher::DeviceEvent::Connected(device) => DeviceEvent {
+                event: "connected".to_string(),
+                path: device.path,
+                serial_number: device.serial_number,
+                is_bl_status: false,
+            },
+            hid_watcher::DeviceEvent::Disconnected(device) => DeviceEvent {
+                event: "disconnected".to_string(),
+                path: device.path,
+                serial_number: device.serial_number,
+                is_bl_status: false,
+            },
+            hid_watcher::DeviceEvent::ModeChanged {
+                device,
+                is_bl_status,
+            } => DeviceEvent {
+                event: "mode_changed".to_string(),
+                path: device.path,
+                serial_number: device.serial_number,
+                is_bl_status,
+            },
+        })
+        .collect();
+    encode_message(PollDeviceEventsRes { events })
+}
+
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
 pub fn cos_check_update() -> Result<Vec<u8>> {
-    let cos_check_update = device_manager::cos_check_update()?;
+    let transport = default_transport()?;
+    let cos_check_update = device_manager::cos_check_update(transport.as_ref())?;
 
     encode_message(CosCheckUpdateRes {
         seid: cos_check_update._ReturnData.seid,
@@ -241,6 +382,7 @@ pub fn cos_check_update() -> Result<Vec<u8>> {
 
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
 pub fn is_bl_status() -> Result<Vec<u8>> {
-    let check_result = device_manager::is_bl_status()?;
+    let transport = default_transport()?;
+    let check_result = device_manager::is_bl_status(transport.as_ref())?;
     encode_message(IsBlStatusRes { check_result })
 }
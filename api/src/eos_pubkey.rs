@@ -1,19 +1,25 @@
 use crate::error_handling::Result;
 use crate::message_handler::encode_message;
-use coin_eos::eosapi::{EosPubkeyReq, EosPubkeyRes};
+use coin_eos::chain;
+use coin_eos::eosapi::EosPubkeyReq;
 use coin_eos::pubkey::EosPubkey;
 use prost::Message;
+use transport::default_transport;
 
 pub fn display_eos_pubkey(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: EosPubkeyReq = EosPubkeyReq::decode(data).expect("imkey_illegal_param");
-    let eos_pubkey = EosPubkey::display_pubkey(&input.path)?;
-    let pubkey_message = EosPubkeyRes { pubkey: eos_pubkey };
+    let profile = chain::profile(&input.chain)?;
+    let pubkey_message =
+        EosPubkey::display_pubkeys(transport.as_ref(), profile.path(&input.path), profile)?;
     encode_message(pubkey_message)
 }
 
 pub fn get_eos_pubkey(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: EosPubkeyReq = EosPubkeyReq::decode(data).expect("imkey_illegal_param");
-    let eos_pubkey = EosPubkey::get_pubkey(&input.path)?;
-    let pubkey_message = EosPubkeyRes { pubkey: eos_pubkey };
+    let profile = chain::profile(&input.chain)?;
+    let pubkey_message =
+        EosPubkey::get_pubkeys(transport.as_ref(), profile.path(&input.path), profile)?;
     encode_message(pubkey_message)
 }
@@ -3,18 +3,21 @@ use crate::message_handler::encode_message;
 use coin_eos::eosapi::{EosMessageSignReq, EosMessageSignRes, EosTxReq};
 use coin_eos::transaction::EosTransaction;
 use prost::Message;
+use transport::default_transport;
 
 pub fn sign_eos_transaction(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: EosTxReq = EosTxReq::decode(data).expect("imkey_illegal_param");
 
-    let signed = EosTransaction::sign_tx(input)?;
+    let signed = EosTransaction::sign_tx(transport.as_ref(), input)?;
     encode_message(signed)
 }
 
 pub fn sign_eos_message(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: EosMessageSignReq = EosMessageSignReq::decode(data).expect("imkey_illegal_param");
 
-    let signed = EosTransaction::sign_message(input)?;
+    let signed = EosTransaction::sign_message(transport.as_ref(), input)?;
     let mes_sign_result = EosMessageSignRes {
         signature: signed.signature,
     };
@@ -1,14 +1,17 @@
 use crate::error_handling::Result;
 use crate::message_handler::encode_message;
-use coin_ethereum::ethapi::{EthMessageSignReq, EthTxReq};
+use coin_ethereum::ethapi::{EthMessageSignReq, EthTxReq, EthTypedDataSignReq};
 use coin_ethereum::transaction::Transaction;
-use coin_ethereum::types::Action;
-use ethereum_types::{Address, U256};
+use coin_ethereum::types::{AccessListItem, Action, TransactionType};
+use common::error::CoinError;
+use ethereum_types::{Address, H256, U256};
 use hex;
 use prost::Message;
 use std::str::FromStr;
+use transport::default_transport;
 
 pub fn sign_eth_transaction(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: EthTxReq = EthTxReq::decode(data).expect("imkey_illegal_param");
     let data_vec = if input.data.starts_with("0x") {
         hex::decode(&input.data[2..]).unwrap()
@@ -21,17 +24,44 @@ pub fn sign_eth_transaction(data: &[u8]) -> Result<Vec<u8>> {
         to = to[2..].to_string();
     }
 
+    let tx_type = match input.tx_type.as_str() {
+        "" | "0" => TransactionType::Legacy,
+        "1" => TransactionType::AccessList,
+        "2" => TransactionType::DynamicFee,
+        _ => return Err(CoinError::ImkeySdkIllegalArgument.into()),
+    };
+    let mut access_list = vec![];
+    for item in &input.access_list {
+        let mut storage_keys = vec![];
+        for storage_key in &item.storage_keys {
+            storage_keys.push(
+                H256::from_str(storage_key.trim_start_matches("0x"))
+                    .map_err(|_| CoinError::ImkeySdkIllegalArgument)?,
+            );
+        }
+        access_list.push(AccessListItem {
+            address: Address::from_str(item.address.trim_start_matches("0x"))
+                .map_err(|_| CoinError::ImkeySdkIllegalArgument)?,
+            storage_keys,
+        });
+    }
+
     let eth_tx = Transaction {
         nonce: U256::from_dec_str(&input.nonce).unwrap(),
-        gas_price: U256::from_dec_str(&input.gas_price).unwrap(),
+        gas_price: parse_amount(&input.gas_price)?,
         gas_limit: U256::from_dec_str(&input.gas_limit).unwrap(),
         to: Action::Call(Address::from_str(&to).unwrap()),
         value: U256::from_dec_str(&input.value).unwrap(),
         data: Vec::from(data_vec.as_slice()),
+        tx_type,
+        max_fee_per_gas: parse_amount(&input.max_fee_per_gas)?,
+        max_priority_fee_per_gas: parse_amount(&input.max_priority_fee_per_gas)?,
+        access_list,
     };
 
     let chain_id = input.chain_id.parse::<u64>().unwrap();
     let tx_out = eth_tx.sign(
+        transport.as_ref(),
         Some(chain_id),
         &input.path,
         &input.payment,
@@ -43,13 +73,33 @@ pub fn sign_eth_transaction(data: &[u8]) -> Result<Vec<u8>> {
 }
 
 pub fn sign_eth_message(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: EthMessageSignReq = EthMessageSignReq::decode(data).expect("imkey_illegal_param");
-    let signed = Transaction::sign_persional_message(input).unwrap();
+    let signed = Transaction::sign_persional_message(transport.as_ref(), input).unwrap();
     encode_message(signed)
 }
 
 pub fn ec_sign(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: EthMessageSignReq = EthMessageSignReq::decode(data).expect("imkey_illegal_param");
-    let signed = Transaction::sign_persional_message(input).unwrap();
+    let signed = Transaction::sign_persional_message(transport.as_ref(), input).unwrap();
     encode_message(signed)
 }
+
+pub fn sign_typed_data(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: EthTypedDataSignReq =
+        EthTypedDataSignReq::decode(data).expect("imkey_illegal_param");
+    let signed = Transaction::sign_typed_data(transport.as_ref(), input)?;
+    encode_message(signed)
+}
+
+/**
+ * fields the transaction type does not use are left empty and read as zero
+ */
+fn parse_amount(amount: &str) -> Result<U256> {
+    if amount.is_empty() {
+        return Ok(U256::zero());
+    }
+    Ok(U256::from_dec_str(amount).map_err(|_| CoinError::ImkeySdkIllegalArgument)?)
+}
@@ -85,6 +85,8 @@ pub unsafe extern "C" fn call_imkey_api(hex_str: *const c_char) -> *const c_char
         "bind_check" => landingpad(|| device_manager::bind_check(&action.param.unwrap().value)),
         "bind_display_code" => landingpad(|| device_manager::bind_display_code()),
         "bind_acquire" => landingpad(|| device_manager::bind_acquire(&action.param.unwrap().value)),
+        "secure_channel_open" => landingpad(|| device_manager::secure_channel_open()),
+        "secure_channel_close" => landingpad(|| device_manager::secure_channel_close()),
         "get_seid" => landingpad(|| device_manager::get_seid()),
         "get_sn" => landingpad(|| device_manager::get_sn()),
         "get_ram_size" => landingpad(|| device_manager::get_ram_size()),
@@ -95,6 +97,9 @@ pub unsafe extern "C" fn call_imkey_api(hex_str: *const c_char) -> *const c_char
         "set_ble_name" => landingpad(|| device_manager::set_ble_name(&action.param.unwrap().value)),
         "get_ble_version" => landingpad(|| device_manager::get_ble_version()),
         "get_sdk_info" => landingpad(|| device_manager::get_sdk_info()),
+        "set_tsm_config" => {
+            landingpad(|| device_manager::set_tsm_config(&action.param.unwrap().value))
+        }
         #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
         "cos_update" => landingpad(|| device_manager::cos_update()),
         #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
@@ -104,6 +109,10 @@ pub unsafe extern "C" fn call_imkey_api(hex_str: *const c_char) -> *const c_char
             landingpad(|| device_manager::device_connect(&action.param.unwrap().value))
         }
         #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+        "list_devices" => landingpad(|| device_manager::list_devices()),
+        #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+        "poll_device_events" => landingpad(|| device_manager::poll_device_events()),
+        #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
         "is_bl_status" => landingpad(|| device_manager::is_bl_status()),
 
         // btc
@@ -113,12 +122,28 @@ pub unsafe extern "C" fn call_imkey_api(hex_str: *const c_char) -> *const c_char
         "btc_segwit_tx_sign" => {
             landingpad(|| btc_signer::sign_segwit_transaction(&action.param.unwrap().value))
         }
+        "btc_native_segwit_tx_sign" => {
+            landingpad(|| btc_signer::sign_native_segwit_transaction(&action.param.unwrap().value))
+        }
+        "btc_taproot_tx_sign" => {
+            landingpad(|| btc_signer::sign_taproot_transaction(&action.param.unwrap().value))
+        }
+        "btc_psbt_sign" => landingpad(|| btc_signer::sign_psbt(&action.param.unwrap().value)),
         "btc_usdt_tx_sign" => {
             landingpad(|| usdt_signer::sign_usdt_transaction(&action.param.unwrap().value))
         }
         "btc_usdt_segwit_tx_sign" => {
             landingpad(|| usdt_signer::sign_usdt_segwit_transaction(&action.param.unwrap().value))
         }
+        "btc_omni_tx_sign" => {
+            landingpad(|| usdt_signer::sign_omni_transaction(&action.param.unwrap().value))
+        }
+        "btc_omni_segwit_tx_sign" => {
+            landingpad(|| usdt_signer::sign_omni_segwit_transaction(&action.param.unwrap().value))
+        }
+        "btc_omni_decode" => {
+            landingpad(|| usdt_signer::decode_omni_output(&action.param.unwrap().value))
+        }
         "btc_get_xpub" => landingpad(|| btc_address::get_btc_xpub(&action.param.unwrap().value)),
         "btc_get_address" => {
             landingpad(|| btc_address::get_btc_address(&action.param.unwrap().value))
@@ -132,6 +157,21 @@ pub unsafe extern "C" fn call_imkey_api(hex_str: *const c_char) -> *const c_char
         "btc_register_segwit_address" => {
             landingpad(|| btc_address::display_segwit_address(&action.param.unwrap().value))
         }
+        "btc_get_native_segwit_xpub" => {
+            landingpad(|| btc_address::get_native_segwit_xpub(&action.param.unwrap().value))
+        }
+        "btc_get_native_segwit_address" => {
+            landingpad(|| btc_address::get_native_segwit_address(&action.param.unwrap().value))
+        }
+        "btc_register_native_segwit_address" => {
+            landingpad(|| btc_address::display_native_segwit_address(&action.param.unwrap().value))
+        }
+        "btc_get_taproot_address" => {
+            landingpad(|| btc_address::get_taproot_address(&action.param.unwrap().value))
+        }
+        "btc_register_taproot_address" => {
+            landingpad(|| btc_address::display_taproot_address(&action.param.unwrap().value))
+        }
 
         // eth
         "eth_tx_sign" => {
@@ -140,8 +180,9 @@ pub unsafe extern "C" fn call_imkey_api(hex_str: *const c_char) -> *const c_char
         "eth_message_sign" => {
             landingpad(|| ethereum_signer::sign_eth_message(&action.param.unwrap().value))
         }
-        "eth_ec_sign" => {
-            landingpad(|| ethereum_signer::ec_sign(&action.param.unwrap().value))
+        "eth_ec_sign" => landingpad(|| ethereum_signer::ec_sign(&action.param.unwrap().value)),
+        "eth_sign_typed_data" => {
+            landingpad(|| ethereum_signer::sign_typed_data(&action.param.unwrap().value))
         }
         "eth_get_address" => {
             landingpad(|| ethereum_address::get_eth_address(&action.param.unwrap().value))
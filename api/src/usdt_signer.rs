@@ -1,12 +1,20 @@
 use crate::error_handling::Result;
 use crate::message_handler::encode_message;
+use bitcoin::Script;
 use bitcoin::{Address, Network};
-use coin_bitcoin::btcapi::{BtcSegwitTxReq, BtcSegwitTxRes, BtcTxReq, BtcTxRes};
+use coin_bitcoin::btcapi::{
+    omni_decode_res, BtcSegwitTxReq, BtcSegwitTxRes, BtcTxReq, BtcTxRes, OmniDecodeReq,
+    OmniDecodeRes, OmniSendAll, OmniSendToOwners, OmniSimpleSend, OmniTxReq,
+};
+use coin_bitcoin::omni::OmniPayload;
 use coin_bitcoin::transaction::{BtcTransaction, Utxo};
+use common::error::CoinError;
 use prost::Message;
 use std::str::FromStr;
+use transport::default_transport;
 
 pub fn sign_usdt_transaction(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: BtcTxReq = BtcTxReq::decode(data).expect("UsdtTxInput");
 
     let mut unspents = Vec::new();
@@ -35,8 +43,12 @@ pub fn sign_usdt_transaction(data: &[u8]) -> Result<Vec<u8>> {
     } else {
         Network::Bitcoin
     };
-    let signed =
-        btc_tx.sign_omni_transaction(network, &input.path_prefix, input.property_id as i32)?;
+    let signed = btc_tx.sign_omni_transaction(
+        transport.as_ref(),
+        network,
+        &input.path_prefix,
+        input.property_id as i32,
+    )?;
     let tx_sign_result = BtcTxRes {
         tx_data: signed.signature,
         tx_hash: signed.tx_hash,
@@ -45,6 +57,7 @@ pub fn sign_usdt_transaction(data: &[u8]) -> Result<Vec<u8>> {
 }
 
 pub fn sign_usdt_segwit_transaction(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
     let input: BtcSegwitTxReq = BtcSegwitTxReq::decode(data).expect("UsdtTxInput");
 
     let mut unspents = Vec::new();
@@ -75,6 +88,7 @@ pub fn sign_usdt_segwit_transaction(data: &[u8]) -> Result<Vec<u8>> {
         Network::Bitcoin
     };
     let signed = btc_tx.sign_omni_segwit_transaction(
+        transport.as_ref(),
         network,
         &input.path_prefix,
         input.property_id as i32,
@@ -86,3 +100,100 @@ pub fn sign_usdt_segwit_transaction(data: &[u8]) -> Result<Vec<u8>> {
     };
     encode_message(tx_sign_result)
 }
+
+pub fn sign_omni_transaction(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: OmniTxReq = OmniTxReq::decode(data).expect("OmniTxInput");
+    let (btc_tx, network, property_id) = omni_transaction(&input)?;
+    let signed = btc_tx.sign_omni_transaction(
+        transport.as_ref(),
+        network,
+        &input.path_prefix,
+        property_id,
+    )?;
+    let tx_sign_result = BtcTxRes {
+        tx_data: signed.signature,
+        tx_hash: signed.tx_hash,
+    };
+    encode_message(tx_sign_result)
+}
+
+pub fn sign_omni_segwit_transaction(data: &[u8]) -> Result<Vec<u8>> {
+    let transport = default_transport()?;
+    let input: OmniTxReq = OmniTxReq::decode(data).expect("OmniTxInput");
+    let (btc_tx, network, property_id) = omni_transaction(&input)?;
+    let signed = btc_tx.sign_omni_segwit_transaction(
+        transport.as_ref(),
+        network,
+        &input.path_prefix,
+        property_id,
+    )?;
+    let tx_sign_result = BtcSegwitTxRes {
+        witness_tx_data: signed.signature,
+        wtx_hash: signed.wtx_id,
+        tx_hash: signed.tx_hash,
+    };
+    encode_message(tx_sign_result)
+}
+
+pub fn decode_omni_output(data: &[u8]) -> Result<Vec<u8>> {
+    let input: OmniDecodeReq = OmniDecodeReq::decode(data).expect("OmniDecodeInput");
+    let script = Script::from(hex::decode(&input.script_pub_key)?);
+    let payload = match OmniPayload::from_script(&script)? {
+        Some(OmniPayload::SimpleSend {
+            property_id,
+            amount,
+        }) => Some(omni_decode_res::Payload::SimpleSend(OmniSimpleSend {
+            property_id,
+            amount: amount as i64,
+        })),
+        Some(OmniPayload::SendToOwners {
+            property_id,
+            amount,
+        }) => Some(omni_decode_res::Payload::SendToOwners(OmniSendToOwners {
+            property_id,
+            amount: amount as i64,
+        })),
+        Some(OmniPayload::SendAll { ecosystem }) => {
+            Some(omni_decode_res::Payload::SendAll(OmniSendAll {
+                ecosystem: u32::from(ecosystem),
+            }))
+        }
+        None => None,
+    };
+    encode_message(OmniDecodeRes { payload })
+}
+
+//the simple send as a transaction of its amount, with the property to send
+fn omni_transaction(input: &OmniTxReq) -> Result<(BtcTransaction, Network, i32)> {
+    let send = input
+        .simple_send
+        .as_ref()
+        .ok_or(CoinError::ImkeyOmniPayloadInvalid)?;
+
+    let mut unspents = Vec::new();
+    for utxo in &input.unspents {
+        let new_utxo = Utxo {
+            txhash: utxo.tx_hash.clone(),
+            vout: utxo.vout,
+            amount: utxo.amount,
+            address: Address::from_str(&utxo.address)?,
+            script_pubkey: utxo.script_pub_key.clone(),
+            derive_path: utxo.derived_path.clone(),
+            sequence: utxo.sequence,
+        };
+        unspents.push(new_utxo);
+    }
+    let btc_tx = BtcTransaction {
+        to: Address::from_str(&input.to)?,
+        amount: send.amount,
+        unspents,
+        fee: input.fee,
+    };
+    let network = if input.network == "TESTNET" {
+        Network::Testnet
+    } else {
+        Network::Bitcoin
+    };
+    Ok((btc_tx, network, send.property_id as i32))
+}
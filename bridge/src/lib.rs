@@ -0,0 +1,524 @@
+#[cfg(test)]
+#[macro_use]
+extern crate failure;
+
+use std::collections::HashSet;
+#[cfg(unix)]
+use std::env;
+#[cfg(unix)]
+use std::fs::{self, DirBuilder, Permissions};
+#[cfg(unix)]
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use transport::bridge::{
+    random_challenge, read_frame, serialize_response, write_frame, FrameAuth, Request, OP_AUTH,
+    OP_CONNECT, OP_DISCONNECT, OP_EXCHANGE,
+};
+use transport::error::BridgeError;
+use transport::{Result, Transport};
+
+//the shortest wait for the key, an idle timeout of zero would spin
+const MIN_WAIT: Duration = Duration::from_millis(10);
+
+//the client connection holding the key
+struct Lease {
+    holder: Option<u64>,
+    //a command of the holder is on the device
+    busy: bool,
+    last_used: Instant,
+    //the holders the key was taken from, until they connect again
+    lost: HashSet<u64>,
+}
+
+/**
+a tcp listener for the bridge. whoever reaches the socket can use the key, so it is only on a
+loopback address unless allow_remote, which the bridge only serves with a key to authenticate
+its clients
+*/
+pub fn bind_tcp(address: &str, allow_remote: bool) -> Result<TcpListener> {
+    let addresses: Vec<_> = address.to_socket_addrs()?.collect();
+    if addresses.is_empty()
+        || (!allow_remote && addresses.iter().any(|address| !address.ip().is_loopback()))
+    {
+        return Err(BridgeError::ListenNotLoopback.into());
+    }
+    Ok(TcpListener::bind(&addresses[..])?)
+}
+
+/**
+bridge.sock in a directory of its own under XDG_RUNTIME_DIR, or HOME without it, made so that
+only the user running the bridge can enter it
+*/
+#[cfg(unix)]
+pub fn default_socket_path() -> Result<PathBuf> {
+    let base = env::var_os("XDG_RUNTIME_DIR")
+        .or_else(|| env::var_os("HOME"))
+        .ok_or(BridgeError::AddressInvalid)?;
+    let dir = PathBuf::from(base).join(".imkey-bridge");
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Err(err) if err.kind() != io::ErrorKind::AlreadyExists => return Err(err.into()),
+        _ => (),
+    }
+    Ok(dir.join("bridge.sock"))
+}
+
+/**
+a unix socket listener for the bridge, which only the user running it may connect to. the
+socket has to be in a directory of that user no one else can enter, so it is never reachable
+by others, not even before its own mode is set. a socket left there by a bridge that is gone
+is replaced
+*/
+#[cfg(unix)]
+pub fn bind_unix(path: &Path) -> Result<UnixListener> {
+    let dir = match path.parent() {
+        Some(dir) if dir.as_os_str().is_empty() => Path::new("."),
+        Some(dir) => dir,
+        None => return Err(BridgeError::AddressInvalid.into()),
+    };
+    let dir_metadata = fs::symlink_metadata(dir)?;
+    if !dir_metadata.is_dir() || dir_metadata.mode() & 0o077 != 0 {
+        return Err(BridgeError::SocketDirNotPrivate.into());
+    }
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() && UnixStream::connect(path).is_err() {
+            fs::remove_file(path)?;
+        }
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, Permissions::from_mode(0o600))?;
+    //the socket is owned by the user running the bridge, the directory has to be too
+    if fs::metadata(path)?.uid() != dir_metadata.uid() {
+        fs::remove_file(path)?;
+        return Err(BridgeError::SocketDirNotPrivate.into());
+    }
+    Ok(listener)
+}
+
+/**
+serves one imKey to clients over length prefixed frames. a client holds the key from its first
+request until it disconnects or closes the connection, or until it has been idle for the idle
+timeout while another client waits. an applet the old holder selected is gone then, so its
+requests fail with lease lost until it connects again. a bridge with a key only serves clients
+that authenticate with it, see FrameAuth
+*/
+pub struct Bridge {
+    transport: Arc<dyn Transport>,
+    idle_timeout: Duration,
+    key: Option<[u8; 16]>,
+    lease: Mutex<Lease>,
+    released: Condvar,
+    next_client: AtomicU64,
+}
+
+impl Bridge {
+    pub fn new(
+        transport: Arc<dyn Transport>,
+        idle_timeout: Duration,
+        key: Option<[u8; 16]>,
+    ) -> Arc<Bridge> {
+        Arc::new(Bridge {
+            transport,
+            idle_timeout,
+            key,
+            lease: Mutex::new(Lease {
+                holder: None,
+                busy: false,
+                last_used: Instant::now(),
+                lost: HashSet::new(),
+            }),
+            released: Condvar::new(),
+            next_client: AtomicU64::new(0),
+        })
+    }
+
+    pub fn serve_tcp(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            stream.set_nodelay(true)?;
+            let bridge = self.clone();
+            thread::spawn(move || bridge.handle(stream));
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    pub fn serve_unix(self: Arc<Self>, listener: UnixListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let bridge = self.clone();
+            thread::spawn(move || bridge.handle(stream));
+        }
+        Ok(())
+    }
+
+    /**
+    answers the requests of one client connection until it closes, then gives its key back
+    */
+    pub fn handle(&self, mut stream: impl Read + Write) {
+        let mut auth = match self.key {
+            Some(key) => match authenticate(&mut stream, key) {
+                Ok(auth) => Some(auth),
+                Err(err) => {
+                    let _ = write_frame(&mut stream, &serialize_response(&Err(err.to_string())));
+                    return;
+                }
+            },
+            None => None,
+        };
+        let client = self.next_client.fetch_add(1, Ordering::SeqCst);
+        loop {
+            let body = match read_frame(&mut stream) {
+                Ok(Some(body)) => body,
+                _ => break,
+            };
+            let body = match &mut auth {
+                Some(auth) => match auth.open(&body) {
+                    Ok(body) => body,
+                    Err(err) => {
+                        let _ =
+                            write_frame(&mut stream, &serialize_response(&Err(err.to_string())));
+                        break;
+                    }
+                },
+                None => body,
+            };
+            let response = Request::parse(&body)
+                .and_then(|request| self.dispatch(client, &request))
+                .map_err(|err| err.to_string());
+            let response = match &mut auth {
+                Some(auth) => auth.seal(&serialize_response(&response)),
+                None => serialize_response(&response),
+            };
+            if write_frame(&mut stream, &response).is_err() {
+                break;
+            }
+        }
+        self.release(client);
+    }
+
+    fn dispatch(&self, client: u64, request: &Request) -> Result<Vec<u8>> {
+        match request.op {
+            OP_CONNECT => {
+                //connecting again starts over with the state of the device
+                self.lease.lock().unwrap().lost.remove(&client);
+                self.acquire(client, request.timeout)?;
+                let result = self.transport.connect();
+                self.done(client);
+                result?;
+                Ok(vec![])
+            }
+            OP_DISCONNECT => {
+                self.release(client);
+                Ok(vec![])
+            }
+            OP_EXCHANGE => {
+                self.acquire(client, request.timeout)?;
+                let result = self
+                    .transport
+                    .exchange(&hex::encode_upper(&request.data), request.timeout);
+                self.done(client);
+                Ok(hex::decode(result?)?)
+            }
+            _ => Err(BridgeError::ProtocolError.into()),
+        }
+    }
+
+    /**
+    waits at most timeout seconds for the key to be free, or for its holder to go idle. the idle
+    holder loses the key, its commands would otherwise reach an applet another client selected
+    */
+    fn acquire(&self, client: u64, timeout: i32) -> Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(timeout.max(0) as u64);
+        let mut lease = self.lease.lock().unwrap();
+        loop {
+            if lease.lost.contains(&client) {
+                return Err(BridgeError::LeaseLost.into());
+            }
+            let idle = !lease.busy && lease.last_used.elapsed() >= self.idle_timeout;
+            if lease.holder.is_none() || lease.holder == Some(client) || idle {
+                if let Some(holder) = lease.holder.filter(|holder| *holder != client) {
+                    lease.lost.insert(holder);
+                }
+                lease.holder = Some(client);
+                lease.busy = true;
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(BridgeError::KeyBusy.into());
+            }
+            let wait = (deadline - now).min(self.idle_timeout).max(MIN_WAIT);
+            lease = self.released.wait_timeout(lease, wait).unwrap().0;
+        }
+    }
+
+    fn done(&self, client: u64) {
+        let mut lease = self.lease.lock().unwrap();
+        if lease.holder == Some(client) {
+            lease.busy = false;
+            lease.last_used = Instant::now();
+        }
+        self.released.notify_all();
+    }
+
+    fn release(&self, client: u64) {
+        let mut lease = self.lease.lock().unwrap();
+        if lease.holder == Some(client) {
+            lease.holder = None;
+            lease.busy = false;
+        }
+        lease.lost.remove(&client);
+        self.released.notify_all();
+    }
+}
+
+/**
+the auth request a client opens its connection with, answered with the challenge the frames
+that follow are authenticated over
+*/
+fn authenticate(stream: &mut (impl Read + Write), key: [u8; 16]) -> Result<FrameAuth> {
+    let body = read_frame(stream)?.ok_or(BridgeError::ProtocolError)?;
+    if Request::parse(&body)?.op != OP_AUTH {
+        return Err(BridgeError::AuthFailed.into());
+    }
+    let challenge = random_challenge()?;
+    write_frame(stream, &serialize_response(&Ok(challenge.to_vec())))?;
+    Ok(FrameAuth::bridge(key, challenge))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bind_tcp, Bridge};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use transport::bridge::{BridgeAddress, BridgeTransport};
+    use transport::{Result, Transport};
+
+    //answers every command with its own bytes and 9000, and keeps what it was sent
+    #[derive(Default)]
+    struct EchoTransport {
+        sent: Mutex<Vec<String>>,
+    }
+
+    impl Transport for EchoTransport {
+        fn connect(&self) -> Result<()> {
+            self.sent.lock().unwrap().push("connect".to_string());
+            Ok(())
+        }
+
+        fn disconnect(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn exchange(&self, apdu: &str, _timeout: i32) -> Result<String> {
+            if apdu == "FF" {
+                return Err(format_err!("imkey_device_not_connect"));
+            }
+            self.sent.lock().unwrap().push(apdu.to_string());
+            Ok(format!("{}9000", apdu))
+        }
+
+        fn timeout(&self) -> i32 {
+            1
+        }
+
+        fn set_timeout(&self, _timeout: i32) {}
+    }
+
+    fn start(idle_timeout: Duration) -> (Arc<EchoTransport>, BridgeAddress) {
+        start_with_key(idle_timeout, None)
+    }
+
+    fn start_with_key(
+        idle_timeout: Duration,
+        key: Option<[u8; 16]>,
+    ) -> (Arc<EchoTransport>, BridgeAddress) {
+        let device = Arc::new(EchoTransport::default());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = BridgeAddress::Tcp(listener.local_addr().unwrap().to_string());
+        let bridge = Bridge::new(device.clone(), idle_timeout, key);
+        thread::spawn(move || bridge.serve_tcp(listener));
+        (device, address)
+    }
+
+    fn client(address: &BridgeAddress) -> BridgeTransport {
+        let client = BridgeTransport::new(address.clone());
+        client.set_timeout(1);
+        client
+    }
+
+    #[test]
+    fn exchange_test() {
+        let (device, address) = start(Duration::from_secs(60));
+        let client = client(&address);
+        assert_eq!(
+            format!(
+                "{}",
+                client.send_apdu("00A4040000".to_string()).err().unwrap()
+            ),
+            "imkey_bridge_not_connected"
+        );
+        client.connect().unwrap();
+        assert_eq!(
+            client.send_apdu("00a4040000".to_string()).unwrap(),
+            "00A40400009000"
+        );
+        //a long apdu and the error of the device as it is
+        let long_apdu = "80".repeat(300);
+        assert_eq!(
+            client.send_apdu(long_apdu.clone()).unwrap(),
+            format!("{}9000", long_apdu)
+        );
+        assert_eq!(
+            format!("{}", client.send_apdu("FF".to_string()).err().unwrap()),
+            "imkey_device_not_connect"
+        );
+        client.disconnect().unwrap();
+        assert_eq!(
+            *device.sent.lock().unwrap(),
+            vec!["connect".to_string(), "00A4040000".to_string(), long_apdu]
+        );
+    }
+
+    #[test]
+    fn lease_test() {
+        let (_device, address) = start(Duration::from_secs(60));
+        let first = client(&address);
+        let second = client(&address);
+        first.connect().unwrap();
+        //the key is held by the first client until it disconnects
+        assert_eq!(
+            format!("{}", second.connect().err().unwrap()),
+            "imkey_bridge_key_busy"
+        );
+        first.disconnect().unwrap();
+        second.connect().unwrap();
+        assert!(second.send_apdu("00A4040000".to_string()).is_ok());
+
+        //closing the connection gives the key back as well
+        drop(second);
+        first.connect().unwrap();
+        assert!(first.send_apdu("00A4040000".to_string()).is_ok());
+    }
+
+    #[test]
+    fn idle_lease_test() {
+        let (_device, address) = start(Duration::from_millis(100));
+        let first = client(&address);
+        let second = client(&address);
+        first.connect().unwrap();
+        //the idle first client loses the key to the waiting second one
+        second.connect().unwrap();
+        assert!(second.send_apdu("00A4040000".to_string()).is_ok());
+        thread::sleep(Duration::from_millis(200));
+        //the applet it selected is gone, nothing more of it reaches the device
+        assert_eq!(
+            format!(
+                "{}",
+                first.send_apdu("00A4040000".to_string()).err().unwrap()
+            ),
+            "imkey_bridge_lease_lost"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                first.send_apdu("00A4040000".to_string()).err().unwrap()
+            ),
+            "imkey_bridge_lease_lost"
+        );
+        //until it connects again, taking the key from the now idle second one
+        first.connect().unwrap();
+        assert!(first.send_apdu("00A4040000".to_string()).is_ok());
+        assert_eq!(
+            format!(
+                "{}",
+                second.send_apdu("00A4040000".to_string()).err().unwrap()
+            ),
+            "imkey_bridge_lease_lost"
+        );
+    }
+
+    #[test]
+    fn auth_test() {
+        let key = [0x2bu8; 16];
+        let (device, address) = start_with_key(Duration::from_secs(60), Some(key));
+        let client = BridgeTransport::with_key(address.clone(), key);
+        client.set_timeout(1);
+        client.connect().unwrap();
+        assert_eq!(
+            client.send_apdu("00A4040000".to_string()).unwrap(),
+            "00A40400009000"
+        );
+        client.disconnect().unwrap();
+
+        //no key or another one does not reach the device
+        for client in &[
+            BridgeTransport::new(address.clone()),
+            BridgeTransport::with_key(address.clone(), [0u8; 16]),
+        ] {
+            client.set_timeout(1);
+            assert_eq!(
+                format!("{}", client.connect().err().unwrap()),
+                "imkey_bridge_auth_failed"
+            );
+        }
+        assert_eq!(
+            *device.sent.lock().unwrap(),
+            vec!["connect".to_string(), "00A4040000".to_string()]
+        );
+    }
+
+    #[test]
+    fn bind_tcp_test() {
+        assert!(bind_tcp("127.0.0.1:0", false).is_ok());
+        assert!(bind_tcp("localhost:0", false).is_ok());
+        for address in &["0.0.0.0:0", "[::]:0", "192.0.2.1:7007"] {
+            assert_eq!(
+                format!("{}", bind_tcp(address, false).err().unwrap()),
+                "imkey_bridge_listen_not_loopback"
+            );
+        }
+        assert!(bind_tcp("0.0.0.0:0", true).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bind_unix_test() {
+        use crate::bind_unix;
+        use std::fs::{self, DirBuilder};
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+        //a directory others can enter is refused
+        let dir = std::env::temp_dir().join(format!("imkey-bridge-{}", std::process::id()));
+        DirBuilder::new().mode(0o755).create(&dir).unwrap();
+        let path = dir.join("bridge.sock");
+        assert_eq!(
+            format!("{}", bind_unix(&path).err().unwrap()),
+            "imkey_bridge_socket_dir_not_private"
+        );
+        assert!(!path.exists());
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+        let listener = bind_unix(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        //a live socket stays, the one of a bridge that is gone is replaced
+        assert!(bind_unix(&path).is_err());
+        drop(listener);
+        assert!(bind_unix(&path).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
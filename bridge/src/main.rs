@@ -0,0 +1,105 @@
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use bridge::{bind_tcp, Bridge};
+#[cfg(unix)]
+use bridge::{bind_unix, default_socket_path};
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use std::env;
+use std::process;
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use std::sync::Arc;
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use std::time::Duration;
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use transport::bridge::{key_from_env, BridgeAddress};
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use transport::error::BridgeError;
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use transport::hid_api::HidTransport;
+use transport::Result;
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use transport::Transport;
+
+#[macro_use]
+extern crate failure;
+
+const USAGE: &str = "usage: imkey-bridge [--listen tcp://127.0.0.1:7007|unix:///path] \
+                     [--allow-remote] [--path <hid path>] [--idle-timeout <seconds>]\n\
+                     IMKEY_BRIDGE_KEY=<hex aes-128 key> authenticates the clients, \
+                     --allow-remote needs it";
+const DEFAULT_IDLE_TIMEOUT: u64 = 10;
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+//a unix socket only its user can reach where there is one, tcp is served on loopback only
+#[cfg(unix)]
+fn default_listen() -> Result<String> {
+    Ok(format!("unix://{}", default_socket_path()?.display()))
+}
+
+#[cfg(not(unix))]
+fn default_listen() -> Result<String> {
+    Ok("tcp://127.0.0.1:7007".to_string())
+}
+
+/**
+owns the hid connection to one imKey and serves it at the listen address
+*/
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+fn run() -> Result<()> {
+    let mut listen = None;
+    let mut allow_remote = false;
+    let mut path = None;
+    let mut idle_timeout = DEFAULT_IDLE_TIMEOUT;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--allow-remote" {
+            allow_remote = true;
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format_err!("{}", USAGE))?;
+        match arg.as_str() {
+            "--listen" => listen = Some(value),
+            "--path" => path = Some(value),
+            "--idle-timeout" => {
+                idle_timeout = value.parse().map_err(|_| format_err!("{}", USAGE))?
+            }
+            _ => return Err(format_err!("{}", USAGE)),
+        }
+    }
+
+    //whoever reaches a remote bridge could use the key, it has to authenticate them
+    let key = key_from_env()?;
+    if allow_remote && key.is_none() {
+        return Err(BridgeError::RemoteNeedsKey.into());
+    }
+    let listen = match listen {
+        Some(listen) => listen,
+        None => default_listen()?,
+    };
+
+    let transport: Arc<dyn Transport> = Arc::new(match path {
+        Some(path) => HidTransport::with_path(&path),
+        None => HidTransport::new(),
+    });
+    //the key may be plugged in later, the connect of a client opens it then
+    if let Err(err) = transport.connect() {
+        println!("device connect failed : {}", err);
+    }
+    let bridge = Bridge::new(transport, Duration::from_secs(idle_timeout), key);
+    println!("imkey bridge listening on {}", listen);
+    match BridgeAddress::parse(&listen)? {
+        BridgeAddress::Tcp(address) => bridge.serve_tcp(bind_tcp(&address, allow_remote)?),
+        #[cfg(unix)]
+        BridgeAddress::Unix(path) => bridge.serve_unix(bind_unix(&path)?),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn run() -> Result<()> {
+    Err(format_err!("imkey-bridge needs a usb hid host"))
+}
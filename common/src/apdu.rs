@@ -93,6 +93,21 @@ impl BtcApdu {
         apdu.to_hex().to_uppercase()
     }
 
+    pub fn btc_taproot_sign(last_one: bool, hash_type: u8, data: Vec<u8>) -> String {
+        if data.len() as u32 > LC_MAX {
+            panic!("data to long");
+        }
+
+        let mut apdu = match last_one {
+            true => ApduHeader::new(0x80, 0x52, 0x80, hash_type, data.len() as u8).to_array(),
+            _ => ApduHeader::new(0x80, 0x52, 0x00, hash_type, data.len() as u8).to_array(),
+        };
+
+        apdu.extend(data.iter());
+        apdu.push(0x00);
+        apdu.to_hex().to_uppercase()
+    }
+
     pub fn omni_prepare_data(p1: u8, data: Vec<u8>) -> String {
         if data.len() as u32 > LC_MAX {
             panic!("data to long");
@@ -142,6 +157,14 @@ impl EthApdu {
     pub fn personal_sign(path: &str) -> String {
         Apdu::sign_digest(0x55, 0x00, 0x00, path)
     }
+
+    pub fn prepare_typed_data_sign(data: Vec<u8>) -> Vec<String> {
+        Apdu::prepare_sign(0x57, data)
+    }
+
+    pub fn typed_data_sign(path: &str) -> String {
+        Apdu::sign_digest(0x58, 0x00, 0x00, path)
+    }
 }
 
 pub struct EosApdu();
@@ -380,6 +403,17 @@ impl ImkApdu {
         apdu.push(0x00);
         apdu.to_hex().to_uppercase()
     }
+
+    /**
+    open a secure channel with the host challenge
+    */
+    pub fn open_secure_channel(host_challenge: &[u8]) -> String {
+        let mut apdu =
+            ApduHeader::new(0x80, 0x74, 0x00, 0x00, host_challenge.len() as u8).to_array();
+        apdu.extend(host_challenge.iter());
+        apdu.push(0x00);
+        apdu.to_hex().to_uppercase()
+    }
 }
 
 pub struct ApduCheck {}
@@ -403,6 +437,7 @@ impl ApduCheck {
             "F080" => Err(ApduError::ImkeyInMenuPage.into()),
             "F081" => Err(ApduError::ImkeyPinNotVerified.into()),
             "6F01" => Err(ApduError::ImkeyBluetoothChannelError.into()),
+            "6982" => Err(ApduError::ImkeySecurityStatusNotSatisfied.into()),
             _ => Err(format_err!("imkey_command_execute_fail_{}", response_data)), //Err(ApduError::ImkeyCommandExecuteFail.into())
         }
     }
@@ -541,6 +576,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn eth_typed_data_sign_test() {
+        assert_eq!(
+            EthApdu::typed_data_sign("m/44'/60'/0'/0/0"),
+            String::from("80580000106d2f3434272f3630272f30272f302f3000")
+        );
+        let data = Vec::from_hex("11223344556677889900").unwrap();
+        for apdu in EthApdu::prepare_typed_data_sign(data) {
+            assert_eq!(apdu, String::from("805700800a1122334455667788990000"));
+        }
+    }
+
     #[test]
     fn eth_get_xpub_test() {
         assert_eq!(
@@ -673,6 +720,14 @@ mod tests {
         assert_eq!(ImkApdu::generate_auth_code(), String::from("8072000000"));
     }
 
+    #[test]
+    fn open_secure_channel_test() {
+        assert_eq!(
+            ImkApdu::open_secure_channel(&[1, 2, 3, 4, 5, 6, 7, 8]),
+            String::from("8074000008010203040506070800")
+        );
+    }
+
     #[test]
     #[should_panic]
     fn identity_verify_test() {
@@ -732,6 +787,7 @@ mod tests {
         assert!(ApduCheck::checke_response("00F080").is_err());
         assert!(ApduCheck::checke_response("00F081").is_err());
         assert!(ApduCheck::checke_response("006F01").is_err());
+        assert!(ApduCheck::checke_response("6982").is_err());
         assert!(ApduCheck::checke_response("000000").is_err());
     }
 }
@@ -26,14 +26,27 @@ pub const BL_AID: &str = "D0426F6F746C6F61646572";
 //path
 pub const COSMOS_PATH: &str = "m/44'/118'/0'/0/0";
 pub const EOS_PATH: &str = "m/44'/194'/0'/0/0";
+pub const WAX_PATH: &str = "m/44'/14001'/0'/0/0";
 pub const ETH_PATH: &str = "m/44'/60'/0'/0/0";
 
+//bech32 prefix of the cosmos hub, other sdk chains bring their own
+pub const COSMOS_HRP: &str = "cosmos";
+
+//sign apdus sent for a canonical eos signature, a quarter of the signatures are canonical
+pub const EOS_MAX_SIGN_ATTEMPTS: usize = 64;
+
 pub const MAX_UTXO_NUMBER: usize = 252;
 pub const EACH_ROUND_NUMBER: usize = 5;
 pub const DUST_THRESHOLD: i64 = 2730;
 pub const MIN_NONDUST_OUTPUT: i64 = 546;
 // max op return size
 pub const MAX_OPRETURN_SIZE: usize = 80;
+// p2pkh address versions
+pub const BTC_P2PKH_VERSION: u8 = 0x00;
+pub const BTC_TESTNET_P2PKH_VERSION: u8 = 0x6f;
+// slip-0132 version bytes of bip84 extended public keys
+pub const ZPUB_VERSION: [u8; 4] = [0x04, 0xb2, 0x47, 0x46];
+pub const VPUB_VERSION: [u8; 4] = [0x04, 0x5f, 0x1c, 0xf6];
 
 // imkey device status
 pub const IMKEY_DEV_STATUS_INACTIVATED: &str = "inactivated";
@@ -86,6 +99,7 @@ pub const APDU_RSP_WALLET_NOT_CREATED: &str = "F000";
 pub const APDU_RSP_IN_MENU_PAGE: &str = "F080";
 pub const APDU_RSP_PIN_NOT_VERIFIED: &str = "F081";
 pub const APDU_BLUETOOTH_CHANNEL_ERROR: &str = "6F01";
+pub const APDU_RSP_SECURITY_STATUS_NOT_SATISFIED: &str = "6982";
 pub const APDU_RSP_SWITCH_BL_STATUS_SUCCESS: &str = "905A";
 
 pub const TIMEOUT_LONG: i32 = 120;
@@ -94,3 +108,6 @@ pub const DEVICE_MODEL_NAME: &str = "imKey Pro";
 pub const NETWORK_CONN_TIMEOUT: u16 = 30;
 pub const NETWORK_WRITE_TIMEOUT: u16 = 30;
 pub const NETWORK_READ_TIMEOUT: u16 = 30;
+pub const NETWORK_REQUEST_TIMEOUT: u16 = 90;
+pub const NETWORK_RETRIES: u32 = 2;
+pub const NETWORK_RETRY_BACKOFF_MILLIS: u64 = 500;
@@ -0,0 +1,364 @@
+use crate::error::CoinError;
+use crate::Result;
+use std::fmt;
+
+//a display string is sent in a one byte length TLV
+const MAX_DISPLAY_LENGTH: usize = 255;
+
+/**
+what the device shows for a transaction: the payment, the receiver and the fee
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayInfo {
+    pub payment: String,
+    pub receiver: String,
+    pub fee: String,
+}
+
+/**
+an amount in the smallest unit of a coin, shown with the coin's decimals
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Amount {
+    pub value: u128,
+    pub decimals: u32,
+    pub symbol: String,
+}
+
+impl Amount {
+    pub fn new(value: u128, decimals: u32, symbol: &str) -> Amount {
+        Amount {
+            value,
+            decimals,
+            symbol: symbol.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = format!(
+            "{:0>width$}",
+            self.value,
+            width = self.decimals as usize + 1
+        );
+        let (integer, fraction) = digits.split_at(digits.len() - self.decimals as usize);
+        let fraction = fraction.trim_end_matches('0');
+        let number = if fraction.is_empty() {
+            integer.to_string()
+        } else {
+            format!("{}.{}", integer, fraction)
+        };
+        if self.symbol.is_empty() {
+            write!(f, "{}", number)
+        } else {
+            write!(f, "{} {}", number, self.symbol)
+        }
+    }
+}
+
+/**
+collects the payments, receivers and fee of a parsed transaction. the strings a caller wants
+to show are verified against them, so the device never shows what is not signed
+*/
+#[derive(Debug, Clone, Default)]
+pub struct DisplayInfoBuilder {
+    payments: Vec<Amount>,
+    receivers: Vec<String>,
+    fees: Vec<Amount>,
+}
+
+impl DisplayInfoBuilder {
+    pub fn new() -> DisplayInfoBuilder {
+        DisplayInfoBuilder::default()
+    }
+
+    pub fn payment(mut self, amount: Amount) -> DisplayInfoBuilder {
+        self.payments.push(amount);
+        self
+    }
+
+    pub fn receiver(mut self, receiver: &str) -> DisplayInfoBuilder {
+        if !self.receivers.iter().any(|known| known == receiver) {
+            self.receivers.push(receiver.to_string());
+        }
+        self
+    }
+
+    pub fn fee(mut self, amount: Amount) -> DisplayInfoBuilder {
+        self.fees.push(amount);
+        self
+    }
+
+    pub fn build(&self) -> DisplayInfo {
+        let join = |amounts: &[Amount]| {
+            amounts
+                .iter()
+                .map(Amount::to_string)
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        DisplayInfo {
+            payment: join(&self.payments),
+            receiver: self.receivers.join(", "),
+            fee: join(&self.fees),
+        }
+    }
+
+    /**
+    the strings to show, always derived from the transaction. the caller's strings are checked
+    against them, an empty one is not checked. payments are compared exactly, in order, by their
+    numbers and units; a fee may be rounded to the digits shown as long as two significant ones
+    remain. receivers are compared as a set, hex addresses ignoring case
+    */
+    pub fn verify(&self, payment: &str, receiver: &str, fee: &str) -> Result<DisplayInfo> {
+        let derived = self.build();
+
+        let payment_matched = same_amounts(payment, &self.payments, |number, amount| {
+            parse_units(number, amount.decimals) == Some(amount.value)
+        });
+        let fee_matched = same_amounts(fee, &self.fees, fee_matches);
+
+        let shown_receivers: Vec<&str> = receiver
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|shown| !shown.is_empty())
+            .collect();
+        let receiver_matched = shown_receivers.iter().all(|shown| {
+            self.receivers
+                .iter()
+                .any(|receiver| same_receiver(shown, receiver))
+        }) && self.receivers.iter().all(|receiver| {
+            shown_receivers
+                .iter()
+                .any(|shown| same_receiver(shown, receiver))
+        });
+
+        if (!payment.is_empty() && !payment_matched)
+            || (!receiver.is_empty() && !receiver_matched)
+            || (!fee.is_empty() && !fee_matched)
+        {
+            return Err(CoinError::ImkeyDisplayMismatchWithData.into());
+        }
+        if [&derived.payment, &derived.receiver, &derived.fee]
+            .iter()
+            .any(|display| display.len() > MAX_DISPLAY_LENGTH)
+        {
+            return Err(CoinError::ImkeyDisplayTooLong.into());
+        }
+        Ok(derived)
+    }
+}
+
+/**
+a display string in the tag, one byte length and value format the applets read
+*/
+pub fn display_tlv(tag: u8, display: &str) -> Result<Vec<u8>> {
+    if display.len() > MAX_DISPLAY_LENGTH {
+        return Err(CoinError::ImkeyDisplayTooLong.into());
+    }
+    let mut tlv = vec![tag, display.len() as u8];
+    tlv.extend(display.as_bytes());
+    Ok(tlv)
+}
+
+/**
+the numbers in a display string without thousands separators, "1,250.5 USDT" gives "1250.5"
+*/
+pub fn numbers(display: &str) -> Vec<String> {
+    display
+        .split(|c: char| !is_number_char(c))
+        .map(|number| number.replace(',', ""))
+        .filter(|number| number.chars().any(|c| c.is_ascii_digit()))
+        .collect()
+}
+
+fn is_number_char(c: char) -> bool {
+    c.is_ascii_digit() || c == '.' || c == ','
+}
+
+//the smallest units of a shown number, none if it is more precise than decimals
+fn parse_units(number: &str, decimals: u32) -> Option<u128> {
+    let mut parts = number.splitn(2, '.');
+    let integer = parts.next().unwrap_or_default();
+    let fraction = parts.next().unwrap_or_default().trim_end_matches('0');
+    if fraction.len() > decimals as usize || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let digits = format!(
+        "{}{:0<width$}",
+        integer,
+        fraction,
+        width = decimals as usize
+    );
+    digits.parse::<u128>().ok()
+}
+
+fn fee_matches(number: &str, fee: &Amount) -> bool {
+    if parse_units(number, fee.decimals) == Some(fee.value) {
+        return true;
+    }
+    let shown_decimals = number.find('.').map_or(0, |dot| number.len() - dot - 1) as u32;
+    if shown_decimals >= fee.decimals {
+        return false;
+    }
+    let unit = 10u128.pow(fee.decimals - shown_decimals);
+    match fee.value.checked_add(unit / 2) {
+        Some(value) => {
+            let rounded = value / unit;
+            rounded >= 10 && parse_units(number, shown_decimals) == Some(rounded)
+        }
+        None => false,
+    }
+}
+
+fn same_receiver(shown: &str, receiver: &str) -> bool {
+    shown == receiver
+        || (shown.starts_with("0x")
+            && receiver.starts_with("0x")
+            && shown.eq_ignore_ascii_case(receiver))
+}
+
+//the shown amounts are the numbers of the amounts in order, each followed by its symbol if any
+fn same_amounts<F>(shown: &str, amounts: &[Amount], same_number: F) -> bool
+where
+    F: Fn(&str, &Amount) -> bool,
+{
+    let mut words = shown
+        .split_whitespace()
+        .map(|word| word.trim_end_matches(','))
+        .filter(|word| !word.is_empty());
+    for amount in amounts {
+        let number = match words.next() {
+            Some(number) if number.chars().all(is_number_char) => number.replace(',', ""),
+            _ => return false,
+        };
+        if !same_number(&number, amount) {
+            return false;
+        }
+        if amount.symbol.is_empty() {
+            continue;
+        }
+        match words.next() {
+            Some(symbol) if symbol.eq_ignore_ascii_case(&amount.symbol) => {}
+            _ => return false,
+        }
+    }
+    words.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::display::{display_tlv, numbers, Amount, DisplayInfo, DisplayInfoBuilder};
+
+    #[test]
+    fn amount_display_test() {
+        assert_eq!(
+            Amount::new(10_000_000_000_000_000, 18, "ETH").to_string(),
+            "0.01 ETH"
+        );
+        assert_eq!(Amount::new(10_000_000, 4, "EOS").to_string(), "1000 EOS");
+        assert_eq!(Amount::new(750, 6, "ATOM").to_string(), "0.00075 ATOM");
+        assert_eq!(Amount::new(0, 8, "").to_string(), "0");
+        assert_eq!(
+            numbers("send 1,250.5 USDT and 3 ETH."),
+            vec!["1250.5".to_string(), "3".to_string()]
+        );
+    }
+
+    #[test]
+    fn verify_test() {
+        let builder = DisplayInfoBuilder::new()
+            .payment(Amount::new(10_000_000_000_000_000, 18, "ETH"))
+            .receiver("0xE6F4142dfFA574D1d9f18770BF73814df07931F3")
+            .fee(Amount::new(3_150_000_000_000_000, 18, "ETH"));
+
+        //empty strings are derived
+        assert_eq!(
+            builder.verify("", "", "").unwrap(),
+            DisplayInfo {
+                payment: "0.01 ETH".to_string(),
+                receiver: "0xE6F4142dfFA574D1d9f18770BF73814df07931F3".to_string(),
+                fee: "0.00315 ETH".to_string(),
+            }
+        );
+        //matching strings are checked, the derived ones are shown
+        let display = builder
+            .verify(
+                "0.0100 eth",
+                "0xe6f4142dffa574d1d9f18770bf73814df07931f3",
+                "0.0032 ETH",
+            )
+            .unwrap();
+        assert_eq!(display, builder.verify("", "", "").unwrap());
+
+        for (payment, receiver, fee) in &[
+            ("0.1 ETH", "", ""),
+            ("0.01 ETH 1", "", ""),
+            ("0.01 USDC", "", ""),
+            ("0.0100 ether", "", ""),
+            ("0.01", "", ""),
+            ("send 0.01 ETH", "", ""),
+            ("", "", "0.0032 ETH to miners"),
+            ("", "0x3535353535353535353535353535353535353535", ""),
+            ("", "", "0.003 ETH"),
+            ("", "", "0.01 ETH"),
+        ] {
+            assert_eq!(
+                format!("{}", builder.verify(payment, receiver, fee).err().unwrap()),
+                "imkey_display_mismatch_with_data"
+            );
+        }
+    }
+
+    #[test]
+    fn verify_receivers_test() {
+        let builder = DisplayInfoBuilder::new()
+            .payment(Amount::new(1000, 8, "BTC"))
+            .receiver("12z6UzsA3tjpaeuvA2Zr9jwx19Azz74D6g")
+            .payment(Amount::new(2000, 8, "BTC"))
+            .receiver("37E2J9ViM4QFiewo7aw5L3drF2QKB99F9e");
+        assert_eq!(
+            builder.build().receiver,
+            "12z6UzsA3tjpaeuvA2Zr9jwx19Azz74D6g, 37E2J9ViM4QFiewo7aw5L3drF2QKB99F9e"
+        );
+        assert!(builder
+            .verify(
+                "0.00001 BTC, 0.00002 BTC",
+                "37E2J9ViM4QFiewo7aw5L3drF2QKB99F9e 12z6UzsA3tjpaeuvA2Zr9jwx19Azz74D6g",
+                ""
+            )
+            .is_ok());
+        //base58 is case sensitive and every receiver has to be shown
+        assert!(builder
+            .verify("", "12Z6UzsA3tjpaeuvA2Zr9jwx19Azz74D6g", "")
+            .is_err());
+        assert!(builder
+            .verify("", "12z6UzsA3tjpaeuvA2Zr9jwx19Azz74D6g", "")
+            .is_err());
+        assert!(builder.verify("0.00003 BTC", "", "").is_err());
+        assert!(builder.verify("0.00001 0.00002", "", "").is_err());
+    }
+
+    #[test]
+    fn display_length_test() {
+        //a display string is never truncated, what does not fit is refused
+        let mut builder = DisplayInfoBuilder::new();
+        for index in 0..10 {
+            builder = builder
+                .payment(Amount::new(1000, 8, "BTC"))
+                .receiver(&format!("{:0>34}", index));
+        }
+        assert_eq!(builder.build().receiver.len(), 358);
+        assert_eq!(
+            format!("{}", builder.verify("", "", "").err().unwrap()),
+            "imkey_display_too_long"
+        );
+
+        assert_eq!(display_tlv(7, "1 BTC").unwrap(), b"\x07\x051 BTC".to_vec());
+        assert_eq!(display_tlv(8, &"a".repeat(255)).unwrap().len(), 257);
+        assert_eq!(
+            format!("{}", display_tlv(8, &"a".repeat(256)).err().unwrap()),
+            "imkey_display_too_long"
+        );
+    }
+}
@@ -34,6 +34,8 @@ pub enum ApduError {
     ImkeyInMenuPage,
     #[fail(display = "imkey_pin_not_verified")]
     ImkeyPinNotVerified,
+    #[fail(display = "imkey_security_status_not_satisfied")]
+    ImkeySecurityStatusNotSatisfied,
 }
 
 #[derive(Fail, Debug, PartialOrd, PartialEq)]
@@ -56,4 +58,54 @@ pub enum CoinError {
     GetXpubError,
     #[fail(display = "address_type_mismatch")]
     AddressTypeMismatch,
+    #[fail(display = "imkey_psbt_utxo_missing")]
+    ImkeyPsbtUtxoMissing,
+    #[fail(display = "imkey_psbt_utxo_mismatch")]
+    ImkeyPsbtUtxoMismatch,
+    #[fail(display = "imkey_psbt_script_not_supported")]
+    ImkeyPsbtScriptNotSupported,
+    #[fail(display = "imkey_psbt_nothing_to_sign")]
+    ImkeyPsbtNothingToSign,
+    #[fail(display = "imkey_typed_data_invalid")]
+    ImkeyTypedDataInvalid,
+    #[fail(display = "imkey_typed_data_version_unsupported")]
+    ImkeyTypedDataVersionUnsupported,
+    #[fail(display = "imkey_calldata_invalid")]
+    ImkeyCalldataInvalid,
+    #[fail(display = "imkey_display_mismatch_with_data")]
+    ImkeyDisplayMismatchWithData,
+    #[fail(display = "imkey_display_too_long")]
+    ImkeyDisplayTooLong,
+    #[fail(display = "imkey_eos_transaction_invalid")]
+    ImkeyEosTransactionInvalid,
+    #[fail(display = "imkey_eos_action_unsupported")]
+    ImkeyEosActionUnsupported,
+    #[fail(display = "imkey_eos_sign_attempts_exceeded")]
+    ImkeyEosSignAttemptsExceeded,
+    #[fail(display = "imkey_eos_chain_unsupported")]
+    ImkeyEosChainUnsupported,
+    #[fail(display = "imkey_eos_chain_id_mismatch")]
+    ImkeyEosChainIdMismatch,
+    #[fail(display = "imkey_cosmos_msg_unsupported")]
+    ImkeyCosmosMsgUnsupported,
+    #[fail(display = "imkey_omni_payload_invalid")]
+    ImkeyOmniPayloadInvalid,
+}
+
+#[derive(Fail, Debug, PartialOrd, PartialEq)]
+pub enum TsmClientError {
+    #[fail(display = "imkey_tsm_url_illegal")]
+    TsmUrlIllegal,
+    #[fail(display = "imkey_tsm_proxy_illegal")]
+    ProxyIllegal,
+    #[fail(display = "imkey_tsm_proxy_connect_fail")]
+    ProxyConnectFail,
+    #[fail(display = "imkey_tsm_pinned_cert_illegal")]
+    PinnedCertIllegal,
+    #[fail(display = "imkey_tsm_cert_pin_mismatch")]
+    CertPinMismatch,
+    #[fail(display = "imkey_tsm_request_timeout")]
+    TsmRequestTimeout,
+    #[fail(display = "imkey_tsm_server_unavailable")]
+    TsmServerUnavailable,
 }
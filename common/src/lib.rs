@@ -1,13 +1,17 @@
 pub mod apdu;
 pub mod applet;
 pub mod constants;
+pub mod display;
 pub mod error;
 pub mod https;
 pub mod path;
+pub mod tls;
 pub mod utility;
 
 #[macro_use]
 extern crate failure;
+#[macro_use]
+extern crate lazy_static;
 use core::result;
 pub type Result<T> = result::Result<T, failure::Error>;
 
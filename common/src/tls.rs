@@ -0,0 +1,190 @@
+use native_tls::{Certificate, HandshakeError, MidHandshakeTlsStream};
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/**
+blocking io view of an async stream for native tls. it keeps the waker of the task last polling
+it, an io call that would block registers that task and gives WouldBlock
+*/
+struct AllowStd<S> {
+    inner: S,
+    waker: Option<Waker>,
+}
+
+impl<S: Unpin> AllowStd<S> {
+    fn register(&mut self, context: &Context<'_>) {
+        match &self.waker {
+            Some(waker) if waker.will_wake(context.waker()) => (),
+            _ => self.waker = Some(context.waker().clone()),
+        }
+    }
+
+    fn with_context<F, R>(&mut self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut Context<'_>, Pin<&mut S>) -> Poll<io::Result<R>>,
+    {
+        //only reached while a task polls, which registers itself first
+        let waker = match &self.waker {
+            Some(waker) => waker,
+            None => return Err(io::ErrorKind::WouldBlock.into()),
+        };
+        let mut context = Context::from_waker(waker);
+        match f(&mut context, Pin::new(&mut self.inner)) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> Read for AllowStd<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.with_context(|context, stream| stream.poll_read(context, buf))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> Write for AllowStd<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.with_context(|context, stream| stream.poll_write(context, buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.with_context(|context, stream| stream.poll_flush(context))
+    }
+}
+
+fn to_poll<T>(result: io::Result<T>) -> Poll<io::Result<T>> {
+    match result {
+        Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Poll::Pending,
+        result => Poll::Ready(result),
+    }
+}
+
+/**
+native tls over an async stream, unlike tokio-tls it hands out the peer certificate
+*/
+pub struct TlsStream<S>(native_tls::TlsStream<AllowStd<S>>);
+
+impl<S: AsyncRead + AsyncWrite + Unpin> TlsStream<S> {
+    pub fn get_ref(&self) -> &S {
+        &self.0.get_ref().inner
+    }
+
+    pub fn peer_certificate(&self) -> native_tls::Result<Option<Certificate>> {
+        self.0.peer_certificate()
+    }
+
+    fn with_context<F, R>(&mut self, context: &mut Context<'_>, f: F) -> Poll<io::Result<R>>
+    where
+        F: FnOnce(&mut native_tls::TlsStream<AllowStd<S>>) -> io::Result<R>,
+    {
+        self.0.get_mut().register(context);
+        to_poll(f(&mut self.0))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for TlsStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.with_context(context, |stream| stream.read(buf))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for TlsStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.with_context(context, |stream| stream.write(buf))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.with_context(context, |stream| stream.flush())
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.with_context(context, |stream| stream.shutdown())
+    }
+}
+
+/**
+runs the client handshake of native tls, resuming it whenever the stream is ready again
+*/
+struct Handshake<'a, S> {
+    start: Option<(&'a native_tls::TlsConnector, &'a str, S)>,
+    mid: Option<MidHandshakeTlsStream<AllowStd<S>>>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Future for Handshake<'_, S> {
+    type Output = Result<TlsStream<S>, native_tls::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        let result = match self.start.take() {
+            Some((connector, domain, inner)) => {
+                let waker = Some(context.waker().clone());
+                connector.connect(domain, AllowStd { inner, waker })
+            }
+            None => {
+                let mut mid = self.mid.take().expect("handshake polled after completion");
+                mid.get_mut().register(context);
+                mid.handshake()
+            }
+        };
+        match result {
+            Ok(stream) => Poll::Ready(Ok(TlsStream(stream))),
+            Err(HandshakeError::WouldBlock(mid)) => {
+                self.mid = Some(mid);
+                Poll::Pending
+            }
+            Err(HandshakeError::Failure(err)) => Poll::Ready(Err(err)),
+        }
+    }
+}
+
+pub async fn connect<S: AsyncRead + AsyncWrite + Unpin>(
+    connector: &native_tls::TlsConnector,
+    domain: &str,
+    stream: S,
+) -> Result<TlsStream<S>, native_tls::Error> {
+    Handshake {
+        start: Some((connector, domain, stream)),
+        mid: None,
+    }
+    .await
+}
+
+/**
+the subjectPublicKeyInfo of a der certificate, the part a key pin is taken over
+*/
+pub fn subject_public_key_info(cert: &[u8]) -> Option<&[u8]> {
+    x509_signature::parse_certificate(cert)
+        .ok()
+        .map(|cert| cert.subject_public_key_info().spki())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tls::subject_public_key_info;
+    use crate::utility::sha256_hash;
+    use hex::FromHex;
+
+    #[test]
+    fn subject_public_key_info_test() {
+        let cert = Vec::from_hex("308201883082012da00302010202145a322ba6e821f017edaffab9e0a6c75562b706e6300a06082a8648ce3d04030230193117301506035504030c0e74736d2e696d6b65792e74657374301e170d3236313031383132343430395a170d3336313031353132343430395a30193117301506035504030c0e74736d2e696d6b65792e746573743059301306072a8648ce3d020106082a8648ce3d030107034200047b857d51be91691ab55430a20693b0abcd28f4c8eeb4fe28d3d802f8d7ec1ac9edfe94aafcbb6f1e79239d8831cd62634779abcd1ef36318ad4699e42fc54c0ba3533051301d0603551d0e04160414e9658a57bf90ed2269742c634e592a1f8bae2475301f0603551d23041830168014e9658a57bf90ed2269742c634e592a1f8bae2475300f0603551d130101ff040530030101ff300a06082a8648ce3d0403020349003046022100f379aa394f52528988d8083472a247fbf4dbbaa4dd0b091c3c7abf640c8319bd0221008b201228d3787738f1bb3b8967051f0275eb85ebf740bdc68ff8aaa1210c08e4").unwrap();
+        let spki = subject_public_key_info(&cert).unwrap();
+        assert_eq!(
+            hex::encode(sha256_hash(spki)),
+            "0c734626e3d6775f8f31832bf5d7695ed3c552dfefa6ffd807c31a230e202e03"
+        );
+
+        assert!(subject_public_key_info(&cert[..cert.len() - 1]).is_none());
+        assert!(subject_public_key_info(b"not a certificate").is_none());
+        assert!(subject_public_key_info(&[]).is_none());
+    }
+}
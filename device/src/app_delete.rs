@@ -3,6 +3,7 @@ use crate::{Result, TsmService};
 use common::constants;
 use common::https;
 use serde::{Deserialize, Serialize};
+use transport::Transport;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -29,7 +30,7 @@ pub struct AppDeleteResponse {
 impl TsmService for AppDeleteRequest {
     type ReturnData = ();
 
-    fn send_message(&mut self) -> Result<()> {
+    fn send_message(&mut self, transport: &dyn Transport) -> Result<()> {
         loop {
             println!("send message：{:#?}", self);
             let req_data = serde_json::to_vec_pretty(&self).unwrap();
@@ -46,8 +47,9 @@ impl TsmService for AppDeleteRequest {
 
                 match return_bean._ReturnData.apdu_list {
                     Some(apdu_list) => {
-                        let handle_result =
-                            ServiceResponse::<AppDeleteResponse>::apdu_handle(apdu_list)?;
+                        let handle_result = ServiceResponse::<AppDeleteResponse>::apdu_handle(
+                            transport, apdu_list,
+                        )?;
                         self.card_ret_data_list = Some(handle_result.0);
                         self.status_word = Some(handle_result.1);
                         self.step_key = next_step_key;
@@ -80,18 +82,20 @@ mod test {
     use crate::app_delete::AppDeleteRequest;
     use crate::device_manager::{get_cert, get_se_id};
     use crate::TsmService;
-    use transport::hid_api::hid_connect;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     #[test]
     #[should_panic]
     pub fn app_delete_test() {
-        assert!(hid_connect("imKey Pro").is_ok());
-        let seid = get_se_id().unwrap();
-        let device_cert = get_cert().unwrap();
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let seid = get_se_id(&transport).unwrap();
+        let device_cert = get_cert(&transport).unwrap();
         let instance_aid = "695F627463".to_string();
         assert!(
             AppDeleteRequest::build_request_data(seid, instance_aid, device_cert)
-                .send_message()
+                .send_message(&transport)
                 .is_ok()
         );
     }
@@ -3,6 +3,7 @@ use crate::{Result, TsmService};
 use common::constants;
 use common::https;
 use serde::{Deserialize, Serialize};
+use transport::Transport;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -30,7 +31,7 @@ pub struct AppUpdateResponse {
 impl TsmService for AppUpdateRequest {
     type ReturnData = ();
 
-    fn send_message(&mut self) -> Result<()> {
+    fn send_message(&mut self, transport: &dyn Transport) -> Result<()> {
         loop {
             println!("send message：{:#?}", self);
             let req_data = serde_json::to_vec_pretty(&self).unwrap();
@@ -47,8 +48,9 @@ impl TsmService for AppUpdateRequest {
 
                 match return_bean._ReturnData.apdu_list {
                     Some(apdu_list) => {
-                        let handle_result =
-                            ServiceResponse::<AppUpdateResponse>::apdu_handle(apdu_list)?;
+                        let handle_result = ServiceResponse::<AppUpdateResponse>::apdu_handle(
+                            transport, apdu_list,
+                        )?;
                         self.card_ret_data_list = Some(handle_result.0);
                         self.status_word = Some(handle_result.1);
                         self.step_key = next_step_key;
@@ -87,28 +89,31 @@ mod test {
     use crate::app_update::AppUpdateRequest;
     use crate::device_manager::{get_cert, get_se_id};
     use crate::TsmService;
-    use transport::hid_api::hid_connect;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     #[test]
     pub fn app_update_test() {
-        assert!(hid_connect("imKey Pro").is_ok());
-        let seid = get_se_id().unwrap();
-        let device_cert = get_cert().unwrap();
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let seid = get_se_id(&transport).unwrap();
+        let device_cert = get_cert(&transport).unwrap();
         let instance_aid = "695F627463".to_string();
         let exe_result =
             AppUpdateRequest::build_request_data(seid, instance_aid, device_cert, None)
-                .send_message();
+                .send_message(&transport);
         assert!(exe_result.is_ok());
     }
 
     #[test]
     pub fn app_update_error_test() {
+        let transport = HidTransport::new();
         let seid = "00000000000000000000000000000000".to_string();
         let device_cert = "00000000000000000000000000".to_string();
         let instance_aid = "695F627463".to_string();
         assert!(
             AppUpdateRequest::build_request_data(seid, instance_aid, device_cert, None)
-                .send_message()
+                .send_message(&transport)
                 .is_err()
         );
     }
@@ -3,6 +3,7 @@ use crate::{Result, TsmService};
 use common::constants;
 use common::https;
 use serde::{Deserialize, Serialize};
+use transport::Transport;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -27,7 +28,7 @@ pub struct AuthCodeStorageResponse {
 impl TsmService for AuthCodeStorageRequest {
     type ReturnData = ();
 
-    fn send_message(&mut self) -> Result<()> {
+    fn send_message(&mut self, _transport: &dyn Transport) -> Result<()> {
         println!("send message：{:#?}", self);
         let req_data = serde_json::to_vec_pretty(&self).unwrap();
         let response_data = https::post(constants::TSM_ACTION_AUTHCODE_STORAGE, req_data)?;
@@ -56,13 +57,15 @@ mod test {
     use crate::auth_code_storage::AuthCodeStorageRequest;
     use crate::device_manager::get_se_id;
     use crate::TsmService;
+    use transport::hid_api::HidTransport;
 
     #[test]
     pub fn auth_code_storage_test() {
-        let seid = get_se_id().unwrap();
+        let transport = HidTransport::new();
+        let seid = get_se_id(&transport).unwrap();
         let auth_code: String = "33FE1AEAEB429C2C3798EBE67B709DBB7140FFD6FA9E8D5FF5476919B618904F88C3B52D8D05FAE7F88E9FAEC576A1EAFAF4D48B7B6670CBB368A34D6FD67F93F6BC008746BDB349A020FE88FBF566CC6ADE322E6B17325DD9AEF310495BECCE4634B61621FE776F4D217B8764CAC0FFD276FD4917CEA3EBECCF19D2EB7237DB395B9B68BA40FC6B040C257658E8D6D0DE9A67E49DF86D065BE1831B02751BBA00F8EDE4242859221C1D7DEF7547D1ADB43188D2A074AAA0C980A822B8B5363D8CCE81E829F83DA6E8D7530AD6F2785F07E417AE59AD2A414D5A203D3052F0106AC801C054374E6F4ADD024C9026E3D4DA9F947B0097F4E10B20E1B684AA6006".to_string();
         assert!(AuthCodeStorageRequest::build_request_data(seid, auth_code)
-            .send_message()
+            .send_message(&transport)
             .is_ok());
     }
 }
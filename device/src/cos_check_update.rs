@@ -2,6 +2,7 @@ use crate::ServiceResponse;
 use crate::{Result, TsmService};
 use common::{constants, https};
 use serde::{Deserialize, Serialize};
+use transport::Transport;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -26,7 +27,10 @@ pub struct CosCheckUpdateResponse {
 impl TsmService for CosCheckUpdateRequest {
     type ReturnData = ServiceResponse<CosCheckUpdateResponse>;
 
-    fn send_message(&mut self) -> Result<ServiceResponse<CosCheckUpdateResponse>> {
+    fn send_message(
+        &mut self,
+        _transport: &dyn Transport,
+    ) -> Result<ServiceResponse<CosCheckUpdateResponse>> {
         println!("send message：{:#?}", self);
         let req_data = serde_json::to_vec_pretty(&self).unwrap();
         let response_data = https::post(constants::TSM_ACTION_COS_CHECK_UPDATE, req_data)?;
@@ -54,14 +58,16 @@ impl CosCheckUpdateRequest {
 mod test {
     use crate::cos_check_update::CosCheckUpdateRequest;
     use crate::TsmService;
+    use transport::hid_api::HidTransport;
 
     #[test]
     #[cfg(not(tarpaulin))]
     pub fn cos_check_update_test() {
+        let transport = HidTransport::new();
         let seid: String = "18080000000000860001010000000106".to_string();
         let cos_version: String = "1.0.10".to_string();
         assert!(CosCheckUpdateRequest::build_request_data(seid, cos_version)
-            .send_message()
+            .send_message(&transport)
             .is_ok());
     }
 }
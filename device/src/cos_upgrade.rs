@@ -6,11 +6,7 @@ use crate::{Result, TsmService};
 use common::utility::hex_to_bytes;
 use common::{constants, https};
 use serde::{Deserialize, Serialize};
-use std::thread;
-use std::time::Duration;
-#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-use transport::hid_api::hid_connect;
-use transport::message::send_apdu;
+use transport::{ReconnectPolicy, Transport};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -39,9 +35,9 @@ pub struct CosUpgradeResponse {
 
 impl CosUpgradeRequest {
     #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-    pub fn cos_upgrade(sdk_version: Option<String>) -> Result<()> {
+    pub fn cos_upgrade(transport: &dyn Transport, sdk_version: Option<String>) -> Result<()> {
         //read se device cert
-        let mut device_cert = get_cert()?;
+        let mut device_cert = get_cert(transport)?;
         //        ApduCheck::checke_response(&device_cert)?; //TODO 在所有manager里的接口中增加check方法
 
         let mut is_jump = false;
@@ -51,11 +47,11 @@ impl CosUpgradeRequest {
         let mut is_bl_status = true;
         //read seid and sn number
         if device_cert.starts_with("bf21") || device_cert.starts_with("BF21") {
-            seid = get_se_id()?;
-            sn = get_sn()?;
+            seid = get_se_id(transport)?;
+            sn = get_sn(transport)?;
             is_bl_status = false;
             //read se cos version
-            se_cos_version = get_firmware_version()?;
+            se_cos_version = get_firmware_version(transport)?;
             se_cos_version = format!(
                 "{}.{}.{}",
                 se_cos_version[0..1].to_string(),
@@ -113,7 +109,7 @@ impl CosUpgradeRequest {
                     Some(apdu_list) => {
                         for (index_val, apdu_val) in apdu_list.iter().enumerate() {
                             //send apdu command and get return data
-                            let res = send_apdu(apdu_val.to_string())?;
+                            let res = transport.send_apdu(apdu_val.to_string())?;
                             apdu_res.push(res.clone());
                             if index_val == apdu_list.len() - 1 {
                                 request_data.status_word =
@@ -123,10 +119,10 @@ impl CosUpgradeRequest {
                                         .eq(&res[res.len() - 4..])
                                 {
                                     if "03".eq(next_step_key.as_str()) {
-                                        reconnect()?;
+                                        reconnect(transport)?;
                                     } else if "05".eq(next_step_key.as_str()) {
-                                        reconnect()?;
-                                        se_cos_version = get_firmware_version()?;
+                                        reconnect(transport)?;
+                                        se_cos_version = get_firmware_version(transport)?;
                                         se_cos_version = format!(
                                             "{}.{}.{}",
                                             se_cos_version[0..1].to_string(),
@@ -154,7 +150,7 @@ impl CosUpgradeRequest {
                                     device_cert.clone(),
                                     sdk_version.clone(),
                                 )
-                                .send_message()?;
+                                .send_message(transport)?;
                             }
                         }
                         None => (),
@@ -172,29 +168,23 @@ impl CosUpgradeRequest {
 reconnect device
 */
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-fn reconnect() -> Result<()> {
-    thread::sleep(Duration::from_millis(1000));
-
-    for _ in 0..5 {
-        if hid_connect(constants::DEVICE_MODEL_NAME).is_ok() {
-            return Ok(());
-        }
-        thread::sleep(Duration::from_millis(1000));
-        continue;
-    }
-
-    Err(ImkeyError::ImkeyDeviceReconnectFail.into())
+fn reconnect(transport: &dyn Transport) -> Result<()> {
+    ReconnectPolicy::default()
+        .retry(|| transport.connect())
+        .map_err(|_| ImkeyError::ImkeyDeviceReconnectFail.into())
 }
 
 #[cfg(test)]
 mod tests {
     use crate::cos_upgrade::CosUpgradeRequest;
-    use transport::hid_api::hid_connect;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     #[test]
     #[cfg(not(tarpaulin))]
     fn cos_upgrade_test() {
-        assert!(hid_connect("imKey Pro").is_ok());
-        assert!(CosUpgradeRequest::cos_upgrade(None).is_ok());
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        assert!(CosUpgradeRequest::cos_upgrade(&transport, None).is_ok());
     }
 }
@@ -15,6 +15,7 @@ use common::constants::{
     BIND_STATUS_UNBOUND, IMK_AID,
 };
 use rand::rngs::OsRng;
+use rand::RngCore;
 use regex::Regex;
 use ring::digest;
 use rsa::{BigUint, PaddingScheme, PublicKey as RSAPublic, RSAPublicKey};
@@ -22,13 +23,17 @@ use secp256k1::ecdh::SharedSecret;
 use secp256k1::{PublicKey, SecretKey};
 use sha1::Sha1;
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use transport::error::SecureChannelError;
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-use transport::hid_api::hid_connect;
-use transport::message::send_apdu;
+use transport::hid_api::HidTransport;
+use transport::secure_channel::{SecureChannel, SecureTransport, CHALLENGE_SIZE, CRYPTOGRAM_SIZE};
+use transport::Transport;
 
 lazy_static! {
-    pub static ref KEY_MANAGER: Mutex<KeyManager> = Mutex::new(KeyManager::new());
+    //the bind keys of every key bound in this process, by device id
+    static ref KEY_MANAGERS: Mutex<HashMap<String, Arc<Mutex<KeyManager>>>> =
+        Mutex::new(HashMap::new());
     static ref BIND_STATUS_MAP: HashMap<&'static str, &'static str> = {
         let mut bind_status_mapping = HashMap::new();
         bind_status_mapping.insert(BIND_STATUS_UNBOUND, "unbound");
@@ -40,16 +45,29 @@ lazy_static! {
     };
 }
 
+/**
+the bind keys of the key the transport talks to, each attached key is bound on its own
+*/
+pub fn key_manager(transport: &dyn Transport) -> Arc<Mutex<KeyManager>> {
+    KEY_MANAGERS
+        .lock()
+        .unwrap()
+        .entry(transport.device_id())
+        .or_insert_with(|| Arc::new(Mutex::new(KeyManager::new())))
+        .clone()
+}
+
 pub struct DeviceManage {}
 
 impl DeviceManage {
-    pub fn bind_check(file_path: &String) -> Result<String> {
+    pub fn bind_check(transport: &dyn Transport, file_path: &String) -> Result<String> {
         //get seid
-        let seid = device_manager::get_se_id()?;
+        let seid = device_manager::get_se_id(transport)?;
         //get SN number
-        let sn = device_manager::get_sn()?;
+        let sn = device_manager::get_sn(transport)?;
         //Calculate encryption key
-        let mut key_manager_obj = KEY_MANAGER.lock().unwrap();
+        let key_manager = key_manager(transport);
+        let mut key_manager_obj = key_manager.lock().unwrap();
         key_manager_obj.gen_encrypt_key(&seid, &sn);
 
         //Get the ciphertext of the local key file
@@ -69,8 +87,8 @@ impl DeviceManage {
         //gen bindchec apdu
         let bind_check_apdu = ImkApdu::bind_check(&key_manager_obj.pub_key);
         //send bindcheck command and get return data
-        select_imk_applet()?;
-        let bind_check_apdu_resp_data = send_apdu(bind_check_apdu)?;
+        select_imk_applet(transport)?;
+        let bind_check_apdu_resp_data = transport.send_apdu(bind_check_apdu)?;
         ApduCheck::checke_response(bind_check_apdu_resp_data.as_str())?;
 
         let status = String::from(&bind_check_apdu_resp_data[..2]);
@@ -80,7 +98,7 @@ impl DeviceManage {
         if status.eq(BIND_STATUS_UNBOUND) || status.eq(BIND_STATUS_BOUND_OTHER) {
             //check se cert
             DeviceCertCheckRequest::build_request_data(seid.clone(), sn, se_pub_key_cert.clone())
-                .send_message()?;
+                .send_message(transport)?;
 
             //get se public key
             key_manager_obj.se_pub_key = hex::decode(get_se_pubkey(se_pub_key_cert)?)?;
@@ -110,9 +128,8 @@ impl DeviceManage {
         Ok(BIND_STATUS_MAP.get(status.as_str()).unwrap().to_string())
     }
 
-    pub fn bind_acquire(binding_code: &String) -> Result<String> {
+    pub fn bind_acquire(transport: &dyn Transport, binding_code: &String) -> Result<String> {
         let temp_binding_code = binding_code.to_uppercase();
-        let binding_code_bytes = temp_binding_code.as_bytes();
         //check auth code
         let bind_code_verify_regex = Regex::new(r"^[A-HJ-NP-Z2-9]{8}$").unwrap();
         if !bind_code_verify_regex.is_match(temp_binding_code.as_ref()) {
@@ -122,15 +139,24 @@ impl DeviceManage {
         let auth_code_ciphertext = auth_code_encrypt(&temp_binding_code)?;
 
         //save auth Code cipher
-        let seid = device_manager::get_se_id()?;
-        AuthCodeStorageRequest::build_request_data(seid, auth_code_ciphertext).send_message()?;
+        let seid = device_manager::get_se_id(transport)?;
+        AuthCodeStorageRequest::build_request_data(seid, auth_code_ciphertext)
+            .send_message(transport)?;
+
+        Self::identity_verify(transport, &temp_binding_code)
+    }
 
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
+    /**
+    prove the bind code to the device with the session key
+    */
+    pub fn identity_verify(transport: &dyn Transport, binding_code: &String) -> Result<String> {
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
         //select IMK applet
-        select_imk_applet()?;
+        select_imk_applet(transport)?;
         //calc HASH
         let mut data: Vec<u8> = vec![];
-        data.extend(binding_code_bytes);
+        data.extend(binding_code.as_bytes());
         data.extend(&key_manager_obj.pub_key);
         data.extend(&key_manager_obj.se_pub_key);
         let data_hash = digest::digest(&digest::SHA256, data.as_slice());
@@ -139,7 +165,7 @@ impl DeviceManage {
         type Aes128Cbc = Cbc<Aes128, Pkcs7>;
         let cipher = Aes128Cbc::new_var(
             &key_manager_obj.session_key,
-            &gen_iv(&temp_binding_code).as_ref(),
+            &gen_iv(binding_code).as_ref(),
         )?;
 
         let ciphertext = cipher.encrypt_vec(data_hash.as_ref());
@@ -150,7 +176,7 @@ impl DeviceManage {
         let identity_verify_apdu = ImkApdu::identity_verify(&apdu_data);
         std::mem::drop(key_manager_obj);
         //send command to device
-        let bind_result = send_apdu(identity_verify_apdu)?;
+        let bind_result = transport.send_apdu(identity_verify_apdu)?;
         ApduCheck::checke_response(&bind_result)?;
         Ok(BIND_STATUS_MAP
             .get(&bind_result[..bind_result.len() - 4])
@@ -158,22 +184,53 @@ impl DeviceManage {
             .to_string())
     }
 
-    pub fn display_bind_code() -> Result<()> {
-        select_imk_applet()?;
-        let gen_auth_code_ret_data = send_apdu(ImkApdu::generate_auth_code())?;
+    pub fn display_bind_code(transport: &dyn Transport) -> Result<()> {
+        select_imk_applet(transport)?;
+        let gen_auth_code_ret_data = transport.send_apdu(ImkApdu::generate_auth_code())?;
         ApduCheck::checke_response(&gen_auth_code_ret_data)
     }
+
+    /**
+    open a secure channel under the session key of the last bind check, the commands after it
+    go encrypted and maced until the transport is closed or disconnected
+    */
+    pub fn open_secure_channel(transport: &SecureTransport) -> Result<()> {
+        let session_key = key_manager(transport).lock().unwrap().session_key.clone();
+        //no session key before a bind check
+        if session_key.is_empty() {
+            return Err(SecureChannelError::SessionKeyInvalid.into());
+        }
+        //the open command and the select before it go plain
+        transport.close();
+        select_imk_applet(transport)?;
+        let mut host_challenge = [0u8; CHALLENGE_SIZE];
+        OsRng::new()?.fill_bytes(&mut host_challenge);
+        let response = transport.send_apdu(ImkApdu::open_secure_channel(&host_challenge))?;
+        ApduCheck::checke_response(&response)?;
+
+        let response_data = hex::decode(&response[..response.len() - 4])?;
+        if response_data.len() != CHALLENGE_SIZE + CRYPTOGRAM_SIZE {
+            return Err(SecureChannelError::ResponseInvalid.into());
+        }
+        let (card_challenge, card_cryptogram) = response_data.split_at(CHALLENGE_SIZE);
+        let channel = SecureChannel::new(&session_key, &host_challenge, card_challenge)?;
+        if channel.card_cryptogram() != card_cryptogram {
+            return Err(SecureChannelError::CryptogramMismatch.into());
+        }
+        transport.open(channel);
+        Ok(())
+    }
 }
 
-fn select_imk_applet() -> Result<()> {
-    let apdu_response = send_apdu(Apdu::select_applet(IMK_AID))?;
+fn select_imk_applet(transport: &dyn Transport) -> Result<()> {
+    let apdu_response = transport.send_apdu(Apdu::select_applet(IMK_AID))?;
     ApduCheck::checke_response(apdu_response.as_str())
 }
 
 /**
 generator iv
 */
-fn gen_iv(auth_code: &String) -> [u8; 16] {
+pub fn gen_iv(auth_code: &String) -> [u8; 16] {
     let salt_bytes = digest::digest(&digest::SHA256, "bindingCode".as_bytes());
     let auth_code_hash = digest::digest(&digest::SHA256, auth_code.as_bytes());
     let mut result = [0u8; 32];
@@ -217,53 +274,57 @@ fn get_se_pubkey(se_pubkey_cert: String) -> Result<String> {
 }
 
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-pub fn bind_test() {
+pub fn bind_test() -> HidTransport {
     //binding device
-       let path = "/Users/joe/work/sdk_gen_key".to_string();
-       let bind_code = "YDSGQPKX".to_string();
+    let path = "/Users/joe/work/sdk_gen_key".to_string();
+    let bind_code = "YDSGQPKX".to_string();
 
     // let path = "/tmp/".to_string();
     // let bind_code = "PVU3FY64".to_string();
 
-    assert!(hid_connect("imKey Pro").is_ok());
-    let check_result = DeviceManage::bind_check(&path).unwrap_or_default();
+    let transport = HidTransport::new();
+    assert!(transport.connect().is_ok());
+    let check_result = DeviceManage::bind_check(&transport, &path).unwrap_or_default();
     if !"bound_this".eq(check_result.as_str()) {
         //If it is not bound to this device, then perform the binding operation
-        let bind_result = DeviceManage::bind_acquire(&bind_code).unwrap_or_default();
+        let bind_result = DeviceManage::bind_acquire(&transport, &bind_code).unwrap_or_default();
         if "5A".eq(bind_result.as_str()) {
             println!("{:?}", "binding success");
         } else {
             println!("{:?}", "binding error");
-            return;
+            return transport;
         }
     } else {
         println!("bind this");
     }
+    transport
 }
 
 #[cfg(test)]
 mod test {
     use crate::device_binding::{auth_code_encrypt, gen_iv, DeviceManage};
     use crate::device_manager::bind_display_code;
-    use transport::hid_api::hid_connect;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     #[test]
     fn device_bind_test() {
         let path = "/tmp/".to_string();
         let bind_code = "PVU3FY64".to_string();
 
-        assert!(hid_connect("imKey Pro").is_ok());
-        let check_result = DeviceManage::bind_check(&path).unwrap();
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let check_result = DeviceManage::bind_check(&transport, &path).unwrap();
         let mut bind_result = String::new();
         println!("result:{}", &check_result);
         if check_result.as_str().eq("unbound") {
-            assert!(bind_display_code().is_ok());
+            assert!(bind_display_code(&transport).is_ok());
             let mut bind_code_temp = String::new();
             println!("please input bind code:");
             let _bl = std::io::stdin().read_line(&mut bind_code_temp).unwrap();
-            bind_result = DeviceManage::bind_acquire(&bind_code_temp).unwrap();
+            bind_result = DeviceManage::bind_acquire(&transport, &bind_code_temp).unwrap();
         } else if check_result.as_str().eq("bound_other") {
-            bind_result = DeviceManage::bind_acquire(&bind_code).unwrap();
+            bind_result = DeviceManage::bind_acquire(&transport, &bind_code).unwrap();
         } else {
             ();
         }
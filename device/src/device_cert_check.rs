@@ -4,6 +4,7 @@ use crate::{Result, TsmService};
 use common::constants;
 use common::https;
 use serde::{Deserialize, Serialize};
+use transport::Transport;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -30,7 +31,7 @@ pub struct DeviceCertCheckResponse {
 impl TsmService for DeviceCertCheckRequest {
     type ReturnData = ();
 
-    fn send_message(&mut self) -> Result<()> {
+    fn send_message(&mut self, _transport: &dyn Transport) -> Result<()> {
         println!("send message：{:#?}", self);
         let req_data = serde_json::to_vec_pretty(&self).unwrap();
         let response_data = https::post(constants::TSM_ACTION_DEVICE_CERT_CHECK, req_data)?;
@@ -69,18 +70,20 @@ mod test {
     use crate::device_cert_check::DeviceCertCheckRequest;
     use crate::device_manager::{get_cert, get_se_id, get_sn};
     use crate::TsmService;
-    use transport::hid_api::hid_connect;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     #[test]
     #[cfg(not(tarpaulin))]
     pub fn device_cert_check_test() {
-        assert!(hid_connect("imKey Pro").is_ok());
-        let seid = get_se_id().unwrap();
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let seid = get_se_id(&transport).unwrap();
         let device_cert = "7F2181C5931018080000000000860001010000000106420200015F200401020304950200805F2504201810145F2404FFFFFFFF53007F4947B0410403089D8A83A87F24D906303A49D39669D17B0F7AB76EB098A65AFEF31154E75DEE5B87B69CBF78F11E831A4961C8A8F031C2869EA0716C798F76F5E91338DC35F002DFFE5F37473045022100EB46DC605568CF8D5051CD67CEC234C66FC6E2561D2FE57D8DDF8D4D204695A6022009C246BE380DD2A8972807D2AE2A0FE22877408717E239AAA0C2530524714A48".to_string();
-        let sn = get_sn().unwrap();
+        let sn = get_sn(&transport).unwrap();
         assert!(
             DeviceCertCheckRequest::build_request_data(seid, sn, device_cert)
-                .send_message()
+                .send_message(&transport)
                 .is_ok()
         );
     }
@@ -0,0 +1,208 @@
+use crate::device_manager::{get_se_id, get_sn, is_bl_status};
+use crate::Result;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use transport::bridge::BRIDGE_ENV;
+use transport::error::{BridgeError, HidError};
+use transport::hid_api::{self, HidDeviceInfo, HidTransport};
+use transport::hid_watcher::DeviceWatcher;
+use transport::Transport;
+
+lazy_static! {
+    //what every key answered when it was last asked, by usb serial number or by path without one
+    static ref ANSWERED: Mutex<HashMap<String, ImkeyDevice>> = Mutex::new(HashMap::new());
+}
+
+/**
+an attached imKey and what it answers about itself. sn and seid stay empty when the key does
+not answer them, in bootloader mode or when another process holds it. a key a transport of
+this process holds is not asked, it is reported with what it answered last
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImkeyDevice {
+    pub path: String,
+    pub serial_number: String,
+    pub sn: String,
+    pub seid: String,
+    pub is_bl_status: bool,
+}
+
+fn answered_key(device: &ImkeyDevice) -> String {
+    if device.serial_number.is_empty() {
+        device.path.clone()
+    } else {
+        device.serial_number.clone()
+    }
+}
+
+/**
+every attached imKey, each asked for its sn, seid and mode over a connection of its own
+*/
+pub fn list_devices() -> Result<Vec<ImkeyDevice>> {
+    let mut devices = vec![];
+    for info in hid_api::enumerate_devices()? {
+        let mut device = ImkeyDevice {
+            path: info.path,
+            serial_number: info.serial_number,
+            sn: String::new(),
+            seid: String::new(),
+            is_bl_status: false,
+        };
+        let transport = HidTransport::probe(&device.path);
+        match transport.connect() {
+            Ok(()) => {
+                device.seid = get_se_id(&transport).unwrap_or_default();
+                device.sn = get_sn(&transport).unwrap_or_default();
+                //selecting the bootloader last leaves the identity queries unaffected
+                device.is_bl_status = is_bl_status(&transport).unwrap_or(false);
+                transport.disconnect()?;
+                ANSWERED
+                    .lock()
+                    .unwrap()
+                    .insert(answered_key(&device), device.clone());
+            }
+            Err(err) if err.downcast_ref::<HidError>() == Some(&HidError::DeviceBusy) => {
+                //apdus of a probe would land in the middle of the session using the key
+                if let Some(answered) = ANSWERED.lock().unwrap().get(&answered_key(&device)) {
+                    device.sn = answered.sn.clone();
+                    device.seid = answered.seid.clone();
+                    device.is_bl_status = answered.is_bl_status;
+                }
+            }
+            Err(_) => {}
+        }
+        devices.push(device);
+    }
+    Ok(devices)
+}
+
+/**
+whether the key at a path is in bootloader mode, none when it cannot be opened or a transport
+of this process holds it
+*/
+pub fn probe_mode(device: &HidDeviceInfo) -> Option<bool> {
+    let transport = HidTransport::probe(&device.path);
+    transport.connect().ok()?;
+    let is_bl_status = is_bl_status(&transport).ok();
+    transport.disconnect().ok()?;
+    is_bl_status
+}
+
+/**
+a watcher that also reports the keys coming back in another mode
+*/
+pub fn device_watcher() -> DeviceWatcher {
+    DeviceWatcher::with_probe(Box::new(probe_mode))
+}
+
+/**
+the device with the sn and the seid asked for, an empty one matches any. the sn is also
+compared with the usb serial number for keys that do not answer apdus
+*/
+pub fn find_device<'a>(
+    devices: &'a [ImkeyDevice],
+    sn: &str,
+    seid: &str,
+) -> Option<&'a ImkeyDevice> {
+    if sn.is_empty() && seid.is_empty() {
+        return None;
+    }
+    devices.iter().find(|device| {
+        (sn.is_empty()
+            || device.sn.eq_ignore_ascii_case(sn)
+            || device.serial_number.eq_ignore_ascii_case(sn))
+            && (seid.is_empty() || device.seid.eq_ignore_ascii_case(seid))
+    })
+}
+
+/**
+points the transport at the imKey with the sn or seid and connects it. with neither the first
+attached imKey is connected as before. over a bridge the key is the one the bridge serves,
+so one can not be picked by sn or seid
+*/
+pub fn connect_device(
+    transport: &dyn Transport,
+    sn: &str,
+    seid: &str,
+) -> Result<Option<ImkeyDevice>> {
+    if sn.is_empty() && seid.is_empty() {
+        hid_api::select_device(None);
+        transport.connect()?;
+        return Ok(None);
+    }
+    if env::var_os(BRIDGE_ENV).is_some() {
+        return Err(BridgeError::DeviceSelectUnsupported.into());
+    }
+    let devices = list_devices()?;
+    let device = find_device(&devices, sn, seid)
+        .ok_or(HidError::DeviceNotFound)?
+        .clone();
+    hid_api::select_device(Some(&device.path));
+    transport.connect()?;
+    Ok(Some(device))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device_discovery::{find_device, list_devices, ImkeyDevice};
+
+    fn device(path: &str, serial_number: &str, sn: &str, seid: &str) -> ImkeyDevice {
+        ImkeyDevice {
+            path: path.to_string(),
+            serial_number: serial_number.to_string(),
+            sn: sn.to_string(),
+            seid: seid.to_string(),
+            is_bl_status: sn.is_empty(),
+        }
+    }
+
+    #[test]
+    fn find_device_test() {
+        let devices = vec![
+            device(
+                "/dev/hidraw0",
+                "",
+                "imKey01191200001",
+                "19060000000200860001010000000014",
+            ),
+            device(
+                "/dev/hidraw1",
+                "",
+                "imKey01191200002",
+                "19060000000200860001010000000015",
+            ),
+            device("/dev/hidraw2", "IMKEY01191200003", "", ""),
+        ];
+        assert_eq!(
+            find_device(&devices, "imKey01191200002", "").unwrap().path,
+            "/dev/hidraw1"
+        );
+        assert_eq!(
+            find_device(&devices, "", "19060000000200860001010000000014")
+                .unwrap()
+                .path,
+            "/dev/hidraw0"
+        );
+        //a key in bootloader mode is found by its usb serial number
+        assert_eq!(
+            find_device(&devices, "imkey01191200003", "").unwrap().path,
+            "/dev/hidraw2"
+        );
+        //both have to match
+        assert!(find_device(
+            &devices,
+            "imKey01191200001",
+            "19060000000200860001010000000015"
+        )
+        .is_none());
+        assert!(find_device(&devices, "", "").is_none());
+    }
+
+    #[test]
+    fn list_devices_test() {
+        for device in list_devices().unwrap() {
+            assert!(!device.path.is_empty());
+        }
+    }
+}
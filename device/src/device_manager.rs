@@ -15,18 +15,19 @@ use common::apdu::{Apdu, ApduCheck};
 use common::applet;
 use common::constants;
 use se_activate::SeActivateRequest;
-use transport::message::send_apdu;
+use transport::secure_channel::SecureTransport;
+use transport::Transport;
 
-pub fn get_se_id() -> Result<String> {
-    send_apdu("00A4040000".to_string())?;
-    let res = send_apdu("80CB800005DFFF028101".to_string())?;
+pub fn get_se_id(transport: &dyn Transport) -> Result<String> {
+    transport.send_apdu("00A4040000".to_string())?;
+    let res = transport.send_apdu("80CB800005DFFF028101".to_string())?;
     ApduCheck::checke_response(res.as_str())?;
     Ok(String::from(&res[0..res.len() - 4]))
 }
 
-pub fn get_sn() -> Result<String> {
-    send_apdu("00A4040000".to_string())?;
-    let res = send_apdu("80CA004400".to_string())?;
+pub fn get_sn(transport: &dyn Transport) -> Result<String> {
+    transport.send_apdu("00A4040000".to_string())?;
+    let res = transport.send_apdu("80CA004400".to_string())?;
     ApduCheck::checke_response(res.as_str())?;
     let hex_decode = hex::decode(String::from(&res[0..res.len() - 4]));
     match hex_decode {
@@ -35,143 +36,149 @@ pub fn get_sn() -> Result<String> {
     }
 }
 
-pub fn get_ram_size() -> Result<String> {
+pub fn get_ram_size(transport: &dyn Transport) -> Result<String> {
     //send_apdu("00A4040000".to_string());
-    let res = send_apdu("80CB800005DFFF02814600".to_string())?;
+    let res = transport.send_apdu("80CB800005DFFF02814600".to_string())?;
     ApduCheck::checke_response(res.as_str())?;
     Ok(res.chars().take(res.len() - 4).collect())
 }
 
-pub fn get_firmware_version() -> Result<String> {
-    send_apdu("00A4040000".to_string())?;
-    let res = send_apdu("80CB800005DFFF02800300".to_string())?;
+pub fn get_firmware_version(transport: &dyn Transport) -> Result<String> {
+    transport.send_apdu("00A4040000".to_string())?;
+    let res = transport.send_apdu("80CB800005DFFF02800300".to_string())?;
     ApduCheck::checke_response(res.as_str())?;
     Ok(res.chars().take(res.len() - 4).collect())
 }
 
-pub fn get_battery_power() -> Result<String> {
-    send_apdu("00A4040000".to_string())?;
-    let res = send_apdu("00D6FEED01".to_string())?;
+pub fn get_battery_power(transport: &dyn Transport) -> Result<String> {
+    transport.send_apdu("00A4040000".to_string())?;
+    let res = transport.send_apdu("00D6FEED01".to_string())?;
     ApduCheck::checke_response(res.as_str())?;
     Ok(res.chars().take(res.len() - 4).collect())
 }
 
-pub fn get_life_time() -> Result<String> {
+pub fn get_life_time(transport: &dyn Transport) -> Result<String> {
     //send_apdu("00A4040000".to_string());
-    let res = send_apdu("FFDCFEED00".to_string())?;
+    let res = transport.send_apdu("FFDCFEED00".to_string())?;
     ApduCheck::checke_response(res.as_str())?;
     Ok(res.chars().take(res.len() - 4).collect())
 }
 
-pub fn get_ble_name() -> Result<String> {
+pub fn get_ble_name(transport: &dyn Transport) -> Result<String> {
     //send_apdu("00A4040000".to_string());
-    let res = send_apdu("FFDB465400".to_string())?;
+    let res = transport.send_apdu("FFDB465400".to_string())?;
     Ok(res.chars().collect())
 }
 
-pub fn set_ble_name(ble_name: String) -> Result<String> {
+pub fn set_ble_name(transport: &dyn Transport, ble_name: String) -> Result<String> {
     let apdu = Apdu::set_ble_name(ble_name.as_ref());
-    let res = send_apdu(apdu)?;
+    let res = transport.send_apdu(apdu)?;
     Ok(res.chars().take(res.len() - 4).collect())
 }
 
-pub fn get_ble_version() -> Result<String> {
-    send_apdu("00A4040000".to_string())?;
-    let res = send_apdu("80CB800005DFFF02810000".to_string())?;
+pub fn get_ble_version(transport: &dyn Transport) -> Result<String> {
+    transport.send_apdu("00A4040000".to_string())?;
+    let res = transport.send_apdu("80CB800005DFFF02810000".to_string())?;
     Ok(res.chars().take(res.len() - 4).collect())
 }
 
-pub fn get_cert() -> Result<String> {
-    send_apdu("00A4040000".to_string())?;
-    let res = send_apdu("80CABF2106A6048302151800".to_string())?;
+pub fn get_cert(transport: &dyn Transport) -> Result<String> {
+    transport.send_apdu("00A4040000".to_string())?;
+    let res = transport.send_apdu("80CABF2106A6048302151800".to_string())?;
     ApduCheck::checke_response(&res)?;
     Ok(res.chars().take(res.len() - 4).collect())
 }
 
-pub fn check_device() -> Result<()> {
-    let seid: String = get_se_id()?;
-    let sn: String = get_sn()?;
-    let device_cert: String = get_cert()?;
-    SeSecureCheckRequest::build_request_data(seid, sn, device_cert).send_message()
+pub fn check_device(transport: &dyn Transport) -> Result<()> {
+    let seid: String = get_se_id(transport)?;
+    let sn: String = get_sn(transport)?;
+    let device_cert: String = get_cert(transport)?;
+    SeSecureCheckRequest::build_request_data(seid, sn, device_cert).send_message(transport)
 }
 
-pub fn active_device() -> Result<()> {
-    let seid: String = get_se_id()?;
-    let sn: String = get_sn()?;
-    let device_cert: String = get_cert()?;
-    SeActivateRequest::build_request_data(seid, sn, device_cert).send_message()
+pub fn active_device(transport: &dyn Transport) -> Result<()> {
+    let seid: String = get_se_id(transport)?;
+    let sn: String = get_sn(transport)?;
+    let device_cert: String = get_cert(transport)?;
+    SeActivateRequest::build_request_data(seid, sn, device_cert).send_message(transport)
 }
 
-pub fn check_update() -> Result<ServiceResponse<SeQueryResponse>> {
-    let seid: String = get_se_id()?;
-    let sn: String = get_sn()?;
+pub fn check_update(transport: &dyn Transport) -> Result<ServiceResponse<SeQueryResponse>> {
+    let seid: String = get_se_id(transport)?;
+    let sn: String = get_sn(transport)?;
     let sdk_version = Some(constants::VERSION.to_string());
-    SeQueryRequest::build_request_data(seid, sn, sdk_version).send_message()
+    SeQueryRequest::build_request_data(seid, sn, sdk_version).send_message(transport)
 }
 
-pub fn app_download(app_name: &str) -> Result<()> {
-    let seid: String = get_se_id()?;
-    let device_cert: String = get_cert()?;
+pub fn app_download(transport: &dyn Transport, app_name: &str) -> Result<()> {
+    let seid: String = get_se_id(transport)?;
+    let device_cert: String = get_cert(transport)?;
     let sdk_version = Some(constants::VERSION.to_string());
     let instance_aid: String = applet::get_instid_by_appname(app_name)
         .expect("imkey_app_name_not_exist")
         .to_string();
     AppDownloadRequest::build_request_data(seid, instance_aid, device_cert, sdk_version)
-        .send_message()
+        .send_message(transport)
 }
 
-pub fn app_update(app_name: &str) -> Result<()> {
-    let seid: String = get_se_id()?;
-    let device_cert: String = get_cert()?;
+pub fn app_update(transport: &dyn Transport, app_name: &str) -> Result<()> {
+    let seid: String = get_se_id(transport)?;
+    let device_cert: String = get_cert(transport)?;
     let sdk_version = Some(constants::VERSION.to_string());
     let instance_aid: String = applet::get_instid_by_appname(app_name)
         .expect("imkey_app_name_not_exist")
         .to_string();
     AppUpdateRequest::build_request_data(seid, instance_aid, device_cert, sdk_version)
-        .send_message()
+        .send_message(transport)
 }
 
-pub fn app_delete(app_name: &str) -> Result<()> {
-    let seid: String = get_se_id()?;
-    let device_cert: String = get_cert()?;
+pub fn app_delete(transport: &dyn Transport, app_name: &str) -> Result<()> {
+    let seid: String = get_se_id(transport)?;
+    let device_cert: String = get_cert(transport)?;
     let instance_aid: String = applet::get_instid_by_appname(app_name)
         .expect("imkey_app_name_not_exist")
         .to_string();
-    AppDeleteRequest::build_request_data(seid, instance_aid, device_cert).send_message()
+    AppDeleteRequest::build_request_data(seid, instance_aid, device_cert).send_message(transport)
 }
 
-pub fn bind_check(file_path: &str) -> Result<String> {
-    DeviceManage::bind_check(&file_path.to_string())
+pub fn bind_check(transport: &dyn Transport, file_path: &str) -> Result<String> {
+    DeviceManage::bind_check(transport, &file_path.to_string())
 }
 
-pub fn bind_display_code() -> Result<()> {
-    DeviceManage::display_bind_code()
+pub fn bind_display_code(transport: &dyn Transport) -> Result<()> {
+    DeviceManage::display_bind_code(transport)
 }
 
-pub fn bind_acquire(bind_code: &str) -> Result<String> {
-    DeviceManage::bind_acquire(&bind_code.to_string())
+pub fn bind_acquire(transport: &dyn Transport, bind_code: &str) -> Result<String> {
+    DeviceManage::bind_acquire(transport, &bind_code.to_string())
+}
+
+pub fn open_secure_channel(transport: &SecureTransport) -> Result<()> {
+    DeviceManage::open_secure_channel(transport)
 }
 
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-pub fn cos_upgrade() -> Result<()> {
-    CosUpgradeRequest::cos_upgrade(None)
+pub fn cos_upgrade(transport: &dyn Transport) -> Result<()> {
+    CosUpgradeRequest::cos_upgrade(transport, None)
 }
 
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-pub fn cos_check_update() -> Result<ServiceResponse<CosCheckUpdateResponse>> {
-    let seid = get_se_id()?;
-    let mut cos_version = get_firmware_version()?;
+pub fn cos_check_update(
+    transport: &dyn Transport,
+) -> Result<ServiceResponse<CosCheckUpdateResponse>> {
+    let seid = get_se_id(transport)?;
+    let mut cos_version = get_firmware_version(transport)?;
     cos_version = format!(
         "{}.{}.{}",
         cos_version[0..1].to_string(),
         cos_version[1..2].to_string(),
         cos_version[2..].to_string()
     );
-    CosCheckUpdateRequest::build_request_data(seid, cos_version).send_message()
+    CosCheckUpdateRequest::build_request_data(seid, cos_version).send_message(transport)
 }
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-pub fn is_bl_status() -> Result<bool> {
-    let res_data = send_apdu(Apdu::select_applet(constants::BL_AID))?;
+pub fn is_bl_status(transport: &dyn Transport) -> Result<bool> {
+    let res_data = transport.send_apdu(Apdu::select_applet(constants::BL_AID))?;
     let check_result = ApduCheck::checke_response(res_data.as_str());
     if check_result.is_err() {
         return Ok(false);
@@ -184,71 +191,80 @@ mod test {
     use crate::device_manager::{
         active_device, app_delete, app_download, app_update, bind_check, is_bl_status,
     };
-    use common::constants;
-    use transport::hid_api::hid_connect;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     #[test]
     fn is_bl_status_test() {
-        assert!(hid_connect(constants::DEVICE_MODEL_NAME).is_ok());
-        let result = is_bl_status();
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let result = is_bl_status(&transport);
         assert!(result.is_ok());
     }
 
     #[test]
     fn app_delete_test() {
-        assert!(hid_connect(constants::DEVICE_MODEL_NAME).is_ok());
-        let result = app_delete("COSMOS");
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let result = app_delete(&transport, "COSMOS");
         assert!(result.is_ok());
     }
 
     #[test]
     #[should_panic(expected = "imkey_app_name_not_exist")]
     fn app_delete_wrong_app_name_test() {
-        assert!(hid_connect(constants::DEVICE_MODEL_NAME).is_ok());
-        app_delete("TEST");
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        app_delete(&transport, "TEST");
     }
 
     #[test]
     fn app_download_test() {
-        assert!(hid_connect(constants::DEVICE_MODEL_NAME).is_ok());
-        let result = app_download("COSMOS");
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let result = app_download(&transport, "COSMOS");
         assert!(result.is_ok());
     }
 
     #[test]
     #[should_panic(expected = "imkey_app_name_not_exist")]
     fn app_download_wrong_appname_test() {
-        assert!(hid_connect(constants::DEVICE_MODEL_NAME).is_ok());
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
         //Enter the wrong app name
-        let result = app_download("TEST");
+        let result = app_download(&transport, "TEST");
     }
 
     #[test]
     fn app_update_test() {
-        assert!(hid_connect(constants::DEVICE_MODEL_NAME).is_ok());
-        let result = app_update("COSMOS");
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let result = app_update(&transport, "COSMOS");
         assert!(result.is_ok());
     }
 
     #[test]
     #[should_panic(expected = "imkey_app_name_not_exist")]
     fn app_update_wrong_app_name_test() {
-        assert!(hid_connect(constants::DEVICE_MODEL_NAME).is_ok());
-        let result = app_update("TEST");
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let result = app_update(&transport, "TEST");
     }
 
     #[test]
     #[should_panic(expected = "No such file or directory")]
     fn bind_check_wrong_path_test() {
-        assert!(hid_connect(constants::DEVICE_MODEL_NAME).is_ok());
-        let result = bind_check("/test/");
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let result = bind_check(&transport, "/test/");
         assert!(result.is_ok());
     }
 
     #[test]
     fn active_device_test() {
-        assert!(hid_connect(constants::DEVICE_MODEL_NAME).is_ok());
-        let result = active_device();
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let result = active_device(&transport);
         assert!(result.is_ok());
     }
 }
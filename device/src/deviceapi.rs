@@ -116,6 +116,48 @@ pub struct GetSdkInfoRes {
 pub struct DeviceConnectReq {
     #[prost(string, tag = "1")]
     pub device_model_name: std::string::String,
+    /// the key to connect among several attached ones, the first one when both are empty
+    #[prost(string, tag = "2")]
+    pub sn: std::string::String,
+    #[prost(string, tag = "3")]
+    pub seid: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImkeyDeviceInfo {
+    #[prost(string, tag = "1")]
+    pub path: std::string::String,
+    /// the usb serial number, which a key in bootloader mode still reports
+    #[prost(string, tag = "2")]
+    pub serial_number: std::string::String,
+    #[prost(string, tag = "3")]
+    pub sn: std::string::String,
+    #[prost(string, tag = "4")]
+    pub seid: std::string::String,
+    #[prost(bool, tag = "5")]
+    pub is_bl_status: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListDevicesRes {
+    #[prost(message, repeated, tag = "1")]
+    pub devices: ::std::vec::Vec<ImkeyDeviceInfo>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeviceEvent {
+    /// connected, disconnected or mode_changed
+    #[prost(string, tag = "1")]
+    pub event: std::string::String,
+    #[prost(string, tag = "2")]
+    pub path: std::string::String,
+    #[prost(string, tag = "3")]
+    pub serial_number: std::string::String,
+    /// the mode a mode_changed key is in now
+    #[prost(bool, tag = "4")]
+    pub is_bl_status: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PollDeviceEventsRes {
+    #[prost(message, repeated, tag = "1")]
+    pub events: ::std::vec::Vec<DeviceEvent>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CosCheckUpdateRes {
@@ -137,3 +179,40 @@ pub struct IsBlStatusRes {
     #[prost(bool, tag = "1")]
     pub check_result: bool,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SetTsmConfigReq {
+    /// the default tsm server when empty
+    #[prost(string, tag = "1")]
+    pub base_url: std::string::String,
+    /// pem or der certificates trusted instead of the system roots
+    #[prost(bytes, repeated, tag = "2")]
+    pub pinned_certs: ::std::vec::Vec<std::vec::Vec<u8>>,
+    /// sha256 of the subjectPublicKeyInfo of the server certificate, one has to match when not empty
+    #[prost(bytes, repeated, tag = "3")]
+    pub pinned_spki_sha256: ::std::vec::Vec<std::vec::Vec<u8>>,
+    /// http://[user:password@]host:port, a direct connection when empty
+    #[prost(string, tag = "4")]
+    pub proxy: std::string::String,
+    /// timeouts in milliseconds, 0 keeps the default
+    #[prost(uint64, tag = "5")]
+    pub connect_timeout_millis: u64,
+    #[prost(uint64, tag = "6")]
+    pub read_timeout_millis: u64,
+    #[prost(uint64, tag = "7")]
+    pub write_timeout_millis: u64,
+    /// deadline of one attempt of a request
+    #[prost(uint64, tag = "8")]
+    pub request_timeout_millis: u64,
+    /// the default retries when not set
+    #[prost(message, optional, tag = "9")]
+    pub retry: ::std::option::Option<TsmRetry>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TsmRetry {
+    /// attempts after the first one, only made when the request never reached the server
+    #[prost(uint32, tag = "1")]
+    pub retries: u32,
+    /// wait before the first retry, doubled for each further one
+    #[prost(uint64, tag = "2")]
+    pub backoff_millis: u64,
+}
@@ -4,6 +4,8 @@ pub mod app_update;
 pub mod auth_code_storage;
 pub mod device_binding;
 pub mod device_cert_check;
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+pub mod device_discovery;
 pub mod se_activate;
 pub mod se_query;
 pub mod se_secure_check;
@@ -23,7 +25,7 @@ pub type Result<T> = result::Result<T, failure::Error>;
 use crate::error::ImkeyError;
 use common::constants;
 use serde::{Deserialize, Serialize};
-use transport::message;
+use transport::Transport;
 
 pub mod cos_check_update;
 
@@ -37,7 +39,7 @@ pub struct ServiceResponse<T> {
 
 pub trait TsmService {
     type ReturnData;
-    fn send_message(&mut self) -> Result<Self::ReturnData>;
+    fn send_message(&mut self, transport: &dyn Transport) -> Result<Self::ReturnData>;
 }
 
 impl<T> ServiceResponse<T> {
@@ -100,7 +102,10 @@ impl<T> ServiceResponse<T> {
         }
     }
 
-    pub fn apdu_handle(apdu_list: Vec<String>) -> Result<(Vec<String>, String)> {
+    pub fn apdu_handle(
+        transport: &dyn Transport,
+        apdu_list: Vec<String>,
+    ) -> Result<(Vec<String>, String)> {
         if apdu_list.is_empty() {
             ()
         }
@@ -108,7 +113,7 @@ impl<T> ServiceResponse<T> {
         let mut status_word: String = String::new();
         for (index_val, apdu_val) in apdu_list.iter().enumerate() {
             //sende apdu command
-            let res = message::send_apdu(apdu_val.to_string())?;
+            let res = transport.send_apdu(apdu_val.to_string())?;
             apdu_res.push(res.clone());
             if index_val == apdu_list.len() - 1 {
                 status_word = String::from(&res[res.len() - 4..]);
@@ -122,15 +127,17 @@ impl<T> ServiceResponse<T> {
 mod tests {
     use crate::app_download::AppDownloadResponse;
     use crate::ServiceResponse;
-    use transport::hid_api::hid_connect;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     #[test]
     fn apdu_handle_test() {
-        assert!(hid_connect("imKey Pro").is_ok());
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
         let apdu_list = vec![
             "00a4040000".to_string(),
             "00a404000600000000000100".to_string(),
         ];
-        assert!(ServiceResponse::<AppDownloadResponse>::apdu_handle(apdu_list).is_ok());
+        assert!(ServiceResponse::<AppDownloadResponse>::apdu_handle(&transport, apdu_list).is_ok());
     }
 }
@@ -3,6 +3,7 @@ use crate::{Result, TsmService};
 use common::constants;
 use common::https;
 use serde::{Deserialize, Serialize};
+use transport::Transport;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -28,7 +29,7 @@ pub struct SeActivateResponse {
 impl TsmService for SeActivateRequest {
     type ReturnData = ();
 
-    fn send_message(&mut self) -> Result<()> {
+    fn send_message(&mut self, transport: &dyn Transport) -> Result<()> {
         loop {
             println!("send message：{:#?}", self);
             let req_data = serde_json::to_vec_pretty(&self).unwrap();
@@ -45,8 +46,9 @@ impl TsmService for SeActivateRequest {
 
                 match return_bean._ReturnData.apdu_list {
                     Some(apdu_list) => {
-                        let handle_result =
-                            ServiceResponse::<SeActivateResponse>::apdu_handle(apdu_list)?;
+                        let handle_result = ServiceResponse::<SeActivateResponse>::apdu_handle(
+                            transport, apdu_list,
+                        )?;
                         self.card_ret_data_list = Some(handle_result.0);
                         self.status_word = Some(handle_result.1);
                         self.step_key = next_step_key;
@@ -79,16 +81,18 @@ mod test {
     use crate::device_manager::{get_cert, get_se_id, get_sn};
     use crate::se_activate::SeActivateRequest;
     use crate::TsmService;
-    use transport::hid_api::hid_connect;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     #[test]
     pub fn se_activate_test() {
-        assert!(hid_connect("imKey Pro").is_ok());
-        let seid = get_se_id().unwrap();
-        let device_cert = get_cert().unwrap();
-        let sn = get_sn().unwrap();
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let seid = get_se_id(&transport).unwrap();
+        let device_cert = get_cert(&transport).unwrap();
+        let sn = get_sn(&transport).unwrap();
         assert!(SeActivateRequest::build_request_data(seid, sn, device_cert)
-            .send_message()
+            .send_message(&transport)
             .is_ok());
     }
 }
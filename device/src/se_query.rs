@@ -3,6 +3,7 @@ use crate::{Result, TsmService};
 use common::constants;
 use common::https;
 use serde::{Deserialize, Serialize};
+use transport::Transport;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -42,7 +43,10 @@ pub struct AvailableAppBean {
 impl TsmService for SeQueryRequest {
     type ReturnData = ServiceResponse<SeQueryResponse>;
 
-    fn send_message(&mut self) -> Result<ServiceResponse<SeQueryResponse>> {
+    fn send_message(
+        &mut self,
+        _transport: &dyn Transport,
+    ) -> Result<ServiceResponse<SeQueryResponse>> {
         println!("send message：{:#?}", self);
         let req_data = serde_json::to_vec_pretty(&self).unwrap();
         let response_data = https::post(constants::TSM_ACTION_SE_QUERY, req_data)?;
@@ -85,24 +89,27 @@ mod tests {
     use crate::device_manager::{get_se_id, get_sn};
     use crate::se_query::SeQueryRequest;
     use crate::TsmService;
-    use transport::hid_api::hid_connect;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     #[test]
     fn se_query_test() {
-        assert!(hid_connect("imKey Pro").is_ok());
-        let seid = get_se_id().unwrap();
-        let sn = get_sn().unwrap();
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let seid = get_se_id(&transport).unwrap();
+        let sn = get_sn(&transport).unwrap();
         assert!(SeQueryRequest::build_request_data(seid, sn, None)
-            .send_message()
+            .send_message(&transport)
             .is_ok());
     }
 
     #[test]
     pub fn se_query_error_test() {
+        let transport = HidTransport::new();
         let seid = "00000000000000000000000000000000".to_string();
         let sn = "000001".to_string();
         assert!(SeQueryRequest::build_request_data(seid, sn, None)
-            .send_message()
+            .send_message(&transport)
             .is_err());
     }
 }
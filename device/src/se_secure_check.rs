@@ -3,6 +3,7 @@ use crate::{Result, TsmService};
 use common::constants;
 use common::https;
 use serde::{Deserialize, Serialize};
+use transport::Transport;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -28,7 +29,7 @@ pub struct SeSecureCheckResponse {
 impl TsmService for SeSecureCheckRequest {
     type ReturnData = ();
 
-    fn send_message(&mut self) -> Result<()> {
+    fn send_message(&mut self, transport: &dyn Transport) -> Result<()> {
         loop {
             println!("send message：{:#?}", self);
             let req_data = serde_json::to_vec_pretty(&self).unwrap();
@@ -45,8 +46,9 @@ impl TsmService for SeSecureCheckRequest {
 
                 match return_bean._ReturnData.apdu_list {
                     Some(apdu_list) => {
-                        let handle_result =
-                            ServiceResponse::<SeSecureCheckResponse>::apdu_handle(apdu_list)?;
+                        let handle_result = ServiceResponse::<SeSecureCheckResponse>::apdu_handle(
+                            transport, apdu_list,
+                        )?;
                         self.card_ret_data_list = Some(handle_result.0);
                         self.status_word = Some(handle_result.1);
                         self.step_key = next_step_key;
@@ -79,29 +81,32 @@ mod test {
     use crate::device_manager::{get_cert, get_se_id, get_sn};
     use crate::se_secure_check::SeSecureCheckRequest;
     use crate::TsmService;
-    use transport::hid_api::hid_connect;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     #[test]
     pub fn se_secure_check_test() {
-        assert!(hid_connect("imKey Pro").is_ok());
-        let seid = get_se_id().unwrap();
-        let sn: String = get_sn().unwrap();
-        let device_cert = get_cert().unwrap();
+        let transport = HidTransport::new();
+        assert!(transport.connect().is_ok());
+        let seid = get_se_id(&transport).unwrap();
+        let sn: String = get_sn(&transport).unwrap();
+        let device_cert = get_cert(&transport).unwrap();
         assert!(
             SeSecureCheckRequest::build_request_data(seid, sn, device_cert)
-                .send_message()
+                .send_message(&transport)
                 .is_ok()
         );
     }
 
     #[test]
     pub fn se_secure_check_error_test() {
+        let transport = HidTransport::new();
         let seid = "00000000000000000000000000000000".to_string();
         let sn = "000001".to_string();
         let device_cert = "00000000000000000000000000000000".to_string();
         assert!(
             SeSecureCheckRequest::build_request_data(seid, sn, device_cert)
-                .send_message()
+                .send_message(&transport)
                 .is_err()
         );
     }
@@ -0,0 +1,234 @@
+extern crate aes_soft as aes;
+
+use crate::command::{ApduResult, Command};
+use crate::se::SeSimulator;
+use crate::simulator_transport::SimulatorTransport;
+use crate::Result;
+use aes::Aes128;
+use block_modes::block_padding::Pkcs7;
+use block_modes::{BlockMode, Cbc};
+use common::constants::{
+    APDU_CONDITIONS_NOT_SATISFIED, APDU_RSP_APPLET_WRONG_DATA, APDU_RSP_FUNCTION_NOT_SUPPORTED,
+    BIND_RESULT_ERROR, BIND_RESULT_SUCCESS, BIND_STATUS_BOUND_OTHER, BIND_STATUS_BOUND_THIS,
+    BIND_STATUS_UNBOUND,
+};
+use device::device_binding::{gen_iv, key_manager, DeviceManage};
+use rand::Rng;
+use ring::digest;
+use secp256k1::ecdh::SharedSecret;
+use secp256k1::{PublicKey, SecretKey};
+use sha1::Sha1;
+use transport::secure_channel::{SecureChannel, CHALLENGE_SIZE};
+use transport::Transport;
+
+//the characters a bind code is made of, see the check in bind_acquire
+const BIND_CODE_CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/**
+the imk applet: bind check, bind code display and identity verify
+*/
+pub(crate) fn process(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    match command.ins {
+        0x71 => bind_check(se, command),
+        0x72 => generate_auth_code(se),
+        0x73 => identity_verify(se, command),
+        0x74 => open_secure_channel(se, command),
+        _ => Err(APDU_RSP_FUNCTION_NOT_SUPPORTED),
+    }
+}
+
+fn bind_check(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    if command.data.len() != 65 {
+        return Err(APDU_RSP_APPLET_WRONG_DATA);
+    }
+    let status = match &se.bound_key {
+        Some(bound_key) if bound_key == &command.data => BIND_STATUS_BOUND_THIS,
+        Some(_) => BIND_STATUS_BOUND_OTHER,
+        None => BIND_STATUS_UNBOUND,
+    };
+    let mut data = hex::decode(status).unwrap();
+    data.extend(se.se_cert());
+    Ok(data)
+}
+
+fn generate_auth_code(se: &mut SeSimulator) -> ApduResult {
+    let mut rng = rand::thread_rng();
+    let auth_code: String = (0..8)
+        .map(|_| BIND_CODE_CHARSET[rng.gen_range(0, BIND_CODE_CHARSET.len())] as char)
+        .collect();
+    se.auth_code = Some(auth_code);
+    Ok(vec![])
+}
+
+fn identity_verify(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    let auth_code = se.auth_code.clone().ok_or(APDU_CONDITIONS_NOT_SATISFIED)?;
+    if command.data.len() <= 65 {
+        return Err(APDU_RSP_APPLET_WRONG_DATA);
+    }
+    let (app_pub_key, ciphertext) = command.data.split_at(65);
+    let se_pri_key = se.se_key[..].to_vec();
+    let session_key =
+        session_key(&se_pri_key, app_pub_key).map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+
+    type Aes128Cbc = Cbc<Aes128, Pkcs7>;
+    let cipher = Aes128Cbc::new_var(&session_key, gen_iv(&auth_code).as_ref())
+        .map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+
+    //sha256 of bind code, app public key and se public key
+    let mut data: Vec<u8> = vec![];
+    data.extend(auth_code.as_bytes());
+    data.extend(app_pub_key);
+    data.extend(se.se_pub_key());
+    let data_hash = digest::digest(&digest::SHA256, &data);
+
+    match cipher.decrypt_vec(ciphertext) {
+        Ok(ref plaintext) if plaintext.as_slice() == data_hash.as_ref() => {
+            se.bound_key = Some(app_pub_key.to_vec());
+            se.auth_code = None;
+            se.secure_channel = None;
+            Ok(hex::decode(BIND_RESULT_SUCCESS).unwrap())
+        }
+        _ => Ok(hex::decode(BIND_RESULT_ERROR).unwrap()),
+    }
+}
+
+/**
+a secure channel under the session key of the bound app, the card challenge and cryptogram go back
+*/
+fn open_secure_channel(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    let bound_key = se.bound_key.clone().ok_or(APDU_CONDITIONS_NOT_SATISFIED)?;
+    let session_key =
+        session_key(&se.se_key[..], &bound_key).map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+    let card_challenge: [u8; CHALLENGE_SIZE] = rand::thread_rng().gen();
+    let channel = SecureChannel::new(&session_key, &command.data, &card_challenge)
+        .map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+    let mut data = card_challenge.to_vec();
+    data.extend(&channel.card_cryptogram());
+    se.secure_channel = Some(channel);
+    Ok(data)
+}
+
+/**
+ecdh session key, the first 16 bytes of sha1 over the shared x coordinate
+*/
+pub fn session_key(pri_key: &[u8], pub_key: &[u8]) -> Result<Vec<u8>> {
+    let public_key = PublicKey::from_slice(pub_key)?;
+    let secret_key = SecretKey::from_slice(pri_key)?;
+    let expect_result: [u8; 64] = [0; 64];
+    let mut x_out = [0u8; 32];
+    SharedSecret::new_with_hash(&public_key, &secret_key, |x, _y| {
+        x_out = x;
+        expect_result.into()
+    })?;
+    let sha1_result = Sha1::from(&x_out[..]).digest().bytes();
+    Ok(sha1_result[..16].to_vec())
+}
+
+/**
+bind the local keys to a fresh simulator, the simulator counterpart of device_binding::bind_test.
+the se certificate is trusted directly instead of being checked by tsm
+*/
+pub fn bind_test() -> SimulatorTransport {
+    let transport = SimulatorTransport::new();
+    assert!(transport.connect().is_ok());
+
+    let key_manager = key_manager(&transport);
+    let mut key_manager_obj = key_manager.lock().unwrap();
+    if key_manager_obj.pri_key.is_empty() {
+        key_manager_obj.gen_local_keys();
+    }
+    key_manager_obj.se_pub_key = transport.se_pub_key();
+    key_manager_obj.session_key =
+        session_key(&key_manager_obj.pri_key, &key_manager_obj.se_pub_key).unwrap();
+    std::mem::drop(key_manager_obj);
+
+    assert!(DeviceManage::display_bind_code(&transport).is_ok());
+    let bind_code = transport
+        .bind_code()
+        .expect("imkey_simulator_bind_code_not_shown");
+    let bind_result = DeviceManage::identity_verify(&transport, &bind_code).unwrap();
+    assert_eq!(bind_result, "success");
+    transport
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bind::bind_test;
+    use common::apdu::{Apdu, ImkApdu};
+    use common::constants::IMK_AID;
+    use device::device_binding::{key_manager, DeviceManage};
+    use device::device_manager;
+    use std::sync::Arc;
+    use transport::secure_channel::SecureTransport;
+    use transport::Transport;
+
+    #[test]
+    fn bind_check_test() {
+        let transport = bind_test();
+        let pub_key = key_manager(&transport).lock().unwrap().pub_key.clone();
+        transport.send_apdu(Apdu::select_applet(IMK_AID)).unwrap();
+        let response = transport.send_apdu(ImkApdu::bind_check(&pub_key)).unwrap();
+        assert!(response.starts_with("55"));
+        assert!(response.contains(&format!(
+            "7F4947B041{}",
+            hex::encode_upper(transport.se_pub_key())
+        )));
+
+        let mut other_key = pub_key.clone();
+        other_key[64] ^= 0x01;
+        let response = transport
+            .send_apdu(ImkApdu::bind_check(&other_key))
+            .unwrap();
+        assert!(response.starts_with("AA"));
+    }
+
+    #[test]
+    fn identity_verify_wrong_code_test() {
+        let transport = bind_test();
+        assert!(DeviceManage::display_bind_code(&transport).is_ok());
+        let bind_code = transport.bind_code().unwrap();
+        let wrong_code = if bind_code == "AAAAAAAA" {
+            "BBBBBBBB".to_string()
+        } else {
+            "AAAAAAAA".to_string()
+        };
+        let bind_result = DeviceManage::identity_verify(&transport, &wrong_code).unwrap();
+        assert_eq!(bind_result, "authcode_error");
+    }
+
+    #[test]
+    fn secure_channel_test() {
+        let simulator = Arc::new(bind_test());
+        let transport = SecureTransport::new(simulator.clone());
+        DeviceManage::open_secure_channel(&transport).unwrap();
+        assert!(transport.is_open());
+        assert_eq!(
+            device_manager::get_sn(&transport).unwrap(),
+            "imKey01191200001"
+        );
+        assert_eq!(
+            device_manager::get_se_id(&transport).unwrap(),
+            "19060000000200860001010000000014"
+        );
+
+        //plain applet commands are refused while the channel is open
+        assert_eq!(
+            simulator.send_apdu("80CA004400".to_string()).unwrap(),
+            "6982"
+        );
+        //and so is a wrapped one whose mac does not verify
+        let forged = simulator
+            .send_apdu("84CA004400000C000000010000000000000000".to_string())
+            .unwrap();
+        assert_eq!(forged, "6982");
+
+        //opening again starts over with fresh challenges
+        DeviceManage::open_secure_channel(&transport).unwrap();
+        assert_eq!(
+            device_manager::get_sn(&transport).unwrap(),
+            "imKey01191200001"
+        );
+        transport.close();
+        assert!(device_manager::get_sn(&transport).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::command::{ApduResult, Command};
+use crate::se::{double_sha256, sha256, tagged_hash, SeSimulator};
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::{Script, Transaction, TxIn, TxOut};
+use common::constants::{
+    APDU_CONDITIONS_NOT_SATISFIED, APDU_RSP_APPLET_WRONG_DATA, APDU_RSP_FUNCTION_NOT_SUPPORTED,
+};
+use std::collections::HashMap;
+use std::mem;
+
+/**
+the transaction the btc applet was prepared with, cleared when the applet is selected
+*/
+#[derive(Default)]
+pub(crate) struct BtcSession {
+    buffer: Vec<u8>,
+    version: u32,
+    input_count: u8,
+    outputs: Vec<TxOut>,
+    lock_time: u32,
+    inputs: HashMap<u8, TxIn>,
+    hash_prevouts: Vec<u8>,
+    hash_sequence: Vec<u8>,
+    hash_amounts: Vec<u8>,
+    hash_script_pubkeys: Vec<u8>,
+}
+
+/**
+the btc applet: legacy (41/42), segwit (31/32), taproot (51/52) and the omni variants (44, 34)
+*/
+pub(crate) fn process(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    match command.ins {
+        0x43 => se.get_xpub(command),
+        0x36 => se.register_address(command),
+        0x41 if command.p1 == 0x80 => prepare_input(se, command),
+        0x41 | 0x31 | 0x34 | 0x51 => prepare(se, command),
+        0x44 => prepare_outputs(se, &command.data),
+        0x42 => sign(se, command),
+        0x32 => segwit_sign(se, command),
+        0x52 => taproot_sign(se, command),
+        _ => Err(APDU_RSP_FUNCTION_NOT_SUPPORTED),
+    }
+}
+
+fn prepare(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    //BtcApdu::btc_prepare keeps p1 for every chunk and marks the last one with p2 80
+    se.btc.buffer.extend(&command.data);
+    if command.p2 != 0x80 {
+        return Ok(vec![]);
+    }
+    let data = mem::replace(&mut se.btc.buffer, vec![]);
+    //bip341 commits to single sha256 hashes of the inputs
+    let taproot = command.ins == 0x51;
+    match command.p1 {
+        0x00 => prepare_outputs(se, &data),
+        0x40 if taproot => {
+            se.btc.hash_prevouts = sha256(&data);
+            Ok(vec![])
+        }
+        0x80 if taproot => {
+            se.btc.hash_sequence = sha256(&data);
+            Ok(vec![])
+        }
+        0x20 if taproot => {
+            se.btc.hash_amounts = sha256(&data);
+            Ok(vec![])
+        }
+        0x10 if taproot => {
+            se.btc.hash_script_pubkeys = sha256(&data);
+            Ok(vec![])
+        }
+        0x40 => {
+            se.btc.hash_prevouts = double_sha256(&data);
+            Ok(vec![])
+        }
+        0x80 => {
+            se.btc.hash_sequence = double_sha256(&data);
+            Ok(vec![])
+        }
+        _ => Err(APDU_RSP_APPLET_WRONG_DATA),
+    }
+}
+
+/**
+01, length, then version | input count | outputs | lock time | hash type | fee | address version
+*/
+fn prepare_outputs(se: &mut SeSimulator, data: &[u8]) -> ApduResult {
+    let payload = se.verify_bind_signature(data)?;
+    if payload.len() < 2 + 5 + 1 + 17 || payload[0] != 0x01 {
+        return Err(APDU_RSP_APPLET_WRONG_DATA);
+    }
+    let body = &payload[2..];
+    let outputs_end = body.len() - 17;
+    se.btc.version = u32_le(&body[..4]);
+    se.btc.input_count = body[4];
+    se.btc.outputs = deserialize(&body[5..outputs_end]).map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+    se.btc.lock_time = u32_le(&body[outputs_end..outputs_end + 4]);
+    se.btc.inputs.clear();
+    Ok(vec![])
+}
+
+fn prepare_input(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    if command.data.is_empty() {
+        return Err(APDU_RSP_APPLET_WRONG_DATA);
+    }
+    let input: TxIn = deserialize(&command.data[1..]).map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+    se.btc.inputs.insert(command.data[0], input);
+    Ok(vec![])
+}
+
+fn sign(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    let index = command.p1;
+    let script_code = match se.btc.inputs.get(&index) {
+        Some(input) if !input.script_sig.is_empty() => input.script_sig.clone(),
+        _ => return Err(APDU_CONDITIONS_NOT_SATISFIED),
+    };
+    let mut input = vec![];
+    for i in 0..se.btc.input_count {
+        let txin = se.btc.inputs.get(&i).ok_or(APDU_CONDITIONS_NOT_SATISFIED)?;
+        input.push(TxIn {
+            script_sig: Script::new(),
+            ..txin.clone()
+        });
+    }
+    let tx = Transaction {
+        version: se.btc.version,
+        lock_time: se.btc.lock_time,
+        input,
+        output: se.btc.outputs.clone(),
+    };
+    let sighash = tx.signature_hash(index as usize, &script_code, command.p2 as u32);
+    se.sign_digest(&command.path()?, &sighash[..], 0)
+}
+
+/**
+bip143 digest, the data carries the outpoint, script code, amount and sequence of the input
+followed by the path to sign with
+*/
+fn segwit_sign(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    if se.btc.hash_prevouts.is_empty() || se.btc.hash_sequence.is_empty() {
+        return Err(APDU_CONDITIONS_NOT_SATISFIED);
+    }
+    let data = &command.data;
+    let input_len = *data.first().ok_or(APDU_RSP_APPLET_WRONG_DATA)? as usize;
+    let input_data = data
+        .get(1..1 + input_len)
+        .ok_or(APDU_RSP_APPLET_WRONG_DATA)?;
+    let path_len = *data.get(1 + input_len).ok_or(APDU_RSP_APPLET_WRONG_DATA)? as usize;
+    let path = data
+        .get(2 + input_len..2 + input_len + path_len)
+        .ok_or(APDU_RSP_APPLET_WRONG_DATA)?;
+    let path = String::from_utf8(path.to_vec()).map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+
+    let mut outputs = vec![];
+    for output in &se.btc.outputs {
+        outputs.extend(serialize(output));
+    }
+
+    let mut preimage = vec![];
+    preimage.extend(&se.btc.version.to_le_bytes());
+    preimage.extend(&se.btc.hash_prevouts);
+    preimage.extend(&se.btc.hash_sequence);
+    preimage.extend(input_data);
+    preimage.extend(double_sha256(&outputs));
+    preimage.extend(&se.btc.lock_time.to_le_bytes());
+    preimage.extend(&(command.p2 as u32).to_le_bytes());
+    se.sign_digest(&path, &double_sha256(&preimage), 0)
+}
+
+/**
+bip341 key path digest without annex, the data carries the input index followed by the path
+*/
+fn taproot_sign(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    if se.btc.hash_prevouts.is_empty()
+        || se.btc.hash_sequence.is_empty()
+        || se.btc.hash_amounts.is_empty()
+        || se.btc.hash_script_pubkeys.is_empty()
+    {
+        return Err(APDU_CONDITIONS_NOT_SATISFIED);
+    }
+    if command.p2 > 0x01 {
+        return Err(APDU_RSP_APPLET_WRONG_DATA);
+    }
+    let data = &command.data;
+    let index = data.get(..4).ok_or(APDU_RSP_APPLET_WRONG_DATA)?;
+    let path_len = *data.get(4).ok_or(APDU_RSP_APPLET_WRONG_DATA)? as usize;
+    let path = data
+        .get(5..5 + path_len)
+        .ok_or(APDU_RSP_APPLET_WRONG_DATA)?;
+    let path = String::from_utf8(path.to_vec()).map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+
+    let mut outputs = vec![];
+    for output in &se.btc.outputs {
+        outputs.extend(serialize(output));
+    }
+
+    let mut sig_msg = vec![0x00, command.p2];
+    sig_msg.extend(&se.btc.version.to_le_bytes());
+    sig_msg.extend(&se.btc.lock_time.to_le_bytes());
+    sig_msg.extend(&se.btc.hash_prevouts);
+    sig_msg.extend(&se.btc.hash_amounts);
+    sig_msg.extend(&se.btc.hash_script_pubkeys);
+    sig_msg.extend(&se.btc.hash_sequence);
+    sig_msg.extend(sha256(&outputs));
+    sig_msg.push(0x00);
+    sig_msg.extend(index);
+    se.sign_taproot(&path, &tagged_hash("TapSighash", &sig_msg))
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    let mut value = [0u8; 4];
+    value.copy_from_slice(bytes);
+    u32::from_le_bytes(value)
+}
@@ -0,0 +1,111 @@
+use common::constants::{APDU_RSP_APPLET_WRONG_DATA, APDU_RSP_SUCCESS, APDU_RSP_WRONG_LENGTH};
+use core::result;
+
+//status word a command is aborted with
+pub type StatusWord = &'static str;
+pub type ApduResult = result::Result<Vec<u8>, StatusWord>;
+
+/**
+short apdu command as built by common::apdu
+*/
+pub struct Command {
+    pub cla: u8,
+    pub ins: u8,
+    pub p1: u8,
+    pub p2: u8,
+    pub data: Vec<u8>,
+}
+
+impl Command {
+    pub fn parse(apdu: &str) -> result::Result<Command, StatusWord> {
+        let bytes = hex::decode(apdu).map_err(|_| APDU_RSP_WRONG_LENGTH)?;
+        if bytes.len() < 4 {
+            return Err(APDU_RSP_WRONG_LENGTH);
+        }
+        //a fifth byte alone is Le, the trailing Le after the data is ignored
+        let data = if bytes.len() > 5 {
+            let lc = bytes[4] as usize;
+            match bytes.get(5..5 + lc) {
+                Some(data) => data.to_vec(),
+                None => return Err(APDU_RSP_WRONG_LENGTH),
+            }
+        } else {
+            vec![]
+        };
+        Ok(Command {
+            cla: bytes[0],
+            ins: bytes[1],
+            p1: bytes[2],
+            p2: bytes[3],
+            data,
+        })
+    }
+
+    /**
+    the bip32 path carried in the data field
+    */
+    pub fn path(&self) -> result::Result<String, StatusWord> {
+        String::from_utf8(self.data.clone()).map_err(|_| APDU_RSP_APPLET_WRONG_DATA)
+    }
+}
+
+/**
+response data followed by the status word, hex encoded like the device returns it
+*/
+pub fn response(result: ApduResult) -> String {
+    match result {
+        Ok(data) => format!("{}{}", hex::encode_upper(data), APDU_RSP_SUCCESS),
+        Err(status_word) => status_word.to_string(),
+    }
+}
+
+/**
+split TLV encoded data with one byte tags and lengths
+*/
+pub fn parse_tlv(data: &[u8]) -> result::Result<Vec<(u8, &[u8])>, StatusWord> {
+    let mut items = vec![];
+    let mut index = 0;
+    while index < data.len() {
+        let tag = data[index];
+        let length = *data.get(index + 1).ok_or(APDU_RSP_APPLET_WRONG_DATA)? as usize;
+        let value = data
+            .get(index + 2..index + 2 + length)
+            .ok_or(APDU_RSP_APPLET_WRONG_DATA)?;
+        items.push((tag, value));
+        index += 2 + length;
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{parse_tlv, response, Command};
+
+    #[test]
+    fn parse_command_test() {
+        let command = Command::parse("80430000086d2f34342f302f3000").ok().unwrap();
+        assert_eq!(command.cla, 0x80);
+        assert_eq!(command.ins, 0x43);
+        assert_eq!(command.path().unwrap(), "m/44/0/0");
+
+        let command = Command::parse("80CA004400").ok().unwrap();
+        assert!(command.data.is_empty());
+
+        assert_eq!(Command::parse("80CA").err(), Some("6700"));
+        assert_eq!(Command::parse("8043000008").err(), None);
+        assert_eq!(Command::parse("80430000086d2f").err(), Some("6700"));
+    }
+
+    #[test]
+    fn response_test() {
+        assert_eq!(response(Ok(vec![0x5a])), "5A9000");
+        assert_eq!(response(Err("6942")), "6942");
+    }
+
+    #[test]
+    fn parse_tlv_test() {
+        let items = parse_tlv(&[0x07, 0x02, 0x61, 0x62, 0x08, 0x00]).unwrap();
+        assert_eq!(items, vec![(0x07, &[0x61u8, 0x62][..]), (0x08, &[][..])]);
+        assert!(parse_tlv(&[0x07, 0x02, 0x61]).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+use crate::command::{self, ApduResult, Command};
+use crate::se::{Prepared, SeSimulator};
+use common::constants::{
+    APDU_CONDITIONS_NOT_SATISFIED, APDU_RSP_APPLET_WRONG_DATA, APDU_RSP_FUNCTION_NOT_SUPPORTED,
+};
+
+/**
+the cosmos applet: prepare (71) carries the sha256 of the sign doc, sign (72) the path
+*/
+pub(crate) fn process(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    match command.ins {
+        0x73 => se.get_xpub(command),
+        0x76 => se.register_address(command),
+        0x71 => prepare(se, command),
+        0x72 => sign(se, command),
+        _ => Err(APDU_RSP_FUNCTION_NOT_SUPPORTED),
+    }
+}
+
+fn prepare(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    let data = match se.collect_chunk(command) {
+        Some(data) => data,
+        None => return Ok(vec![]),
+    };
+    let payload = se.verify_bind_signature(&data)?;
+    let digest = command::parse_tlv(payload)?
+        .into_iter()
+        .find(|(tag, _)| *tag == 0x01)
+        .map(|(_, value)| value.to_vec())
+        .ok_or(APDU_RSP_APPLET_WRONG_DATA)?;
+    se.prepared = Some(Prepared {
+        ins: command.ins,
+        digest,
+        path: None,
+    });
+    Ok(vec![])
+}
+
+fn sign(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    let digest = match se.prepared.take() {
+        Some(prepared) if prepared.ins + 1 == command.ins => prepared.digest,
+        _ => return Err(APDU_CONDITIONS_NOT_SATISFIED),
+    };
+    se.sign_digest(&command.path()?, &digest, 0)
+}
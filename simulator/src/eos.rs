@@ -0,0 +1,69 @@
+use crate::command::{self, ApduResult, Command};
+use crate::se::{Prepared, SeSimulator};
+use common::constants::{
+    APDU_CONDITIONS_NOT_SATISFIED, APDU_RSP_APPLET_WRONG_DATA, APDU_RSP_FUNCTION_NOT_SUPPORTED,
+};
+
+/**
+the eos applet: transactions (61/62) and messages (64/65), signed with a host chosen nonce
+*/
+pub(crate) fn process(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    match command.ins {
+        0x63 => se.get_xpub(command),
+        0x66 => se.register_address(command),
+        0x61 | 0x64 => prepare(se, command),
+        0x62 | 0x65 => sign(se, command),
+        _ => Err(APDU_RSP_FUNCTION_NOT_SUPPORTED),
+    }
+}
+
+fn prepare(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    let data = match se.collect_chunk(command) {
+        Some(data) => data,
+        None => return Ok(vec![]),
+    };
+    let payload = se.verify_bind_signature(&data)?;
+
+    //01 the digest, 02 the path, the display tags are not checked
+    let mut digest = None;
+    let mut path = None;
+    for (tag, value) in command::parse_tlv(payload)? {
+        match tag {
+            0x01 => digest = Some(value.to_vec()),
+            0x02 => {
+                path = Some(
+                    String::from_utf8(value.to_vec()).map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?,
+                )
+            }
+            _ => {}
+        }
+    }
+    let digest = digest.ok_or(APDU_RSP_APPLET_WRONG_DATA)?;
+    let path = path.ok_or(APDU_RSP_APPLET_WRONG_DATA)?;
+
+    //the response is the public key of the path, the host checks it against its own
+    let public_key = se.public_key(&path)?;
+    se.prepared = Some(Prepared {
+        ins: command.ins,
+        digest,
+        path: Some(path),
+    });
+    Ok(public_key)
+}
+
+fn sign(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    //the prepared data is kept so the host can retry with the next nonce
+    let (digest, path) = match se.prepared {
+        Some(Prepared {
+            ins,
+            ref digest,
+            path: Some(ref path),
+        }) if ins + 1 == command.ins => (digest.clone(), path.clone()),
+        _ => return Err(APDU_CONDITIONS_NOT_SATISFIED),
+    };
+    if command.data.len() != 2 {
+        return Err(APDU_RSP_APPLET_WRONG_DATA);
+    }
+    let nonce = ((command.data[0] as usize) << 8) + command.data[1] as usize;
+    se.sign_digest(&path, &digest, nonce)
+}
@@ -0,0 +1,5 @@
+#[derive(Fail, Debug, PartialOrd, PartialEq)]
+pub enum SimulatorError {
+    #[fail(display = "imkey_device_not_connect")]
+    DeviceNotConnect,
+}
@@ -0,0 +1,54 @@
+use crate::command::{ApduResult, Command};
+use crate::se::{Prepared, SeSimulator};
+use common::constants::{
+    APDU_CONDITIONS_NOT_SATISFIED, APDU_RSP_APPLET_WRONG_DATA, APDU_RSP_FUNCTION_NOT_SUPPORTED,
+};
+
+/**
+the eth applet: transactions (51/52), personal messages (54/55) and typed data (57/58)
+*/
+pub(crate) fn process(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    match command.ins {
+        0x53 => se.get_xpub(command),
+        0x56 => se.register_address(command),
+        0x51 | 0x54 | 0x57 => prepare(se, command),
+        0x52 | 0x55 | 0x58 => sign(se, command),
+        _ => Err(APDU_RSP_FUNCTION_NOT_SUPPORTED),
+    }
+}
+
+fn prepare(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    let data = match se.collect_chunk(command) {
+        Some(data) => data,
+        None => return Ok(vec![]),
+    };
+    let payload = se.verify_bind_signature(&data)?;
+
+    //01, two bytes length, then the rlp encoded transaction, the prefixed message
+    //or the eip-712 digest, which is signed as it is
+    if payload.len() < 3 || payload[0] != 0x01 {
+        return Err(APDU_RSP_APPLET_WRONG_DATA);
+    }
+    let length = ((payload[1] as usize) << 8) + payload[2] as usize;
+    let message = payload
+        .get(3..3 + length)
+        .ok_or(APDU_RSP_APPLET_WRONG_DATA)?;
+    se.prepared = Some(Prepared {
+        ins: command.ins,
+        digest: match command.ins {
+            0x57 if message.len() == 32 => message.to_vec(),
+            0x57 => return Err(APDU_RSP_APPLET_WRONG_DATA),
+            _ => tiny_keccak::keccak256(message).to_vec(),
+        },
+        path: None,
+    });
+    Ok(vec![])
+}
+
+fn sign(se: &mut SeSimulator, command: &Command) -> ApduResult {
+    let prepared = match se.prepared.take() {
+        Some(ref prepared) if prepared.ins + 1 == command.ins => prepared.digest.clone(),
+        _ => return Err(APDU_CONDITIONS_NOT_SATISFIED),
+    };
+    se.sign_digest(&command.path()?, &prepared, 0)
+}
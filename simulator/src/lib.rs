@@ -0,0 +1,20 @@
+pub mod bind;
+mod btc;
+mod command;
+mod cosmos;
+mod eos;
+pub mod error;
+mod eth;
+pub mod se;
+pub mod simulator_transport;
+pub mod tsm;
+#[macro_use]
+extern crate failure;
+#[macro_use]
+extern crate lazy_static;
+use core::result;
+pub type Result<T> = result::Result<T, failure::Error>;
+
+pub use crate::bind::bind_test;
+pub use crate::se::SeSimulator;
+pub use crate::simulator_transport::SimulatorTransport;
@@ -0,0 +1,551 @@
+use crate::btc::BtcSession;
+use crate::command::{self, ApduResult, Command, StatusWord};
+use crate::{bind, btc, cosmos, eos, eth, Result};
+use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey};
+use bitcoin::Network;
+use common::constants::{
+    APDU_CONDITIONS_NOT_SATISFIED, APDU_RSP_APPLET_NOT_EXIST, APDU_RSP_APPLET_WRONG_DATA,
+    APDU_RSP_CLA_NOT_SUPPORTED, APDU_RSP_FUNCTION_NOT_SUPPORTED, APDU_RSP_INCORRECT_P1P2,
+    APDU_RSP_SECURITY_STATUS_NOT_SATISFIED, APDU_RSP_SIGNATURE_VERIFY_FAILED, BTC_AID, COSMOS_AID,
+    EOS_AID, ETH_AID, IMK_AID,
+};
+use core::result;
+use ring::{digest, pbkdf2};
+use secp256k1::ffi::{self, CPtr};
+use secp256k1::recovery::RecoverableSignature;
+use secp256k1::{All, Message, PublicKey, Secp256k1, SecretKey, Signature};
+use std::mem;
+use std::num::NonZeroU32;
+use std::str::FromStr;
+use transport::secure_channel::SecureChannel;
+
+//the mnemonic the sdk test cases are written against
+pub const TEST_MNEMONIC: &str =
+    "inject kidney empty canal shadow pact comfort wife crush horse wife sketch";
+
+//the simulated se key pair, its public key is issued in the se certificate
+const SE_PRIVATE_KEY: &str = "3c2356fc61d8c106458bb0a5e5e5ecd959178a1bc913232159c02fe91d368f86";
+const SEID: &str = "19060000000200860001010000000014";
+const SN: &str = "imKey01191200001";
+const FIRMWARE_VERSION: &str = "1429";
+const BLE_VERSION: &str = "0106";
+const RAM_SIZE: &str = "0FA0";
+const BATTERY_POWER: &str = "64";
+const LIFE_TIME: &str = "80";
+const BLE_NAME: &str = "imKey Pro";
+//n - 1 of secp256k1, multiplying a secret key by it negates the key
+const CURVE_ORDER_MINUS_ONE: [u8; 32] = [
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE,
+    0xBA, 0xAE, 0xDC, 0xE6, 0xAF, 0x48, 0xA0, 0x3B, 0xBF, 0xD2, 0x5E, 0x8C, 0xD0, 0x36, 0x41, 0x40,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Applet {
+    Isd,
+    Imk,
+    Btc,
+    Eth,
+    Eos,
+    Cosmos,
+}
+
+/**
+a message prepared for signing, kept until the applet is reselected
+*/
+pub(crate) struct Prepared {
+    pub ins: u8,
+    pub digest: Vec<u8>,
+    pub path: Option<String>,
+}
+
+/**
+software secure element answering the imKey applet apdu set
+*/
+pub struct SeSimulator {
+    pub(crate) secp: Secp256k1<All>,
+    //bip32 derivation runs on the secp256k1 version bitcoin is built with
+    pub(crate) bip32_secp: bitcoin::secp256k1::Secp256k1<bitcoin::secp256k1::All>,
+    pub(crate) master_key: ExtendedPrivKey,
+    pub(crate) se_key: SecretKey,
+    pub(crate) applet: Applet,
+    pub(crate) bound_key: Option<Vec<u8>>,
+    pub(crate) auth_code: Option<String>,
+    pub(crate) ble_name: String,
+    pub(crate) registered_addresses: Vec<String>,
+    pub(crate) buffer: Vec<u8>,
+    pub(crate) prepared: Option<Prepared>,
+    pub(crate) btc: BtcSession,
+    pub(crate) secure_channel: Option<SecureChannel>,
+}
+
+impl SeSimulator {
+    /**
+    simulator holding the wallet of TEST_MNEMONIC
+    */
+    pub fn new() -> SeSimulator {
+        SeSimulator::from_mnemonic(TEST_MNEMONIC).expect("imkey_simulator_init_fail")
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Result<SeSimulator> {
+        let mut seed = [0u8; 64];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA512,
+            NonZeroU32::new(2048).unwrap(),
+            b"mnemonic",
+            mnemonic.as_bytes(),
+            &mut seed,
+        );
+        SeSimulator::from_seed(&seed)
+    }
+
+    pub fn from_seed(seed: &[u8]) -> Result<SeSimulator> {
+        Ok(SeSimulator {
+            secp: Secp256k1::new(),
+            bip32_secp: bitcoin::secp256k1::Secp256k1::new(),
+            master_key: ExtendedPrivKey::new_master(Network::Bitcoin, seed)?,
+            se_key: SecretKey::from_slice(&hex::decode(SE_PRIVATE_KEY)?)?,
+            applet: Applet::Isd,
+            bound_key: None,
+            auth_code: None,
+            ble_name: BLE_NAME.to_string(),
+            registered_addresses: vec![],
+            buffer: vec![],
+            prepared: None,
+            btc: BtcSession::default(),
+            secure_channel: None,
+        })
+    }
+
+    /**
+    execute one apdu command and return the response with its status word
+    */
+    pub fn process(&mut self, apdu: &str) -> String {
+        //the class of a wrapped command has the secure messaging bit
+        if apdu.starts_with("84") || apdu.starts_with("04") {
+            return self.process_secure(apdu);
+        }
+        let result = Command::parse(apdu).and_then(|command| self.dispatch(&command));
+        command::response(result)
+    }
+
+    /**
+    a wrapped command is processed like a plain one and its response wrapped, one that does not
+    verify is refused before any applet sees it
+    */
+    fn process_secure(&mut self, apdu: &str) -> String {
+        let mut channel = match self.secure_channel.take() {
+            Some(channel) => channel,
+            None => return APDU_CONDITIONS_NOT_SATISFIED.to_string(),
+        };
+        let command = hex::decode(apdu)
+            .map_err(failure::Error::from)
+            .and_then(|apdu| channel.unwrap_command(&apdu));
+        let response = match command {
+            Ok(command) => {
+                let response = self.process(&hex::encode_upper(command));
+                hex::encode_upper(
+                    channel
+                        .wrap_response(&hex::decode(response).unwrap())
+                        .unwrap(),
+                )
+            }
+            Err(_) => APDU_RSP_SECURITY_STATUS_NOT_SATISFIED.to_string(),
+        };
+        self.secure_channel = Some(channel);
+        response
+    }
+
+    /**
+    uncompressed se public key, the one bind_check certifies
+    */
+    pub fn se_pub_key(&self) -> Vec<u8> {
+        PublicKey::from_secret_key(&self.secp, &self.se_key)
+            .serialize_uncompressed()
+            .to_vec()
+    }
+
+    /**
+    the bind code currently shown on the device screen
+    */
+    pub fn bind_code(&self) -> Option<String> {
+        self.auth_code.clone()
+    }
+
+    /**
+    the addresses the wallet applets were asked to display
+    */
+    pub fn registered_addresses(&self) -> &[String] {
+        &self.registered_addresses
+    }
+
+    fn dispatch(&mut self, command: &Command) -> ApduResult {
+        //with a secure channel open only selects, the open command and device commands go plain
+        if self.secure_channel.is_some()
+            && !matches!(
+                (command.cla, command.ins),
+                (0x00, 0xA4) | (0x80, 0x74) | (0xFF, _)
+            )
+        {
+            return Err(APDU_RSP_SECURITY_STATUS_NOT_SATISFIED);
+        }
+        match (command.cla, command.ins) {
+            (0x00, 0xA4) => self.select(command),
+            (0x80, 0xCA) | (0x80, 0xCB) | (0x00, 0xD6) | (0xFF, _) => self.device_info(command),
+            (0x80, _) => match self.applet {
+                Applet::Imk => bind::process(self, command),
+                Applet::Btc => btc::process(self, command),
+                Applet::Eth => eth::process(self, command),
+                Applet::Eos => eos::process(self, command),
+                Applet::Cosmos => cosmos::process(self, command),
+                Applet::Isd => Err(APDU_RSP_FUNCTION_NOT_SUPPORTED),
+            },
+            _ => Err(APDU_RSP_CLA_NOT_SUPPORTED),
+        }
+    }
+
+    fn select(&mut self, command: &Command) -> ApduResult {
+        let aid = hex::encode_upper(&command.data);
+        self.applet = match aid.as_str() {
+            "" => Applet::Isd,
+            IMK_AID => Applet::Imk,
+            BTC_AID => Applet::Btc,
+            ETH_AID => Applet::Eth,
+            EOS_AID => Applet::Eos,
+            COSMOS_AID => Applet::Cosmos,
+            _ => return Err(APDU_RSP_APPLET_NOT_EXIST),
+        };
+        //selecting drops whatever the previous applet had prepared
+        self.buffer.clear();
+        self.prepared = None;
+        self.btc = BtcSession::default();
+        Ok(vec![])
+    }
+
+    fn device_info(&mut self, command: &Command) -> ApduResult {
+        let data = match (command.ins, command.p1, command.p2) {
+            (0xCB, 0x80, 0x00) => match hex::encode_upper(&command.data).as_str() {
+                "DFFF028101" => hex::decode(SEID).unwrap(),
+                "DFFF028003" => hex::decode(FIRMWARE_VERSION).unwrap(),
+                "DFFF028146" => hex::decode(RAM_SIZE).unwrap(),
+                "DFFF028100" => hex::decode(BLE_VERSION).unwrap(),
+                _ => return Err(APDU_RSP_APPLET_WRONG_DATA),
+            },
+            (0xCA, 0x00, 0x44) => SN.as_bytes().to_vec(),
+            (0xCA, 0xBF, 0x21) => self.se_cert(),
+            (0xD6, 0xFE, 0xED) => hex::decode(BATTERY_POWER).unwrap(),
+            (0xDC, 0xFE, 0xED) => hex::decode(LIFE_TIME).unwrap(),
+            (0xDB, 0x46, 0x54) => self.ble_name.as_bytes().to_vec(),
+            (0xDA, 0x46, 0x54) => {
+                self.ble_name = String::from_utf8_lossy(&command.data).to_string();
+                vec![]
+            }
+            _ => return Err(APDU_RSP_INCORRECT_P1P2),
+        };
+        Ok(data)
+    }
+
+    /**
+    se certificate in the production layout, self signed by the simulated se key
+    */
+    pub(crate) fn se_cert(&self) -> Vec<u8> {
+        let mut key_data = vec![0xB0, 0x41];
+        key_data.extend(self.se_pub_key());
+        key_data.extend(&[0xF0, 0x02, 0xDF, 0xFE]);
+
+        let mut body = tlv(&[0x93], &hex::decode(SEID).unwrap());
+        body.extend(tlv(&[0x42], &[0x00, 0x01]));
+        body.extend(tlv(&[0x5F, 0x20], &[0x01, 0x02, 0x03, 0x04]));
+        body.extend(tlv(&[0x95], &[0x00, 0x80]));
+        body.extend(tlv(&[0x5F, 0x25], &[0x20, 0x18, 0x10, 0x14]));
+        body.extend(tlv(&[0x5F, 0x24], &[0xFF, 0xFF, 0xFF, 0xFF]));
+        body.extend(tlv(&[0x53], &[]));
+        body.extend(tlv(&[0x7F, 0x49], &key_data));
+        let signature = self.se_sign(&body);
+        body.extend(tlv(&[0x5F, 0x37], &signature));
+
+        tlv(&[0xBF, 0x21], &tlv(&[0x7F, 0x21], &body))
+    }
+
+    /**
+    der signature over sha256(data) with the se key, as appended to verified responses
+    */
+    pub(crate) fn se_sign(&self, data: &[u8]) -> Vec<u8> {
+        let hash = digest::digest(&digest::SHA256, data);
+        let message = Message::from_slice(hash.as_ref()).unwrap();
+        self.secp
+            .sign(&message, &self.se_key)
+            .serialize_der()
+            .to_vec()
+    }
+
+    /**
+    private key and chain code of the path
+    */
+    pub(crate) fn derive(&self, path: &str) -> result::Result<(SecretKey, Vec<u8>), StatusWord> {
+        let path = DerivationPath::from_str(path.trim_end_matches('/'))
+            .map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+        let extended_key = self
+            .master_key
+            .derive_priv(&self.bip32_secp, &path)
+            .map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+        let key = SecretKey::from_slice(&extended_key.private_key.to_bytes())
+            .map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+        Ok((key, extended_key.chain_code[..].to_vec()))
+    }
+
+    pub(crate) fn public_key(&self, path: &str) -> result::Result<Vec<u8>, StatusWord> {
+        let (key, _) = self.derive(path)?;
+        Ok(PublicKey::from_secret_key(&self.secp, &key)
+            .serialize_uncompressed()
+            .to_vec())
+    }
+
+    /**
+    public key and chain code of the path, signed by the se key when p1 is 01
+    */
+    pub(crate) fn get_xpub(&self, command: &Command) -> ApduResult {
+        let (key, chain_code) = self.derive(&command.path()?)?;
+        let mut data = PublicKey::from_secret_key(&self.secp, &key)
+            .serialize_uncompressed()
+            .to_vec();
+        data.extend(chain_code);
+        if command.p1 == 0x01 {
+            let signature = self.se_sign(&data);
+            data.extend(signature);
+        }
+        Ok(data)
+    }
+
+    pub(crate) fn register_address(&mut self, command: &Command) -> ApduResult {
+        let address =
+            String::from_utf8(command.data.clone()).map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+        self.registered_addresses.push(address);
+        Ok(vec![])
+    }
+
+    /**
+    collect the chunks of Apdu::prepare_sign, returns the data once the last one arrives
+    */
+    pub(crate) fn collect_chunk(&mut self, command: &Command) -> Option<Vec<u8>> {
+        if command.p1 == 0x00 {
+            self.buffer.clear();
+        }
+        self.buffer.extend(&command.data);
+        if command.p2 == 0x80 {
+            Some(mem::replace(&mut self.buffer, vec![]))
+        } else {
+            None
+        }
+    }
+
+    /**
+    check the bind signature in front of prepared data, returns the signed payload
+    */
+    pub(crate) fn verify_bind_signature<'a>(
+        &self,
+        data: &'a [u8],
+    ) -> result::Result<&'a [u8], StatusWord> {
+        if data.len() < 2 || data[0] != 0x00 {
+            return Err(APDU_RSP_APPLET_WRONG_DATA);
+        }
+        let signature_len = data[1] as usize;
+        let signature = data
+            .get(2..2 + signature_len)
+            .ok_or(APDU_RSP_APPLET_WRONG_DATA)?;
+        let payload = &data[2 + signature_len..];
+
+        let bound_key = self
+            .bound_key
+            .as_ref()
+            .ok_or(APDU_CONDITIONS_NOT_SATISFIED)?;
+        let public_key =
+            PublicKey::from_slice(bound_key).map_err(|_| APDU_RSP_SIGNATURE_VERIFY_FAILED)?;
+        let signature =
+            Signature::from_der(signature).map_err(|_| APDU_RSP_SIGNATURE_VERIFY_FAILED)?;
+        let message = Message::from_slice(&double_sha256(payload)).unwrap();
+        self.secp
+            .verify(&message, &signature, &public_key)
+            .map_err(|_| APDU_RSP_SIGNATURE_VERIFY_FAILED)?;
+        Ok(payload)
+    }
+
+    /**
+    sign a digest with the key of path, the nonce is fed to rfc6979 as extra entropy
+    so the host can grind for a canonical signature.
+    the response is 41 followed by r, s and the recovery id
+    */
+    pub(crate) fn sign_digest(&self, path: &str, digest: &[u8], nonce: usize) -> ApduResult {
+        let (key, _) = self.derive(path)?;
+        let message = Message::from_slice(digest).map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+        let signature = if nonce == 0 {
+            self.secp.sign_recoverable(&message, &key)
+        } else {
+            let mut extra_entropy = [0u8; 32];
+            extra_entropy[24..].copy_from_slice(&(nonce as u64).to_be_bytes());
+            let mut signature = ffi::recovery::RecoverableSignature::new();
+            unsafe {
+                assert_eq!(
+                    ffi::recovery::secp256k1_ecdsa_sign_recoverable(
+                        *self.secp.ctx(),
+                        &mut signature,
+                        message.as_c_ptr(),
+                        key.as_c_ptr(),
+                        ffi::secp256k1_nonce_function_rfc6979,
+                        extra_entropy.as_ptr() as *const ffi::types::c_void,
+                    ),
+                    1
+                );
+            }
+            RecoverableSignature::from(signature)
+        };
+        let (recovery_id, compact) = signature.serialize_compact();
+
+        let mut data = vec![0x41];
+        data.extend(compact.iter());
+        data.push(recovery_id.to_i32() as u8);
+        Ok(data)
+    }
+
+    /**
+    bip340 signature of a digest with the bip86 tweaked key of path, aux randomness is zero.
+    the response is r followed by s
+    */
+    pub(crate) fn sign_taproot(&self, path: &str, digest: &[u8]) -> ApduResult {
+        let (mut key, _) = self.derive(path)?;
+        self.negate_if_odd(&mut key);
+        let internal_key = self.x_only(&key);
+        key.add_assign(&tagged_hash("TapTweak", &internal_key))
+            .map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+        self.negate_if_odd(&mut key);
+        let output_key = self.x_only(&key);
+
+        let aux_hash = tagged_hash("BIP0340/aux", &[0u8; 32]);
+        let mut nonce_data: Vec<u8> = key[..]
+            .iter()
+            .zip(aux_hash.iter())
+            .map(|(key_byte, aux_byte)| key_byte ^ aux_byte)
+            .collect();
+        nonce_data.extend(&output_key);
+        nonce_data.extend(digest);
+        let mut nonce = SecretKey::from_slice(&tagged_hash("BIP0340/nonce", &nonce_data))
+            .map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+        self.negate_if_odd(&mut nonce);
+        let r = self.x_only(&nonce);
+
+        let mut challenge_data = r.clone();
+        challenge_data.extend(&output_key);
+        challenge_data.extend(digest);
+        let challenge = tagged_hash("BIP0340/challenge", &challenge_data);
+        key.mul_assign(&challenge)
+            .map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+        nonce
+            .add_assign(&key[..])
+            .map_err(|_| APDU_RSP_APPLET_WRONG_DATA)?;
+
+        let mut data = r;
+        data.extend(&nonce[..]);
+        Ok(data)
+    }
+
+    fn negate_if_odd(&self, key: &mut SecretKey) {
+        if PublicKey::from_secret_key(&self.secp, key).serialize()[0] == 0x03 {
+            key.mul_assign(&CURVE_ORDER_MINUS_ONE)
+                .expect("n - 1 is a valid tweak");
+        }
+    }
+
+    fn x_only(&self, key: &SecretKey) -> Vec<u8> {
+        PublicKey::from_secret_key(&self.secp, key).serialize()[1..].to_vec()
+    }
+}
+
+impl Default for SeSimulator {
+    fn default() -> Self {
+        SeSimulator::new()
+    }
+}
+
+pub(crate) fn double_sha256(data: &[u8]) -> Vec<u8> {
+    let hash = digest::digest(&digest::SHA256, data);
+    digest::digest(&digest::SHA256, hash.as_ref())
+        .as_ref()
+        .to_vec()
+}
+
+pub(crate) fn sha256(data: &[u8]) -> Vec<u8> {
+    digest::digest(&digest::SHA256, data).as_ref().to_vec()
+}
+
+/**
+bip340 tagged hash
+*/
+pub(crate) fn tagged_hash(tag: &str, data: &[u8]) -> Vec<u8> {
+    let tag_hash = sha256(tag.as_bytes());
+    let mut tagged_data = tag_hash.clone();
+    tagged_data.extend(&tag_hash);
+    tagged_data.extend(data);
+    sha256(&tagged_data)
+}
+
+fn tlv(tag: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut data = tag.to_vec();
+    if value.len() >= 0x80 {
+        data.push(0x81);
+    }
+    data.push(value.len() as u8);
+    data.extend(value);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::se::SeSimulator;
+    use common::apdu::{Apdu, BtcApdu, CoinCommonApdu, EthApdu};
+    use common::constants::BTC_AID;
+    use ring::digest;
+    use secp256k1::{Message, PublicKey, Secp256k1, Signature};
+
+    #[test]
+    fn get_xpub_verify_test() {
+        let mut se = SeSimulator::new();
+        assert_eq!(se.process(&BtcApdu::select_applet()), "9000");
+        let response = se.process(&BtcApdu::get_xpub("m/44'/0'/0'/0/0", true));
+        assert!(response.ends_with("9000"));
+        let data = hex::decode(&response[..response.len() - 4]).unwrap();
+        let (xpub, signature) = data.split_at(97);
+        assert_eq!(xpub[0], 0x04);
+
+        let secp = Secp256k1::new();
+        let hash = digest::digest(&digest::SHA256, xpub);
+        let message = Message::from_slice(hash.as_ref()).unwrap();
+        let signature = Signature::from_der(signature).unwrap();
+        let se_pub_key = PublicKey::from_slice(&se.se_pub_key()).unwrap();
+        assert!(secp.verify(&message, &signature, &se_pub_key).is_ok());
+
+        let response = se.process(&BtcApdu::get_xpub("m/44'/0'/0'/0/0", false));
+        assert_eq!(response.len(), 194 + 4);
+    }
+
+    #[test]
+    fn status_word_test() {
+        let mut se = SeSimulator::new();
+        assert_eq!(se.process(&Apdu::select_applet("695F00000000")), "6A82");
+        assert_eq!(
+            se.process(&EthApdu::sign_digest("m/44'/60'/0'/0/0")),
+            "6D00"
+        );
+        assert_eq!(se.process(&Apdu::select_applet(BTC_AID)), "9000");
+        assert_eq!(se.process("80FF0000"), "6D00");
+
+        assert_eq!(se.process(&EthApdu::select_applet()), "9000");
+        assert_eq!(
+            se.process(&EthApdu::sign_digest("m/44'/60'/0'/0/0")),
+            "6985"
+        );
+        //prepared data needs a bound key, then a valid bind signature
+        let prepare = EthApdu::prepare_sign(vec![0x00, 0x02, 0x30, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(se.process(&prepare[0]), "6985");
+        se.bound_key = Some(se.se_pub_key());
+        assert_eq!(se.process(&prepare[0]), "6942");
+    }
+}
@@ -0,0 +1,125 @@
+use crate::error::SimulatorError;
+use crate::se::SeSimulator;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use transport::transport::DEFAULT_TIMEOUT;
+use transport::Transport;
+
+//every simulator is a key of its own, bound on its own
+static NEXT_SIMULATOR_ID: AtomicUsize = AtomicUsize::new(0);
+
+/**
+transport answered by a software se instead of a connected imKey
+*/
+pub struct SimulatorTransport {
+    se: Mutex<SeSimulator>,
+    connected: AtomicBool,
+    timeout: AtomicI32,
+    id: usize,
+}
+
+impl SimulatorTransport {
+    pub fn new() -> Self {
+        SimulatorTransport::with_simulator(SeSimulator::new())
+    }
+
+    pub fn with_simulator(se: SeSimulator) -> Self {
+        SimulatorTransport {
+            se: Mutex::new(se),
+            connected: AtomicBool::new(false),
+            timeout: AtomicI32::new(DEFAULT_TIMEOUT),
+            id: NEXT_SIMULATOR_ID.fetch_add(1, Ordering::SeqCst),
+        }
+    }
+
+    pub fn se_pub_key(&self) -> Vec<u8> {
+        self.se.lock().unwrap().se_pub_key()
+    }
+
+    pub fn bind_code(&self) -> Option<String> {
+        self.se.lock().unwrap().bind_code()
+    }
+
+    pub fn registered_addresses(&self) -> Vec<String> {
+        self.se.lock().unwrap().registered_addresses().to_vec()
+    }
+}
+
+impl Default for SimulatorTransport {
+    fn default() -> Self {
+        SimulatorTransport::new()
+    }
+}
+
+impl Transport for SimulatorTransport {
+    fn connect(&self) -> transport::Result<()> {
+        self.connected.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn disconnect(&self) -> transport::Result<()> {
+        self.connected.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn exchange(&self, apdu: &str, _timeout: i32) -> transport::Result<String> {
+        if !self.connected.load(Ordering::SeqCst) {
+            return Err(SimulatorError::DeviceNotConnect.into());
+        }
+        println!("-->{}", apdu);
+        let apdu_response = self.se.lock().unwrap().process(apdu);
+        println!("<--{}", apdu_response);
+        Ok(apdu_response)
+    }
+
+    fn timeout(&self) -> i32 {
+        self.timeout.load(Ordering::SeqCst)
+    }
+
+    fn set_timeout(&self, timeout: i32) {
+        self.timeout.store(timeout, Ordering::SeqCst);
+    }
+
+    fn device_id(&self) -> String {
+        format!("simulator-{}", self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulator_transport::SimulatorTransport;
+    use device::device_manager;
+    use transport::Transport;
+
+    #[test]
+    fn device_info_test() {
+        let transport = SimulatorTransport::new();
+        assert!(device_manager::get_se_id(&transport).is_err());
+        assert!(transport.connect().is_ok());
+
+        assert_eq!(
+            device_manager::get_se_id(&transport).unwrap(),
+            "19060000000200860001010000000014"
+        );
+        assert_eq!(
+            device_manager::get_sn(&transport).unwrap(),
+            "imKey01191200001"
+        );
+        assert_eq!(
+            device_manager::get_firmware_version(&transport).unwrap(),
+            "1429"
+        );
+        assert_eq!(device_manager::get_ram_size(&transport).unwrap(), "0FA0");
+        assert_eq!(device_manager::get_battery_power(&transport).unwrap(), "64");
+        assert_eq!(device_manager::get_life_time(&transport).unwrap(), "80");
+        assert_eq!(device_manager::get_ble_version(&transport).unwrap(), "0106");
+        assert!(device_manager::set_ble_name(&transport, "imKey Lite".to_string()).is_ok());
+        assert_eq!(
+            device_manager::get_ble_name(&transport).unwrap(),
+            format!("{}9000", hex::encode_upper("imKey Lite"))
+        );
+        let cert = device_manager::get_cert(&transport).unwrap();
+        assert!(cert.starts_with("BF21"));
+        assert!(cert.contains("931019060000000200860001010000000014"));
+    }
+}
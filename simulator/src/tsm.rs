@@ -0,0 +1,419 @@
+use crate::Result;
+use common::constants::{
+    TSM_ACTION_APP_DELETE, TSM_ACTION_APP_DOWNLOAD, TSM_ACTION_APP_UPDATE,
+    TSM_ACTION_AUTHCODE_STORAGE, TSM_ACTION_COS_CHECK_UPDATE, TSM_ACTION_COS_UPGRADE,
+    TSM_ACTION_DEVICE_CERT_CHECK, TSM_ACTION_SE_ACTIVATE, TSM_ACTION_SE_QUERY,
+    TSM_ACTION_SE_SECURE_CHECK, TSM_END_FLAG, TSM_RETURN_CODE_SUCCESS,
+};
+use common::https::{self, TsmHandler};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+//return code of a request the script has no answer for
+pub const TSM_RETURNCODE_MOCK_UNEXPECTED_STEP: &str = "BMOCK0001";
+
+lazy_static! {
+    //one mock tsm is installed at a time, tests using it run one after another
+    static ref INSTALL_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/**
+one answer of a multi step tsm flow: the apdus the card runs for the request with step_key,
+then the step key the sdk continues with. the sdk only moves on to next_step_key
+when the step carries an apdu_list
+*/
+#[derive(Debug, Clone)]
+pub struct TsmStep {
+    pub step_key: String,
+    pub next_step_key: String,
+    pub apdu_list: Option<Vec<String>>,
+    pub data: Map<String, Value>,
+}
+
+impl TsmStep {
+    pub fn new(step_key: &str, next_step_key: &str) -> Self {
+        TsmStep {
+            step_key: step_key.to_string(),
+            next_step_key: next_step_key.to_string(),
+            apdu_list: None,
+            data: Map::new(),
+        }
+    }
+
+    /**
+    the last answer of a flow
+    */
+    pub fn end(step_key: &str) -> Self {
+        TsmStep::new(step_key, TSM_END_FLAG)
+    }
+
+    pub fn apdus(mut self, apdu_list: &[&str]) -> Self {
+        self.apdu_list = Some(apdu_list.iter().map(|apdu| apdu.to_string()).collect());
+        self
+    }
+
+    /**
+    an extra field of the return data, e.g. instanceAidList for cos upgrade
+    */
+    pub fn with(mut self, key: &str, value: Value) -> Self {
+        self.data.insert(key.to_string(), value);
+        self
+    }
+}
+
+/**
+a request the mock tsm received
+*/
+#[derive(Debug, Clone)]
+pub struct TsmRequest {
+    pub action: String,
+    pub body: Value,
+}
+
+impl TsmRequest {
+    pub fn step_key(&self) -> Option<&str> {
+        self.body["stepKey"].as_str()
+    }
+
+    pub fn status_word(&self) -> Option<&str> {
+        self.body["statusWord"].as_str()
+    }
+
+    pub fn card_ret_data_list(&self) -> Vec<String> {
+        match self.body["cardRetDataList"].as_array() {
+            Some(list) => list
+                .iter()
+                .filter_map(|data| data.as_str().map(str::to_string))
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+/**
+scriptable in process stand-in for the tsm server.
+multi step actions answer from their scripted flow, by default a flow that ends at once.
+single shot actions (seInfoQuery, deviceCertCheck, authCodeStorage, cosCheckUpdate) answer
+a successful default that set_return_data overrides. set_return_code fails an action
+*/
+#[derive(Default)]
+pub struct MockTsm {
+    flows: Mutex<HashMap<String, Vec<TsmStep>>>,
+    return_data: Mutex<HashMap<String, Value>>,
+    return_codes: Mutex<HashMap<String, String>>,
+    requests: Mutex<Vec<TsmRequest>>,
+}
+
+impl MockTsm {
+    pub fn new() -> Arc<MockTsm> {
+        Arc::new(MockTsm::default())
+    }
+
+    pub fn set_flow(&self, action: &str, steps: Vec<TsmStep>) {
+        self.flows.lock().unwrap().insert(action.to_string(), steps);
+    }
+
+    pub fn set_return_data(&self, action: &str, data: Value) {
+        self.return_data
+            .lock()
+            .unwrap()
+            .insert(action.to_string(), data);
+    }
+
+    pub fn set_return_code(&self, action: &str, return_code: &str) {
+        self.return_codes
+            .lock()
+            .unwrap()
+            .insert(action.to_string(), return_code.to_string());
+    }
+
+    /**
+    the requests of an action in the order they were received
+    */
+    pub fn requests(&self, action: &str) -> Vec<TsmRequest> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.action == action)
+            .cloned()
+            .collect()
+    }
+
+    /**
+    route common::https::post to this mock until the guard is dropped
+    */
+    pub fn install(self: &Arc<Self>) -> MockTsmGuard {
+        let lock = INSTALL_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        https::set_tsm_handler(Some(self.clone()));
+        MockTsmGuard { _lock: lock }
+    }
+
+    fn answer(&self, action: &str, body: &Value) -> (String, Value) {
+        if let Some(return_code) = self.return_codes.lock().unwrap().get(action) {
+            return (return_code.clone(), json!({ "seid": body["seid"] }));
+        }
+        if let Some(data) = self.return_data.lock().unwrap().get(action) {
+            return (TSM_RETURN_CODE_SUCCESS.to_string(), data.clone());
+        }
+        match action {
+            TSM_ACTION_SE_QUERY => (
+                TSM_RETURN_CODE_SUCCESS.to_string(),
+                json!({
+                    "seid": body["seid"],
+                    "sn": body["sn"],
+                    "sdkMode": "normal",
+                    "availableAppBeanList": [],
+                    "nextStepKey": TSM_END_FLAG,
+                }),
+            ),
+            TSM_ACTION_DEVICE_CERT_CHECK => (
+                TSM_RETURN_CODE_SUCCESS.to_string(),
+                json!({
+                    "seid": body["seid"],
+                    "verifyResult": true,
+                    "nextStepKey": TSM_END_FLAG,
+                }),
+            ),
+            TSM_ACTION_AUTHCODE_STORAGE => (
+                TSM_RETURN_CODE_SUCCESS.to_string(),
+                json!({ "seid": body["seid"], "nextStepKey": TSM_END_FLAG }),
+            ),
+            TSM_ACTION_COS_CHECK_UPDATE => (
+                TSM_RETURN_CODE_SUCCESS.to_string(),
+                json!({
+                    "seid": body["seid"],
+                    "isLatest": true,
+                    "latestCosVersion": body["cosVersion"],
+                    "isUpdateSuccess": true,
+                }),
+            ),
+            TSM_ACTION_SE_ACTIVATE
+            | TSM_ACTION_SE_SECURE_CHECK
+            | TSM_ACTION_APP_DOWNLOAD
+            | TSM_ACTION_APP_UPDATE
+            | TSM_ACTION_APP_DELETE
+            | TSM_ACTION_COS_UPGRADE => self.answer_step(action, body),
+            _ => (
+                TSM_RETURNCODE_MOCK_UNEXPECTED_STEP.to_string(),
+                json!({ "seid": body["seid"] }),
+            ),
+        }
+    }
+
+    fn answer_step(&self, action: &str, body: &Value) -> (String, Value) {
+        let step_key = body["stepKey"].as_str().unwrap_or_default();
+        let step = match self.flows.lock().unwrap().get(action) {
+            Some(steps) => steps.iter().find(|step| step.step_key == step_key).cloned(),
+            None => Some(TsmStep::end(step_key)),
+        };
+        let step = match step {
+            Some(step) => step,
+            None => {
+                println!("mock tsm has no step {} for {}", step_key, action);
+                return (
+                    TSM_RETURNCODE_MOCK_UNEXPECTED_STEP.to_string(),
+                    json!({ "seid": body["seid"] }),
+                );
+            }
+        };
+        let mut data = step.data;
+        data.insert("seid".to_string(), body["seid"].clone());
+        data.insert("nextStepKey".to_string(), json!(step.next_step_key));
+        data.insert("apduList".to_string(), json!(step.apdu_list));
+        (TSM_RETURN_CODE_SUCCESS.to_string(), Value::Object(data))
+    }
+}
+
+impl TsmHandler for MockTsm {
+    fn post(&self, action: &str, req_data: &[u8]) -> Result<String> {
+        let body: Value = serde_json::from_slice(req_data)?;
+        self.requests.lock().unwrap().push(TsmRequest {
+            action: action.to_string(),
+            body: body.clone(),
+        });
+        let (return_code, return_data) = self.answer(action, &body);
+        Ok(json!({
+            "_ReturnCode": return_code,
+            "_ReturnMsg": "",
+            "_ReturnData": return_data,
+        })
+        .to_string())
+    }
+}
+
+/**
+keeps the mock tsm installed, common::https goes back to the server on drop
+*/
+pub struct MockTsmGuard {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Drop for MockTsmGuard {
+    fn drop(&mut self) {
+        https::set_tsm_handler(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulator_transport::SimulatorTransport;
+    use crate::tsm::{MockTsm, TsmStep};
+    use common::constants::{
+        TSM_ACTION_APP_DOWNLOAD, TSM_ACTION_COS_UPGRADE, TSM_ACTION_DEVICE_CERT_CHECK,
+        TSM_ACTION_SE_ACTIVATE, TSM_ACTION_SE_QUERY, TSM_RETURNCODE_APP_DOWNLOAD_FAIL,
+    };
+    use device::cos_upgrade::CosUpgradeRequest;
+    use device::device_cert_check::DeviceCertCheckRequest;
+    use device::device_manager;
+    use device::error::ImkeyError;
+    use device::TsmService;
+    use serde_json::json;
+    use transport::Transport;
+
+    fn connected_transport() -> SimulatorTransport {
+        let transport = SimulatorTransport::new();
+        assert!(transport.connect().is_ok());
+        transport
+    }
+
+    #[test]
+    fn se_activate_test() {
+        let tsm = MockTsm::new();
+        tsm.set_flow(
+            TSM_ACTION_SE_ACTIVATE,
+            vec![
+                TsmStep::new("01", "02").apdus(&["00A4040000", "80CB800005DFFF028101"]),
+                TsmStep::new("02", "03").apdus(&["80CA004400"]),
+                TsmStep::end("03"),
+            ],
+        );
+        let _guard = tsm.install();
+
+        let transport = connected_transport();
+        assert!(device_manager::active_device(&transport).is_ok());
+
+        let requests = tsm.requests(TSM_ACTION_SE_ACTIVATE);
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].step_key(), Some("01"));
+        assert!(requests[0].card_ret_data_list().is_empty());
+        assert_eq!(requests[1].step_key(), Some("02"));
+        assert_eq!(requests[1].status_word(), Some("9000"));
+        assert_eq!(
+            requests[1].card_ret_data_list(),
+            vec!["9000", "190600000002008600010100000000149000"]
+        );
+        assert_eq!(requests[2].step_key(), Some("03"));
+        assert_eq!(
+            requests[2].card_ret_data_list(),
+            vec![format!("{}9000", hex::encode_upper("imKey01191200001"))]
+        );
+    }
+
+    #[test]
+    fn app_download_test() {
+        let tsm = MockTsm::new();
+        tsm.set_flow(
+            TSM_ACTION_APP_DOWNLOAD,
+            vec![
+                TsmStep::new("01", "02").apdus(&["00A4040005695F627463"]),
+                TsmStep::end("02"),
+            ],
+        );
+        let _guard = tsm.install();
+
+        let transport = connected_transport();
+        assert!(device_manager::app_download(&transport, "BTC").is_ok());
+        let requests = tsm.requests(TSM_ACTION_APP_DOWNLOAD);
+        assert_eq!(requests[0].body["instanceAid"], json!("695F627463"));
+        assert_eq!(requests[1].status_word(), Some("9000"));
+
+        tsm.set_return_code(TSM_ACTION_APP_DOWNLOAD, TSM_RETURNCODE_APP_DOWNLOAD_FAIL);
+        let error = device_manager::app_download(&transport, "BTC")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.downcast::<ImkeyError>().unwrap(),
+            ImkeyError::ImkeyTsmAppDownloadFail
+        );
+    }
+
+    #[test]
+    fn unexpected_step_test() {
+        let tsm = MockTsm::new();
+        //the flow stops answering after the first step
+        tsm.set_flow(
+            TSM_ACTION_SE_ACTIVATE,
+            vec![TsmStep::new("01", "02").apdus(&["00A4040000"])],
+        );
+        let _guard = tsm.install();
+
+        let transport = connected_transport();
+        let error = device_manager::active_device(&transport).err().unwrap();
+        assert_eq!(
+            error.downcast::<ImkeyError>().unwrap(),
+            ImkeyError::ImkeyTsmServerError
+        );
+    }
+
+    #[test]
+    fn query_and_cert_check_test() {
+        let tsm = MockTsm::new();
+        let _guard = tsm.install();
+
+        let transport = connected_transport();
+        let response = device_manager::check_update(&transport).unwrap();
+        assert_eq!(
+            response._ReturnData.sn,
+            Some("imKey01191200001".to_string())
+        );
+        let response = device_manager::cos_check_update(&transport).unwrap();
+        assert!(response._ReturnData.is_latest);
+        assert_eq!(tsm.requests(TSM_ACTION_SE_QUERY).len(), 1);
+
+        let cert = device_manager::get_cert(&transport).unwrap();
+        let seid = device_manager::get_se_id(&transport).unwrap();
+        let sn = device_manager::get_sn(&transport).unwrap();
+        assert!(
+            DeviceCertCheckRequest::build_request_data(seid.clone(), sn.clone(), cert.clone())
+                .send_message(&transport)
+                .is_ok()
+        );
+        tsm.set_return_data(
+            TSM_ACTION_DEVICE_CERT_CHECK,
+            json!({ "seid": seid, "verifyResult": false }),
+        );
+        let error = DeviceCertCheckRequest::build_request_data(seid, sn, cert)
+            .send_message(&transport)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.downcast::<ImkeyError>().unwrap(),
+            ImkeyError::ImkeySeCertInvalid
+        );
+    }
+
+    #[test]
+    fn cos_upgrade_test() {
+        let tsm = MockTsm::new();
+        tsm.set_flow(
+            TSM_ACTION_COS_UPGRADE,
+            vec![
+                TsmStep::new("01", "02").apdus(&["00A4040000"]),
+                TsmStep::new("02", "06").with("instanceAidList", json!(["695F657468"])),
+                TsmStep::end("06"),
+            ],
+        );
+        let _guard = tsm.install();
+
+        let transport = connected_transport();
+        assert!(CosUpgradeRequest::cos_upgrade(&transport, None).is_ok());
+        let requests = tsm.requests(TSM_ACTION_COS_UPGRADE);
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].body["seCosVersion"], json!("1.4.29"));
+        let downloads = tsm.requests(TSM_ACTION_APP_DOWNLOAD);
+        assert_eq!(downloads.len(), 1);
+        assert_eq!(downloads[0].body["instanceAid"], json!("695F657468"));
+    }
+}
@@ -0,0 +1,547 @@
+use crate::error::BridgeError;
+use crate::secure_channel::cmac;
+use crate::transport::{Transport, DEFAULT_TIMEOUT};
+use crate::Result;
+use hex::FromHex;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+//serve the default transport from the bridge at this address instead of a local device
+pub const BRIDGE_ENV: &str = "IMKEY_BRIDGE";
+//the hex aes-128 key a bridge and its clients authenticate each other with
+pub const BRIDGE_KEY_ENV: &str = "IMKEY_BRIDGE_KEY";
+
+//an apdu and its response are far below this, a longer frame is a broken peer
+pub const MAX_FRAME_SIZE: usize = 0x1_0000;
+
+pub const OP_CONNECT: u8 = 0x01;
+pub const OP_DISCONNECT: u8 = 0x02;
+pub const OP_EXCHANGE: u8 = 0x03;
+pub const OP_AUTH: u8 = 0x04;
+
+pub const CHALLENGE_SIZE: usize = 16;
+const MAC_SIZE: usize = 16;
+//which way a frame travels, so a request can never pass for a response
+const DIRECTION_REQUEST: u8 = 0x01;
+const DIRECTION_RESPONSE: u8 = 0x02;
+
+const STATUS_OK: u8 = 0x00;
+const STATUS_ERROR: u8 = 0x01;
+
+/**
+a request to the bridge: the operation, the timeout in seconds and the apdu bytes of an exchange
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub op: u8,
+    pub timeout: i32,
+    pub data: Vec<u8>,
+}
+
+impl Request {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut body = vec![self.op];
+        body.extend_from_slice(&self.timeout.to_be_bytes());
+        body.extend_from_slice(&self.data);
+        body
+    }
+
+    pub fn parse(body: &[u8]) -> Result<Request> {
+        if body.len() < 5 {
+            return Err(BridgeError::ProtocolError.into());
+        }
+        let mut timeout = [0u8; 4];
+        timeout.copy_from_slice(&body[1..5]);
+        Ok(Request {
+            op: body[0],
+            timeout: i32::from_be_bytes(timeout),
+            data: body[5..].to_vec(),
+        })
+    }
+}
+
+/**
+the response bytes of the device, or the error the bridge or the device failed with
+*/
+pub fn serialize_response(response: &std::result::Result<Vec<u8>, String>) -> Vec<u8> {
+    match response {
+        Ok(data) => {
+            let mut body = vec![STATUS_OK];
+            body.extend_from_slice(data);
+            body
+        }
+        Err(message) => {
+            let mut body = vec![STATUS_ERROR];
+            body.extend_from_slice(message.as_bytes());
+            body
+        }
+    }
+}
+
+pub fn parse_response(body: &[u8]) -> Result<std::result::Result<Vec<u8>, String>> {
+    match body.first() {
+        Some(&STATUS_OK) => Ok(Ok(body[1..].to_vec())),
+        Some(&STATUS_ERROR) => Ok(Err(String::from_utf8_lossy(&body[1..]).into_owned())),
+        _ => Err(BridgeError::ProtocolError.into()),
+    }
+}
+
+/**
+a frame is the big endian u32 length of the body and the body
+*/
+pub fn write_frame(stream: &mut impl Write, body: &[u8]) -> Result<()> {
+    if body.len() > MAX_FRAME_SIZE {
+        return Err(BridgeError::FrameTooLong.into());
+    }
+    let mut frame = (body.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(body);
+    stream.write_all(&frame)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/**
+the next frame body, none when the peer closed the stream between frames
+*/
+pub fn read_frame(stream: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut length = [0u8; 4];
+    match stream.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_SIZE {
+        return Err(BridgeError::FrameTooLong.into());
+    }
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/**
+the key of BRIDGE_KEY_ENV, none when it is not set
+*/
+pub fn key_from_env() -> Result<Option<[u8; 16]>> {
+    match env::var(BRIDGE_KEY_ENV) {
+        Ok(key) => Ok(Some(parse_key(&key)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+pub fn parse_key(key: &str) -> Result<[u8; 16]> {
+    <[u8; 16]>::from_hex(key.trim()).map_err(|_| BridgeError::KeyInvalid.into())
+}
+
+pub fn random_challenge() -> Result<[u8; CHALLENGE_SIZE]> {
+    let mut challenge = [0u8; CHALLENGE_SIZE];
+    OsRng::new()?.fill_bytes(&mut challenge);
+    Ok(challenge)
+}
+
+/**
+the frames of a connection both ends hold the key of. the client asks with an auth request, the
+bridge answers with a random challenge in the clear, then every frame body carries a cmac over
+the challenge, its direction, its number on the connection and itself. a frame can neither be
+forged nor replayed nor sent back the other way, it is not encrypted though
+*/
+pub struct FrameAuth {
+    key: [u8; 16],
+    challenge: [u8; CHALLENGE_SIZE],
+    sent: u32,
+    received: u32,
+    //the direction of the frames this end sends
+    direction: u8,
+}
+
+impl FrameAuth {
+    pub fn client(key: [u8; 16], challenge: [u8; CHALLENGE_SIZE]) -> Self {
+        FrameAuth {
+            key,
+            challenge,
+            sent: 0,
+            received: 0,
+            direction: DIRECTION_REQUEST,
+        }
+    }
+
+    pub fn bridge(key: [u8; 16], challenge: [u8; CHALLENGE_SIZE]) -> Self {
+        FrameAuth {
+            direction: DIRECTION_RESPONSE,
+            ..FrameAuth::client(key, challenge)
+        }
+    }
+
+    pub fn seal(&mut self, body: &[u8]) -> Vec<u8> {
+        let mac = self.mac(self.direction, self.sent, body);
+        self.sent += 1;
+        let mut frame = body.to_vec();
+        frame.extend_from_slice(&mac);
+        frame
+    }
+
+    pub fn open(&mut self, frame: &[u8]) -> Result<Vec<u8>> {
+        if frame.len() < MAC_SIZE {
+            return Err(BridgeError::AuthFailed.into());
+        }
+        let (body, mac) = frame.split_at(frame.len() - MAC_SIZE);
+        let direction = match self.direction {
+            DIRECTION_REQUEST => DIRECTION_RESPONSE,
+            _ => DIRECTION_REQUEST,
+        };
+        if self.mac(direction, self.received, body)[..] != *mac {
+            return Err(BridgeError::AuthFailed.into());
+        }
+        self.received += 1;
+        Ok(body.to_vec())
+    }
+
+    fn mac(&self, direction: u8, number: u32, body: &[u8]) -> [u8; MAC_SIZE] {
+        let mut data = self.challenge.to_vec();
+        data.push(direction);
+        data.extend_from_slice(&number.to_be_bytes());
+        data.extend_from_slice(body);
+        cmac(&self.key, &data)
+    }
+}
+
+/**
+where a bridge listens: tcp://host:port, or unix:///path on unix
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum BridgeAddress {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl BridgeAddress {
+    pub fn parse(address: &str) -> Result<BridgeAddress> {
+        if address.starts_with("tcp://") && address.len() > 6 {
+            return Ok(BridgeAddress::Tcp(address[6..].to_string()));
+        }
+        #[cfg(unix)]
+        {
+            if address.starts_with("unix://") && address.len() > 7 {
+                return Ok(BridgeAddress::Unix(PathBuf::from(&address[7..])));
+            }
+        }
+        Err(BridgeError::AddressInvalid.into())
+    }
+
+    pub fn connect(&self) -> Result<BridgeStream> {
+        match self {
+            BridgeAddress::Tcp(address) => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_nodelay(true)?;
+                Ok(BridgeStream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            BridgeAddress::Unix(path) => Ok(BridgeStream::Unix(UnixStream::connect(path)?)),
+        }
+    }
+}
+
+pub enum BridgeStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl BridgeStream {
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            BridgeStream::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            BridgeStream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+}
+
+impl Read for BridgeStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            BridgeStream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            BridgeStream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for BridgeStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            BridgeStream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            BridgeStream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            BridgeStream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            BridgeStream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/**
+a client connection to a bridge, authenticated when the transport has a key
+*/
+struct BridgeConnection {
+    stream: BridgeStream,
+    auth: Option<FrameAuth>,
+}
+
+impl BridgeConnection {
+    fn request(&mut self, request: &Request) -> Result<Vec<u8>> {
+        //the bridge may wait a timeout for the key and another one for the device
+        let timeout = Duration::from_secs(request.timeout.max(1) as u64 * 2 + 5);
+        self.stream.set_read_timeout(Some(timeout))?;
+        let body = request.serialize();
+        match &mut self.auth {
+            Some(auth) => write_frame(&mut self.stream, &auth.seal(&body))?,
+            None => write_frame(&mut self.stream, &body)?,
+        }
+        let body = read_frame(&mut self.stream)?.ok_or(BridgeError::ProtocolError)?;
+        let body = match &mut self.auth {
+            Some(auth) => auth.open(&body)?,
+            None => body,
+        };
+        parse_response(&body)?.map_err(|message| format_err!("{}", message))
+    }
+}
+
+/**
+transport to an imKey served by a bridge. connect takes the key for this connection until
+disconnect, so the commands of other processes do not interleave with the ones sent here
+*/
+pub struct BridgeTransport {
+    address: BridgeAddress,
+    //the aes key of a bridge that authenticates its clients
+    key: Option<[u8; 16]>,
+    connection: Mutex<Option<BridgeConnection>>,
+    timeout: AtomicI32,
+}
+
+impl BridgeTransport {
+    pub fn new(address: BridgeAddress) -> Self {
+        BridgeTransport {
+            address,
+            key: None,
+            connection: Mutex::new(None),
+            timeout: AtomicI32::new(DEFAULT_TIMEOUT),
+        }
+    }
+
+    pub fn with_key(address: BridgeAddress, key: [u8; 16]) -> Self {
+        BridgeTransport {
+            key: Some(key),
+            ..BridgeTransport::new(address)
+        }
+    }
+}
+
+impl Transport for BridgeTransport {
+    fn connect(&self) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        //a new stream every time, closing the old one gives its key back
+        *connection = None;
+        let mut bridge_connection = BridgeConnection {
+            stream: self.address.connect()?,
+            auth: None,
+        };
+        if let Some(key) = self.key {
+            let challenge = bridge_connection.request(&Request {
+                op: OP_AUTH,
+                timeout: self.timeout(),
+                data: vec![],
+            })?;
+            if challenge.len() != CHALLENGE_SIZE {
+                return Err(BridgeError::ProtocolError.into());
+            }
+            let mut bridge_challenge = [0u8; CHALLENGE_SIZE];
+            bridge_challenge.copy_from_slice(&challenge);
+            bridge_connection.auth = Some(FrameAuth::client(key, bridge_challenge));
+        }
+        bridge_connection.request(&Request {
+            op: OP_CONNECT,
+            timeout: self.timeout(),
+            data: vec![],
+        })?;
+        *connection = Some(bridge_connection);
+        Ok(())
+    }
+
+    fn disconnect(&self) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        if let Some(mut bridge_connection) = connection.take() {
+            bridge_connection.request(&Request {
+                op: OP_DISCONNECT,
+                timeout: self.timeout(),
+                data: vec![],
+            })?;
+        }
+        Ok(())
+    }
+
+    fn exchange(&self, apdu: &str, timeout: i32) -> Result<String> {
+        let mut connection = self.connection.lock().unwrap();
+        let bridge_connection = connection.as_mut().ok_or(BridgeError::NotConnected)?;
+        let response = bridge_connection.request(&Request {
+            op: OP_EXCHANGE,
+            timeout,
+            data: Vec::from_hex(apdu)?,
+        })?;
+        Ok(hex::encode_upper(response))
+    }
+
+    fn timeout(&self) -> i32 {
+        self.timeout.load(Ordering::SeqCst)
+    }
+
+    fn set_timeout(&self, timeout: i32) {
+        self.timeout.store(timeout, Ordering::SeqCst);
+    }
+}
+
+/**
+a bridge transport when IMKEY_BRIDGE holds its address, authenticated with the key of
+IMKEY_BRIDGE_KEY when that is set
+*/
+pub fn bridge_from_env() -> Result<Option<Arc<dyn Transport>>> {
+    let address = match env::var(BRIDGE_ENV) {
+        Ok(address) => BridgeAddress::parse(&address)?,
+        Err(_) => return Ok(None),
+    };
+    Ok(Some(Arc::new(match key_from_env()? {
+        Some(key) => BridgeTransport::with_key(address, key),
+        None => BridgeTransport::new(address),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bridge::{
+        parse_key, parse_response, read_frame, serialize_response, write_frame, BridgeAddress,
+        FrameAuth, Request, MAX_FRAME_SIZE, OP_EXCHANGE,
+    };
+    use std::io::Cursor;
+
+    #[test]
+    fn frame_test() {
+        let mut stream = vec![];
+        write_frame(&mut stream, &[0x01, 0x02, 0x03]).unwrap();
+        write_frame(&mut stream, &[]).unwrap();
+        assert_eq!(hex::encode(&stream), "0000000301020300000000");
+
+        let mut stream = Cursor::new(stream);
+        assert_eq!(
+            read_frame(&mut stream).unwrap(),
+            Some(vec![0x01, 0x02, 0x03])
+        );
+        assert_eq!(read_frame(&mut stream).unwrap(), Some(vec![]));
+        assert_eq!(read_frame(&mut stream).unwrap(), None);
+
+        //a body shorter than its length
+        let mut stream = Cursor::new(hex::decode("000000030102").unwrap());
+        assert!(read_frame(&mut stream).is_err());
+        let mut stream = Cursor::new(hex::decode("00010001").unwrap());
+        assert_eq!(
+            format!("{}", read_frame(&mut stream).err().unwrap()),
+            "imkey_bridge_frame_too_long"
+        );
+        assert!(write_frame(&mut vec![], &vec![0u8; MAX_FRAME_SIZE + 1]).is_err());
+    }
+
+    #[test]
+    fn request_test() {
+        let request = Request {
+            op: OP_EXCHANGE,
+            timeout: 20,
+            data: hex::decode("00A4040000").unwrap(),
+        };
+        assert_eq!(hex::encode(request.serialize()), "030000001400a4040000");
+        assert_eq!(Request::parse(&request.serialize()).unwrap(), request);
+        assert_eq!(
+            format!("{}", Request::parse(&[0x03, 0x00]).err().unwrap()),
+            "imkey_bridge_protocol_error"
+        );
+    }
+
+    #[test]
+    fn response_test() {
+        let ok = serialize_response(&Ok(vec![0x90, 0x00]));
+        assert_eq!(hex::encode(&ok), "009000");
+        assert_eq!(parse_response(&ok).unwrap(), Ok(vec![0x90, 0x00]));
+        let error = serialize_response(&Err("imkey_device_not_connect".to_string()));
+        assert_eq!(
+            parse_response(&error).unwrap(),
+            Err("imkey_device_not_connect".to_string())
+        );
+        assert!(parse_response(&[]).is_err());
+        assert!(parse_response(&[0x02]).is_err());
+    }
+
+    #[test]
+    fn address_test() {
+        assert_eq!(
+            BridgeAddress::parse("tcp://127.0.0.1:7007").unwrap(),
+            BridgeAddress::Tcp("127.0.0.1:7007".to_string())
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            BridgeAddress::parse("unix:///tmp/imkey.sock").unwrap(),
+            BridgeAddress::Unix("/tmp/imkey.sock".into())
+        );
+        for address in &["127.0.0.1:7007", "tcp://", "http://127.0.0.1"] {
+            assert_eq!(
+                format!("{}", BridgeAddress::parse(address).err().unwrap()),
+                "imkey_bridge_address_invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn frame_auth_test() {
+        let key = parse_key("2b7e151628aed2a6abf7158809cf4f3c").unwrap();
+        let challenge = [0x11u8; 16];
+        let mut client = FrameAuth::client(key, challenge);
+        let mut bridge = FrameAuth::bridge(key, challenge);
+
+        let request = client.seal(&[0x03, 0x00]);
+        assert_eq!(request.len(), 18);
+        assert_eq!(bridge.open(&request).unwrap(), vec![0x03, 0x00]);
+        let response = bridge.seal(&[0x00, 0x90, 0x00]);
+        assert_eq!(client.open(&response).unwrap(), vec![0x00, 0x90, 0x00]);
+
+        //a replayed, reflected, altered or foreign frame
+        assert!(bridge.open(&request).is_err());
+        let request = client.seal(&[0x03, 0x00]);
+        assert!(client.open(&request).is_err());
+        let mut altered = request.clone();
+        altered[0] = 0x01;
+        assert!(bridge.open(&altered).is_err());
+        let mut other = FrameAuth::bridge([0u8; 16], challenge);
+        assert_eq!(
+            format!("{}", other.open(&request).err().unwrap()),
+            "imkey_bridge_auth_failed"
+        );
+        assert!(bridge.open(&[0x03]).is_err());
+        assert_eq!(bridge.open(&request).unwrap(), vec![0x03, 0x00]);
+
+        assert!(parse_key("2b7e1516").is_err());
+        assert!(parse_key("not a key").is_err());
+    }
+}
@@ -6,4 +6,95 @@ pub enum HidError {
     DeviceConnectInterfaceNotCalled,
     #[fail(display = "device_data_read_time_out")]
     DeviceDataReadTimeOut,
+    #[fail(display = "imkey_device_not_found")]
+    DeviceNotFound,
+    #[fail(display = "imkey_device_reconnect_fail")]
+    DeviceReconnectFail,
+    #[fail(display = "imkey_device_busy")]
+    DeviceBusy,
+}
+
+#[derive(Fail, Debug, PartialOrd, PartialEq)]
+pub enum FixtureError {
+    #[fail(display = "apdu_fixture_format_error")]
+    FixtureFormatError,
+    //the apdus are kept for the debug output of the error
+    #[fail(display = "apdu_replay_mismatch")]
+    ReplayMismatch { expected: String, received: String },
+    #[fail(display = "apdu_replay_exhausted")]
+    ReplayExhausted { received: String },
+    #[fail(display = "apdu_replay_unfinished")]
+    ReplayUnfinished { next: String },
+}
+
+#[derive(Fail, Debug, PartialOrd, PartialEq)]
+pub enum FrameError {
+    #[fail(display = "hid_frame_command_invalid")]
+    CommandInvalid,
+    #[fail(display = "hid_frame_payload_too_long")]
+    PayloadTooLong,
+    #[fail(display = "hid_frame_short_read")]
+    ShortRead,
+    #[fail(display = "hid_frame_channel_mismatch")]
+    ChannelMismatch,
+    #[fail(display = "hid_frame_sequence_error")]
+    SequenceError,
+    #[fail(display = "hid_frame_unexpected_report")]
+    UnexpectedReport,
+    #[fail(display = "hid_frame_incomplete")]
+    Incomplete,
+}
+
+#[derive(Fail, Debug, PartialOrd, PartialEq)]
+pub enum BridgeError {
+    #[fail(display = "imkey_bridge_address_invalid")]
+    AddressInvalid,
+    #[fail(display = "imkey_bridge_frame_too_long")]
+    FrameTooLong,
+    #[fail(display = "imkey_bridge_protocol_error")]
+    ProtocolError,
+    #[fail(display = "imkey_bridge_not_connected")]
+    NotConnected,
+    #[fail(display = "imkey_bridge_key_busy")]
+    KeyBusy,
+    #[fail(display = "imkey_bridge_lease_lost")]
+    LeaseLost,
+    #[fail(display = "imkey_bridge_listen_not_loopback")]
+    ListenNotLoopback,
+    #[fail(display = "imkey_bridge_key_invalid")]
+    KeyInvalid,
+    #[fail(display = "imkey_bridge_auth_failed")]
+    AuthFailed,
+    #[fail(display = "imkey_bridge_remote_needs_key")]
+    RemoteNeedsKey,
+    #[fail(display = "imkey_bridge_socket_dir_not_private")]
+    SocketDirNotPrivate,
+    #[fail(display = "imkey_bridge_device_select_unsupported")]
+    DeviceSelectUnsupported,
+}
+
+#[derive(Fail, Debug, PartialOrd, PartialEq)]
+pub enum SecureChannelError {
+    #[fail(display = "imkey_secure_channel_session_key_invalid")]
+    SessionKeyInvalid,
+    #[fail(display = "imkey_secure_channel_challenge_invalid")]
+    ChallengeInvalid,
+    #[fail(display = "imkey_secure_channel_cryptogram_mismatch")]
+    CryptogramMismatch,
+    #[fail(display = "imkey_secure_channel_command_invalid")]
+    CommandInvalid,
+    #[fail(display = "imkey_secure_channel_response_invalid")]
+    ResponseInvalid,
+    #[fail(display = "imkey_secure_channel_padding_invalid")]
+    PaddingInvalid,
+    #[fail(display = "imkey_secure_channel_mac_missing")]
+    MacMissing,
+    #[fail(display = "imkey_secure_channel_mac_mismatch")]
+    MacMismatch,
+    #[fail(display = "imkey_secure_channel_replay_detected")]
+    ReplayDetected,
+    #[fail(display = "imkey_secure_channel_counter_exhausted")]
+    CounterExhausted,
+    #[fail(display = "imkey_secure_channel_lost")]
+    ChannelLost,
 }
@@ -0,0 +1,351 @@
+use crate::error::FixtureError;
+use crate::transport::{Transport, DEFAULT_TIMEOUT};
+use crate::Result;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+
+//debug builds record every exchange of the default transport to this file
+pub const RECORD_ENV: &str = "IMKEY_APDU_RECORD";
+//debug builds serve the default transport from this file instead of a device
+pub const REPLAY_ENV: &str = "IMKEY_APDU_REPLAY";
+
+const COMMAND_PREFIX: &str = "-->";
+const RESPONSE_PREFIX: &str = "<--";
+const ERROR_PREFIX: &str = "<!-";
+
+/**
+one command and what the device answered, an error when the exchange failed
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    pub apdu: String,
+    pub response: std::result::Result<String, String>,
+}
+
+/**
+parse a fixture: lines of -->command followed by <--response or <!-error,
+blank lines and lines starting with # are ignored
+*/
+pub fn parse_fixture(content: &str) -> Result<Vec<Exchange>> {
+    let mut exchanges = vec![];
+    let mut apdu: Option<String> = None;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with(COMMAND_PREFIX) && apdu.is_none() {
+            apdu = Some(line[COMMAND_PREFIX.len()..].to_string());
+            continue;
+        }
+        let response = if line.starts_with(RESPONSE_PREFIX) {
+            Ok(line[RESPONSE_PREFIX.len()..].to_string())
+        } else if line.starts_with(ERROR_PREFIX) {
+            Err(line[ERROR_PREFIX.len()..].to_string())
+        } else {
+            return Err(FixtureError::FixtureFormatError.into());
+        };
+        match apdu.take() {
+            Some(apdu) => exchanges.push(Exchange { apdu, response }),
+            None => return Err(FixtureError::FixtureFormatError.into()),
+        }
+    }
+    if apdu.is_some() {
+        return Err(FixtureError::FixtureFormatError.into());
+    }
+    Ok(exchanges)
+}
+
+fn format_exchange(exchange: &Exchange) -> String {
+    match &exchange.response {
+        Ok(response) => format!(
+            "{}{}\n{}{}\n",
+            COMMAND_PREFIX, exchange.apdu, RESPONSE_PREFIX, response
+        ),
+        Err(error) => format!(
+            "{}{}\n{}{}\n",
+            COMMAND_PREFIX, exchange.apdu, ERROR_PREFIX, error
+        ),
+    }
+}
+
+/**
+forwards to another transport and appends every exchange to a fixture file
+*/
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    file: Mutex<File>,
+}
+
+impl RecordingTransport {
+    /**
+    the fixture file is truncated, exchanges are flushed as they happen
+    */
+    pub fn new<P: AsRef<Path>>(inner: Arc<dyn Transport>, path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(RecordingTransport {
+            inner,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn connect(&self) -> Result<()> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&self) -> Result<()> {
+        self.inner.disconnect()
+    }
+
+    fn exchange(&self, apdu: &str, timeout: i32) -> Result<String> {
+        let result = self.inner.exchange(apdu, timeout);
+        let exchange = Exchange {
+            apdu: apdu.to_string(),
+            response: match &result {
+                Ok(response) => Ok(response.clone()),
+                Err(error) => Err(error.to_string()),
+            },
+        };
+        let mut file = self.file.lock().unwrap();
+        file.write_all(format_exchange(&exchange).as_bytes())?;
+        file.flush()?;
+        result
+    }
+
+    fn timeout(&self) -> i32 {
+        self.inner.timeout()
+    }
+
+    fn set_timeout(&self, timeout: i32) {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn device_id(&self) -> String {
+        self.inner.device_id()
+    }
+
+    fn reconnect_count(&self) -> u32 {
+        self.inner.reconnect_count()
+    }
+}
+
+/**
+answers from a recorded fixture, each command has to match the recorded one in order
+*/
+pub struct ReplayTransport {
+    exchanges: Vec<Exchange>,
+    position: Mutex<usize>,
+    timeout: AtomicI32,
+}
+
+impl ReplayTransport {
+    pub fn new(exchanges: Vec<Exchange>) -> Self {
+        ReplayTransport {
+            exchanges,
+            position: Mutex::new(0),
+            timeout: AtomicI32::new(DEFAULT_TIMEOUT),
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Ok(ReplayTransport::new(parse_fixture(&content)?))
+    }
+
+    /**
+    check that the session consumed the whole fixture
+    */
+    pub fn finish(&self) -> Result<()> {
+        let position = self.position.lock().unwrap();
+        if *position < self.exchanges.len() {
+            return Err(FixtureError::ReplayUnfinished {
+                next: self.exchanges[*position].apdu.clone(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn connect(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn disconnect(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn exchange(&self, apdu: &str, _timeout: i32) -> Result<String> {
+        let mut position = self.position.lock().unwrap();
+        let exchange =
+            self.exchanges
+                .get(*position)
+                .ok_or_else(|| FixtureError::ReplayExhausted {
+                    received: apdu.to_string(),
+                })?;
+        if !exchange.apdu.eq_ignore_ascii_case(apdu) {
+            return Err(FixtureError::ReplayMismatch {
+                expected: exchange.apdu.clone(),
+                received: apdu.to_string(),
+            }
+            .into());
+        }
+        *position += 1;
+        match &exchange.response {
+            Ok(response) => Ok(response.clone()),
+            Err(error) => Err(format_err!("{}", error)),
+        }
+    }
+
+    fn timeout(&self) -> i32 {
+        self.timeout.load(Ordering::SeqCst)
+    }
+
+    fn set_timeout(&self, timeout: i32) {
+        self.timeout.store(timeout, Ordering::SeqCst);
+    }
+}
+
+/**
+the fixture IMKEY_APDU_REPLAY names to serve instead of a device. only debug builds read it
+*/
+pub fn replay_from_env() -> Result<Option<Arc<dyn Transport>>> {
+    match env::var_os(REPLAY_ENV) {
+        Some(path) if cfg!(debug_assertions) => Ok(Some(Arc::new(ReplayTransport::from_file(
+            PathBuf::from(path),
+        )?))),
+        _ => Ok(None),
+    }
+}
+
+/**
+wrap a transport to record it to the fixture IMKEY_APDU_RECORD names. only debug builds read it
+*/
+pub fn record_from_env(transport: Arc<dyn Transport>) -> Result<Arc<dyn Transport>> {
+    match env::var_os(RECORD_ENV) {
+        Some(path) if cfg!(debug_assertions) => Ok(Arc::new(RecordingTransport::new(
+            transport,
+            PathBuf::from(path),
+        )?)),
+        _ => Ok(transport),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::FixtureError;
+    use crate::fixture::{parse_fixture, Exchange, RecordingTransport, ReplayTransport};
+    use crate::transport::{Transport, DEFAULT_TIMEOUT};
+    use crate::Result;
+    use std::env;
+    use std::fs;
+    use std::sync::Arc;
+
+    //answers 9000 to a select and fails everything else
+    struct SelectOnlyTransport;
+
+    impl Transport for SelectOnlyTransport {
+        fn connect(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn disconnect(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn exchange(&self, apdu: &str, _timeout: i32) -> Result<String> {
+            if apdu.starts_with("00A4") {
+                Ok("9000".to_string())
+            } else {
+                Err(format_err!("imkey_command_execute_fail_6D00"))
+            }
+        }
+
+        fn timeout(&self) -> i32 {
+            DEFAULT_TIMEOUT
+        }
+
+        fn set_timeout(&self, _timeout: i32) {}
+    }
+
+    #[test]
+    fn parse_fixture_test() {
+        let exchanges = parse_fixture(
+            "# bitcoin select\n-->00A4040005695F627463\n<--9000\n\n-->80430000\n<!-imkey_device_not_connect\n",
+        )
+        .unwrap();
+        assert_eq!(
+            exchanges,
+            vec![
+                Exchange {
+                    apdu: "00A4040005695F627463".to_string(),
+                    response: Ok("9000".to_string()),
+                },
+                Exchange {
+                    apdu: "80430000".to_string(),
+                    response: Err("imkey_device_not_connect".to_string()),
+                },
+            ]
+        );
+        assert!(parse_fixture("<--9000").is_err());
+        assert!(parse_fixture("-->00A40400\n-->00A40400").is_err());
+        assert!(parse_fixture("-->00A40400").is_err());
+    }
+
+    #[test]
+    fn record_replay_test() {
+        let path = env::temp_dir().join("imkey_record_replay_test.apdu");
+        let recorder = RecordingTransport::new(Arc::new(SelectOnlyTransport), &path).unwrap();
+        assert_eq!(
+            recorder.send_apdu("00A4040000".to_string()).unwrap(),
+            "9000"
+        );
+        assert!(recorder
+            .send_apdu("80CB800005DFFF028101".to_string())
+            .is_err());
+        drop(recorder);
+
+        let replay = ReplayTransport::from_file(&path).unwrap();
+        assert!(replay.finish().is_err());
+        assert_eq!(replay.send_apdu("00a4040000".to_string()).unwrap(), "9000");
+        let error = replay
+            .send_apdu("80CB800005DFFF028101".to_string())
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "imkey_command_execute_fail_6D00");
+        assert!(replay.finish().is_ok());
+        let error = replay.send_apdu("00A4040000".to_string()).err().unwrap();
+        assert_eq!(
+            error.downcast::<FixtureError>().unwrap(),
+            FixtureError::ReplayExhausted {
+                received: "00A4040000".to_string()
+            }
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replay_mismatch_test() {
+        let replay = ReplayTransport::new(parse_fixture("-->00A4040000\n<--9000").unwrap());
+        let error = replay.send_apdu("80CA004400".to_string()).err().unwrap();
+        assert_eq!(
+            error.downcast::<FixtureError>().unwrap(),
+            FixtureError::ReplayMismatch {
+                expected: "00A4040000".to_string(),
+                received: "80CA004400".to_string()
+            }
+        );
+        //a mismatch does not consume the recorded exchange
+        assert_eq!(replay.send_apdu("00A4040000".to_string()).unwrap(), "9000");
+    }
+}
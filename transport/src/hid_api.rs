@@ -1,175 +1,500 @@
 use super::error::HidError;
-use crate::message::send_apdu;
+use crate::hid_frame::{encode, FrameDecoder, CMD_INIT, CMD_MSG, REPORT_SIZE};
+use crate::transport::{ReconnectPolicy, Transport, DEFAULT_TIMEOUT};
 use crate::Result;
 use hex::FromHex;
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
 use hidapi::{HidApi, HidDevice};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicI32, AtomicU32, AtomicUsize, Ordering};
 use std::sync::Mutex;
 
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
 lazy_static! {
     pub static ref HID_API: Mutex<HidApi> =
         Mutex::new(HidApi::new().expect("hid_initialization_error"));
-    pub static ref HID_DEVICE: Mutex<Vec<HidDevice>> = Mutex::new(vec![]);
+    //the device transports without a path of their own connect to, the first imKey when none
+    static ref SELECTED_PATH: Mutex<Option<String>> = Mutex::new(None);
+    //the key every transport holds open by its id, also while it is reopened
+    static ref OPEN_DEVICES: Mutex<HashMap<usize, HidDeviceInfo>> = Mutex::new(HashMap::new());
 }
 
+static NEXT_TRANSPORT_ID: AtomicUsize = AtomicUsize::new(0);
+
 //const RETRY_SEC: u64 = 1;
 const DEV_VID: u16 = 0x096e;
 const DEV_PID: u16 = 0x0891;
+//the reports of a broken response follow each other, a pause this long means they are all read
+const DRAIN_TIMEOUT_MILLIS: i32 = 50;
 
-pub fn hid_send(apdu: &String, timeout: i32) -> Result<String> {
-    //get hid_device obj
-    let hid_device_obj: &Vec<HidDevice> = &HID_DEVICE.lock().unwrap();
-    if hid_device_obj.is_empty() {
-        drop(hid_device_obj);
-        return Err(HidError::DeviceConnectInterfaceNotCalled.into());
-    }
-    println!("-->{}", apdu);
-    send_device_message(
-        &hid_device_obj.get(0).unwrap(),
-        Vec::from_hex(apdu.as_str()).unwrap().as_slice(),
-    )?;
-    let return_data = read_device_response(&hid_device_obj.get(0).unwrap(), timeout)?;
-    //    drop(hid_device_obj);
-    let apdu_response = hex::encode_upper(return_data);
-    println!("<--{}", apdu_response.clone());
-    Ok(apdu_response)
+/**
+an attached imKey as the hid layer reports it, before any apdu is sent to it
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct HidDeviceInfo {
+    pub path: String,
+    pub serial_number: String,
 }
 
-fn first_write_read_device_response(device: &hidapi::HidDevice) -> Result<Vec<u8>> {
-    let first_send_cmd: [u8; 8] = [0x00, 0x00, 0x00, 0x00, 0x01, 0x86, 0x00, 0x00];
-    let mut send_first_data_string = String::new();
-    for u in &first_send_cmd[..first_send_cmd.len()] {
-        send_first_data_string.push_str((format!("{:02X}", u)).as_ref());
+/**
+every attached imKey, in the order the os lists them. a key exposing several interfaces is
+listed once
+*/
+pub fn enumerate_devices() -> Result<Vec<HidDeviceInfo>> {
+    let mut hid_api = HID_API.lock().unwrap();
+    hid_api.refresh_devices()?;
+    let mut devices: Vec<HidDeviceInfo> = vec![];
+    for info in hid_api.device_list() {
+        if info.vendor_id() != DEV_VID || info.product_id() != DEV_PID {
+            continue;
+        }
+        let path = info.path().to_string_lossy().into_owned();
+        if devices.iter().any(|device| device.path == path) {
+            continue;
+        }
+        devices.push(HidDeviceInfo {
+            path,
+            serial_number: info.serial_number().unwrap_or_default().to_string(),
+        });
     }
-    let _res = device.write(&first_send_cmd)?;
-    let mut buf = vec![0; 64];
-    device.read_timeout(&mut buf, 300_000)?;
+    Ok(devices)
+}
+
+/**
+the path the default transport connects to from its next connect, the first imKey for none
+*/
+pub fn select_device(path: Option<&str>) {
+    let mut selected = SELECTED_PATH.lock().unwrap();
+    *selected = path.map(str::to_string);
+}
+
+pub fn selected_device() -> Option<String> {
+    SELECTED_PATH.lock().unwrap().clone()
+}
 
-    let mut receive_first_data_string = String::new();
-    for u in &buf[..buf.len()] {
-        receive_first_data_string.push_str((format!("{:02X}", u)).as_ref());
+//the usb serial number stays when the path changes, the path is all a key without one has
+fn is_same_device(open: &HidDeviceInfo, device: &HidDeviceInfo) -> bool {
+    if !open.serial_number.is_empty() && open.serial_number == device.serial_number {
+        return true;
     }
+    !open.path.is_empty() && open.path == device.path
+}
 
-    Ok(buf[..64].to_vec())
+//the card manager, selected on every connect
+const SELECT_ISD: &str = "00A40400";
+
+/**
+what the open device was connected as, to find it again after the usb re-enumerated it
+*/
+#[derive(Debug, Default)]
+struct Connection {
+    //the path it was selected by, none for the first imKey
+    path: Option<String>,
+    serial_number: String,
+    //the select command of the current applet
+    applet: Option<String>,
 }
 
-fn read_device_response(device: &hidapi::HidDevice, timeout: i32) -> Result<Vec<u8>> {
-    let mut buf = vec![0; 64];
-    device.read(&mut buf)?;
+/**
+transport over a usb hid connected imKey
+*/
+pub struct HidTransport {
+    id: TransportId,
+    device: Mutex<Option<HidDevice>>,
+    timeout: AtomicI32,
+    path: Option<String>,
+    connection: Mutex<Connection>,
+    reconnect_policy: ReconnectPolicy,
+    //the reopens under an exchange
+    reconnects: AtomicU32,
+    //a probe leaves a key another transport holds alone
+    probe: bool,
+}
 
-    let msg_size = (buf[5] as u8 & 0xFF) + (buf[6] as u8 & 0xFF);
-    let mut data = Vec::new();
-    data.extend_from_slice(&buf[7..]);
-    while data.len() < (msg_size as usize) {
-        device.read_timeout(&mut buf, timeout * 1000)?;
-        data.extend_from_slice(&buf[5..64]);
+impl HidTransport {
+    pub fn new() -> Self {
+        HidTransport {
+            id: TransportId::next(),
+            device: Mutex::new(None),
+            timeout: AtomicI32::new(DEFAULT_TIMEOUT),
+            path: None,
+            connection: Mutex::new(Connection::default()),
+            reconnect_policy: ReconnectPolicy::default(),
+            reconnects: AtomicU32::new(0),
+            probe: false,
+        }
     }
-    data.truncate(msg_size as usize);
 
-    let mut receive_data_string = String::new();
-    for u in &data[..data.len()] {
-        receive_data_string.push_str((format!("{:02X}", u)).as_ref());
+    /**
+    a transport to the imKey at one hid path whatever device is selected
+    */
+    pub fn with_path(path: &str) -> Self {
+        HidTransport {
+            path: Some(path.to_string()),
+            ..HidTransport::new()
+        }
     }
 
-    Ok(data[..msg_size as usize].to_vec())
-}
+    /**
+    how a command that is safe to repeat waits for a device that went away, no attempts
+    turns the reconnect off
+    */
+    pub fn with_reconnect_policy(self, reconnect_policy: ReconnectPolicy) -> Self {
+        HidTransport {
+            reconnect_policy,
+            ..self
+        }
+    }
 
-fn send_device_message(device: &hidapi::HidDevice, msg: &[u8]) -> Result<usize> {
-    let msg_size = msg.len();
-    let mut headerdata = Vec::new();
-    // first pack
-    headerdata.push(0x00 as u8);
-    headerdata.push(0x00 as u8);
-    headerdata.push(0x00 as u8);
-    headerdata.push(0x00 as u8);
-    headerdata.push(0x01 as u8);
-    headerdata.push(0x83 as u8);
-    headerdata.push((msg_size & 0xFF00) as u8);
-    headerdata.push((msg_size & 0x00FF) as u8);
-    let mut data = Vec::new();
-    if (msg_size + 8) < 65 {
-        data.extend_from_slice(&headerdata[0..8]);
-        data.extend_from_slice(&msg[0..msg_size]);
-    } else {
-        let mut datalenflage = 0;
-        let mut flg = 0;
-        loop {
-            if !(datalenflage == 0) {
-                if datalenflage + 65 - 6 > msg_size {
-                    data.extend_from_slice(&headerdata[0..5]);
-                    data.push(flg as u8);
-                    data.extend_from_slice(&msg[datalenflage..msg_size]);
-                    break;
-                }
-                data.extend_from_slice(&headerdata[0..5]);
-                data.push(flg as u8);
-                flg = 1 + flg;
-                data.extend_from_slice(&msg[datalenflage..datalenflage + 65 - 6]);
-                datalenflage += 65 - 6;
-            } else {
-                data.extend_from_slice(&headerdata[0..8]);
-                data.extend_from_slice(&msg[datalenflage..65 - 8]);
-                datalenflage += 65 - 8;
+    /**
+    a transport to ask the key at a path about itself. it fails with busy instead of opening a
+    key another transport holds, and does not wait for a key that goes away
+    */
+    pub fn probe(path: &str) -> Self {
+        HidTransport {
+            probe: true,
+            ..HidTransport::with_path(path).with_reconnect_policy(ReconnectPolicy {
+                attempts: 0,
+                ..ReconnectPolicy::default()
+            })
+        }
+    }
+
+    fn open(&self, hid_api: &HidApi, path: Option<&str>) -> Result<HidDevice> {
+        let hid_device = match path {
+            Some(path) => {
+                let path = CString::new(path).map_err(|_| HidError::DeviceNotFound)?;
+                hid_api.open_path(&path)?
             }
+            None => hid_api.open(DEV_VID, DEV_PID)?,
+        };
+        Ok(hid_device)
+    }
+
+    /**
+    opens and initializes the device and selects the card manager, the usb serial number of
+    the device is returned
+    */
+    fn open_device(&self, path: Option<&str>) -> Result<String> {
+        let hid_api = HID_API.lock().unwrap();
+        let hid_device = self.open(&hid_api, path)?;
+        let serial_number = hid_device
+            .get_serial_number_string()
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let device = HidDeviceInfo {
+            path: path.unwrap_or_default().to_string(),
+            serial_number: serial_number.clone(),
+        };
+        //checked and held under the hid lock, no probe opens the key in between
+        let previous = {
+            let mut open_devices = OPEN_DEVICES.lock().unwrap();
+            if self.probe
+                && open_devices
+                    .iter()
+                    .any(|(id, open)| *id != self.id.0 && is_same_device(open, &device))
+            {
+                return Err(HidError::DeviceBusy.into());
+            }
+            open_devices.insert(self.id.0, device)
+        };
+        drop(hid_api);
+        if let Err(err) = first_write_read_device_response(&hid_device) {
+            //the key held before stays held
+            let mut open_devices = OPEN_DEVICES.lock().unwrap();
+            match previous {
+                Some(previous) => open_devices.insert(self.id.0, previous),
+                None => open_devices.remove(&self.id.0),
+            };
+            return Err(err);
         }
+        *self.device.lock().unwrap() = Some(hid_device);
+        self.exchange_once(SELECT_ISD, self.timeout())?;
+        Ok(serial_number)
     }
 
-    while data.len() % 65 > 0 {
-        data.push(0);
+    /**
+    the path the connected key has now, the usb gives it another one when it re-enumerates
+    */
+    fn find_connected(&self) -> Option<String> {
+        let serial_number = self.connection.lock().unwrap().serial_number.clone();
+        if serial_number.is_empty() {
+            return None;
+        }
+        enumerate_devices()
+            .ok()?
+            .into_iter()
+            .find(|device| device.serial_number == serial_number)
+            .map(|device| device.path)
     }
 
-    let total_written = 0;
-    for chunk in data.chunks(65) {
-        device.write(&chunk)?;
+    fn set_connection(
+        &self,
+        path: Option<String>,
+        open_path: Option<String>,
+        serial_number: String,
+    ) {
+        //a selection by path follows the key to its new path
+        let path = path.and(open_path);
+        if self.path.is_none() && path.is_some() {
+            select_device(path.as_deref());
+        }
+        let mut connection = self.connection.lock().unwrap();
+        connection.path = path;
+        connection.serial_number = serial_number;
+    }
+
+    /**
+    opens the connected key again and selects the applet that was selected before it went away
+    */
+    fn reopen(&self) -> Result<()> {
+        let (path, serial_number, applet) = {
+            let connection = self.connection.lock().unwrap();
+            (
+                connection.path.clone(),
+                connection.serial_number.clone(),
+                connection.applet.clone(),
+            )
+        };
+        let open_path = if serial_number.is_empty() {
+            path.clone()
+        } else {
+            Some(self.find_connected().ok_or(HidError::DeviceNotFound)?)
+        };
+
+        let serial_number = self.open_device(open_path.as_deref())?;
+        self.set_connection(path, open_path, serial_number);
+        if let Some(applet) = applet {
+            if applet != SELECT_ISD
+                && !self
+                    .exchange_once(&applet, self.timeout())?
+                    .ends_with("9000")
+            {
+                return Err(HidError::DeviceReconnectFail.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn exchange_once(&self, apdu: &str, timeout: i32) -> Result<String> {
+        let device = self.device.lock().unwrap();
+        let hid_device = match device.as_ref() {
+            Some(hid_device) => hid_device,
+            None => return Err(HidError::DeviceConnectInterfaceNotCalled.into()),
+        };
+        send_device_message(hid_device, Vec::from_hex(apdu)?.as_slice())?;
+        let return_data = read_device_response(hid_device, timeout)?;
+        let apdu_response = hex::encode_upper(return_data);
+        if is_select(apdu) && apdu_response.ends_with("9000") {
+            self.connection.lock().unwrap().applet = Some(apdu.to_uppercase());
+        }
+        Ok(apdu_response)
     }
-    Ok(total_written)
 }
 
-pub fn hid_connect(_device_model_name: &str) -> Result<()> {
-    //get hid initialization obj
-    let hid_api = HID_API.lock().unwrap();
+impl Default for HidTransport {
+    fn default() -> Self {
+        HidTransport::new()
+    }
+}
 
-    //connect device
-    match hid_api.open(DEV_VID, DEV_PID) {
-        Ok(hid_device) => {
-            println!("device connected!!!");
-            first_write_read_device_response(&hid_device)?;
-            drop(hid_api);
-            let mut hid_device_obj = HID_DEVICE.lock().unwrap();
-            *hid_device_obj = vec![hid_device];
-            drop(hid_device_obj);
-            send_apdu("00A40400".to_string())?;
-            return Ok(());
+/**
+the id a transport holds keys by, which lets its key go when the transport is dropped
+*/
+struct TransportId(usize);
+
+impl TransportId {
+    fn next() -> Self {
+        TransportId(NEXT_TRANSPORT_ID.fetch_add(1, Ordering::SeqCst))
+    }
+}
+
+impl Drop for TransportId {
+    fn drop(&mut self) {
+        OPEN_DEVICES.lock().unwrap().remove(&self.0);
+    }
+}
+
+impl Transport for HidTransport {
+    fn connect(&self) -> Result<()> {
+        let path = self.path.clone().or_else(selected_device);
+        //connecting the same selection again finds the key wherever the usb put it
+        let open_path = if self.connection.lock().unwrap().path == path {
+            self.find_connected().or_else(|| path.clone())
+        } else {
+            path.clone()
+        };
+
+        //connect device
+        match self.open_device(open_path.as_deref()) {
+            Ok(serial_number) => {
+                self.set_connection(path, open_path, serial_number);
+                Ok(())
+            }
+            Err(err) => {
+                //another device than the open one was asked for
+                if self.connection.lock().unwrap().path != path {
+                    return Err(err);
+                }
+                //Check if the connection is normal
+                match self.exchange_once(SELECT_ISD, self.timeout()) {
+                    Ok(_apdu_res) => Ok(()),
+                    Err(_err) => Err(err),
+                }
+            }
         }
-        Err(err) => {
-            println!("device connect failed : {}", err);
-            drop(hid_api);
-            //Check if the connection is normal
-            match send_apdu("00A40400".to_string()) {
-                Ok(_apdu_res) => {
-                    return Ok(());
+    }
+
+    fn disconnect(&self) -> Result<()> {
+        let mut device = self.device.lock().unwrap();
+        *device = None;
+        *self.connection.lock().unwrap() = Connection::default();
+        OPEN_DEVICES.lock().unwrap().remove(&self.id.0);
+        Ok(())
+    }
+
+    /**
+    a command that is safe to repeat is sent again once the device is back when the usb
+    connection was lost, the others fail with the hid error. the reopen is counted for the
+    transports above that keep state on the se
+    */
+    fn exchange(&self, apdu: &str, timeout: i32) -> Result<String> {
+        match self.exchange_once(apdu, timeout) {
+            Err(err)
+                if self.reconnect_policy.attempts > 0
+                    && is_idempotent(apdu)
+                    && err.downcast_ref::<hidapi::HidError>().is_some() =>
+            {
+                *self.device.lock().unwrap() = None;
+                //the key stays held while it is reopened, and is let go when it does not return
+                if let Err(err) = self.reconnect_policy.retry(|| self.reopen()) {
+                    OPEN_DEVICES.lock().unwrap().remove(&self.id.0);
+                    return Err(err);
                 }
-                Err(_err) => {
-                    return Err(err.into());
+                self.reconnects.fetch_add(1, Ordering::SeqCst);
+                self.exchange_once(apdu, timeout)
+            }
+            result => result,
+        }
+    }
+
+    fn timeout(&self) -> i32 {
+        self.timeout.load(Ordering::SeqCst)
+    }
+
+    fn set_timeout(&self, timeout: i32) {
+        self.timeout.store(timeout, Ordering::SeqCst);
+    }
+
+    /**
+    the usb serial number, which stays the same when the key re-enumerates under another path
+    */
+    fn device_id(&self) -> String {
+        let connection = self.connection.lock().unwrap();
+        if !connection.serial_number.is_empty() {
+            return connection.serial_number.clone();
+        }
+        connection.path.clone().unwrap_or_default()
+    }
+
+    fn reconnect_count(&self) -> u32 {
+        self.reconnects.load(Ordering::SeqCst)
+    }
+}
+
+fn is_select(apdu: &str) -> bool {
+    apdu.len() >= 6 && apdu[..6].eq_ignore_ascii_case("00A404")
+}
+
+/**
+commands that only read, select and get data, which the device answers the same way again
+*/
+fn is_idempotent(apdu: &str) -> bool {
+    if apdu.len() < 4 {
+        return false;
+    }
+    let header = apdu[..4].to_uppercase();
+    is_select(apdu) || header == "80CA" || header == "80CB"
+}
+
+fn first_write_read_device_response(device: &hidapi::HidDevice) -> Result<Vec<u8>> {
+    write_reports(device, CMD_INIT, &[])?;
+    let mut buf = vec![0; REPORT_SIZE];
+    device.read_timeout(&mut buf, 300_000)?;
+    Ok(buf)
+}
+
+fn read_device_response(device: &hidapi::HidDevice, timeout: i32) -> Result<Vec<u8>> {
+    read_response(|buf, millis| Ok(device.read_timeout(buf, millis)?), timeout)
+}
+
+/**
+decodes the reports of one response, a broken one is read to its end so the next command
+does not get its leftover reports
+*/
+fn read_response<F>(mut read: F, timeout: i32) -> Result<Vec<u8>>
+where
+    F: FnMut(&mut [u8], i32) -> Result<usize>,
+{
+    let mut decoder = FrameDecoder::new();
+    let mut buf = vec![0; REPORT_SIZE];
+    //the first report waits for the device, which may wait for the user
+    let mut size = read(&mut buf, -1)?;
+    loop {
+        match decoder.push(&buf[..size]) {
+            Ok(true) => return decoder.finish(),
+            Ok(false) => {}
+            Err(err) => {
+                while let Ok(size) = read(&mut buf, DRAIN_TIMEOUT_MILLIS) {
+                    if size == 0 {
+                        break;
+                    }
                 }
+                return Err(err);
             }
         }
-    };
+        size = read(&mut buf, timeout * 1000)?;
+        if size == 0 {
+            return Err(HidError::DeviceDataReadTimeOut.into());
+        }
+    }
+}
+
+fn send_device_message(device: &hidapi::HidDevice, msg: &[u8]) -> Result<usize> {
+    write_reports(device, CMD_MSG, msg)?;
+    Ok(msg.len())
+}
+
+fn write_reports(device: &hidapi::HidDevice, cmd: u8, payload: &[u8]) -> Result<()> {
+    for report in encode(cmd, payload)? {
+        //report id 0 goes first on every write
+        let mut data = Vec::with_capacity(REPORT_SIZE + 1);
+        data.push(0x00);
+        data.extend_from_slice(&report);
+        device.write(&data)?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
-    use crate::hid_api::hid_connect;
-    use crate::message::send_apdu;
+    use crate::hid_api::{
+        is_idempotent, is_same_device, read_response, HidDeviceInfo, HidTransport,
+    };
+    use crate::hid_frame::{encode, CMD_MSG};
+    use crate::transport::Transport;
+    use std::collections::VecDeque;
 
     #[test]
     fn hid_test() {
-        let connect_result = hid_connect("imKey Pro");
+        let transport = HidTransport::new();
+        let connect_result = transport.connect();
         match connect_result {
             Ok(()) => {
-                assert!(send_apdu("00A4040000".to_string()).is_ok());
-                assert!(send_apdu("00A404007500000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000100".to_string()).is_ok())
+                assert!(transport.send_apdu("00A4040000".to_string()).is_ok());
+                assert!(transport.send_apdu("00A404007500000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000100".to_string()).is_ok())
             }
             Err(err) => println!("{}", err),
         }
@@ -178,14 +503,110 @@ mod test {
     #[test]
     fn hid_connect_test() {
         //Test equipment has been connected and connected again
-        let connect_result = hid_connect("imKey Pro");
+        let transport = HidTransport::new();
+        let connect_result = transport.connect();
         if connect_result.is_ok() {
-            assert!(hid_connect("imKey Pro").is_ok());
+            assert!(transport.connect().is_ok());
         }
     }
 
     #[test]
     fn hid_device_is_empty_test() {
-        assert!(send_apdu("00A4040000".to_string()).is_err());
+        let transport = HidTransport::new();
+        assert!(transport.send_apdu("00A4040000".to_string()).is_err());
+    }
+
+    #[test]
+    fn is_idempotent_test() {
+        assert!(is_idempotent("00A4040005695F62746300"));
+        assert!(is_idempotent("00a40400"));
+        assert!(is_idempotent("80CB800005DFFF028101"));
+        assert!(is_idempotent("80CA004400"));
+        //prepare and sign commands change the state of the applet
+        assert!(!is_idempotent("80418000427A222FB0"));
+        assert!(!is_idempotent("8062000002006500"));
+        assert!(!is_idempotent("00A4"));
+        assert!(!is_idempotent(""));
+    }
+
+    #[test]
+    fn is_same_device_test() {
+        let device = |path: &str, serial_number: &str| HidDeviceInfo {
+            path: path.to_string(),
+            serial_number: serial_number.to_string(),
+        };
+        //a key opened as the first imKey is known by its serial number only
+        assert!(is_same_device(
+            &device("", "01"),
+            &device("/dev/hidraw0", "01")
+        ));
+        //the usb moved it to another path
+        assert!(is_same_device(
+            &device("/dev/hidraw0", "01"),
+            &device("/dev/hidraw3", "01")
+        ));
+        assert!(is_same_device(
+            &device("/dev/hidraw0", ""),
+            &device("/dev/hidraw0", "")
+        ));
+        assert!(!is_same_device(
+            &device("/dev/hidraw0", "01"),
+            &device("/dev/hidraw1", "02")
+        ));
+        assert!(!is_same_device(
+            &device("", ""),
+            &device("/dev/hidraw0", "")
+        ));
+    }
+
+    #[test]
+    fn read_response_test() {
+        let payload: Vec<u8> = (0..150).map(|i| i as u8).collect();
+        let reports = encode(CMD_MSG, &payload).unwrap();
+        assert_eq!(reports.len(), 3);
+
+        //reports of one response, then nothing until the next command
+        let mut pending: VecDeque<[u8; 64]> = VecDeque::new();
+        let read = |pending: &mut VecDeque<[u8; 64]>, buf: &mut [u8]| match pending.pop_front() {
+            Some(report) => {
+                buf.copy_from_slice(&report);
+                Ok(report.len())
+            }
+            None => Ok(0),
+        };
+
+        pending.extend(reports.iter().cloned());
+        assert_eq!(
+            read_response(|buf, _| read(&mut pending, buf), 5).unwrap(),
+            payload
+        );
+
+        //a report out of order fails the response and the rest of it is dropped
+        pending.extend(vec![reports[0], reports[2], reports[1]]);
+        assert_eq!(
+            format!(
+                "{}",
+                read_response(|buf, _| read(&mut pending, buf), 5)
+                    .err()
+                    .unwrap()
+            ),
+            "hid_frame_sequence_error"
+        );
+        assert!(pending.is_empty());
+
+        pending.extend(encode(CMD_MSG, &[0x90, 0x00]).unwrap());
+        assert_eq!(
+            read_response(|buf, _| read(&mut pending, buf), 5).unwrap(),
+            vec![0x90, 0x00]
+        );
+    }
+
+    #[test]
+    fn hid_disconnect_test() {
+        let transport = HidTransport::new();
+        if transport.connect().is_ok() {
+            assert!(transport.disconnect().is_ok());
+            assert!(transport.send_apdu("00A4040000".to_string()).is_err());
+        }
     }
 }
@@ -0,0 +1,287 @@
+use crate::error::FrameError;
+use crate::Result;
+
+//reports read from the device, the os adds the report id byte to written ones
+pub const REPORT_SIZE: usize = 64;
+//the only channel the imKey talks on
+pub const CHANNEL: [u8; 4] = [0x00, 0x00, 0x00, 0x01];
+//an apdu or its response
+pub const CMD_MSG: u8 = 0x83;
+//sent once after opening the device
+pub const CMD_INIT: u8 = 0x86;
+
+//channel, command and two length bytes
+const INIT_HEADER_SIZE: usize = 7;
+//channel and sequence
+const CONT_HEADER_SIZE: usize = 5;
+const INIT_DATA_SIZE: usize = REPORT_SIZE - INIT_HEADER_SIZE;
+const CONT_DATA_SIZE: usize = REPORT_SIZE - CONT_HEADER_SIZE;
+//continuation sequences run from 0 to 0x7f, the high bit marks an initialization report
+const MAX_SEQUENCE: usize = 0x80;
+pub const MAX_PAYLOAD_SIZE: usize = INIT_DATA_SIZE + MAX_SEQUENCE * CONT_DATA_SIZE;
+
+/**
+splits a payload into reports: an initialization one with the command and the big endian
+length, then continuations numbered from 0. the last report is zero padded
+*/
+pub fn encode(cmd: u8, payload: &[u8]) -> Result<Vec<[u8; REPORT_SIZE]>> {
+    if cmd & 0x80 == 0 {
+        return Err(FrameError::CommandInvalid.into());
+    }
+    if payload.len() > MAX_PAYLOAD_SIZE {
+        return Err(FrameError::PayloadTooLong.into());
+    }
+    let mut report = [0u8; REPORT_SIZE];
+    report[..4].copy_from_slice(&CHANNEL);
+    report[4] = cmd;
+    report[5..7].copy_from_slice(&(payload.len() as u16).to_be_bytes());
+    let first = payload.len().min(INIT_DATA_SIZE);
+    report[INIT_HEADER_SIZE..INIT_HEADER_SIZE + first].copy_from_slice(&payload[..first]);
+    let mut reports = vec![report];
+
+    for (sequence, chunk) in payload[first..].chunks(CONT_DATA_SIZE).enumerate() {
+        let mut report = [0u8; REPORT_SIZE];
+        report[..4].copy_from_slice(&CHANNEL);
+        report[4] = sequence as u8;
+        report[CONT_HEADER_SIZE..CONT_HEADER_SIZE + chunk.len()].copy_from_slice(chunk);
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+/**
+reassembles one payload from the reports read in order. a report on another channel, a
+continuation out of sequence or a short read fails the whole payload
+*/
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    cmd: Option<u8>,
+    length: usize,
+    sequence: u8,
+    payload: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        FrameDecoder::default()
+    }
+
+    /**
+    adds the next report read, true once the payload is complete
+    */
+    pub fn push(&mut self, report: &[u8]) -> Result<bool> {
+        if report.len() < REPORT_SIZE {
+            return Err(FrameError::ShortRead.into());
+        }
+        if report[..4] != CHANNEL {
+            return Err(FrameError::ChannelMismatch.into());
+        }
+        if self.is_complete() {
+            return Err(FrameError::UnexpectedReport.into());
+        }
+
+        match self.cmd {
+            None => {
+                if report[4] & 0x80 == 0 {
+                    return Err(FrameError::SequenceError.into());
+                }
+                let length = u16::from_be_bytes([report[5], report[6]]) as usize;
+                //more than the continuation sequences can carry
+                if length > MAX_PAYLOAD_SIZE {
+                    return Err(FrameError::PayloadTooLong.into());
+                }
+                self.cmd = Some(report[4]);
+                self.length = length;
+                let first = self.length.min(INIT_DATA_SIZE);
+                self.payload
+                    .extend_from_slice(&report[INIT_HEADER_SIZE..INIT_HEADER_SIZE + first]);
+            }
+            Some(_) => {
+                if report[4] != self.sequence {
+                    return Err(FrameError::SequenceError.into());
+                }
+                self.sequence += 1;
+                let rest = (self.length - self.payload.len()).min(CONT_DATA_SIZE);
+                self.payload
+                    .extend_from_slice(&report[CONT_HEADER_SIZE..CONT_HEADER_SIZE + rest]);
+            }
+        }
+        Ok(self.is_complete())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.cmd.is_some() && self.payload.len() == self.length
+    }
+
+    /**
+    the command of the initialization report, none before it is read
+    */
+    pub fn cmd(&self) -> Option<u8> {
+        self.cmd
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>> {
+        if !self.is_complete() {
+            return Err(FrameError::Incomplete.into());
+        }
+        Ok(self.payload)
+    }
+}
+
+/**
+the payload of a complete list of reports
+*/
+pub fn decode(reports: &[&[u8]]) -> Result<Vec<u8>> {
+    let mut decoder = FrameDecoder::new();
+    for report in reports {
+        decoder.push(report)?;
+    }
+    decoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hid_frame::{
+        decode, encode, FrameDecoder, CMD_INIT, CMD_MSG, MAX_PAYLOAD_SIZE, REPORT_SIZE,
+    };
+
+    fn payload(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i * 7 + size) as u8).collect()
+    }
+
+    fn round_trip(size: usize) {
+        let payload = payload(size);
+        let reports = encode(CMD_MSG, &payload).unwrap();
+        let expected_reports = if size <= 57 {
+            1
+        } else {
+            //the initialization report and one for every started 59 bytes after it
+            2 + (size - 58) / 59
+        };
+        assert_eq!(reports.len(), expected_reports, "size {}", size);
+        let reports: Vec<&[u8]> = reports.iter().map(|report| &report[..]).collect();
+        assert_eq!(decode(&reports).unwrap(), payload, "size {}", size);
+    }
+
+    #[test]
+    fn encode_test() {
+        let reports = encode(CMD_INIT, &[]).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(
+            &reports[0][..7],
+            &[0x00, 0x00, 0x00, 0x01, 0x86, 0x00, 0x00]
+        );
+        assert!(reports[0][7..].iter().all(|byte| *byte == 0));
+
+        //the length is 16 bits, 0x012c for 300 bytes
+        let payload = payload(300);
+        let reports = encode(CMD_MSG, &payload).unwrap();
+        assert_eq!(reports.len(), 6);
+        assert_eq!(
+            &reports[0][..7],
+            &[0x00, 0x00, 0x00, 0x01, 0x83, 0x01, 0x2c]
+        );
+        assert_eq!(&reports[0][7..], &payload[..57]);
+        for (sequence, report) in reports[1..].iter().enumerate() {
+            assert_eq!(&report[..5], &[0x00, 0x00, 0x00, 0x01, sequence as u8]);
+        }
+        assert_eq!(&reports[1][5..], &payload[57..116]);
+        //57 + 4 * 59 = 293, 7 bytes and padding in the last report
+        assert_eq!(&reports[5][5..12], &payload[293..]);
+        assert!(reports[5][12..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn round_trip_test() {
+        for size in (0..700).chain(vec![
+            0xff,
+            0x100,
+            0x101,
+            0x1ff,
+            0x200,
+            MAX_PAYLOAD_SIZE - 1,
+            MAX_PAYLOAD_SIZE,
+        ]) {
+            round_trip(size);
+        }
+    }
+
+    #[test]
+    fn encode_limits_test() {
+        assert_eq!(
+            format!(
+                "{}",
+                encode(CMD_MSG, &payload(MAX_PAYLOAD_SIZE + 1))
+                    .err()
+                    .unwrap()
+            ),
+            "hid_frame_payload_too_long"
+        );
+        assert_eq!(
+            format!("{}", encode(0x03, &[]).err().unwrap()),
+            "hid_frame_command_invalid"
+        );
+    }
+
+    #[test]
+    fn decode_error_test() {
+        let payload = payload(300);
+        let reports = encode(CMD_MSG, &payload).unwrap();
+        let error = |reports: Vec<&[u8]>| format!("{}", decode(&reports).err().unwrap());
+
+        //a continuation missing, repeated or swapped
+        assert_eq!(
+            error(vec![&reports[0], &reports[2]]),
+            "hid_frame_sequence_error"
+        );
+        assert_eq!(
+            error(vec![&reports[0], &reports[1], &reports[1]]),
+            "hid_frame_sequence_error"
+        );
+        assert_eq!(
+            error(vec![&reports[0], &reports[2], &reports[1]]),
+            "hid_frame_sequence_error"
+        );
+        //a continuation first
+        assert_eq!(error(vec![&reports[1]]), "hid_frame_sequence_error");
+        //another channel
+        let mut other_channel = reports[1];
+        other_channel[3] = 0x02;
+        assert_eq!(
+            error(vec![&reports[0], &other_channel]),
+            "hid_frame_channel_mismatch"
+        );
+        //a read of less than a report
+        assert_eq!(
+            error(vec![&reports[0], &reports[1][..REPORT_SIZE - 1]]),
+            "hid_frame_short_read"
+        );
+        //the reads stop before the length
+        assert_eq!(
+            error(reports[..5].iter().map(|report| &report[..]).collect()),
+            "hid_frame_incomplete"
+        );
+        //a report after the payload is complete
+        let mut all: Vec<&[u8]> = reports.iter().map(|report| &report[..]).collect();
+        all.push(&reports[5]);
+        assert_eq!(error(all), "hid_frame_unexpected_report");
+        //a length the sequences cannot reach, 0xffff
+        let mut too_long = reports[0];
+        too_long[5..7].copy_from_slice(&[0xff, 0xff]);
+        assert_eq!(error(vec![&too_long]), "hid_frame_payload_too_long");
+        let mut longest = reports[0];
+        longest[5..7].copy_from_slice(&(MAX_PAYLOAD_SIZE as u16 + 1).to_be_bytes());
+        assert_eq!(error(vec![&longest]), "hid_frame_payload_too_long");
+    }
+
+    #[test]
+    fn decoder_test() {
+        let reports = encode(CMD_MSG, &payload(100)).unwrap();
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.cmd(), None);
+        assert!(!decoder.push(&reports[0]).unwrap());
+        assert_eq!(decoder.cmd(), Some(CMD_MSG));
+        assert!(decoder.push(&reports[1]).unwrap());
+        assert_eq!(decoder.finish().unwrap(), payload(100));
+    }
+}
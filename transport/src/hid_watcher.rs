@@ -0,0 +1,233 @@
+use crate::hid_api::{enumerate_devices, HidDeviceInfo};
+use crate::Result;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/**
+a change in the attached imKeys between two polls
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceEvent {
+    Connected(HidDeviceInfo),
+    Disconnected(HidDeviceInfo),
+    //a key came back in bootloader mode after normal mode or the other way round
+    ModeChanged {
+        device: HidDeviceInfo,
+        is_bl_status: bool,
+    },
+}
+
+/**
+whether a key that just appeared is in bootloader mode, none when it does not answer
+*/
+pub type ModeProbe = Box<dyn Fn(&HidDeviceInfo) -> Option<bool> + Send>;
+
+/**
+compares the attached imKeys with the ones of the last poll. a key switching mode leaves the
+usb and comes back, so its mode is probed once when it appears, never while a session may use it
+*/
+pub struct DeviceWatcher {
+    devices: Vec<HidDeviceInfo>,
+    //the last mode seen of every key, by usb serial number or by path without one
+    modes: HashMap<String, bool>,
+    probe: Option<ModeProbe>,
+}
+
+impl DeviceWatcher {
+    pub fn new() -> Self {
+        DeviceWatcher {
+            devices: vec![],
+            modes: HashMap::new(),
+            probe: None,
+        }
+    }
+
+    pub fn with_probe(probe: ModeProbe) -> Self {
+        DeviceWatcher {
+            probe: Some(probe),
+            ..DeviceWatcher::new()
+        }
+    }
+
+    /**
+    the events since the last poll, every attached key is connected on the first one
+    */
+    pub fn poll(&mut self) -> Result<Vec<DeviceEvent>> {
+        let devices = enumerate_devices()?;
+        Ok(self.update(devices))
+    }
+
+    fn update(&mut self, devices: Vec<HidDeviceInfo>) -> Vec<DeviceEvent> {
+        let mut events = vec![];
+        for device in &self.devices {
+            if !devices.iter().any(|current| current.path == device.path) {
+                events.push(DeviceEvent::Disconnected(device.clone()));
+            }
+        }
+        for device in &devices {
+            if self.devices.iter().any(|known| known.path == device.path) {
+                continue;
+            }
+            events.push(DeviceEvent::Connected(device.clone()));
+            let is_bl_status = match self.probe.as_ref().and_then(|probe| probe(device)) {
+                Some(is_bl_status) => is_bl_status,
+                None => continue,
+            };
+            let key = if device.serial_number.is_empty() {
+                &device.path
+            } else {
+                &device.serial_number
+            };
+            if let Some(last) = self.modes.insert(key.clone(), is_bl_status) {
+                if last != is_bl_status {
+                    events.push(DeviceEvent::ModeChanged {
+                        device: device.clone(),
+                        is_bl_status,
+                    });
+                }
+            }
+        }
+        self.devices = devices;
+        events
+    }
+
+    /**
+    polls on a thread of its own and hands every event to on_event until the handle is
+    stopped or dropped. a failed poll is retried on the next interval
+    */
+    pub fn spawn(
+        mut self,
+        interval: Duration,
+        on_event: impl Fn(DeviceEvent) + Send + 'static,
+    ) -> WatchHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = thread::spawn(move || {
+            while !stopped.load(Ordering::SeqCst) {
+                if let Ok(events) = self.poll() {
+                    events.into_iter().for_each(&on_event);
+                }
+                thread::sleep(interval);
+            }
+        });
+        WatchHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Default for DeviceWatcher {
+    fn default() -> Self {
+        DeviceWatcher::new()
+    }
+}
+
+/**
+a watcher polling on its thread, which stops it when dropped
+*/
+pub struct WatchHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchHandle {
+    pub fn stop(self) {
+        //the drop stops the thread and waits for it
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hid_api::HidDeviceInfo;
+    use crate::hid_watcher::{DeviceEvent, DeviceWatcher};
+    use std::sync::{Arc, Mutex};
+
+    fn device(path: &str, serial_number: &str) -> HidDeviceInfo {
+        HidDeviceInfo {
+            path: path.to_string(),
+            serial_number: serial_number.to_string(),
+        }
+    }
+
+    #[test]
+    fn connect_disconnect_test() {
+        let mut watcher = DeviceWatcher::new();
+        let first = device("/dev/hidraw0", "01");
+        let second = device("/dev/hidraw1", "02");
+        assert_eq!(
+            watcher.update(vec![first.clone()]),
+            vec![DeviceEvent::Connected(first.clone())]
+        );
+        assert_eq!(watcher.update(vec![first.clone()]), vec![]);
+        assert_eq!(
+            watcher.update(vec![first.clone(), second.clone()]),
+            vec![DeviceEvent::Connected(second.clone())]
+        );
+        assert_eq!(
+            watcher.update(vec![second.clone()]),
+            vec![DeviceEvent::Disconnected(first)]
+        );
+        assert_eq!(
+            watcher.update(vec![]),
+            vec![DeviceEvent::Disconnected(second)]
+        );
+    }
+
+    #[test]
+    fn mode_changed_test() {
+        //the mode the next probe answers
+        let mode = Arc::new(Mutex::new(Some(false)));
+        let probe_mode = mode.clone();
+        let mut watcher = DeviceWatcher::with_probe(Box::new(move |_| *probe_mode.lock().unwrap()));
+        let normal = device("/dev/hidraw0", "01");
+        assert_eq!(
+            watcher.update(vec![normal.clone()]),
+            vec![DeviceEvent::Connected(normal.clone())]
+        );
+
+        //the switch to bootloader re-enumerates the key at another path
+        *mode.lock().unwrap() = Some(true);
+        assert_eq!(
+            watcher.update(vec![]),
+            vec![DeviceEvent::Disconnected(normal)]
+        );
+        let bootloader = device("/dev/hidraw3", "01");
+        assert_eq!(
+            watcher.update(vec![bootloader.clone()]),
+            vec![
+                DeviceEvent::Connected(bootloader.clone()),
+                DeviceEvent::ModeChanged {
+                    device: bootloader.clone(),
+                    is_bl_status: true
+                }
+            ]
+        );
+
+        //an unanswered probe keeps the mode last seen
+        *mode.lock().unwrap() = None;
+        watcher.update(vec![]);
+        assert_eq!(
+            watcher.update(vec![bootloader.clone()]),
+            vec![DeviceEvent::Connected(bootloader.clone())]
+        );
+        *mode.lock().unwrap() = Some(true);
+        watcher.update(vec![]);
+        assert_eq!(
+            watcher.update(vec![bootloader.clone()]),
+            vec![DeviceEvent::Connected(bootloader)]
+        );
+    }
+}
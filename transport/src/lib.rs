@@ -1,12 +1,21 @@
+pub mod bridge;
 pub mod error;
+pub mod fixture;
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
 pub mod hid_api;
+pub mod hid_frame;
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+pub mod hid_watcher;
 pub mod message;
+pub mod secure_channel;
+pub mod transport;
 #[macro_use]
 extern crate failure;
 use core::result;
 pub type Result<T> = result::Result<T, failure::Error>;
 
+pub use crate::transport::{default_transport, secure_transport, ReconnectPolicy, Transport};
+
 //#[macro_use]
 ////extern crate log;
 #[macro_use]
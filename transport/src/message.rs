@@ -1,8 +1,8 @@
-#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-use super::hid_api;
+use crate::transport::{Transport, DEFAULT_TIMEOUT};
 use crate::Result;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
+use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Mutex;
 use std::sync::RwLock;
 use std::thread;
@@ -110,49 +110,61 @@ pub fn set_apdu_return(apdu_return: *const c_char) {
     drop(_apdu_return);
 }
 
-#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-pub fn send_apdu(apdu: String) -> Result<String> {
-    send_apdu_timeout(apdu, 20)
+/**
+transport over the callback registered by the host app, used on mobile
+*/
+pub struct CallbackTransport {
+    timeout: AtomicI32,
 }
 
-#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-pub fn send_apdu_timeout(apdu: String, timeout: i32) -> Result<String> {
-    hid_api::hid_send(&apdu, timeout)
+impl CallbackTransport {
+    pub fn new() -> Self {
+        CallbackTransport {
+            timeout: AtomicI32::new(DEFAULT_TIMEOUT),
+        }
+    }
 }
 
-#[cfg(any(target_os = "android", target_os = "ios"))]
-pub fn send_apdu(apdu: String) -> Result<String> {
-    send_apdu_timeout(apdu, 20)
+impl Default for CallbackTransport {
+    fn default() -> Self {
+        CallbackTransport::new()
+    }
 }
 
-#[cfg(any(target_os = "android", target_os = "ios"))]
-pub fn send_apdu_timeout(apdu: String, timeout: i32) -> Result<String> {
-    // set_apdu_r(apdu);
-    // get_apdu_return_r().unwrap()
+impl Transport for CallbackTransport {
+    //the connection is managed by the host app
+    fn connect(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn disconnect(&self) -> Result<()> {
+        Ok(())
+    }
 
-    let callback = CALLBACK.lock().unwrap();
-    let ptr = callback(CString::new(apdu).unwrap().into_raw(), timeout);
-
-    // let mut res = unsafe { Ok(CStr::from_ptr(ptr).to_string_lossy().into_owned()) }?;
-    // let prefix = "communication_error_";
-    // if res.starts_with(prefix){
-    //     let error = &res[..prefix.len()-1];
-    //     return Err(format_err!("{}", error))
-    // }else {
-    //     return Ok(res)
-    // }
-
-    unsafe {
-        let res = CStr::from_ptr(ptr).to_string_lossy().into_owned();
-        let prefix = "communication_error_";
-        return if res.starts_with(prefix) {
-            let error = &res[prefix.len()..];
-            println!("{}", error);
-            Err(format_err!("{}", error))
-        } else {
-            println!("{}", res);
-            Ok(res)
-        };
+    fn exchange(&self, apdu: &str, timeout: i32) -> Result<String> {
+        let callback = CALLBACK.lock().unwrap();
+        let ptr = callback(CString::new(apdu).unwrap().into_raw(), timeout);
+
+        unsafe {
+            let res = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+            let prefix = "communication_error_";
+            if res.starts_with(prefix) {
+                let error = &res[prefix.len()..];
+                println!("{}", error);
+                Err(format_err!("{}", error))
+            } else {
+                println!("{}", res);
+                Ok(res)
+            }
+        }
+    }
+
+    fn timeout(&self) -> i32 {
+        self.timeout.load(Ordering::SeqCst)
+    }
+
+    fn set_timeout(&self, timeout: i32) {
+        self.timeout.store(timeout, Ordering::SeqCst);
     }
 }
 
@@ -182,3 +194,13 @@ fn test_callback() {
     let result = unsafe { CStr::from_ptr(ptr).to_string_lossy().into_owned() };
     println!("callback result:{:#?}", result);
 }
+
+#[test]
+fn test_callback_transport() {
+    let transport = CallbackTransport::new();
+    assert_eq!(transport.timeout(), DEFAULT_TIMEOUT);
+    transport.set_timeout(120);
+    assert_eq!(transport.timeout(), 120);
+    let result = transport.send_apdu("00A4040000".to_string()).unwrap();
+    assert_eq!(result, "need set callback!");
+}
@@ -0,0 +1,765 @@
+use crate::error::SecureChannelError;
+use crate::{Result, Transport};
+use aes_soft::block_cipher_trait::generic_array::GenericArray;
+use aes_soft::block_cipher_trait::BlockCipher;
+use aes_soft::Aes128;
+use std::sync::{Arc, Mutex};
+
+const BLOCK_SIZE: usize = 16;
+pub const CHALLENGE_SIZE: usize = 8;
+pub const CRYPTOGRAM_SIZE: usize = 8;
+const COUNTER_SIZE: usize = 4;
+const MAC_SIZE: usize = 8;
+//the class bit telling the se the command is wrapped
+const CLA_SECURE_MESSAGING: u8 = 0x04;
+
+//derivation constants of gp scp03
+const DERIVE_CARD_CRYPTOGRAM: u8 = 0x00;
+const DERIVE_S_ENC: u8 = 0x04;
+const DERIVE_S_MAC: u8 = 0x06;
+
+//which way a block travels, so a command can never pass for a response
+const DIRECTION_COMMAND: u8 = 0x00;
+const DIRECTION_RESPONSE: u8 = 0x80;
+
+/**
+aes-cmac of rfc 4493
+*/
+pub fn cmac(key: &[u8; 16], data: &[u8]) -> [u8; 16] {
+    let cipher = Aes128::new(GenericArray::from_slice(key));
+    let mut subkey = [0u8; BLOCK_SIZE];
+    encrypt_block(&cipher, &mut subkey);
+    let k1 = double(&subkey);
+    let k2 = double(&k1);
+
+    //empty data is one empty block, padded like any incomplete one
+    let mut chunks: Vec<&[u8]> = data.chunks(BLOCK_SIZE).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    let last = chunks.len() - 1;
+    let mut mac = [0u8; BLOCK_SIZE];
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let mut block = [0u8; BLOCK_SIZE];
+        block[..chunk.len()].copy_from_slice(chunk);
+        if index == last {
+            if chunk.len() == BLOCK_SIZE {
+                xor(&mut block, &k1);
+            } else {
+                block[chunk.len()] = 0x80;
+                xor(&mut block, &k2);
+            }
+        }
+        xor(&mut mac, &block);
+        encrypt_block(&cipher, &mut mac);
+    }
+    mac
+}
+
+fn encrypt_block(cipher: &Aes128, block: &mut [u8; 16]) {
+    cipher.encrypt_block(GenericArray::from_mut_slice(block));
+}
+
+fn double(block: &[u8; 16]) -> [u8; 16] {
+    let mut result = [0u8; BLOCK_SIZE];
+    for index in 0..BLOCK_SIZE {
+        let carry = block.get(index + 1).map_or(0, |next| next >> 7);
+        result[index] = block[index] << 1 | carry;
+    }
+    if block[0] & 0x80 != 0 {
+        result[BLOCK_SIZE - 1] ^= 0x87;
+    }
+    result
+}
+
+fn xor(block: &mut [u8; 16], other: &[u8; 16]) {
+    block
+        .iter_mut()
+        .zip(other.iter())
+        .for_each(|(byte, other)| *byte ^= other);
+}
+
+/**
+the scp03 key derivation, nist sp 800-108 in counter mode with cmac, for one block of output
+*/
+fn derive(key: &[u8; 16], constant: u8, context: &[u8], bits: u16) -> [u8; 16] {
+    let mut data = vec![0u8; 11];
+    data.push(constant);
+    data.push(0x00);
+    data.extend(&bits.to_be_bytes());
+    data.push(0x01);
+    data.extend(context);
+    cmac(key, &data)
+}
+
+/**
+the header and the data field of a short apdu, the trailing Le is left out
+*/
+fn split_command(apdu: &[u8]) -> Result<(&[u8], &[u8])> {
+    if apdu.len() < 4 {
+        return Err(SecureChannelError::CommandInvalid.into());
+    }
+    let data = if apdu.len() > 5 {
+        let lc = apdu[4] as usize;
+        apdu.get(5..5 + lc)
+            .ok_or(SecureChannelError::CommandInvalid)?
+    } else {
+        &[]
+    };
+    Ok((&apdu[..4], data))
+}
+
+/**
+one secure messaging session between the app and the se, keyed from the bind session key and a
+challenge of each side. both ends count the commands, the se only takes a counter above the last
+one it saw, so a recorded command can not be played again and a response only verifies for the
+command it answers
+*/
+pub struct SecureChannel {
+    enc_key: [u8; 16],
+    mac_key: [u8; 16],
+    card_cryptogram: [u8; 8],
+    counter: u32,
+}
+
+impl SecureChannel {
+    pub fn new(
+        session_key: &[u8],
+        host_challenge: &[u8],
+        card_challenge: &[u8],
+    ) -> Result<SecureChannel> {
+        if session_key.len() != BLOCK_SIZE {
+            return Err(SecureChannelError::SessionKeyInvalid.into());
+        }
+        if host_challenge.len() != CHALLENGE_SIZE || card_challenge.len() != CHALLENGE_SIZE {
+            return Err(SecureChannelError::ChallengeInvalid.into());
+        }
+        let mut key = [0u8; BLOCK_SIZE];
+        key.copy_from_slice(session_key);
+        let mut context = host_challenge.to_vec();
+        context.extend(card_challenge);
+
+        let mac_key = derive(&key, DERIVE_S_MAC, &context, 128);
+        let mut card_cryptogram = [0u8; CRYPTOGRAM_SIZE];
+        card_cryptogram.copy_from_slice(
+            &derive(&mac_key, DERIVE_CARD_CRYPTOGRAM, &context, 64)[..CRYPTOGRAM_SIZE],
+        );
+        Ok(SecureChannel {
+            enc_key: derive(&key, DERIVE_S_ENC, &context, 128),
+            mac_key,
+            card_cryptogram,
+            counter: 0,
+        })
+    }
+
+    /**
+    what the se answers the open command with, proving it holds the same session key
+    */
+    pub fn card_cryptogram(&self) -> [u8; 8] {
+        self.card_cryptogram
+    }
+
+    /**
+    the counter of the last command wrapped or taken
+    */
+    pub fn counter(&self) -> u32 {
+        self.counter
+    }
+
+    /**
+    encrypts the data field and appends the counter and a mac, the result is an extended length
+    apdu as the data can outgrow a short Lc
+    */
+    pub fn wrap_command(&mut self, apdu: &[u8]) -> Result<Vec<u8>> {
+        let (header, data) = split_command(apdu)?;
+        if header[0] & CLA_SECURE_MESSAGING != 0 {
+            return Err(SecureChannelError::CommandInvalid.into());
+        }
+        let counter = self
+            .counter
+            .checked_add(1)
+            .ok_or(SecureChannelError::CounterExhausted)?;
+        self.counter = counter;
+
+        let mut header = header.to_vec();
+        header[0] |= CLA_SECURE_MESSAGING;
+        let mut body = counter.to_be_bytes().to_vec();
+        body.extend(self.encrypt(DIRECTION_COMMAND, counter, data));
+        let mac = self.mac(DIRECTION_COMMAND, &header, &body);
+        body.extend(&mac);
+
+        let mut wrapped = header;
+        wrapped.push(0x00);
+        wrapped.extend(&(body.len() as u16).to_be_bytes());
+        wrapped.extend(body);
+        wrapped.extend(&[0x00, 0x00]);
+        Ok(wrapped)
+    }
+
+    /**
+    the response data and status word of the last command wrapped. an error status word comes
+    without data or mac and is handed on as it is, a success one has to verify
+    */
+    pub fn unwrap_response(&self, response: &[u8]) -> Result<Vec<u8>> {
+        if response.len() < 2 {
+            return Err(SecureChannelError::ResponseInvalid.into());
+        }
+        let (body, status_word) = response.split_at(response.len() - 2);
+        if body.is_empty() && status_word != [0x90, 0x00] {
+            return Ok(status_word.to_vec());
+        }
+        if body.len() < MAC_SIZE {
+            return Err(SecureChannelError::MacMissing.into());
+        }
+        let (ciphertext, mac) = body.split_at(body.len() - MAC_SIZE);
+        let mut authenticated = self.counter.to_be_bytes().to_vec();
+        authenticated.extend(ciphertext);
+        if self.mac(DIRECTION_RESPONSE, status_word, &authenticated)[..] != *mac {
+            return Err(SecureChannelError::MacMismatch.into());
+        }
+        let mut data = self.decrypt(DIRECTION_RESPONSE, self.counter, ciphertext)?;
+        data.extend(status_word);
+        Ok(data)
+    }
+
+    /**
+    the se side of wrap_command, gives back the plain short apdu
+    */
+    pub fn unwrap_command(&mut self, apdu: &[u8]) -> Result<Vec<u8>> {
+        if apdu.len() < 7 || apdu[0] & CLA_SECURE_MESSAGING == 0 || apdu[4] != 0x00 {
+            return Err(SecureChannelError::CommandInvalid.into());
+        }
+        let header = &apdu[..4];
+        let lc = u16::from_be_bytes([apdu[5], apdu[6]]) as usize;
+        let body = apdu
+            .get(7..7 + lc)
+            .ok_or(SecureChannelError::CommandInvalid)?;
+        if body.len() < COUNTER_SIZE + MAC_SIZE {
+            return Err(SecureChannelError::CommandInvalid.into());
+        }
+        let (body, mac) = body.split_at(body.len() - MAC_SIZE);
+        if self.mac(DIRECTION_COMMAND, header, body)[..] != *mac {
+            return Err(SecureChannelError::MacMismatch.into());
+        }
+        let mut counter = [0u8; COUNTER_SIZE];
+        counter.copy_from_slice(&body[..COUNTER_SIZE]);
+        let counter = u32::from_be_bytes(counter);
+        if counter <= self.counter {
+            return Err(SecureChannelError::ReplayDetected.into());
+        }
+        self.counter = counter;
+
+        let data = self.decrypt(DIRECTION_COMMAND, counter, &body[COUNTER_SIZE..])?;
+        if data.len() > 0xFF {
+            return Err(SecureChannelError::CommandInvalid.into());
+        }
+        let mut command = header.to_vec();
+        command[0] &= !CLA_SECURE_MESSAGING;
+        if !data.is_empty() {
+            command.push(data.len() as u8);
+            command.extend(data);
+        }
+        command.push(0x00);
+        Ok(command)
+    }
+
+    /**
+    the se side of unwrap_response, an error status word goes back alone
+    */
+    pub fn wrap_response(&self, response: &[u8]) -> Result<Vec<u8>> {
+        if response.len() < 2 {
+            return Err(SecureChannelError::ResponseInvalid.into());
+        }
+        let (data, status_word) = response.split_at(response.len() - 2);
+        if status_word != [0x90, 0x00] {
+            return Ok(status_word.to_vec());
+        }
+        let ciphertext = self.encrypt(DIRECTION_RESPONSE, self.counter, data);
+        let mut authenticated = self.counter.to_be_bytes().to_vec();
+        authenticated.extend(&ciphertext);
+        let mut wrapped = ciphertext;
+        wrapped.extend(&self.mac(DIRECTION_RESPONSE, status_word, &authenticated));
+        wrapped.extend(status_word);
+        Ok(wrapped)
+    }
+
+    /**
+    every message gets its own iv, the counter and the direction encrypted
+    */
+    fn iv(&self, direction: u8, counter: u32) -> [u8; 16] {
+        let mut iv = [0u8; BLOCK_SIZE];
+        iv[0] = direction;
+        iv[1..1 + COUNTER_SIZE].copy_from_slice(&counter.to_be_bytes());
+        encrypt_block(
+            &Aes128::new(GenericArray::from_slice(&self.enc_key)),
+            &mut iv,
+        );
+        iv
+    }
+
+    /**
+    aes-cbc with iso 9797-1 method 2 padding, empty data stays empty
+    */
+    fn encrypt(&self, direction: u8, counter: u32, data: &[u8]) -> Vec<u8> {
+        if data.is_empty() {
+            return vec![];
+        }
+        let cipher = Aes128::new(GenericArray::from_slice(&self.enc_key));
+        let mut padded = data.to_vec();
+        padded.push(0x80);
+        padded.resize((data.len() / BLOCK_SIZE + 1) * BLOCK_SIZE, 0x00);
+        let mut chain = self.iv(direction, counter);
+        let mut ciphertext = vec![];
+        for chunk in padded.chunks(BLOCK_SIZE) {
+            let mut block = [0u8; BLOCK_SIZE];
+            block.copy_from_slice(chunk);
+            xor(&mut chain, &block);
+            encrypt_block(&cipher, &mut chain);
+            ciphertext.extend(&chain);
+        }
+        ciphertext
+    }
+
+    fn decrypt(&self, direction: u8, counter: u32, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        if ciphertext.is_empty() {
+            return Ok(vec![]);
+        }
+        let cipher = Aes128::new(GenericArray::from_slice(&self.enc_key));
+        let mut chain = self.iv(direction, counter);
+        let mut data = vec![];
+        for chunk in ciphertext.chunks(BLOCK_SIZE) {
+            if chunk.len() != BLOCK_SIZE {
+                return Err(SecureChannelError::PaddingInvalid.into());
+            }
+            let mut block = [0u8; BLOCK_SIZE];
+            block.copy_from_slice(chunk);
+            let next = block;
+            cipher.decrypt_block(GenericArray::from_mut_slice(&mut block));
+            xor(&mut block, &chain);
+            data.extend(&block);
+            chain = next;
+        }
+        //the padding is one 0x80 after the data and zeros up to the block end
+        match data.iter().rposition(|byte| *byte != 0x00) {
+            Some(end) if data[end] == 0x80 && data.len() - end <= BLOCK_SIZE => {
+                data.truncate(end);
+                Ok(data)
+            }
+            _ => Err(SecureChannelError::PaddingInvalid.into()),
+        }
+    }
+
+    fn mac(&self, direction: u8, header: &[u8], body: &[u8]) -> [u8; 8] {
+        let mut data = vec![direction];
+        data.extend(header);
+        data.extend(body);
+        let mut mac = [0u8; MAC_SIZE];
+        mac.copy_from_slice(&cmac(&self.mac_key, &data)[..MAC_SIZE]);
+        mac
+    }
+}
+
+/**
+wraps the commands sent through another transport once a secure channel is open, and passes
+them on as they are before. selects stay plain so the se knows the applet to route to, as do
+the 0xFF device commands, which are not iso ones. a device the inner transport opened again
+has dropped the channel, which is then lost: the commands it would have wrapped fail until the
+channel is closed or opened again, they never go plain
+*/
+pub struct SecureTransport {
+    inner: Arc<dyn Transport>,
+    state: Mutex<ChannelState>,
+}
+
+enum ChannelState {
+    Closed,
+    //the channel and the reconnect count of the inner transport it was opened at
+    Open(SecureChannel, u32),
+    Lost,
+}
+
+impl SecureTransport {
+    pub fn new(inner: Arc<dyn Transport>) -> Self {
+        SecureTransport {
+            inner,
+            state: Mutex::new(ChannelState::Closed),
+        }
+    }
+
+    pub fn open(&self, channel: SecureChannel) {
+        *self.state.lock().unwrap() = ChannelState::Open(channel, self.inner.reconnect_count());
+    }
+
+    pub fn close(&self) {
+        *self.state.lock().unwrap() = ChannelState::Closed;
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), ChannelState::Open(..))
+    }
+
+    pub fn is_lost(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), ChannelState::Lost)
+    }
+}
+
+fn is_wrapped(apdu: &str) -> bool {
+    if apdu.len() < 6 || apdu[..6].eq_ignore_ascii_case("00A404") {
+        return false;
+    }
+    apdu[..2].eq_ignore_ascii_case("00") || apdu[..2].eq_ignore_ascii_case("80")
+}
+
+impl Transport for SecureTransport {
+    fn connect(&self) -> Result<()> {
+        self.inner.connect()
+    }
+
+    fn disconnect(&self) -> Result<()> {
+        //the se forgets the channel once it is off the usb
+        self.close();
+        self.inner.disconnect()
+    }
+
+    fn exchange(&self, apdu: &str, timeout: i32) -> Result<String> {
+        //held over the exchange, the counters have to reach the se in order
+        let mut state = self.state.lock().unwrap();
+        let result = match &mut *state {
+            ChannelState::Open(channel, _) if is_wrapped(apdu) => {
+                let command = channel.wrap_command(&hex::decode(apdu)?)?;
+                self.inner
+                    .exchange(&hex::encode_upper(command), timeout)
+                    .and_then(|response| channel.unwrap_response(&hex::decode(response)?))
+                    .map(hex::encode_upper)
+            }
+            ChannelState::Lost if is_wrapped(apdu) => {
+                return Err(SecureChannelError::ChannelLost.into())
+            }
+            _ => self.inner.exchange(apdu, timeout),
+        };
+        //whatever the se answered after it was reopened, it has no channel any more
+        if let ChannelState::Open(_, reconnects) = *state {
+            if reconnects != self.inner.reconnect_count() {
+                *state = ChannelState::Lost;
+                return Err(SecureChannelError::ChannelLost.into());
+            }
+        }
+        result
+    }
+
+    fn timeout(&self) -> i32 {
+        self.inner.timeout()
+    }
+
+    fn set_timeout(&self, timeout: i32) {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn device_id(&self) -> String {
+        self.inner.device_id()
+    }
+
+    fn reconnect_count(&self) -> u32 {
+        self.inner.reconnect_count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::SecureChannelError;
+    use crate::secure_channel::{cmac, SecureChannel, SecureTransport};
+    use crate::{Result, Transport};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    const SESSION_KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const HOST_CHALLENGE: &str = "0102030405060708";
+    const CARD_CHALLENGE: &str = "1112131415161718";
+
+    fn channel() -> SecureChannel {
+        SecureChannel::new(
+            &hex::decode(SESSION_KEY).unwrap(),
+            &hex::decode(HOST_CHALLENGE).unwrap(),
+            &hex::decode(CARD_CHALLENGE).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn error<T>(result: Result<T>) -> SecureChannelError {
+        result
+            .err()
+            .unwrap()
+            .downcast::<SecureChannelError>()
+            .unwrap()
+    }
+
+    #[test]
+    fn cmac_test() {
+        //the examples of rfc 4493
+        let mut key = [0u8; 16];
+        key.copy_from_slice(&hex::decode(SESSION_KEY).unwrap());
+        let message = hex::decode(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(cmac(&key, &[])),
+            "bb1d6929e95937287fa37d129b756746"
+        );
+        assert_eq!(
+            hex::encode(cmac(&key, &message[..16])),
+            "070a16b46b4d4144f79bdd9dd04a287c"
+        );
+        assert_eq!(
+            hex::encode(cmac(&key, &message[..40])),
+            "dfa66747de9ae63030ca32611497c827"
+        );
+        assert_eq!(
+            hex::encode(cmac(&key, &message)),
+            "51f0bebf7e3b9d92fc49741779363cfe"
+        );
+    }
+
+    #[test]
+    fn wrap_unwrap_test() {
+        let mut host = channel();
+        let mut card = channel();
+        assert_eq!(host.card_cryptogram(), card.card_cryptogram());
+
+        let apdu = hex::decode("80430000086d2f34342f302f3000").unwrap();
+        let wrapped = host.wrap_command(&apdu).unwrap();
+        assert_eq!(wrapped[0], 0x84);
+        assert!(!wrapped.windows(8).any(|window| window == &apdu[5..13]));
+        assert_eq!(card.unwrap_command(&wrapped).unwrap(), apdu);
+
+        let response = card.wrap_response(&[0x5a, 0x90, 0x00]).unwrap();
+        assert_eq!(host.unwrap_response(&response).unwrap(), [0x5a, 0x90, 0x00]);
+        //an error goes plain
+        assert_eq!(card.wrap_response(&[0x69, 0x42]).unwrap(), [0x69, 0x42]);
+        assert_eq!(host.unwrap_response(&[0x69, 0x42]).unwrap(), [0x69, 0x42]);
+
+        //data longer than a short Lc once wrapped
+        let mut apdu = vec![0x80, 0x41, 0x00, 0x00, 0xF5];
+        apdu.extend(vec![0x31; 0xF5]);
+        let wrapped = host.wrap_command(&apdu).unwrap();
+        assert_eq!(card.counter(), 1);
+        let mut command = card.unwrap_command(&wrapped).unwrap();
+        assert_eq!(command.pop(), Some(0x00));
+        assert_eq!(command, apdu);
+        assert_eq!(card.counter(), 2);
+
+        let command = host.wrap_command(&[0x80, 0x72, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(
+            card.unwrap_command(&command).unwrap(),
+            [0x80, 0x72, 0x00, 0x00, 0x00]
+        );
+        let response = card.wrap_response(&[0x90, 0x00]).unwrap();
+        assert_eq!(response.len(), 10);
+        assert_eq!(host.unwrap_response(&response).unwrap(), [0x90, 0x00]);
+    }
+
+    #[test]
+    fn known_answer_test() {
+        //computed apart from this code, the se applet has to produce the same bytes
+        let mut host = channel();
+        let mut card = channel();
+        assert_eq!(hex::encode(host.card_cryptogram()), "737b71df1a5e817f");
+
+        let apdu = hex::decode("80430000086D2F34342F302F3000").unwrap();
+        let wrapped = hex::decode(
+            "8443000000001C00000001DE8555D786B458221E998F45215647057F7F471F3457F6420000",
+        )
+        .unwrap();
+        assert_eq!(host.wrap_command(&apdu).unwrap(), wrapped);
+        assert_eq!(card.unwrap_command(&wrapped).unwrap(), apdu);
+
+        let response = hex::decode("F0BA3869A9FB04C69429367E3F6FCBD577FAD7EEB3C0CA1D9000").unwrap();
+        assert_eq!(card.wrap_response(&[0x5a, 0x90, 0x00]).unwrap(), response);
+        assert_eq!(host.unwrap_response(&response).unwrap(), [0x5a, 0x90, 0x00]);
+    }
+
+    #[test]
+    fn tamper_replay_test() {
+        let mut host = channel();
+        let mut card = channel();
+        let apdu = hex::decode("80430000086d2f34342f302f3000").unwrap();
+
+        let mut wrapped = host.wrap_command(&apdu).unwrap();
+        wrapped[12] ^= 0x01;
+        assert_eq!(
+            error(card.unwrap_command(&wrapped)),
+            SecureChannelError::MacMismatch
+        );
+        wrapped[12] ^= 0x01;
+        assert!(card.unwrap_command(&wrapped).is_ok());
+        assert_eq!(
+            error(card.unwrap_command(&wrapped)),
+            SecureChannelError::ReplayDetected
+        );
+
+        //the response to an earlier command does not verify for a later one
+        let response = card.wrap_response(&[0x5a, 0x90, 0x00]).unwrap();
+        let next = host.wrap_command(&apdu).unwrap();
+        card.unwrap_command(&next).unwrap();
+        assert_eq!(
+            error(host.unwrap_response(&response)),
+            SecureChannelError::MacMismatch
+        );
+        assert_eq!(
+            error(host.unwrap_response(&[0x90, 0x00])),
+            SecureChannelError::MacMissing
+        );
+
+        //a channel of other challenges shares nothing
+        let other = SecureChannel::new(
+            &hex::decode(SESSION_KEY).unwrap(),
+            &hex::decode(CARD_CHALLENGE).unwrap(),
+            &hex::decode(HOST_CHALLENGE).unwrap(),
+        )
+        .unwrap();
+        assert_ne!(other.card_cryptogram(), host.card_cryptogram());
+        assert_eq!(
+            error(SecureChannel::new(&[0u8; 8], &[0u8; 8], &[0u8; 8])),
+            SecureChannelError::SessionKeyInvalid
+        );
+    }
+
+    /**
+    a se answering every plain command with its data field, through a card side channel once open
+    */
+    struct EchoCard {
+        channel: Mutex<Option<SecureChannel>>,
+        received: Mutex<Vec<String>>,
+        reconnects: AtomicU32,
+    }
+
+    impl Transport for EchoCard {
+        fn connect(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn disconnect(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn exchange(&self, apdu: &str, _timeout: i32) -> Result<String> {
+            self.received.lock().unwrap().push(apdu.to_string());
+            let mut channel = self.channel.lock().unwrap();
+            let bytes = hex::decode(apdu)?;
+            if bytes[0] & 0x04 == 0 || bytes[0] == 0xFF {
+                return Ok("9000".to_string());
+            }
+            let channel = channel.as_mut().unwrap();
+            let command = channel.unwrap_command(&bytes)?;
+            let mut response = command[5..command.len() - 1].to_vec();
+            response.extend(&[0x90, 0x00]);
+            Ok(hex::encode_upper(channel.wrap_response(&response)?))
+        }
+
+        fn timeout(&self) -> i32 {
+            20
+        }
+
+        fn set_timeout(&self, _timeout: i32) {}
+
+        fn reconnect_count(&self) -> u32 {
+            self.reconnects.load(Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn secure_transport_test() {
+        let card = Arc::new(EchoCard {
+            channel: Mutex::new(Some(channel())),
+            received: Mutex::new(vec![]),
+            reconnects: AtomicU32::new(0),
+        });
+        let transport = SecureTransport::new(card.clone());
+        let apdu = "80430000086D2F34342F302F3000";
+        assert_eq!(transport.send_apdu(apdu.to_string()).unwrap(), "9000");
+
+        transport.open(channel());
+        assert!(transport.is_open());
+        assert_eq!(
+            transport
+                .send_apdu("00A4040005695F62746300".to_string())
+                .unwrap(),
+            "9000"
+        );
+        assert_eq!(
+            transport.send_apdu(apdu.to_string()).unwrap(),
+            "6D2F34342F302F309000"
+        );
+        assert_eq!(
+            transport.send_apdu(apdu.to_string()).unwrap(),
+            "6D2F34342F302F309000"
+        );
+        let received = card.received.lock().unwrap().clone();
+        assert_eq!(received[1], "00A4040005695F62746300");
+        assert!(received[2].starts_with("84430000"));
+        assert_ne!(received[2], received[3]);
+
+        transport.disconnect().unwrap();
+        assert!(!transport.is_open());
+    }
+
+    #[test]
+    fn secure_transport_reconnect_test() {
+        let card = Arc::new(EchoCard {
+            channel: Mutex::new(Some(channel())),
+            received: Mutex::new(vec![]),
+            reconnects: AtomicU32::new(3),
+        });
+        let transport = SecureTransport::new(card.clone());
+        transport.open(channel());
+        let apdu = "80430000086D2F34342F302F3000";
+        assert_eq!(
+            transport.send_apdu(apdu.to_string()).unwrap(),
+            "6D2F34342F302F309000"
+        );
+
+        //a select repeated after the device was opened again still went through plain
+        card.reconnects.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(
+            error(transport.send_apdu("00A4040005695F62746300".to_string())),
+            SecureChannelError::ChannelLost
+        );
+        assert!(!transport.is_open());
+        assert!(transport.is_lost());
+        //a command the channel would have wrapped never goes plain, it fails until the
+        //channel is opened again
+        let received = card.received.lock().unwrap().len();
+        for _ in 0..2 {
+            assert_eq!(
+                error(transport.send_apdu(apdu.to_string())),
+                SecureChannelError::ChannelLost
+            );
+        }
+        assert_eq!(card.received.lock().unwrap().len(), received);
+        //selects still go plain, so the channel can be opened again
+        assert_eq!(
+            transport
+                .send_apdu("00A4040005695F62746300".to_string())
+                .unwrap(),
+            "9000"
+        );
+
+        *card.channel.lock().unwrap() = Some(channel());
+        transport.open(channel());
+        assert!(!transport.is_lost());
+        assert_eq!(
+            transport.send_apdu(apdu.to_string()).unwrap(),
+            "6D2F34342F302F309000"
+        );
+        //a channel closed on purpose passes commands on plain
+        card.reconnects.fetch_add(1, Ordering::SeqCst);
+        assert!(transport.send_apdu(apdu.to_string()).is_err());
+        transport.close();
+        assert_eq!(transport.send_apdu(apdu.to_string()).unwrap(), "9000");
+        assert_eq!(
+            card.received.lock().unwrap().last().unwrap(),
+            "80430000086D2F34342F302F3000"
+        );
+    }
+}
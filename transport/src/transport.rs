@@ -0,0 +1,195 @@
+use crate::bridge::bridge_from_env;
+use crate::error::HidError;
+use crate::fixture::{record_from_env, replay_from_env};
+use crate::secure_channel::SecureTransport;
+use crate::Result;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use crate::hid_api::HidTransport;
+#[cfg(any(target_os = "android", target_os = "ios"))]
+use crate::message::CallbackTransport;
+
+//default apdu exchange timeout, in seconds
+pub const DEFAULT_TIMEOUT: i32 = 20;
+
+/**
+how often and how far apart to try a connect again after the device went away
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    pub attempts: u32,
+    pub interval: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            attempts: 5,
+            interval: Duration::from_millis(1000),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /**
+    waits an interval before every attempt, the usb re-enumeration takes about a second
+    */
+    pub fn retry(&self, connect: impl Fn() -> Result<()>) -> Result<()> {
+        for _ in 0..self.attempts {
+            thread::sleep(self.interval);
+            if connect().is_ok() {
+                return Ok(());
+            }
+        }
+        Err(HidError::DeviceReconnectFail.into())
+    }
+}
+
+/**
+a channel that apdu commands are exchanged with one device through
+*/
+pub trait Transport: Send + Sync {
+    fn connect(&self) -> Result<()>;
+
+    fn disconnect(&self) -> Result<()>;
+
+    fn exchange(&self, apdu: &str, timeout: i32) -> Result<String>;
+
+    fn timeout(&self) -> i32;
+
+    fn set_timeout(&self, timeout: i32);
+
+    /**
+    the key this transport talks to, what is kept per key like the bind keys is looked up by
+    it. empty for a transport that only ever reaches one key
+    */
+    fn device_id(&self) -> String {
+        String::new()
+    }
+
+    /**
+    how often the device was opened again under an exchange without the caller knowing. the se
+    forgets what it kept for the connection before, like a secure channel
+    */
+    fn reconnect_count(&self) -> u32 {
+        0
+    }
+
+    fn send_apdu(&self, apdu: String) -> Result<String> {
+        self.exchange(&apdu, self.timeout())
+    }
+
+    fn send_apdu_timeout(&self, apdu: String, timeout: i32) -> Result<String> {
+        self.exchange(&apdu, timeout)
+    }
+}
+
+/**
+the transport handed out, and the secure transport under it whose channel is opened and closed
+*/
+struct DefaultTransport {
+    transport: Arc<dyn Transport>,
+    secure: Arc<SecureTransport>,
+}
+
+lazy_static! {
+    //built on first use, a failed build is tried again on the next call
+    static ref DEFAULT_TRANSPORT: Mutex<Option<DefaultTransport>> = Mutex::new(None);
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+fn platform_transport() -> Arc<dyn Transport> {
+    Arc::new(HidTransport::new())
+}
+
+#[cfg(any(target_os = "android", target_os = "ios"))]
+fn platform_transport() -> Arc<dyn Transport> {
+    Arc::new(CallbackTransport::new())
+}
+
+fn build_default_transport() -> Result<DefaultTransport> {
+    //there is no device to open a secure channel with, its handshake fails against the fixture
+    if let Some(replay) = replay_from_env()? {
+        return Ok(DefaultTransport {
+            transport: replay.clone(),
+            secure: Arc::new(SecureTransport::new(replay)),
+        });
+    }
+    let device = match bridge_from_env()? {
+        Some(bridge) => bridge,
+        None => platform_transport(),
+    };
+    let secure = Arc::new(SecureTransport::new(device));
+    //recorded above the secure channel, the fixture keeps the plain commands a replay can match
+    Ok(DefaultTransport {
+        transport: record_from_env(secure.clone())?,
+        secure,
+    })
+}
+
+fn with_default_transport<T>(f: impl FnOnce(&DefaultTransport) -> T) -> Result<T> {
+    let mut default_transport = DEFAULT_TRANSPORT.lock().unwrap();
+    if default_transport.is_none() {
+        *default_transport = Some(build_default_transport()?);
+    }
+    Ok(f(default_transport.as_ref().unwrap()))
+}
+
+/**
+the platform transport used by the c api: hid on desktop, the registered callback on mobile,
+or the bridge at IMKEY_BRIDGE. in debug builds IMKEY_APDU_RECORD records its exchanges to a
+fixture, IMKEY_APDU_REPLAY replays one instead. a bad IMKEY_BRIDGE address or fixture is
+returned as the error
+*/
+pub fn default_transport() -> Result<Arc<dyn Transport>> {
+    with_default_transport(|default_transport| default_transport.transport.clone())
+}
+
+/**
+the secure transport under the default transport, to open and close its channel. it is plain
+until opened
+*/
+pub fn secure_transport() -> Result<Arc<SecureTransport>> {
+    with_default_transport(|default_transport| default_transport.secure.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::HidError;
+    use crate::transport::ReconnectPolicy;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    #[test]
+    fn reconnect_policy_test() {
+        let policy = ReconnectPolicy {
+            attempts: 3,
+            interval: Duration::from_millis(1),
+        };
+        let attempts = Cell::new(0);
+        let result = policy.retry(|| {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 3 {
+                Err(HidError::DeviceNotFound.into())
+            } else {
+                Ok(())
+            }
+        });
+        assert!(result.is_ok());
+        assert_eq!(attempts.get(), 3);
+
+        attempts.set(0);
+        let result = policy.retry(|| {
+            attempts.set(attempts.get() + 1);
+            Err(HidError::DeviceNotFound.into())
+        });
+        assert_eq!(
+            format!("{}", result.err().unwrap()),
+            "imkey_device_reconnect_fail"
+        );
+        assert_eq!(attempts.get(), 3);
+    }
+}
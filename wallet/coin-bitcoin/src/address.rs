@@ -1,12 +1,15 @@
 use crate::common::get_xpub_data;
+use crate::taproot::{encode_address, p2tr_address, tweak_public_key};
 use crate::Result;
+use bitcoin::util::base58;
 use bitcoin::util::bip32::{ChainCode, ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint};
 use bitcoin::{Address, Network, PublicKey};
 use common::apdu::{ApduCheck, BtcApdu, CoinCommonApdu};
-use common::error::CommonError;
+use common::constants::{VPUB_VERSION, ZPUB_VERSION};
+use common::error::{CoinError, CommonError};
 use common::path::check_path_validity;
 use std::str::FromStr;
-use transport::message::send_apdu;
+use transport::Transport;
 
 pub struct BtcAddress();
 
@@ -14,12 +17,12 @@ impl BtcAddress {
     /**
     get btc xpub by path
     */
-    pub fn get_xpub(network: Network, path: &str) -> Result<String> {
+    pub fn get_xpub(transport: &dyn Transport, network: Network, path: &str) -> Result<String> {
         //path check
         check_path_validity(path)?;
 
         //get xpub data
-        let xpub_data = get_xpub_data(path, true)?;
+        let xpub_data = get_xpub_data(transport, path, true)?;
         let xpub_data = &xpub_data[..194].to_string();
 
         //get public key and chain code
@@ -27,7 +30,7 @@ impl BtcAddress {
         let chain_code = &xpub_data[130..];
 
         //build parent public key obj
-        let parent_xpub = get_xpub_data(Self::get_parent_path(path)?, true)?;
+        let parent_xpub = get_xpub_data(transport, Self::get_parent_path(path)?, true)?;
         let parent_xpub = &parent_xpub[..130].to_string();
         let mut parent_pub_key_obj = PublicKey::from_str(parent_xpub)?;
         parent_pub_key_obj.compressed = true;
@@ -66,12 +69,12 @@ impl BtcAddress {
     /**
     get btc address by path
     */
-    pub fn get_address(network: Network, path: &str) -> Result<String> {
+    pub fn get_address(transport: &dyn Transport, network: Network, path: &str) -> Result<String> {
         //path check
         check_path_validity(path)?;
 
         //get xpub
-        let xpub_data = get_xpub_data(path, true)?;
+        let xpub_data = get_xpub_data(transport, path, true)?;
         let pub_key = &xpub_data[..130];
 
         let mut pub_key_obj = PublicKey::from_str(pub_key)?;
@@ -83,12 +86,16 @@ impl BtcAddress {
     /**
     get segwit address by path
     */
-    pub fn get_segwit_address(network: Network, path: &str) -> Result<String> {
+    pub fn get_segwit_address(
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+    ) -> Result<String> {
         //path check
         check_path_validity(path)?;
 
         //get xpub
-        let xpub_data = get_xpub_data(path, true)?;
+        let xpub_data = get_xpub_data(transport, path, true)?;
         let pub_key = &xpub_data[..130];
 
         let mut pub_key_obj = PublicKey::from_str(pub_key)?;
@@ -97,6 +104,70 @@ impl BtcAddress {
         Ok(Address::p2shwpkh(&pub_key_obj, network).to_string())
     }
 
+    /**
+    get native segwit(bech32) address by path
+    */
+    pub fn get_native_segwit_address(
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+    ) -> Result<String> {
+        //path check
+        check_path_validity(path)?;
+
+        //get xpub
+        let xpub_data = get_xpub_data(transport, path, true)?;
+        let pub_key = &xpub_data[..130];
+
+        let mut pub_key_obj = PublicKey::from_str(pub_key)?;
+        pub_key_obj.compressed = true;
+
+        Ok(Address::p2wpkh(&pub_key_obj, network).to_string())
+    }
+
+    /**
+    get bip86 taproot(bech32m) address by path, the key path only output key of the public key
+    */
+    pub fn get_taproot_address(
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+    ) -> Result<String> {
+        //path check
+        check_path_validity(path)?;
+
+        //get xpub
+        let xpub_data = get_xpub_data(transport, path, true)?;
+        let pub_key = &xpub_data[..130];
+
+        let mut pub_key_obj = PublicKey::from_str(pub_key)?;
+        pub_key_obj.compressed = true;
+
+        let output_key = tweak_public_key(&pub_key_obj.to_bytes())?;
+        Ok(encode_address(&p2tr_address(&output_key, network)))
+    }
+
+    /**
+    get bip84 xpub by path, zpub on mainnet and vpub on testnet
+    */
+    pub fn get_native_segwit_xpub(
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+    ) -> Result<String> {
+        let version = match network {
+            Network::Bitcoin => ZPUB_VERSION,
+            Network::Testnet => VPUB_VERSION,
+            _ => return Err(CoinError::ImkeySdkIllegalArgument.into()),
+        };
+        let xpub = Self::get_xpub(transport, network, path)?;
+
+        //replace the xpub/tpub version bytes
+        let mut xpub_bytes = base58::from_check(xpub.as_str())?;
+        xpub_bytes[..4].copy_from_slice(&version);
+        Ok(base58::check_encode_slice(&xpub_bytes))
+    }
+
     /**
     get parent public key path
     */
@@ -113,24 +184,62 @@ impl BtcAddress {
         Ok(&path[..end_flg])
     }
 
-    pub fn display_address(network: Network, path: &str) -> Result<String> {
+    pub fn display_address(
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+    ) -> Result<String> {
         //path check
         check_path_validity(path)?;
-        let address_str = Self::get_address(network, path)?;
+        let address_str = Self::get_address(transport, network, path)?;
         //        let apdu_res = send_apdu(BtcApdu::btc_coin_reg(address_str.clone().into_bytes()))?;
-        let apdu_res = send_apdu(BtcApdu::register_address(
+        let apdu_res = transport.send_apdu(BtcApdu::register_address(
             &address_str.clone().into_bytes().to_vec(),
         ))?;
         ApduCheck::checke_response(apdu_res.as_str())?;
         Ok(address_str)
     }
 
-    pub fn display_segwit_address(network: Network, path: &str) -> Result<String> {
+    pub fn display_segwit_address(
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+    ) -> Result<String> {
         //path check
         check_path_validity(path)?;
-        let address_str = Self::get_segwit_address(network, path)?;
+        let address_str = Self::get_segwit_address(transport, network, path)?;
         //        let apdu_res = send_apdu(BtcApdu::btc_coin_reg(address_str.clone().into_bytes()))?;
-        let apdu_res = send_apdu(BtcApdu::register_address(
+        let apdu_res = transport.send_apdu(BtcApdu::register_address(
+            &address_str.clone().into_bytes().to_vec(),
+        ))?;
+        ApduCheck::checke_response(apdu_res.as_str())?;
+        Ok(address_str)
+    }
+
+    pub fn display_native_segwit_address(
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+    ) -> Result<String> {
+        //path check
+        check_path_validity(path)?;
+        let address_str = Self::get_native_segwit_address(transport, network, path)?;
+        let apdu_res = transport.send_apdu(BtcApdu::register_address(
+            &address_str.clone().into_bytes().to_vec(),
+        ))?;
+        ApduCheck::checke_response(apdu_res.as_str())?;
+        Ok(address_str)
+    }
+
+    pub fn display_taproot_address(
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+    ) -> Result<String> {
+        //path check
+        check_path_validity(path)?;
+        let address_str = Self::get_taproot_address(transport, network, path)?;
+        let apdu_res = transport.send_apdu(BtcApdu::register_address(
             &address_str.clone().into_bytes().to_vec(),
         ))?;
         ApduCheck::checke_response(apdu_res.as_str())?;
@@ -141,16 +250,19 @@ impl BtcAddress {
 #[cfg(test)]
 mod test {
     use crate::address::BtcAddress;
-    use bitcoin::Network;
+    use bitcoin::util::base58;
+    use bitcoin::{Address, Network};
     use device::device_binding::bind_test;
+    use std::str::FromStr;
+    use transport::Transport;
 
     #[test]
     fn get_xpub_test() {
-        bind_test();
+        let transport = bind_test();
 
         let version: Network = Network::Bitcoin;
         let path: &str = "m/44'/0'/0'/0/0";
-        let get_xpub_result = BtcAddress::get_xpub(version, path);
+        let get_xpub_result = BtcAddress::get_xpub(&transport, version, path);
         assert!(get_xpub_result.is_ok());
         let xpub = get_xpub_result.ok().unwrap();
         assert_eq!("xpub6FuzpGNBc46EfvmcvECyqXjrzGcKErQgpQcpvhw1tiC5yXvi1jUkzudMpdg5AaguiFstdVR5ASDbSceBswKRy6cAhpTgozmgxMUayPDrLLX", xpub);
@@ -158,31 +270,31 @@ mod test {
 
     #[test]
     fn get_xpub_path_error_test() {
-        bind_test();
+        let transport = bind_test();
 
         let version: Network = Network::Bitcoin;
         let path: &str = "m/44'";
-        let get_xpub_result = BtcAddress::get_xpub(version, path);
+        let get_xpub_result = BtcAddress::get_xpub(&transport, version, path);
         assert!(get_xpub_result.is_err());
     }
 
     #[test]
     fn get_xpub_path_is_null_test() {
-        bind_test();
+        let transport = bind_test();
 
         let version: Network = Network::Bitcoin;
         let path: &str = "";
-        let get_xpub_result = BtcAddress::get_xpub(version, path);
+        let get_xpub_result = BtcAddress::get_xpub(&transport, version, path);
         assert!(get_xpub_result.is_err());
     }
 
     #[test]
     fn get_address_test() {
-        bind_test();
+        let transport = bind_test();
 
         let version: Network = Network::Bitcoin;
         let path: &str = "m/44'/0'/0'/0/0";
-        let get_btc_address_result = BtcAddress::get_address(version, path);
+        let get_btc_address_result = BtcAddress::get_address(&transport, version, path);
 
         assert!(get_btc_address_result.is_ok());
         let btc_address = get_btc_address_result.ok().unwrap();
@@ -191,11 +303,11 @@ mod test {
 
     #[test]
     fn get_segwit_address_test() {
-        bind_test();
+        let transport = bind_test();
 
         let version: Network = Network::Bitcoin;
         let path: &str = "m/49'/0'/0'/0/22";
-        let segwit_address_result = BtcAddress::get_segwit_address(version, path);
+        let segwit_address_result = BtcAddress::get_segwit_address(&transport, version, path);
 
         assert!(segwit_address_result.is_ok());
         let segwit_address = segwit_address_result.ok().unwrap();
@@ -225,10 +337,10 @@ mod test {
 
     #[test]
     fn display_address_test() {
-        bind_test();
+        let transport = bind_test();
         let version: Network = Network::Bitcoin;
         let path: &str = "m/44'/0'/0'/0/0";
-        let result = BtcAddress::display_address(version, path);
+        let result = BtcAddress::display_address(&transport, version, path);
 
         assert!(result.is_ok());
         let btc_address = result.ok().unwrap();
@@ -237,13 +349,94 @@ mod test {
 
     #[test]
     fn display_segwit_address_test() {
-        bind_test();
+        let transport = bind_test();
         let network: Network = Network::Bitcoin;
         let path: &str = "m/49'/0'/0'/0/22";
-        let result = BtcAddress::display_segwit_address(network, path);
+        let result = BtcAddress::display_segwit_address(&transport, network, path);
 
         assert!(result.is_ok());
         let segwit_address = result.ok().unwrap();
         assert_eq!("37E2J9ViM4QFiewo7aw5L3drF2QKB99F9e", segwit_address);
     }
+
+    #[test]
+    fn native_segwit_address_simulator_test() {
+        let transport = simulator::bind_test();
+
+        let address =
+            BtcAddress::get_native_segwit_address(&transport, Network::Bitcoin, "m/84'/0'/0'/0/0")
+                .unwrap();
+        assert!(address.starts_with("bc1q"));
+        let p2pkh_address =
+            BtcAddress::get_address(&transport, Network::Bitcoin, "m/84'/0'/0'/0/0").unwrap();
+        let native_script = Address::from_str(&address).unwrap().script_pubkey();
+        let p2pkh_script = Address::from_str(&p2pkh_address).unwrap().script_pubkey();
+        assert!(native_script.is_v0_p2wpkh());
+        //both pay to the same public key hash
+        assert_eq!(
+            native_script.as_bytes()[2..],
+            p2pkh_script.as_bytes()[3..23]
+        );
+
+        let displayed = BtcAddress::display_native_segwit_address(
+            &transport,
+            Network::Testnet,
+            "m/84'/1'/0'/0/0",
+        )
+        .unwrap();
+        assert!(displayed.starts_with("tb1q"));
+    }
+
+    #[test]
+    fn native_segwit_xpub_simulator_test() {
+        let transport = simulator::bind_test();
+
+        let xpub = BtcAddress::get_xpub(&transport, Network::Bitcoin, "m/84'/0'/0'").unwrap();
+        let zpub = BtcAddress::get_native_segwit_xpub(&transport, Network::Bitcoin, "m/84'/0'/0'")
+            .unwrap();
+        assert!(zpub.starts_with("zpub"));
+        assert_eq!(
+            base58::from_check(&xpub).unwrap()[4..],
+            base58::from_check(&zpub).unwrap()[4..]
+        );
+
+        let vpub = BtcAddress::get_native_segwit_xpub(&transport, Network::Testnet, "m/84'/1'/0'")
+            .unwrap();
+        assert!(vpub.starts_with("vpub"));
+
+        let result =
+            BtcAddress::get_native_segwit_xpub(&transport, Network::Regtest, "m/84'/1'/0'");
+        assert_eq!(
+            format!("{}", result.err().unwrap()),
+            "imkey_sdk_illegal_argument"
+        );
+    }
+
+    #[test]
+    fn taproot_address_simulator_test() {
+        //bip86 test vectors
+        let transport = simulator::SimulatorTransport::with_simulator(
+            simulator::SeSimulator::from_mnemonic(
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            )
+            .unwrap(),
+        );
+        transport.connect().unwrap();
+
+        let address =
+            BtcAddress::get_taproot_address(&transport, Network::Bitcoin, "m/86'/0'/0'/0/0")
+                .unwrap();
+        assert_eq!(
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+            address
+        );
+        let address =
+            BtcAddress::display_taproot_address(&transport, Network::Bitcoin, "m/86'/0'/0'/0/1")
+                .unwrap();
+        assert_eq!(
+            "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh",
+            address
+        );
+        assert_eq!(vec![address], transport.registered_addresses());
+    }
 }
@@ -16,6 +16,13 @@ pub struct Utxo {
     pub sequence: i64,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BtcOutput {
+    #[prost(string, tag = "1")]
+    pub address: std::string::String,
+    #[prost(int64, tag = "2")]
+    pub amount: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BtcTxReq {
     #[prost(string, tag = "1")]
     pub to: std::string::String,
@@ -35,6 +42,14 @@ pub struct BtcTxReq {
     pub network: std::string::String,
     #[prost(string, tag = "9")]
     pub path_prefix: std::string::String,
+    #[prost(message, repeated, tag = "10")]
+    pub outputs: ::std::vec::Vec<BtcOutput>,
+    #[prost(string, tag = "11")]
+    pub change_address: std::string::String,
+    #[prost(string, tag = "12")]
+    pub change_path: std::string::String,
+    #[prost(int64, tag = "13")]
+    pub dust_threshold: i64,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BtcTxRes {
@@ -63,6 +78,14 @@ pub struct BtcSegwitTxReq {
     pub network: std::string::String,
     #[prost(string, tag = "9")]
     pub path_prefix: std::string::String,
+    #[prost(message, repeated, tag = "10")]
+    pub outputs: ::std::vec::Vec<BtcOutput>,
+    #[prost(string, tag = "11")]
+    pub change_address: std::string::String,
+    #[prost(string, tag = "12")]
+    pub change_path: std::string::String,
+    #[prost(int64, tag = "13")]
+    pub dust_threshold: i64,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BtcSegwitTxRes {
@@ -74,6 +97,20 @@ pub struct BtcSegwitTxRes {
     pub tx_hash: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BtcPsbtReq {
+    #[prost(string, tag = "1")]
+    pub psbt: std::string::String,
+    #[prost(string, tag = "2")]
+    pub network: std::string::String,
+    #[prost(string, tag = "3")]
+    pub path_prefix: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BtcPsbtRes {
+    #[prost(string, tag = "1")]
+    pub psbt: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BtcXpubReq {
     #[prost(string, tag = "1")]
     pub network: std::string::String,
@@ -97,3 +134,61 @@ pub struct BtcAddressRes {
     #[prost(string, tag = "1")]
     pub address: std::string::String,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OmniSimpleSend {
+    #[prost(uint32, tag = "1")]
+    pub property_id: u32,
+    #[prost(int64, tag = "2")]
+    pub amount: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OmniSendToOwners {
+    #[prost(uint32, tag = "1")]
+    pub property_id: u32,
+    #[prost(int64, tag = "2")]
+    pub amount: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OmniSendAll {
+    /// 1 for the main ecosystem, 2 for the test one
+    #[prost(uint32, tag = "1")]
+    pub ecosystem: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OmniTxReq {
+    /// the receiver of the reference output
+    #[prost(string, tag = "1")]
+    pub to: std::string::String,
+    #[prost(int64, tag = "2")]
+    pub fee: i64,
+    #[prost(message, repeated, tag = "3")]
+    pub unspents: ::std::vec::Vec<Utxo>,
+    #[prost(string, tag = "4")]
+    pub network: std::string::String,
+    #[prost(string, tag = "5")]
+    pub path_prefix: std::string::String,
+    /// only simple sends are signed, the device has no screen for the other payloads
+    #[prost(message, optional, tag = "6")]
+    pub simple_send: ::std::option::Option<OmniSimpleSend>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OmniDecodeReq {
+    #[prost(string, tag = "1")]
+    pub script_pub_key: std::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OmniDecodeRes {
+    #[prost(oneof = "omni_decode_res::Payload", tags = "1, 2, 3")]
+    pub payload: ::std::option::Option<omni_decode_res::Payload>,
+}
+pub mod omni_decode_res {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Payload {
+        #[prost(message, tag = "1")]
+        SimpleSend(super::OmniSimpleSend),
+        #[prost(message, tag = "2")]
+        SendToOwners(super::OmniSendToOwners),
+        #[prost(message, tag = "3")]
+        SendAll(super::OmniSendAll),
+    }
+}
@@ -1,15 +1,25 @@
+use crate::taproot::{encode_address, p2tr_address, tweak_public_key};
 use crate::transaction::Utxo;
 use crate::Result;
+use bitcoin::consensus::serialize;
+use bitcoin::hashes::hex::FromHex;
 use bitcoin::secp256k1::Secp256k1 as BitcoinSecp256k1;
+use bitcoin::util::address::{AddressType, Payload};
 use bitcoin::util::base58;
 use bitcoin::util::bip32::{ChainCode, ChildNumber, ExtendedPubKey};
-use bitcoin::{Address, Network, PublicKey};
+use bitcoin::util::psbt::serialize::Serialize;
+use bitcoin::{Address, Network, PublicKey, Script, SigHashType, Transaction, TxIn};
+use bitcoin_hashes::hash160;
+use bitcoin_hashes::Hash;
 use common::apdu::{ApduCheck, BtcApdu, CoinCommonApdu};
+use common::constants::{
+    BTC_P2PKH_VERSION, BTC_TESTNET_P2PKH_VERSION, EACH_ROUND_NUMBER, TIMEOUT_LONG,
+};
 use common::error::CoinError;
-use common::utility::sha256_hash;
+use common::utility::{bigint_to_byte_vec, secp256k1_sign, sha256_hash};
 use secp256k1::{Message, PublicKey as PublicKey2, Secp256k1, Signature};
 use std::str::FromStr;
-use transport::message::send_apdu;
+use transport::Transport;
 
 /**
 utxo address verify
@@ -56,9 +66,18 @@ pub fn address_verify(
                 network,
             )
             .to_string()),
+            TransTypeFlg::NATIVESEGWIT => Ok(Address::p2wpkh(
+                &PublicKey::from_str(extend_public_key.public_key.to_string().as_str())?,
+                network,
+            )
+            .to_string()),
+            TransTypeFlg::TAPROOT => Ok(encode_address(&p2tr_address(
+                &tweak_public_key(&extend_public_key.public_key.to_bytes())?,
+                network,
+            ))),
         };
         let se_gen_address_str = se_gen_address?;
-        let utxo_address = utxo.address.to_string();
+        let utxo_address = encode_address(&utxo.address);
         if !se_gen_address_str.eq(&utxo_address) {
             return Err(CoinError::ImkeyAddressMismatchWithPath.into());
         }
@@ -70,22 +89,68 @@ pub fn address_verify(
 /**
 Transaction type identification
 */
+#[derive(Clone, Copy, PartialEq)]
 pub enum TransTypeFlg {
     BTC,
     SEGWIT,
+    NATIVESEGWIT,
+    TAPROOT,
+}
+
+impl TransTypeFlg {
+    /**
+    p2pkh, p2sh(assumed to wrap p2wpkh) or p2wpkh utxo
+    */
+    pub fn from_address(address: &Address) -> Result<TransTypeFlg> {
+        match address.address_type() {
+            Some(AddressType::P2pkh) => Ok(TransTypeFlg::BTC),
+            Some(AddressType::P2sh) => Ok(TransTypeFlg::SEGWIT),
+            Some(AddressType::P2wpkh) => Ok(TransTypeFlg::NATIVESEGWIT),
+            _ => Err(CoinError::AddressTypeMismatch.into()),
+        }
+    }
 }
 
 /**
 get xpub
 */
-pub fn get_xpub_data(path: &str, verify_flag: bool) -> Result<String> {
-    let select_response = send_apdu(BtcApdu::select_applet())?;
+pub fn get_xpub_data(transport: &dyn Transport, path: &str, verify_flag: bool) -> Result<String> {
+    let select_response = transport.send_apdu(BtcApdu::select_applet())?;
     ApduCheck::checke_response(&select_response)?;
-    let xpub_data = send_apdu(BtcApdu::get_xpub(path, verify_flag))?;
+    let xpub_data = transport.send_apdu(BtcApdu::get_xpub(path, verify_flag))?;
     ApduCheck::checke_response(&xpub_data)?;
     Ok(xpub_data)
 }
 
+/**
+get xpub and check the se signed it, returns the public key hex and the chain code
+*/
+pub fn get_verified_xpub(
+    transport: &dyn Transport,
+    path: &str,
+    se_pub_key: &[u8],
+) -> Result<(String, Vec<u8>)> {
+    let xpub_data = get_xpub_data(transport, path, true)?;
+    let xpub_data = &xpub_data[..xpub_data.len() - 4];
+
+    //parsing xpub data
+    let sign_source_val = &xpub_data[..194];
+    let sign_result = &xpub_data[194..];
+    let pub_key = &sign_source_val[..130];
+    let chain_code = hex::decode(&sign_source_val[130..])?;
+
+    //use se public key verify sign
+    let sign_verify_result = secp256k1_sign_verify(
+        se_pub_key,
+        hex::decode(sign_result)?.as_slice(),
+        hex::decode(sign_source_val)?.as_slice(),
+    );
+    if sign_verify_result.is_err() || !sign_verify_result.ok().unwrap() {
+        return Err(CoinError::ImkeySignatureVerifyFail.into());
+    }
+    Ok((pub_key.to_string(), chain_code))
+}
+
 /**
 sign verify
 */
@@ -127,6 +192,268 @@ pub fn get_address_version(network: Network, address: &str) -> Result<u8> {
     Ok(address_bytes.as_slice()[0])
 }
 
+/**
+the device shows every base58 output with one version, so they all have to share it,
+bech32 addresses only have to belong to the network
+*/
+pub fn get_outputs_address_version(network: Network, addresses: &[Address]) -> Result<u8> {
+    let mut address_version = None;
+    for address in addresses {
+        match address.payload {
+            Payload::WitnessProgram { .. } => {
+                if address.network != network {
+                    return Err(CoinError::AddressTypeMismatch.into());
+                }
+            }
+            _ => {
+                let version = get_address_version(network, address.to_string().as_str())?;
+                if *address_version.get_or_insert(version) != version {
+                    return Err(CoinError::AddressTypeMismatch.into());
+                }
+            }
+        }
+    }
+    match (address_version, network) {
+        (Some(version), _) => Ok(version),
+        (None, Network::Bitcoin) => Ok(BTC_P2PKH_VERSION),
+        (None, Network::Testnet) => Ok(BTC_TESTNET_P2PKH_VERSION),
+        _ => Err(CoinError::ImkeySdkIllegalArgument.into()),
+    }
+}
+
+/**
+an input the device signs: its index in the transaction, its type, the key recorded for it and
+the utxo it spends
+*/
+pub struct SignInput {
+    pub index: usize,
+    pub trans_type_flg: TransTypeFlg,
+    pub pub_key: PublicKey,
+    pub utxo: Utxo,
+}
+
+/**
+sign the inputs of tx with sighash all. legacy inputs are signed in a 41/42 session and segwit
+ones in a 31/32 session, the output data is prepared again for each session the transaction needs.
+the signatures are returned in the order of sign_inputs
+*/
+pub fn sign_tx_inputs(
+    transport: &dyn Transport,
+    tx: &Transaction,
+    sign_inputs: &[SignInput],
+    path: &str,
+    fee: i64,
+    address_version: u8,
+    pri_key: &[u8],
+) -> Result<Vec<Vec<u8>>> {
+    let (legacy_inputs, segwit_inputs): (Vec<&SignInput>, Vec<&SignInput>) = sign_inputs
+        .iter()
+        .partition(|sign_input| sign_input.trans_type_flg == TransTypeFlg::BTC);
+
+    let mut signatures = vec![];
+    if !legacy_inputs.is_empty() {
+        prepare_outputs(transport, tx, fee, address_version, 0x41, pri_key)?;
+        let legacy_signatures = sign_p2pkh_inputs(transport, tx, &legacy_inputs, path)?;
+        signatures.extend(
+            legacy_inputs
+                .iter()
+                .map(|input| input.index)
+                .zip(legacy_signatures),
+        );
+    }
+    if !segwit_inputs.is_empty() {
+        prepare_outputs(transport, tx, fee, address_version, 0x31, pri_key)?;
+        let segwit_signatures = sign_segwit_inputs(transport, tx, &segwit_inputs, path)?;
+        signatures.extend(
+            segwit_inputs
+                .iter()
+                .map(|input| input.index)
+                .zip(segwit_signatures),
+        );
+    }
+
+    let mut ordered_signatures = vec![];
+    for sign_input in sign_inputs {
+        let position = signatures
+            .iter()
+            .position(|(index, _)| *index == sign_input.index)
+            .ok_or(CoinError::ImkeySdkIllegalArgument)?;
+        ordered_signatures.push(signatures.remove(position).1);
+    }
+    Ok(ordered_signatures)
+}
+
+/**
+output data: version | input number | outputs | lock time | sign type | fee | address version,
+after the 01 tag and its one byte length. outputs that do not fit the length are refused
+*/
+pub fn output_data(
+    tx: &Transaction,
+    input_count: usize,
+    fee: i64,
+    address_version: u8,
+) -> Result<Vec<u8>> {
+    if input_count > 0xFF {
+        return Err(CoinError::ImkeySdkIllegalArgument.into());
+    }
+    let mut data = vec![];
+    data.extend(serialize(&tx.version));
+    data.push(input_count as u8);
+    data.extend(serialize(&tx.output));
+    data.extend(serialize(&tx.lock_time));
+    data.extend(SigHashType::All.serialize().iter());
+    data.extend(bigint_to_byte_vec(fee));
+    data.push(address_version);
+    if data.len() > 0xFF {
+        return Err(CoinError::ImkeySdkIllegalArgument.into());
+    }
+
+    //set 01 tag and length
+    data.insert(0, data.len() as u8);
+    data.insert(0, 0x01);
+    Ok(data)
+}
+
+/**
+the output data of tx, signed with the binding key
+*/
+pub fn prepare_outputs(
+    transport: &dyn Transport,
+    tx: &Transaction,
+    fee: i64,
+    address_version: u8,
+    ins: u8,
+    pri_key: &[u8],
+) -> Result<()> {
+    let output_serialize_data = output_data(tx, tx.input.len(), fee, address_version)?;
+
+    //use local private key sign data
+    let mut output_pareper_data = secp256k1_sign(pri_key, &output_serialize_data)?;
+    output_pareper_data.insert(0, output_pareper_data.len() as u8);
+    output_pareper_data.insert(0, 0x00);
+    output_pareper_data.extend(output_serialize_data.iter());
+
+    let btc_prepare_apdu_vec = BtcApdu::btc_prepare(ins, 0x00, &output_pareper_data);
+    for temp_str in btc_prepare_apdu_vec {
+        ApduCheck::checke_response(&transport.send_apdu_timeout(temp_str, TIMEOUT_LONG)?)?;
+    }
+    Ok(())
+}
+
+fn sign_p2pkh_inputs(
+    transport: &dyn Transport,
+    tx: &Transaction,
+    sign_inputs: &[&SignInput],
+    path: &str,
+) -> Result<Vec<Vec<u8>>> {
+    let mut signatures = vec![];
+    for round in sign_inputs.chunks(EACH_ROUND_NUMBER) {
+        //every input is prepared, the ones signed this round carry their lock script
+        for (index, txin) in tx.input.iter().enumerate() {
+            let script_sig = match round.iter().find(|sign_input| sign_input.index == index) {
+                Some(sign_input) => {
+                    Script::from(Vec::from_hex(sign_input.utxo.script_pubkey.as_str())?)
+                }
+                None => Script::default(),
+            };
+            let temp_serialize_txin = TxIn {
+                script_sig,
+                witness: vec![],
+                ..txin.clone()
+            };
+            let mut input_data_vec = vec![index as u8];
+            input_data_vec.extend_from_slice(serialize(&temp_serialize_txin).as_slice());
+            let btc_perpare_apdu = BtcApdu::btc_perpare_input(0x80, &input_data_vec);
+            ApduCheck::checke_response(&transport.send_apdu(btc_perpare_apdu)?)?;
+        }
+        for sign_input in round {
+            let btc_sign_apdu = BtcApdu::btc_sign(
+                sign_input.index as u8,
+                SigHashType::All.as_u32() as u8,
+                format!("{}{}", path, sign_input.utxo.derive_path).as_str(),
+            );
+            let btc_sign_apdu_return = transport.send_apdu(btc_sign_apdu)?;
+            ApduCheck::checke_response(&btc_sign_apdu_return)?;
+            signatures.push(der_signature(
+                &btc_sign_apdu_return[2..btc_sign_apdu_return.len() - 6],
+            )?);
+        }
+    }
+    Ok(signatures)
+}
+
+/**
+bip143 signing, p2sh-p2wpkh and p2wpkh inputs share the p2pkh script code
+*/
+fn sign_segwit_inputs(
+    transport: &dyn Transport,
+    tx: &Transaction,
+    sign_inputs: &[&SignInput],
+    path: &str,
+) -> Result<Vec<Vec<u8>>> {
+    let mut txhash_vout_vec = vec![];
+    let mut sequence_vec: Vec<u8> = vec![];
+    for txin in &tx.input {
+        txhash_vout_vec.extend(serialize(&txin.previous_output).iter());
+        sequence_vec.extend(serialize(&txin.sequence).iter());
+    }
+
+    let mut sign_apdu_vec: Vec<String> = vec![];
+    for (index, sign_input) in sign_inputs.iter().enumerate() {
+        let txin = &tx.input[sign_input.index];
+        let mut data: Vec<u8> = vec![];
+        //txhash and vout
+        data.extend(serialize(&txin.previous_output).iter());
+
+        //lock script
+        let pub_key_hash = hash160::Hash::hash(&sign_input.pub_key.to_bytes()).into_inner();
+        let script_hex = format!("76a914{}88ac", hex::encode(pub_key_hash));
+        let script = Script::from(hex::decode(script_hex)?);
+        data.extend(serialize(&script).iter());
+
+        //amount and sequence
+        data.extend(serialize(&(sign_input.utxo.amount as u64)).iter());
+        data.extend(serialize(&txin.sequence).iter());
+        //set length
+        data.insert(0, data.len() as u8);
+        //address
+        let sign_path = format!("{}{}", path, sign_input.utxo.derive_path);
+        data.push(sign_path.len() as u8);
+        data.extend_from_slice(sign_path.as_bytes());
+
+        let last_one = index == sign_inputs.len() - 1;
+        sign_apdu_vec.push(BtcApdu::btc_segwit_sign(last_one, 0x01, data));
+    }
+
+    let mut txhash_vout_prepare_apdu_vec = BtcApdu::btc_prepare(0x31, 0x40, &txhash_vout_vec);
+    let mut sequence_prepare_apdu_vec = BtcApdu::btc_prepare(0x31, 0x80, &sequence_vec);
+    txhash_vout_prepare_apdu_vec.append(&mut sequence_prepare_apdu_vec);
+    for apdu in txhash_vout_prepare_apdu_vec {
+        ApduCheck::checke_response(&transport.send_apdu(apdu)?)?;
+    }
+
+    let mut signatures = vec![];
+    for sign_apdu in sign_apdu_vec {
+        let sign_apdu_return_data = transport.send_apdu(sign_apdu)?;
+        ApduCheck::checke_response(&sign_apdu_return_data)?;
+        signatures.push(der_signature(
+            &sign_apdu_return_data[2..sign_apdu_return_data.len() - 6],
+        )?);
+    }
+    Ok(signatures)
+}
+
+/**
+der encoded low s signature with the sighash all byte
+*/
+pub fn der_signature(signed: &str) -> Result<Vec<u8>> {
+    let mut signnture_obj = Signature::from_compact(Vec::from_hex(signed)?.as_slice())?;
+    signnture_obj.normalize_s();
+    let mut signature = signnture_obj.serialize_der().to_vec();
+    signature.push(SigHashType::All.as_u32() as u8);
+    Ok(signature)
+}
+
 pub struct TxSignResult {
     pub signature: String,
     pub tx_hash: String,
@@ -135,9 +462,33 @@ pub struct TxSignResult {
 
 #[cfg(test)]
 mod test {
-    use crate::common::get_address_version;
-    use crate::transaction::Utxo;
-    use bitcoin::Network;
+    use crate::common::{get_address_version, get_outputs_address_version, output_data};
+    use bitcoin::{Address, Network, Script, Transaction, TxOut};
+    use std::str::FromStr;
+
+    #[test]
+    fn output_data_test() {
+        //23 bytes around the outputs, an output of 9 bytes and its script
+        let tx = |script_len: usize| Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut {
+                value: 0,
+                script_pubkey: Script::from(vec![0x6a; script_len]),
+            }],
+        };
+        let data = output_data(&tx(223), 2, 1000, 0).unwrap();
+        assert_eq!(data.len(), 257);
+        assert_eq!(data[..2], [0x01, 0xFF]);
+        assert_eq!(data[6], 0x02);
+        //the length would not fit its byte
+        assert_eq!(
+            format!("{}", output_data(&tx(224), 2, 1000, 0).err().unwrap()),
+            "imkey_sdk_illegal_argument"
+        );
+        assert!(output_data(&tx(1), 256, 1000, 0).is_err());
+    }
 
     #[test]
     fn get_address_version_test() {
@@ -167,4 +518,45 @@ mod test {
             "imkey_sdk_illegal_argument"
         );
     }
+
+    #[test]
+    fn get_outputs_address_version_test() {
+        let p2pkh = Address::from_str("12z6UzsA3tjpaeuvA2Zr9jwx19Azz74D6g").unwrap();
+        let p2sh = Address::from_str("37E2J9ViM4QFiewo7aw5L3drF2QKB99F9e").unwrap();
+        let p2wpkh = Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap();
+        let testnet_p2wpkh =
+            Address::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx").unwrap();
+
+        assert_eq!(
+            5,
+            get_outputs_address_version(Network::Bitcoin, &[p2wpkh.clone(), p2sh.clone()]).unwrap()
+        );
+        //a p2pkh output would be shown with the p2sh version
+        assert_eq!(
+            format!(
+                "{}",
+                get_outputs_address_version(Network::Bitcoin, &[p2wpkh.clone(), p2sh, p2pkh])
+                    .err()
+                    .unwrap()
+            ),
+            "address_type_mismatch"
+        );
+        assert_eq!(
+            0,
+            get_outputs_address_version(Network::Bitcoin, &[p2wpkh.clone()]).unwrap()
+        );
+        assert_eq!(
+            0x6f,
+            get_outputs_address_version(Network::Testnet, &[testnet_p2wpkh]).unwrap()
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                get_outputs_address_version(Network::Testnet, &[p2wpkh])
+                    .err()
+                    .unwrap()
+            ),
+            "address_type_mismatch"
+        );
+    }
 }
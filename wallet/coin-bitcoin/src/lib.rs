@@ -1,6 +1,9 @@
 pub mod address;
 pub mod btcapi;
 pub mod common;
+pub mod omni;
+pub mod psbt;
+pub mod taproot;
 pub mod transaction;
 pub mod usdt_transaction;
 extern crate failure;
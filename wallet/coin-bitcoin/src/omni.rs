@@ -0,0 +1,221 @@
+use crate::Result;
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::blockdata::{opcodes, script::Builder};
+use bitcoin::{Script, TxOut};
+use common::error::CoinError;
+
+//class c transactions carry the payload in an op_return after this marker
+const OMNI_MARKER: &[u8] = b"omni";
+
+const SIMPLE_SEND: u16 = 0;
+const SEND_TO_OWNERS: u16 = 3;
+const SEND_ALL: u16 = 4;
+
+pub const MAIN_ECOSYSTEM: u8 = 1;
+pub const TEST_ECOSYSTEM: u8 = 2;
+
+/**
+the omni layer payloads the wallet decodes, all of them version 0. only simple sends are signed,
+the device has no screen for the others
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum OmniPayload {
+    //amount of the property to the reference output
+    SimpleSend { property_id: u32, amount: u64 },
+    //amount of the property shared among its holders, no reference output
+    SendToOwners { property_id: u32, amount: u64 },
+    //every property of the ecosystem the sender holds to the reference output
+    SendAll { ecosystem: u8 },
+}
+
+impl OmniPayload {
+    /**
+    version, type and fields, all big endian
+    */
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = vec![0x00, 0x00];
+        match self {
+            OmniPayload::SimpleSend {
+                property_id,
+                amount,
+            } => {
+                data.extend_from_slice(&SIMPLE_SEND.to_be_bytes());
+                data.extend_from_slice(&property_id.to_be_bytes());
+                data.extend_from_slice(&amount.to_be_bytes());
+            }
+            OmniPayload::SendToOwners {
+                property_id,
+                amount,
+            } => {
+                data.extend_from_slice(&SEND_TO_OWNERS.to_be_bytes());
+                data.extend_from_slice(&property_id.to_be_bytes());
+                data.extend_from_slice(&amount.to_be_bytes());
+            }
+            OmniPayload::SendAll { ecosystem } => {
+                data.extend_from_slice(&SEND_ALL.to_be_bytes());
+                data.push(*ecosystem);
+            }
+        }
+        data
+    }
+
+    pub fn parse(data: &[u8]) -> Result<OmniPayload> {
+        if data.len() < 4 || data[..2] != [0x00, 0x00] {
+            return Err(CoinError::ImkeyOmniPayloadInvalid.into());
+        }
+        let tx_type = u16::from_be_bytes([data[2], data[3]]);
+        let fields = &data[4..];
+        let property_and_amount = || {
+            if fields.len() != 12 {
+                return Err(CoinError::ImkeyOmniPayloadInvalid);
+            }
+            let mut property_id = [0u8; 4];
+            property_id.copy_from_slice(&fields[..4]);
+            let mut amount = [0u8; 8];
+            amount.copy_from_slice(&fields[4..]);
+            Ok((u32::from_be_bytes(property_id), u64::from_be_bytes(amount)))
+        };
+        match tx_type {
+            SIMPLE_SEND => {
+                let (property_id, amount) = property_and_amount()?;
+                Ok(OmniPayload::SimpleSend {
+                    property_id,
+                    amount,
+                })
+            }
+            SEND_TO_OWNERS => {
+                let (property_id, amount) = property_and_amount()?;
+                Ok(OmniPayload::SendToOwners {
+                    property_id,
+                    amount,
+                })
+            }
+            SEND_ALL if fields.len() == 1 => Ok(OmniPayload::SendAll {
+                ecosystem: fields[0],
+            }),
+            _ => Err(CoinError::ImkeyOmniPayloadInvalid.into()),
+        }
+    }
+
+    /**
+    the payload of an omni op_return output, none for any other script
+    */
+    pub fn from_script(script: &Script) -> Result<Option<OmniPayload>> {
+        if !script.is_op_return() {
+            return Ok(None);
+        }
+        let data = match script.iter(false).nth(1) {
+            Some(Instruction::PushBytes(data)) if data.starts_with(OMNI_MARKER) => data,
+            _ => return Ok(None),
+        };
+        Ok(Some(OmniPayload::parse(&data[OMNI_MARKER.len()..])?))
+    }
+
+    pub fn to_output(&self) -> TxOut {
+        let mut omni_data = OMNI_MARKER.to_vec();
+        omni_data.extend(self.serialize());
+        TxOut {
+            value: 0u64,
+            script_pubkey: Builder::new()
+                .push_opcode(opcodes::all::OP_RETURN)
+                .push_slice(&omni_data[..])
+                .into_script(),
+        }
+    }
+
+    pub fn check(&self) -> Result<()> {
+        let valid = match self {
+            OmniPayload::SimpleSend { amount, .. } | OmniPayload::SendToOwners { amount, .. } => {
+                *amount > 0 && *amount <= i64::MAX as u64
+            }
+            OmniPayload::SendAll { ecosystem } => {
+                *ecosystem == MAIN_ECOSYSTEM || *ecosystem == TEST_ECOSYSTEM
+            }
+        };
+        if !valid {
+            return Err(CoinError::ImkeyOmniPayloadInvalid.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::omni::{OmniPayload, MAIN_ECOSYSTEM};
+    use bitcoin::Script;
+
+    #[test]
+    fn serialize_test() {
+        let simple_send = OmniPayload::SimpleSend {
+            property_id: 31,
+            amount: 10_050_000_000,
+        };
+        assert_eq!(
+            hex::encode(simple_send.to_output().script_pubkey.as_bytes()),
+            "6a146f6d6e69000000000000001f000000025706d480"
+        );
+        let send_to_owners = OmniPayload::SendToOwners {
+            property_id: 3,
+            amount: 100,
+        };
+        assert_eq!(
+            hex::encode(send_to_owners.serialize()),
+            "00000003000000030000000000000064"
+        );
+        let send_all = OmniPayload::SendAll {
+            ecosystem: MAIN_ECOSYSTEM,
+        };
+        assert_eq!(hex::encode(send_all.serialize()), "0000000401");
+
+        for payload in &[simple_send, send_to_owners, send_all] {
+            let script = payload.to_output().script_pubkey;
+            assert_eq!(
+                OmniPayload::from_script(&script).unwrap().as_ref(),
+                Some(payload)
+            );
+        }
+    }
+
+    #[test]
+    fn from_script_test() {
+        //p2pkh and a non omni op_return are no omni outputs
+        for script in &[
+            "76a914383fb81cb0a3fc724b5e08cf8bbd404336d711f688ac",
+            "6a0568656c6c6f",
+            "6a",
+        ] {
+            let script = Script::from(hex::decode(script).unwrap());
+            assert_eq!(OmniPayload::from_script(&script).unwrap(), None);
+        }
+        //an unknown type, a truncated amount and another version
+        for script in &[
+            "6a0a6f6d6e69000000320000",
+            "6a106f6d6e69000000000000001f00000002",
+            "6a146f6d6e69000100000000001f0000000257045680",
+        ] {
+            let script = Script::from(hex::decode(script).unwrap());
+            assert_eq!(
+                format!("{}", OmniPayload::from_script(&script).err().unwrap()),
+                "imkey_omni_payload_invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn check_test() {
+        assert!(OmniPayload::SendAll { ecosystem: 2 }.check().is_ok());
+        assert!(OmniPayload::SendAll { ecosystem: 3 }.check().is_err());
+        assert!(OmniPayload::SimpleSend {
+            property_id: 31,
+            amount: 0
+        }
+        .check()
+        .is_err());
+        assert!(OmniPayload::SendToOwners {
+            property_id: 31,
+            amount: u64::MAX
+        }
+        .check()
+        .is_err());
+    }
+}
@@ -0,0 +1,505 @@
+use crate::common::{
+    address_verify, get_outputs_address_version, get_verified_xpub, sign_tx_inputs, SignInput,
+    TransTypeFlg,
+};
+use crate::transaction::Utxo;
+use crate::Result;
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::util::psbt::{Input, PartiallySignedTransaction};
+use bitcoin::{Address, Network, PublicKey, Script, SigHashType, TxIn, TxOut};
+use bitcoin_hashes::hex::ToHex;
+use common::constants::MAX_UTXO_NUMBER;
+use common::error::CoinError;
+use common::path::check_path_validity;
+use device::device_binding::key_manager;
+use transport::Transport;
+
+/**
+a bip174 partially signed transaction, coin selection and outputs are up to whoever built it
+*/
+pub struct BtcPsbt {
+    pub psbt: PartiallySignedTransaction,
+}
+
+impl BtcPsbt {
+    pub fn from_hex(psbt: &str) -> Result<Self> {
+        Ok(BtcPsbt {
+            psbt: deserialize(&Vec::from_hex(psbt)?)?,
+        })
+    }
+
+    pub fn to_hex(&self) -> String {
+        serialize(&self.psbt).to_hex()
+    }
+
+    /**
+    sign the inputs whose bip32 derivation lies under path and return the psbt with their
+    partial signatures added. every input needs its utxo for the fee, a legacy one its whole
+    previous transaction. the inputs signed have to be all p2pkh or all p2sh-p2wpkh and use
+    sighash all
+    */
+    pub fn sign_psbt(
+        &self,
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+    ) -> Result<PartiallySignedTransaction> {
+        //path check
+        check_path_validity(path)?;
+        let mut path_str = path.to_string();
+        if !path.ends_with('/') {
+            path_str = format!("{}{}", path_str, "/");
+        }
+
+        let tx = &self.psbt.global.unsigned_tx;
+        if self.psbt.inputs.len() != tx.input.len() || self.psbt.outputs.len() != tx.output.len() {
+            return Err(CoinError::ImkeySdkIllegalArgument.into());
+        }
+        //check utxo number
+        if tx.input.len() > MAX_UTXO_NUMBER {
+            return Err(CoinError::ImkeyExceededMaxUtxoNumber.into());
+        }
+
+        //find the inputs to sign and the amount of every input
+        let mut total_amount = 0u64;
+        let mut sign_inputs: Vec<SignInput> = vec![];
+        let mut input_type = None;
+        for (index, (txin, input)) in tx.input.iter().zip(self.psbt.inputs.iter()).enumerate() {
+            let derivation = relative_derivation(input, &path_str);
+            let spent = spent_output(txin, input, derivation.is_some())?;
+            total_amount += spent.value;
+
+            let (pub_key, derive_path) = match derivation {
+                Some(derivation) => derivation,
+                None => continue,
+            };
+            let spent_type = get_input_type(&spent.script_pubkey, input)?;
+            if *input_type.get_or_insert(spent_type) != spent_type
+                || input.sighash_type.unwrap_or(SigHashType::All) != SigHashType::All
+            {
+                return Err(CoinError::ImkeyPsbtScriptNotSupported.into());
+            }
+            let address = Address::from_script(&spent.script_pubkey, network)
+                .ok_or(CoinError::ImkeyPsbtScriptNotSupported)?;
+            sign_inputs.push(SignInput {
+                index,
+                trans_type_flg: spent_type,
+                pub_key,
+                utxo: Utxo {
+                    txhash: txin.previous_output.txid.to_hex(),
+                    vout: txin.previous_output.vout as i32,
+                    amount: spent.value as i64,
+                    address,
+                    script_pubkey: spent.script_pubkey.to_hex(),
+                    derive_path,
+                    sequence: txin.sequence as i64,
+                },
+            });
+        }
+        let input_type = input_type.ok_or(CoinError::ImkeyPsbtNothingToSign)?;
+
+        //calc fee
+        let output_amount: u64 = tx.output.iter().map(|txout| txout.value).sum();
+        if total_amount < output_amount {
+            return Err(CoinError::ImkeyInsufficientFunds.into());
+        }
+        let fee = (total_amount - output_amount) as i64;
+
+        //get xpub signed by the se
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
+        let (pub_key, chain_code) =
+            get_verified_xpub(transport, path_str.as_str(), &key_manager_obj.se_pub_key)?;
+
+        //utxo address verify, the keys in the psbt have to be the ones the device derives
+        let utxos: Vec<Utxo> = sign_inputs
+            .iter()
+            .map(|sign_input| sign_input.utxo.clone())
+            .collect();
+        let utxo_pub_key_vec = address_verify(&utxos, &pub_key, &chain_code, network, input_type)?;
+        for (sign_input, utxo_pub_key) in sign_inputs.iter().zip(utxo_pub_key_vec.iter()) {
+            if sign_input.pub_key.to_string() != *utxo_pub_key {
+                return Err(CoinError::ImkeyAddressMismatchWithPath.into());
+            }
+        }
+
+        let address_version =
+            get_outputs_address_version(network, &get_output_addresses(&tx.output, network))?;
+        let signatures = sign_tx_inputs(
+            transport,
+            tx,
+            &sign_inputs,
+            &path_str,
+            fee,
+            address_version,
+            &key_manager_obj.pri_key,
+        )?;
+
+        let mut psbt = self.psbt.clone();
+        for (sign_input, signature) in sign_inputs.iter().zip(signatures) {
+            psbt.inputs[sign_input.index]
+                .partial_sigs
+                .insert(sign_input.pub_key, signature);
+        }
+        Ok(psbt)
+    }
+}
+
+/**
+the output an input spends. a legacy signature does not commit to the amount spent, and a
+segwit one commits to the amount of its own input only, so two signings of one transaction with
+other amounts could pay away an unseen fee (cve-2020-14199). an input to sign needs its whole
+previous transaction, checked against the outpoint and any witness utxo given along
+*/
+fn spent_output(txin: &TxIn, input: &Input, to_sign: bool) -> Result<TxOut> {
+    let previous_output = match &input.non_witness_utxo {
+        Some(previous_tx) => {
+            if previous_tx.txid() != txin.previous_output.txid {
+                return Err(CoinError::ImkeyPsbtUtxoMismatch.into());
+            }
+            match previous_tx.output.get(txin.previous_output.vout as usize) {
+                Some(txout) => Some(txout.clone()),
+                None => return Err(CoinError::ImkeyPsbtUtxoMismatch.into()),
+            }
+        }
+        None => None,
+    };
+    match (previous_output, &input.witness_utxo) {
+        (Some(txout), Some(witness_utxo)) if txout != *witness_utxo => {
+            Err(CoinError::ImkeyPsbtUtxoMismatch.into())
+        }
+        (Some(txout), _) => Ok(txout),
+        //an input of another signer only adds its amount to the fee shown
+        (None, Some(txout)) if !to_sign && is_segwit(&txout.script_pubkey, input) => {
+            Ok(txout.clone())
+        }
+        _ => Err(CoinError::ImkeyPsbtUtxoMissing.into()),
+    }
+}
+
+fn is_segwit(script_pubkey: &Script, input: &Input) -> bool {
+    let is_witness_program = |script: &Script| script.is_v0_p2wpkh() || script.is_v0_p2wsh();
+    if is_witness_program(script_pubkey) {
+        return true;
+    }
+    match &input.redeem_script {
+        Some(redeem_script) => {
+            is_witness_program(redeem_script) && redeem_script.to_p2sh() == *script_pubkey
+        }
+        None => false,
+    }
+}
+
+/**
+the key of an input derived under the account path and its path relative to the account,
+None when the input belongs to someone else
+*/
+fn relative_derivation(input: &Input, path: &str) -> Option<(PublicKey, String)> {
+    input
+        .hd_keypaths
+        .iter()
+        .find_map(|(pub_key, (_, derivation_path))| {
+            let full_path = derivation_path.to_string();
+            if !full_path.starts_with(path) {
+                return None;
+            }
+            let derive_path = full_path[path.len()..].to_string();
+            //the device derives addresses with non hardened indexes below the account
+            let is_normal = !derive_path.is_empty()
+                && derive_path
+                    .split('/')
+                    .all(|index| index.parse::<u32>().is_ok());
+            if is_normal {
+                Some((*pub_key, derive_path))
+            } else {
+                None
+            }
+        })
+}
+
+fn get_input_type(script_pubkey: &Script, input: &Input) -> Result<TransTypeFlg> {
+    if script_pubkey.is_p2pkh() {
+        return Ok(TransTypeFlg::BTC);
+    }
+    match &input.redeem_script {
+        Some(redeem_script)
+            if redeem_script.is_v0_p2wpkh() && redeem_script.to_p2sh() == *script_pubkey =>
+        {
+            Ok(TransTypeFlg::SEGWIT)
+        }
+        _ => Err(CoinError::ImkeyPsbtScriptNotSupported.into()),
+    }
+}
+
+/**
+the addresses the outputs pay to, outputs without one like op_return are left out
+*/
+fn get_output_addresses(outputs: &[TxOut], network: Network) -> Vec<Address> {
+    outputs
+        .iter()
+        .filter_map(|txout| Address::from_script(&txout.script_pubkey, network))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::address::BtcAddress;
+    use crate::psbt::BtcPsbt;
+    use bitcoin::hashes::hex::FromHex;
+    use bitcoin::secp256k1::{Message, Secp256k1, Signature};
+    use bitcoin::util::bip143::SighashComponents;
+    use bitcoin::util::bip32::{DerivationPath, ExtendedPubKey, Fingerprint};
+    use bitcoin::util::psbt::PartiallySignedTransaction;
+    use bitcoin::{Address, Network, OutPoint, PublicKey, Script, Transaction, TxIn, TxOut};
+    use bitcoin_hashes::hash160;
+    use bitcoin_hashes::hex::ToHex;
+    use bitcoin_hashes::sha256d::Hash as Hash256;
+    use bitcoin_hashes::Hash;
+    use common::error::CoinError;
+    use std::str::FromStr;
+    use transport::Transport;
+
+    fn build_psbt(version: u32, inputs: &[(&str, u32)], to: &str, amount: u64) -> BtcPsbt {
+        let tx = Transaction {
+            version,
+            lock_time: 0,
+            input: inputs
+                .iter()
+                .map(|(txhash, vout)| TxIn {
+                    previous_output: OutPoint {
+                        txid: Hash256::from_hex(txhash).unwrap(),
+                        vout: *vout,
+                    },
+                    script_sig: Script::new(),
+                    sequence: 0xFFFFFFFF,
+                    witness: vec![],
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: amount,
+                script_pubkey: Address::from_str(to).unwrap().script_pubkey(),
+            }],
+        };
+        BtcPsbt {
+            psbt: PartiallySignedTransaction::from_unsigned_tx(tx).unwrap(),
+        }
+    }
+
+    //a transaction paying value to the script at vout 0
+    fn previous_tx(script: &str, value: u64) -> Transaction {
+        Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::from(Vec::from_hex(script).unwrap()),
+            }],
+        }
+    }
+
+    fn add_input_key(
+        psbt: &mut BtcPsbt,
+        transport: &dyn Transport,
+        index: usize,
+        path: &str,
+    ) -> PublicKey {
+        let xpub = BtcAddress::get_xpub(transport, Network::Testnet, path).unwrap();
+        let pub_key = ExtendedPubKey::from_str(&xpub).unwrap().public_key;
+        psbt.psbt.inputs[index].hd_keypaths.insert(
+            pub_key,
+            (
+                Fingerprint::default(),
+                DerivationPath::from_str(path).unwrap(),
+            ),
+        );
+        pub_key
+    }
+
+    fn p2pkh_script_code(pub_key: &PublicKey) -> Script {
+        let pub_key_hash = hash160::Hash::hash(&pub_key.to_bytes()).into_inner();
+        Script::from(hex::decode(format!("76a914{}88ac", hex::encode(pub_key_hash))).unwrap())
+    }
+
+    fn verify(sighash: &[u8], signature: &[u8], pub_key: &PublicKey) -> bool {
+        //the last byte is the sighash type
+        let (signature, sighash_type) = signature.split_at(signature.len() - 1);
+        let signature = Signature::from_der(signature).unwrap();
+        sighash_type == [0x01]
+            && Secp256k1::verification_only()
+                .verify(
+                    &Message::from_slice(sighash).unwrap(),
+                    &signature,
+                    &pub_key.key,
+                )
+                .is_ok()
+    }
+
+    #[test]
+    fn sign_p2pkh_psbt_test() {
+        let transport = simulator::bind_test();
+
+        let previous_txs: Vec<Transaction> = [
+            "76a914118c3123196e030a8a607c22bafc1577af61497d88ac",
+            "76a914383fb81cb0a3fc724b5e08cf8bbd404336d711f688ac",
+            //an input of another signer
+            "76a914a189f2f7836812aa7a0e36e28a20a10e64010bf688ac",
+        ]
+        .iter()
+        .map(|script| previous_tx(script, 200000000))
+        .collect();
+        let txids: Vec<String> = previous_txs.iter().map(|tx| tx.txid().to_hex()).collect();
+        let mut btc_psbt = build_psbt(
+            1,
+            &[
+                (txids[0].as_str(), 0),
+                (txids[1].as_str(), 0),
+                (txids[2].as_str(), 0),
+            ],
+            "moLK3tBG86ifpDDTqAQzs4a9cUoNjVLRE3",
+            550000000,
+        );
+        for (input, previous_tx) in btc_psbt.psbt.inputs.iter_mut().zip(previous_txs) {
+            input.non_witness_utxo = Some(previous_tx);
+        }
+        let pub_keys = vec![
+            add_input_key(&mut btc_psbt, &transport, 0, "m/44'/1'/0'/0/22"),
+            add_input_key(&mut btc_psbt, &transport, 1, "m/44'/1'/0'/0/0"),
+        ];
+
+        let btc_psbt = BtcPsbt::from_hex(&btc_psbt.to_hex()).unwrap();
+        let signed = btc_psbt
+            .sign_psbt(&transport, Network::Testnet, "m/44'/1'/0'")
+            .unwrap();
+        let tx = &signed.global.unsigned_tx;
+        for (index, pub_key) in pub_keys.iter().enumerate() {
+            let signature = &signed.inputs[index].partial_sigs[pub_key];
+            let sighash = tx.signature_hash(index, &p2pkh_script_code(pub_key), 0x01);
+            assert!(verify(&sighash[..], signature, pub_key));
+        }
+        assert!(signed.inputs[2].partial_sigs.is_empty());
+    }
+
+    #[test]
+    fn sign_p2sh_p2wpkh_psbt_test() {
+        let transport = simulator::bind_test();
+
+        let previous_txs: Vec<Transaction> = [
+            "a9142d2b1ef5ee4cf6c3ebc8cf66a602783798f7875987",
+            "a91481af6d803fdc6dca1f3a1d03f5ffe8124cd1b44787",
+        ]
+        .iter()
+        .map(|script| previous_tx(script, 50000))
+        .collect();
+        let txids: Vec<String> = previous_txs.iter().map(|tx| tx.txid().to_hex()).collect();
+        let mut btc_psbt = build_psbt(
+            2,
+            &[(txids[0].as_str(), 0), (txids[1].as_str(), 0)],
+            "2N9wBy6f1KTUF5h2UUeqRdKnBT6oSMh4Whp",
+            88000,
+        );
+        let mut pub_keys = vec![];
+        for (index, previous_tx) in previous_txs.iter().enumerate() {
+            let path = format!("m/49'/1'/0'/0/{}", index);
+            let pub_key = add_input_key(&mut btc_psbt, &transport, index, &path);
+            let pub_key_hash = hash160::Hash::hash(&pub_key.to_bytes()).into_inner();
+            let input = &mut btc_psbt.psbt.inputs[index];
+            input.witness_utxo = Some(previous_tx.output[0].clone());
+            input.redeem_script = Some(Script::from(
+                hex::decode(format!("0014{}", hex::encode(pub_key_hash))).unwrap(),
+            ));
+            pub_keys.push(pub_key);
+        }
+        let sign =
+            |btc_psbt: &BtcPsbt| btc_psbt.sign_psbt(&transport, Network::Testnet, "m/49'/1'/0'/");
+
+        //the witness utxo alone does not prove the amount spent
+        assert_eq!(
+            format!("{}", sign(&btc_psbt).err().unwrap()),
+            "imkey_psbt_utxo_missing"
+        );
+        for (input, previous_tx) in btc_psbt.psbt.inputs.iter_mut().zip(&previous_txs) {
+            input.non_witness_utxo = Some(previous_tx.clone());
+        }
+        //nor is a witness utxo of another amount taken
+        btc_psbt.psbt.inputs[1].witness_utxo = Some(TxOut {
+            value: 500000,
+            ..previous_txs[1].output[0].clone()
+        });
+        assert_eq!(
+            format!("{}", sign(&btc_psbt).err().unwrap()),
+            "imkey_psbt_utxo_mismatch"
+        );
+        btc_psbt.psbt.inputs[1].witness_utxo = Some(previous_txs[1].output[0].clone());
+
+        let signed = sign(&btc_psbt).unwrap();
+        let tx = &signed.global.unsigned_tx;
+        let sighash_components = SighashComponents::new(tx);
+        for (index, pub_key) in pub_keys.iter().enumerate() {
+            let signature = &signed.inputs[index].partial_sigs[pub_key];
+            let sighash = sighash_components.sighash_all(
+                &tx.input[index],
+                &p2pkh_script_code(pub_key),
+                50000,
+            );
+            assert!(verify(&sighash[..], signature, pub_key));
+        }
+    }
+
+    #[test]
+    fn sign_psbt_error_test() {
+        let transport = simulator::bind_test();
+        let funding_tx = previous_tx(
+            "76a914118c3123196e030a8a607c22bafc1577af61497d88ac",
+            200000000,
+        );
+        let mut btc_psbt = build_psbt(
+            1,
+            &[(funding_tx.txid().to_hex().as_str(), 0)],
+            "moLK3tBG86ifpDDTqAQzs4a9cUoNjVLRE3",
+            100000000,
+        );
+        let sign = |btc_psbt: &BtcPsbt| {
+            btc_psbt
+                .sign_psbt(&transport, Network::Testnet, "m/44'/1'/0'")
+                .err()
+                .unwrap()
+                .downcast::<CoinError>()
+                .unwrap()
+        };
+        assert_eq!(sign(&btc_psbt), CoinError::ImkeyPsbtUtxoMissing);
+
+        //a legacy signature does not commit to the amount, the witness utxo is not enough
+        btc_psbt.psbt.inputs[0].witness_utxo = Some(funding_tx.output[0].clone());
+        assert_eq!(sign(&btc_psbt), CoinError::ImkeyPsbtUtxoMissing);
+
+        let mut other_tx = funding_tx.clone();
+        other_tx.lock_time = 1;
+        btc_psbt.psbt.inputs[0].non_witness_utxo = Some(other_tx);
+        assert_eq!(sign(&btc_psbt), CoinError::ImkeyPsbtUtxoMismatch);
+
+        btc_psbt.psbt.inputs[0].non_witness_utxo = Some(funding_tx);
+        assert_eq!(sign(&btc_psbt), CoinError::ImkeyPsbtNothingToSign);
+
+        let pub_key = add_input_key(&mut btc_psbt, &transport, 0, "m/44'/1'/0'/0/22");
+        btc_psbt.psbt.global.unsigned_tx.output[0].value = 300000000;
+        assert_eq!(sign(&btc_psbt), CoinError::ImkeyInsufficientFunds);
+
+        //native segwit inputs are not signed by this flow
+        let pub_key_hash = hash160::Hash::hash(&pub_key.to_bytes()).into_inner();
+        let funding_tx = previous_tx(&format!("0014{}", hex::encode(pub_key_hash)), 200000000);
+        btc_psbt.psbt.global.unsigned_tx.input[0]
+            .previous_output
+            .txid = funding_tx.txid();
+        btc_psbt.psbt.global.unsigned_tx.output[0].value = 100000000;
+        btc_psbt.psbt.inputs[0].witness_utxo = Some(funding_tx.output[0].clone());
+        btc_psbt.psbt.inputs[0].non_witness_utxo = Some(funding_tx);
+        assert_eq!(sign(&btc_psbt), CoinError::ImkeyPsbtScriptNotSupported);
+    }
+}
@@ -0,0 +1,422 @@
+use crate::Result;
+use bitcoin::bech32::u5;
+use bitcoin::consensus::serialize;
+use bitcoin::util::address::Payload;
+use bitcoin::{Address, Network, Transaction, TxOut};
+use bitcoin_hashes::{sha256, Hash, HashEngine};
+use common::error::CoinError;
+use secp256k1::{PublicKey, Secp256k1};
+use std::str::FromStr;
+
+const BECH32_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+//bip341 sighash types a key path spend is signed with
+pub const SIGHASH_DEFAULT: u8 = 0x00;
+pub const SIGHASH_ALL: u8 = 0x01;
+
+/**
+bip340 tagged hash, sha256(sha256(tag) | sha256(tag) | data)
+*/
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag_hash[..]);
+    engine.input(&tag_hash[..]);
+    engine.input(data);
+    sha256::Hash::from_engine(engine).into_inner()
+}
+
+/**
+bip86 output key of an internal public key: the internal key is taken with even y and tweaked
+with tagged_hash("TapTweak", x), only the x coordinate of the result is kept
+*/
+pub fn tweak_public_key(public_key: &[u8]) -> Result<[u8; 32]> {
+    let secp = Secp256k1::verification_only();
+    let internal_key = PublicKey::from_slice(public_key)?.serialize();
+
+    let mut even_key = vec![0x02];
+    even_key.extend_from_slice(&internal_key[1..]);
+    let mut output_key = PublicKey::from_slice(&even_key)?;
+    output_key.add_exp_assign(&secp, &tagged_hash("TapTweak", &internal_key[1..]))?;
+
+    let mut x_only = [0u8; 32];
+    x_only.copy_from_slice(&output_key.serialize()[1..]);
+    Ok(x_only)
+}
+
+/**
+segwit v1 address paying to an output key
+*/
+pub fn p2tr_address(output_key: &[u8; 32], network: Network) -> Address {
+    Address {
+        network,
+        payload: Payload::WitnessProgram {
+            version: u5::try_from_u8(1).expect("1<32"),
+            program: output_key.to_vec(),
+        },
+    }
+}
+
+/**
+the text form of an address, segwit v1 and later use the bech32m checksum of bip350
+*/
+pub fn encode_address(address: &Address) -> String {
+    match &address.payload {
+        Payload::WitnessProgram { version, program } if version.to_u8() > 0 => {
+            let hrp = get_hrp(address.network);
+            let mut data = vec![version.to_u8()];
+            data.extend(convert_bits(program, 8, 5, true).expect("8 to 5 bits always pads"));
+            let checksum =
+                polymod(&[hrp_expand(hrp), data.clone(), vec![0u8; 6]].concat()) ^ BECH32M_CONST;
+            for index in 0..6 {
+                data.push(((checksum >> (5 * (5 - index))) & 31) as u8);
+            }
+            let data: String = data
+                .iter()
+                .map(|value| BECH32_CHARSET[*value as usize] as char)
+                .collect();
+            format!("{}1{}", hrp, data)
+        }
+        _ => address.to_string(),
+    }
+}
+
+/**
+parse an address, segwit v1+ addresses must carry the bech32m checksum of bip350
+while bitcoin only knows the bech32 one
+*/
+pub fn parse_address(address: &str) -> Result<Address> {
+    match Address::from_str(address) {
+        Ok(parsed) => match parsed.payload {
+            Payload::WitnessProgram { version, .. } if version.to_u8() > 0 => {
+                Err(CoinError::AddressTypeMismatch.into())
+            }
+            _ => Ok(parsed),
+        },
+        Err(error) => decode_bech32m_address(address).ok_or_else(|| error.into()),
+    }
+}
+
+fn decode_bech32m_address(address: &str) -> Option<Address> {
+    if address.to_lowercase() != address && address.to_uppercase() != address {
+        return None;
+    }
+    let address = address.to_lowercase();
+    let separator = address.rfind('1')?;
+    let (hrp, data) = (&address[..separator], &address[separator + 1..]);
+    let network = match hrp {
+        "bc" => Network::Bitcoin,
+        "tb" => Network::Testnet,
+        "bcrt" => Network::Regtest,
+        _ => return None,
+    };
+    let mut values = vec![];
+    for char in data.bytes() {
+        values.push(BECH32_CHARSET.iter().position(|value| *value == char)? as u8);
+    }
+    if values.len() < 7 || polymod(&[hrp_expand(hrp), values.clone()].concat()) != BECH32M_CONST {
+        return None;
+    }
+    let version = values[0];
+    let program = convert_bits(&values[1..values.len() - 6], 5, 8, false)?;
+    if !(1..=16).contains(&version) || !(2..=40).contains(&program.len()) {
+        return None;
+    }
+    Some(Address {
+        network,
+        payload: Payload::WitnessProgram {
+            version: u5::try_from_u8(version).ok()?,
+            program,
+        },
+    })
+}
+
+/**
+bip341 signature hash of a key path spend without annex,
+spent_outputs are the outputs every input of tx spends
+*/
+pub fn taproot_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    spent_outputs: &[TxOut],
+    sighash_type: u8,
+) -> Result<[u8; 32]> {
+    if spent_outputs.len() != tx.input.len()
+        || input_index >= tx.input.len()
+        || (sighash_type != SIGHASH_DEFAULT && sighash_type != SIGHASH_ALL)
+    {
+        return Err(CoinError::ImkeySdkIllegalArgument.into());
+    }
+    let prevouts: Vec<u8> = tx
+        .input
+        .iter()
+        .flat_map(|txin| serialize(&txin.previous_output))
+        .collect();
+    let amounts: Vec<u8> = spent_outputs
+        .iter()
+        .flat_map(|txout| serialize(&txout.value))
+        .collect();
+    let script_pubkeys: Vec<u8> = spent_outputs
+        .iter()
+        .flat_map(|txout| serialize(&txout.script_pubkey))
+        .collect();
+    let sequences: Vec<u8> = tx
+        .input
+        .iter()
+        .flat_map(|txin| serialize(&txin.sequence))
+        .collect();
+    let outputs: Vec<u8> = tx.output.iter().flat_map(serialize).collect();
+
+    //epoch | hash type | version | lock time | input and output hashes | spend type | input index
+    let mut data = vec![0x00, sighash_type];
+    data.extend(serialize(&tx.version));
+    data.extend(serialize(&tx.lock_time));
+    data.extend(&sha256::Hash::hash(&prevouts)[..]);
+    data.extend(&sha256::Hash::hash(&amounts)[..]);
+    data.extend(&sha256::Hash::hash(&script_pubkeys)[..]);
+    data.extend(&sha256::Hash::hash(&sequences)[..]);
+    data.extend(&sha256::Hash::hash(&outputs)[..]);
+    data.push(0x00);
+    data.extend(serialize(&(input_index as u32)));
+    Ok(tagged_hash("TapSighash", &data))
+}
+
+/**
+bip340 verification: s * G == R + e * P with R and P taken with even y
+*/
+pub fn schnorr_verify(output_key: &[u8; 32], message: &[u8], signature: &[u8]) -> Result<bool> {
+    if signature.len() != 64 {
+        return Ok(false);
+    }
+    let secp = Secp256k1::new();
+    let (r, s) = signature.split_at(32);
+
+    let mut challenge_data = r.to_vec();
+    challenge_data.extend_from_slice(output_key);
+    challenge_data.extend_from_slice(message);
+    let challenge = tagged_hash("BIP0340/challenge", &challenge_data);
+
+    let lift_x = |x: &[u8]| PublicKey::from_slice(&[&[0x02u8][..], x].concat());
+    let (r_point, mut e_point) = match (lift_x(r), lift_x(output_key)) {
+        (Ok(r_point), Ok(p_point)) => (r_point, p_point),
+        _ => return Ok(false),
+    };
+    let s_key = match secp256k1::SecretKey::from_slice(s) {
+        Ok(s_key) => s_key,
+        Err(_) => return Ok(false),
+    };
+    e_point.mul_assign(&secp, &challenge)?;
+    let expected = r_point.combine(&e_point)?;
+    Ok(PublicKey::from_secret_key(&secp, &s_key) == expected)
+}
+
+fn get_hrp(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "bc",
+        Network::Testnet => "tb",
+        Network::Regtest => "bcrt",
+    }
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut values: Vec<u8> = hrp.bytes().map(|char| char >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|char| char & 31));
+    values
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = (checksum & 0x01ff_ffff) << 5 ^ *value as u32;
+        for (index, generator) in GENERATOR.iter().enumerate() {
+            if (top >> index) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut accumulator: u32 = 0;
+    let mut bits: u32 = 0;
+    let max_value = (1 << to) - 1;
+    let mut result = vec![];
+    for value in data {
+        if (*value as u32) >> from != 0 {
+            return None;
+        }
+        accumulator = (accumulator << from) | *value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push(((accumulator >> bits) & max_value) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            result.push(((accumulator << (to - bits)) & max_value) as u8);
+        }
+    } else if bits >= from || ((accumulator << (to - bits)) & max_value) != 0 {
+        return None;
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::taproot::{
+        encode_address, p2tr_address, parse_address, schnorr_verify, tagged_hash, taproot_sighash,
+        tweak_public_key, SIGHASH_ALL, SIGHASH_DEFAULT,
+    };
+    use bitcoin::consensus::deserialize;
+    use bitcoin::{Network, Script, Transaction, TxOut};
+
+    #[test]
+    fn bip86_output_key_test() {
+        //bip86 test vector, m/86'/0'/0'/0/0
+        let internal_key =
+            hex::decode("03cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115")
+                .unwrap();
+        let output_key = tweak_public_key(&internal_key).unwrap();
+        assert_eq!(
+            "a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+            hex::encode(output_key)
+        );
+        assert_eq!(
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+            encode_address(&p2tr_address(&output_key, Network::Bitcoin))
+        );
+    }
+
+    #[test]
+    fn parse_address_test() {
+        //bip350 test vector
+        let address =
+            parse_address("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0")
+                .unwrap();
+        assert_eq!(Network::Bitcoin, address.network);
+        assert_eq!(
+            "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            hex::encode(address.script_pubkey().as_bytes())
+        );
+        assert_eq!(
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            encode_address(&address)
+        );
+
+        let address = parse_address("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx").unwrap();
+        assert!(address.script_pubkey().is_v0_p2wpkh());
+        assert_eq!(
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+            encode_address(&address)
+        );
+
+        //bech32 checksum on a v1 program and a broken checksum
+        assert!(parse_address(
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx"
+        )
+        .is_err());
+        assert!(
+            parse_address("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj1")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn schnorr_verify_test() {
+        //bip340 test vector 1
+        let public_key: [u8; 32] = {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(
+                &hex::decode("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659")
+                    .unwrap(),
+            );
+            key
+        };
+        let message =
+            hex::decode("243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89")
+                .unwrap();
+        let mut signature = hex::decode("6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a").unwrap();
+        assert!(schnorr_verify(&public_key, &message, &signature).unwrap());
+
+        signature[63] ^= 0x01;
+        assert!(!schnorr_verify(&public_key, &message, &signature).unwrap());
+        assert_eq!(32, tagged_hash("TapTweak", &[]).len());
+
+        //bip340 test vector 6, R has an odd y
+        let signature = hex::decode("fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a14602975563cc27944640ac607cd107ae10923d9ef7a73c643e166be5ebeafa34b1ac553e2").unwrap();
+        assert!(!schnorr_verify(&public_key, &message, &signature).unwrap());
+    }
+
+    #[test]
+    fn taproot_sighash_test() {
+        //bip341 keyPathSpending test vector
+        let tx: Transaction = deserialize(&hex::decode("02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c010000000000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6dafdd990000000000fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d957275419a41842000000006b4830450221008f3b8f8f0537c420654d2283673a761b7ee2ea3c130753103e08ce79201cf32a022079e7ab904a1980ef1c5890b648c8783f4d10103dd62f740d13daa79e298d50c201210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798fffffffff0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b0100000000feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd3c5a52ee1239e0ba6c0000000000feffffff956149bdc66faa968eb2be2d2faa29718acbfe3941215893a2a3446d32acd050000000000000000000e664b9773b88c09c32cb70a2a3e4da0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf0000000000ffffffffa778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af10100000000ffffffff0200ca9a3b000000001976a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac807840cb0000000020ac9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab962b0065cd1d").unwrap()).unwrap();
+        let spent_outputs: Vec<TxOut> = [
+            (
+                "512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343",
+                420000000,
+            ),
+            (
+                "5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3",
+                462000000,
+            ),
+            (
+                "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+                294000000,
+            ),
+            (
+                "5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e",
+                504000000,
+            ),
+            (
+                "512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605",
+                630000000,
+            ),
+            ("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc", 378000000),
+            (
+                "512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831",
+                672000000,
+            ),
+            (
+                "5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5",
+                546000000,
+            ),
+            (
+                "512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220",
+                588000000,
+            ),
+        ]
+        .iter()
+        .map(|(script, value)| TxOut {
+            value: *value,
+            script_pubkey: Script::from(hex::decode(script).unwrap()),
+        })
+        .collect();
+
+        assert_eq!(
+            "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669",
+            hex::encode(taproot_sighash(&tx, 3, &spent_outputs, SIGHASH_ALL).unwrap())
+        );
+        assert_eq!(
+            "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef",
+            hex::encode(taproot_sighash(&tx, 4, &spent_outputs, SIGHASH_DEFAULT).unwrap())
+        );
+
+        //the anyonecanpay, none and single hash types of the vector are not supported
+        assert!(taproot_sighash(&tx, 0, &spent_outputs, 0x03).is_err());
+        assert!(taproot_sighash(&tx, 9, &spent_outputs, SIGHASH_ALL).is_err());
+        assert!(taproot_sighash(&tx, 3, &spent_outputs[1..], SIGHASH_ALL).is_err());
+    }
+}
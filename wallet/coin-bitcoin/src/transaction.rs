@@ -1,16 +1,19 @@
 use crate::address::BtcAddress;
 use crate::common::{
-    address_verify, get_address_version, get_xpub_data, secp256k1_sign_verify, TransTypeFlg,
-    TxSignResult,
+    address_verify, get_outputs_address_version, get_verified_xpub, get_xpub_data, output_data,
+    prepare_outputs, secp256k1_sign_verify, sign_tx_inputs, SignInput, TransTypeFlg, TxSignResult,
 };
+use crate::taproot::{parse_address, schnorr_verify, taproot_sighash, SIGHASH_DEFAULT};
 use crate::Result;
 use bitcoin::blockdata::{opcodes, script::Builder};
 use bitcoin::consensus::serialize;
 use bitcoin::hashes::core::str::FromStr;
 use bitcoin::hashes::hex::FromHex;
-use bitcoin::util::psbt::serialize::Serialize;
+use bitcoin::util::address::Payload;
+use bitcoin::util::base58;
 use bitcoin::{
-    Address, BitcoinHash, Network, OutPoint, Script, SigHashType, Transaction, TxIn, TxOut,
+    Address, BitcoinHash, Network, OutPoint, PublicKey, Script, SigHashType, Transaction, TxIn,
+    TxOut,
 };
 use bitcoin_hashes::hash160;
 use bitcoin_hashes::hex::ToHex;
@@ -18,14 +21,16 @@ use bitcoin_hashes::sha256d::Hash as Hash256;
 use bitcoin_hashes::Hash;
 use common::apdu::{ApduCheck, BtcApdu};
 use common::constants::{
-    DUST_THRESHOLD, EACH_ROUND_NUMBER, MAX_OPRETURN_SIZE, MAX_UTXO_NUMBER, TIMEOUT_LONG,
+    DUST_THRESHOLD, EACH_ROUND_NUMBER, MAX_OPRETURN_SIZE, MAX_UTXO_NUMBER, MIN_NONDUST_OUTPUT,
+    TIMEOUT_LONG,
 };
+use common::display::{Amount, DisplayInfo, DisplayInfoBuilder};
 use common::error::CoinError;
 use common::path::check_path_validity;
-use common::utility::{bigint_to_byte_vec, hex_to_bytes, secp256k1_sign};
-use device::device_binding::KEY_MANAGER;
+use common::utility::{hex_to_bytes, secp256k1_sign};
+use device::device_binding::key_manager;
 use secp256k1::Signature;
-use transport::message::{send_apdu, send_apdu_timeout};
+use transport::Transport;
 
 #[derive(Clone)]
 pub struct Utxo {
@@ -45,13 +50,155 @@ pub struct BtcTransaction {
     pub fee: i64,
 }
 
+#[derive(Clone)]
+pub struct BtcOutput {
+    pub address: Address,
+    pub amount: i64,
+}
+
+/**
+ * where the change of a transaction goes, a path is relative to the account path
+ */
+#[derive(Clone)]
+pub enum ChangeOutput {
+    Path(String),
+    Address(Address),
+}
+
+impl ChangeOutput {
+    pub fn index(change_idx: i32) -> ChangeOutput {
+        ChangeOutput::Path(format!("1/{}", change_idx))
+    }
+}
+
+/**
+ * change at or below change_threshold is left to the miner,
+ * recipient outputs below min_output are refused
+ */
+#[derive(Clone, Copy)]
+pub struct DustPolicy {
+    pub change_threshold: i64,
+    pub min_output: i64,
+}
+
+impl Default for DustPolicy {
+    fn default() -> Self {
+        DustPolicy {
+            change_threshold: DUST_THRESHOLD,
+            min_output: MIN_NONDUST_OUTPUT,
+        }
+    }
+}
+
+/**
+ * transaction paying any number of recipients, every recipient is part of the
+ * output data sent to the device so each one is shown for confirmation
+ */
+pub struct BtcMultiTransaction {
+    pub outputs: Vec<BtcOutput>,
+    pub unspents: Vec<Utxo>,
+    pub fee: i64,
+    pub change: ChangeOutput,
+    pub dust_policy: DustPolicy,
+}
+
 impl BtcTransaction {
     pub fn sign_transaction(
         &self,
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+        change_idx: i32,
+        extra_data: &Vec<u8>,
+    ) -> Result<TxSignResult> {
+        self.to_multi_transaction(change_idx)
+            .sign_transaction(transport, network, path, extra_data)
+    }
+
+    pub fn sign_segwit_transaction(
+        &self,
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+        change_idx: i32,
+        extra_data: &Vec<u8>,
+    ) -> Result<TxSignResult> {
+        self.to_multi_transaction(change_idx)
+            .sign_segwit_transaction(transport, network, path, extra_data)
+    }
+
+    pub fn sign_taproot_transaction(
+        &self,
+        transport: &dyn Transport,
         network: Network,
         path: &str,
         change_idx: i32,
         extra_data: &Vec<u8>,
+    ) -> Result<TxSignResult> {
+        self.to_multi_transaction(change_idx)
+            .sign_taproot_transaction(transport, network, path, extra_data)
+    }
+
+    fn to_multi_transaction(&self, change_idx: i32) -> BtcMultiTransaction {
+        BtcMultiTransaction {
+            outputs: vec![BtcOutput {
+                address: self.to.clone(),
+                amount: self.amount,
+            }],
+            unspents: self.unspents.clone(),
+            fee: self.fee,
+            change: ChangeOutput::index(change_idx),
+            dust_policy: DustPolicy {
+                change_threshold: DUST_THRESHOLD,
+                min_output: 0,
+            },
+        }
+    }
+
+    pub fn get_total_amount(&self) -> i64 {
+        let mut total_amount: i64 = 0;
+        for unspent in &self.unspents {
+            total_amount += unspent.amount;
+        }
+        total_amount
+    }
+
+    pub fn get_change_amount(&self) -> i64 {
+        let total_amount = self.get_total_amount();
+        let change_amout = total_amount - self.amount - self.fee;
+        change_amout
+    }
+
+    pub fn build_send_to_output(&self) -> TxOut {
+        TxOut {
+            value: self.amount as u64,
+            script_pubkey: self.to.script_pubkey(),
+        }
+    }
+
+    pub fn build_op_return_output(&self, extra_data: &Vec<u8>) -> TxOut {
+        let opreturn_script = Builder::new()
+            .push_opcode(opcodes::all::OP_RETURN)
+            .push_slice(&extra_data[..])
+            .into_script();
+        TxOut {
+            value: 0u64,
+            script_pubkey: opreturn_script,
+        }
+    }
+
+    pub fn build_lock_script(&self, signed: &str, utxo_public_key: &str) -> Result<Script> {
+        build_lock_script(signed, utxo_public_key)
+    }
+}
+
+impl BtcMultiTransaction {
+    pub fn sign_transaction(
+        &self,
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+        extra_data: &Vec<u8>,
     ) -> Result<TxSignResult> {
         //path check
         check_path_validity(path)?;
@@ -65,7 +212,7 @@ impl BtcTransaction {
         }
 
         //get xpub and sign data
-        let xpub_data = get_xpub_data(path_str.as_str(), true)?;
+        let xpub_data = get_xpub_data(transport, path_str.as_str(), true)?;
         let xpub_data = &xpub_data[..xpub_data.len() - 4].to_string();
 
         //parsing xpub data
@@ -75,7 +222,8 @@ impl BtcTransaction {
         let chain_code = &sign_source_val[130..];
 
         //use se public key verify sign
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
         let sign_verify_result = secp256k1_sign_verify(
             &key_manager_obj.se_pub_key.as_slice(),
             hex::decode(sign_result).unwrap().as_slice(),
@@ -94,33 +242,15 @@ impl BtcTransaction {
             TransTypeFlg::BTC,
         )?;
 
-        //calc utxo total amount
-        if self.get_total_amount() < self.amount {
-            return Err(CoinError::ImkeyInsufficientFunds.into());
-        }
-
-        //add send to output
-        let mut txouts: Vec<TxOut> = vec![];
-        txouts.push(self.build_send_to_output());
-
-        //add change output
-        if self.get_change_amount() > DUST_THRESHOLD {
-            let path_temp = format!("{}{}{}", path_str, "1/", change_idx);
-            let address_str = BtcAddress::get_address(network, path_temp.as_str())?;
-            let address_obj = Address::from_str(address_str.as_str())?;
-            txouts.push(TxOut {
-                value: self.get_change_amount() as u64,
-                script_pubkey: address_obj.script_pubkey(),
-            });
-        }
-
-        //add the op_return
-        if !extra_data.is_empty() {
-            if extra_data.len() > MAX_OPRETURN_SIZE {
-                return Err(CoinError::ImkeySdkIllegalArgument.into());
-            }
-            txouts.push(self.build_op_return_output(&extra_data))
-        }
+        //add send to, change and op_return outputs
+        let txouts = self.build_outputs(
+            transport,
+            network,
+            path_str.as_str(),
+            extra_data,
+            TransTypeFlg::BTC,
+        )?;
+        let fee = self.get_total_amount() - get_outputs_amount(&txouts);
 
         //output data serialize
         let mut tx_to_sign = Transaction {
@@ -129,27 +259,9 @@ impl BtcTransaction {
             input: vec![],
             output: txouts,
         };
-        let mut output_serialize_data = serialize(&tx_to_sign);
-
-        output_serialize_data.remove(5);
-        output_serialize_data.remove(5);
-        //add sign type
-        output_serialize_data.extend(SigHashType::All.serialize().iter());
-
-        //set input number
-        output_serialize_data.remove(4);
-        output_serialize_data.insert(4, self.unspents.len() as u8);
-
-        //add fee amount
-        output_serialize_data.extend(bigint_to_byte_vec(self.fee));
-
-        //add address version
-        let address_version = get_address_version(network, self.to.to_string().as_str())?;
-        output_serialize_data.push(address_version);
-
-        //set 01 tag and length
-        output_serialize_data.insert(0, output_serialize_data.len() as u8);
-        output_serialize_data.insert(0, 0x01);
+        let address_version = self.get_address_version(network)?;
+        let output_serialize_data =
+            output_data(&tx_to_sign, self.unspents.len(), fee, address_version)?;
 
         //use local private key sign data
         let mut output_pareper_data =
@@ -160,7 +272,7 @@ impl BtcTransaction {
 
         let btc_prepare_apdu_vec = BtcApdu::btc_prepare(0x41, 0x00, &output_pareper_data);
         for temp_str in btc_prepare_apdu_vec {
-            ApduCheck::checke_response(&send_apdu_timeout(temp_str, TIMEOUT_LONG)?)?;
+            ApduCheck::checke_response(&transport.send_apdu_timeout(temp_str, TIMEOUT_LONG)?)?;
         }
 
         let mut lock_script_ver: Vec<Script> = vec![];
@@ -186,7 +298,7 @@ impl BtcTransaction {
                 input_data_vec.extend_from_slice(serialize(&temp_serialize_txin).as_slice());
                 let btc_perpare_apdu = BtcApdu::btc_perpare_input(0x80, &input_data_vec);
                 //send perpare apdu to device
-                ApduCheck::checke_response(&send_apdu(btc_perpare_apdu)?)?;
+                ApduCheck::checke_response(&transport.send_apdu(btc_perpare_apdu)?)?;
             }
             for y in i * EACH_ROUND_NUMBER..(i + 1) * EACH_ROUND_NUMBER {
                 if y >= utxo_pub_key_vec.len() {
@@ -198,14 +310,14 @@ impl BtcTransaction {
                     format!("{}{}", path_str, self.unspents.get(y).unwrap().derive_path).as_str(),
                 );
                 //sign data
-                let btc_sign_apdu_return = send_apdu(btc_sign_apdu)?;
+                let btc_sign_apdu_return = transport.send_apdu(btc_sign_apdu)?;
                 ApduCheck::checke_response(&btc_sign_apdu_return)?;
                 let btc_sign_apdu_return =
                     &btc_sign_apdu_return[..btc_sign_apdu_return.len() - 4].to_string();
                 let sign_result_str =
                     btc_sign_apdu_return[2..btc_sign_apdu_return.len() - 2].to_string();
 
-                lock_script_ver.push(self.build_lock_script(
+                lock_script_ver.push(build_lock_script(
                     sign_result_str.as_str(),
                     utxo_pub_key_vec.get(y).unwrap(),
                 )?)
@@ -235,9 +347,9 @@ impl BtcTransaction {
 
     pub fn sign_segwit_transaction(
         &self,
+        transport: &dyn Transport,
         network: Network,
         path: &str,
-        change_idx: i32,
         extra_data: &Vec<u8>,
     ) -> Result<TxSignResult> {
         //path check
@@ -252,7 +364,7 @@ impl BtcTransaction {
         }
 
         //get xpub and sign data
-        let xpub_data = get_xpub_data(path_str.as_str(), true)?;
+        let xpub_data = get_xpub_data(transport, path_str.as_str(), true)?;
         let xpub_data = &xpub_data[..xpub_data.len() - 4].to_string();
 
         //parsing xpub data
@@ -262,7 +374,8 @@ impl BtcTransaction {
         let chain_code = &sign_source_val[130..];
 
         //use se public key verify sign
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
         let sign_verify_result = secp256k1_sign_verify(
             &key_manager_obj.se_pub_key.as_slice(),
             hex::decode(sign_result).unwrap().as_slice(),
@@ -280,32 +393,15 @@ impl BtcTransaction {
             TransTypeFlg::SEGWIT,
         )?;
 
-        //calc utxo total amount
-        if self.get_total_amount() < self.amount {
-            return Err(CoinError::ImkeyInsufficientFunds.into());
-        }
-
-        //add send to output
-        let mut txouts: Vec<TxOut> = Vec::new();
-        txouts.push(self.build_send_to_output());
-
-        //add change output
-        if self.get_change_amount() > DUST_THRESHOLD {
-            let path_temp = format!("{}{}{}", path_str, "1/", change_idx);
-            let address_str = BtcAddress::get_segwit_address(network, path_temp.as_str())?;
-            let address_obj = Address::from_str(address_str.as_str())?;
-            txouts.push(TxOut {
-                value: self.get_change_amount() as u64,
-                script_pubkey: address_obj.script_pubkey(),
-            });
-        }
-        //add the op_return
-        if !extra_data.is_empty() {
-            if extra_data.len() > MAX_OPRETURN_SIZE {
-                return Err(CoinError::ImkeySdkIllegalArgument.into());
-            }
-            txouts.push(self.build_op_return_output(extra_data));
-        }
+        //add send to, change and op_return outputs
+        let txouts = self.build_outputs(
+            transport,
+            network,
+            path_str.as_str(),
+            extra_data,
+            TransTypeFlg::SEGWIT,
+        )?;
+        let fee = self.get_total_amount() - get_outputs_amount(&txouts);
 
         //8.output data serialize
         let mut tx_to_sign = Transaction {
@@ -314,28 +410,9 @@ impl BtcTransaction {
             input: vec![],
             output: txouts,
         };
-        let mut output_serialize_data = serialize(&tx_to_sign);
-
-        output_serialize_data.remove(5);
-        output_serialize_data.remove(5);
-
-        //add sign type
-        output_serialize_data.extend(SigHashType::All.serialize().iter());
-
-        //set input number
-        output_serialize_data.remove(4);
-        output_serialize_data.insert(4, self.unspents.len() as u8);
-
-        //add fee amount
-        output_serialize_data.extend(bigint_to_byte_vec(self.fee));
-
-        //add address version
-        let address_version = get_address_version(network, self.to.to_string().as_str())?;
-        output_serialize_data.push(address_version);
-
-        //set 01 tag and length
-        output_serialize_data.insert(0, output_serialize_data.len() as u8);
-        output_serialize_data.insert(0, 0x01);
+        let address_version = self.get_address_version(network)?;
+        let output_serialize_data =
+            output_data(&tx_to_sign, self.unspents.len(), fee, address_version)?;
 
         //use local private key sign data
         let mut output_pareper_data =
@@ -347,7 +424,7 @@ impl BtcTransaction {
         let btc_prepare_apdu_vec = BtcApdu::btc_prepare(0x31, 0x00, &output_pareper_data);
         //send output pareper command
         for temp_str in btc_prepare_apdu_vec {
-            ApduCheck::checke_response(&send_apdu_timeout(temp_str, TIMEOUT_LONG)?)?;
+            ApduCheck::checke_response(&transport.send_apdu_timeout(temp_str, TIMEOUT_LONG)?)?;
         }
 
         let mut txinputs: Vec<TxIn> = vec![];
@@ -413,14 +490,14 @@ impl BtcTransaction {
         let mut sequence_prepare_apdu_vec = BtcApdu::btc_prepare(0x31, 0x80, &sequence_vec);
         txhash_vout_prepare_apdu_vec.append(&mut sequence_prepare_apdu_vec);
         for apdu in txhash_vout_prepare_apdu_vec {
-            ApduCheck::checke_response(&send_apdu(apdu)?)?;
+            ApduCheck::checke_response(&transport.send_apdu(apdu)?)?;
         }
 
         //send sign apdu
         let mut witnesses: Vec<(Vec<u8>, Vec<u8>)> = vec![];
         for (index, wegwit_sign_apdu) in sign_apdu_vec.iter().enumerate() {
             //send sign apdu
-            let sign_apdu_return_data = send_apdu(wegwit_sign_apdu.clone())?;
+            let sign_apdu_return_data = transport.send_apdu(wegwit_sign_apdu.clone())?;
             ApduCheck::checke_response(&sign_apdu_return_data)?;
             //build signature obj
             let sign_result_vec =
@@ -467,6 +544,268 @@ impl BtcTransaction {
         })
     }
 
+    /**
+     * spends any mix of p2pkh, p2sh-p2wpkh and p2wpkh utxos, the type of every utxo is taken
+     * from its address. change derived from a path goes to a native segwit address
+     */
+    pub fn sign_native_segwit_transaction(
+        &self,
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+        extra_data: &Vec<u8>,
+    ) -> Result<TxSignResult> {
+        //path check
+        check_path_validity(path)?;
+        let mut path_str = path.to_string();
+        if !path.ends_with('/') {
+            path_str = format!("{}{}", path_str, "/");
+        }
+        //check utxo number
+        if self.unspents.len() > MAX_UTXO_NUMBER {
+            return Err(CoinError::ImkeyExceededMaxUtxoNumber.into());
+        }
+
+        //get xpub signed by the se
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
+        let (pub_key, chain_code) =
+            get_verified_xpub(transport, path_str.as_str(), &key_manager_obj.se_pub_key)?;
+
+        //utxo address verify, every utxo against its own address type
+        let mut sign_inputs: Vec<SignInput> = vec![];
+        for (index, unspent) in self.unspents.iter().enumerate() {
+            let trans_type_flg = TransTypeFlg::from_address(&unspent.address)?;
+            let utxo_pub_key_vec = address_verify(
+                &vec![unspent.clone()],
+                &pub_key,
+                &chain_code,
+                network,
+                trans_type_flg,
+            )?;
+            sign_inputs.push(SignInput {
+                index,
+                trans_type_flg,
+                pub_key: PublicKey::from_str(&utxo_pub_key_vec[0])?,
+                utxo: unspent.clone(),
+            });
+        }
+
+        //add send to, change and op_return outputs
+        let txouts = self.build_outputs(
+            transport,
+            network,
+            path_str.as_str(),
+            extra_data,
+            TransTypeFlg::NATIVESEGWIT,
+        )?;
+        let fee = self.get_total_amount() - get_outputs_amount(&txouts);
+
+        let mut txinputs: Vec<TxIn> = vec![];
+        for unspent in &self.unspents {
+            txinputs.push(TxIn {
+                previous_output: OutPoint {
+                    txid: Hash256::from_hex(&unspent.txhash)?,
+                    vout: unspent.vout as u32,
+                },
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF as u32,
+                witness: vec![],
+            });
+        }
+        let mut tx_to_sign = Transaction {
+            version: 2u32,
+            lock_time: 0u32,
+            input: txinputs,
+            output: txouts,
+        };
+
+        let signatures = sign_tx_inputs(
+            transport,
+            &tx_to_sign,
+            &sign_inputs,
+            path_str.as_str(),
+            fee,
+            self.get_address_version(network)?,
+            &key_manager_obj.pri_key,
+        )?;
+
+        for (sign_input, signature) in sign_inputs.iter().zip(signatures) {
+            let pub_key_bytes = sign_input.pub_key.to_bytes();
+            let txin = &mut tx_to_sign.input[sign_input.index];
+            match sign_input.trans_type_flg {
+                TransTypeFlg::BTC => {
+                    txin.script_sig = Builder::new()
+                        .push_slice(&signature)
+                        .push_slice(&pub_key_bytes)
+                        .into_script();
+                }
+                TransTypeFlg::SEGWIT => {
+                    let pub_key_hash = hash160::Hash::hash(&pub_key_bytes).into_inner();
+                    let redeem_script = Builder::new()
+                        .push_int(0)
+                        .push_slice(&pub_key_hash)
+                        .into_script();
+                    txin.script_sig = Builder::new()
+                        .push_slice(redeem_script.as_bytes())
+                        .into_script();
+                    txin.witness = vec![signature, pub_key_bytes];
+                }
+                TransTypeFlg::NATIVESEGWIT => {
+                    txin.witness = vec![signature, pub_key_bytes];
+                }
+                TransTypeFlg::TAPROOT => return Err(CoinError::AddressTypeMismatch.into()),
+            }
+        }
+
+        let tx_bytes = serialize(&tx_to_sign);
+        Ok(TxSignResult {
+            signature: tx_bytes.to_hex(),
+            tx_hash: tx_to_sign.txid().to_hex(),
+            wtx_id: tx_to_sign.bitcoin_hash().to_hex(),
+        })
+    }
+
+    /**
+     * bip86 key path spend of taproot utxos, every input is signed with a bip340 signature over
+     * its bip341 digest (SIGHASH_DEFAULT). change derived from a path goes to a taproot address
+     */
+    pub fn sign_taproot_transaction(
+        &self,
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+        extra_data: &Vec<u8>,
+    ) -> Result<TxSignResult> {
+        //path check
+        check_path_validity(path)?;
+        let mut path_str = path.to_string();
+        if !path.ends_with('/') {
+            path_str = format!("{}{}", path_str, "/");
+        }
+        //check utxo number
+        if self.unspents.len() > MAX_UTXO_NUMBER {
+            return Err(CoinError::ImkeyExceededMaxUtxoNumber.into());
+        }
+
+        //get xpub signed by the se
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
+        let (pub_key, chain_code) =
+            get_verified_xpub(transport, path_str.as_str(), &key_manager_obj.se_pub_key)?;
+        //utxo address verify
+        address_verify(
+            &self.unspents,
+            &pub_key,
+            &chain_code,
+            network,
+            TransTypeFlg::TAPROOT,
+        )?;
+
+        //add send to, change and op_return outputs
+        let txouts = self.build_outputs(
+            transport,
+            network,
+            path_str.as_str(),
+            extra_data,
+            TransTypeFlg::TAPROOT,
+        )?;
+        let fee = self.get_total_amount() - get_outputs_amount(&txouts);
+
+        let mut txinputs: Vec<TxIn> = vec![];
+        let mut spent_outputs: Vec<TxOut> = vec![];
+        for unspent in &self.unspents {
+            txinputs.push(TxIn {
+                previous_output: OutPoint {
+                    txid: Hash256::from_hex(&unspent.txhash)?,
+                    vout: unspent.vout as u32,
+                },
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF as u32,
+                witness: vec![],
+            });
+            spent_outputs.push(TxOut {
+                value: unspent.amount as u64,
+                script_pubkey: unspent.address.script_pubkey(),
+            });
+        }
+        let mut tx_to_sign = Transaction {
+            version: 2u32,
+            lock_time: 0u32,
+            input: txinputs,
+            output: txouts,
+        };
+
+        prepare_outputs(
+            transport,
+            &tx_to_sign,
+            fee,
+            self.get_address_version(network)?,
+            0x51,
+            &key_manager_obj.pri_key,
+        )?;
+
+        //the device hashes what every input spends besides the outpoints and sequences
+        let mut txhash_vout_vec = vec![];
+        let mut amount_vec = vec![];
+        let mut script_pubkey_vec = vec![];
+        let mut sequence_vec = vec![];
+        for (txin, spent_output) in tx_to_sign.input.iter().zip(spent_outputs.iter()) {
+            txhash_vout_vec.extend(serialize(&txin.previous_output));
+            amount_vec.extend(serialize(&spent_output.value));
+            script_pubkey_vec.extend(serialize(&spent_output.script_pubkey));
+            sequence_vec.extend(serialize(&txin.sequence));
+        }
+        let mut prepare_apdu_vec = BtcApdu::btc_prepare(0x51, 0x40, &txhash_vout_vec);
+        prepare_apdu_vec.append(&mut BtcApdu::btc_prepare(0x51, 0x20, &amount_vec));
+        prepare_apdu_vec.append(&mut BtcApdu::btc_prepare(0x51, 0x10, &script_pubkey_vec));
+        prepare_apdu_vec.append(&mut BtcApdu::btc_prepare(0x51, 0x80, &sequence_vec));
+        for apdu in prepare_apdu_vec {
+            ApduCheck::checke_response(&transport.send_apdu(apdu)?)?;
+        }
+
+        let mut witnesses = vec![];
+        for (index, unspent) in self.unspents.iter().enumerate() {
+            //input index | path
+            let mut data: Vec<u8> = serialize(&(index as u32));
+            let sign_path = format!("{}{}", path_str, unspent.derive_path);
+            data.push(sign_path.len() as u8);
+            data.extend_from_slice(sign_path.as_bytes());
+
+            let last_one = index == self.unspents.len() - 1;
+            let sign_apdu = BtcApdu::btc_taproot_sign(last_one, SIGHASH_DEFAULT, data);
+            let sign_apdu_return_data = transport.send_apdu(sign_apdu)?;
+            ApduCheck::checke_response(&sign_apdu_return_data)?;
+            let signature =
+                Vec::from_hex(&sign_apdu_return_data[..sign_apdu_return_data.len() - 4])?;
+
+            //check the signature against the output key the utxo pays to
+            let output_key = match &unspent.address.payload {
+                Payload::WitnessProgram { program, .. } if program.len() == 32 => {
+                    let mut output_key = [0u8; 32];
+                    output_key.copy_from_slice(program);
+                    output_key
+                }
+                _ => return Err(CoinError::AddressTypeMismatch.into()),
+            };
+            let sighash = taproot_sighash(&tx_to_sign, index, &spent_outputs, SIGHASH_DEFAULT)?;
+            if !schnorr_verify(&output_key, &sighash, &signature)? {
+                return Err(CoinError::ImkeySignatureVerifyFail.into());
+            }
+            witnesses.push(vec![signature]);
+        }
+        for (txin, witness) in tx_to_sign.input.iter_mut().zip(witnesses) {
+            txin.witness = witness;
+        }
+
+        let tx_bytes = serialize(&tx_to_sign);
+        Ok(TxSignResult {
+            signature: tx_bytes.to_hex(),
+            tx_hash: tx_to_sign.txid().to_hex(),
+            wtx_id: tx_to_sign.bitcoin_hash().to_hex(),
+        })
+    }
+
     pub fn get_total_amount(&self) -> i64 {
         let mut total_amount: i64 = 0;
         for unspent in &self.unspents {
@@ -475,17 +814,141 @@ impl BtcTransaction {
         total_amount
     }
 
+    pub fn get_output_amount(&self) -> i64 {
+        let mut output_amount: i64 = 0;
+        for output in &self.outputs {
+            output_amount += output.amount;
+        }
+        output_amount
+    }
+
     pub fn get_change_amount(&self) -> i64 {
-        let total_amount = self.get_total_amount();
-        let change_amout = total_amount - self.amount - self.fee;
-        change_amout
+        self.get_total_amount() - self.get_output_amount() - self.fee
     }
 
-    pub fn build_send_to_output(&self) -> TxOut {
-        TxOut {
-            value: self.amount as u64,
-            script_pubkey: self.to.script_pubkey(),
+    fn build_outputs(
+        &self,
+        transport: &dyn Transport,
+        network: Network,
+        path: &str,
+        extra_data: &Vec<u8>,
+        trans_type_flg: TransTypeFlg,
+    ) -> Result<Vec<TxOut>> {
+        if self.outputs.is_empty() {
+            return Err(CoinError::ImkeySdkIllegalArgument.into());
+        }
+
+        //add send to outputs
+        let mut txouts: Vec<TxOut> = vec![];
+        for output in &self.outputs {
+            if output.amount < self.dust_policy.min_output {
+                return Err(CoinError::ImkeyAmountLessThanMinimum.into());
+            }
+            txouts.push(TxOut {
+                value: output.amount as u64,
+                script_pubkey: output.address.script_pubkey(),
+            });
+        }
+
+        //calc utxo total amount
+        if self.fee < 0 || self.get_change_amount() < 0 {
+            return Err(CoinError::ImkeyInsufficientFunds.into());
+        }
+
+        //add change output
+        if self.get_change_amount() > self.dust_policy.change_threshold {
+            let script_pubkey = match &self.change {
+                ChangeOutput::Address(address) => {
+                    if address.network != network {
+                        return Err(CoinError::AddressTypeMismatch.into());
+                    }
+                    address.script_pubkey()
+                }
+                ChangeOutput::Path(change_path) => {
+                    let path_temp = format!("{}{}", path, change_path);
+                    check_path_validity(path_temp.as_str())?;
+                    let address_str = match trans_type_flg {
+                        TransTypeFlg::BTC => {
+                            BtcAddress::get_address(transport, network, path_temp.as_str())?
+                        }
+                        TransTypeFlg::SEGWIT => {
+                            BtcAddress::get_segwit_address(transport, network, path_temp.as_str())?
+                        }
+                        TransTypeFlg::NATIVESEGWIT => BtcAddress::get_native_segwit_address(
+                            transport,
+                            network,
+                            path_temp.as_str(),
+                        )?,
+                        TransTypeFlg::TAPROOT => {
+                            BtcAddress::get_taproot_address(transport, network, path_temp.as_str())?
+                        }
+                    };
+                    parse_address(address_str.as_str())?.script_pubkey()
+                }
+            };
+            txouts.push(TxOut {
+                value: self.get_change_amount() as u64,
+                script_pubkey,
+            });
+        }
+
+        //add the op_return
+        if !extra_data.is_empty() {
+            if extra_data.len() > MAX_OPRETURN_SIZE {
+                return Err(CoinError::ImkeySdkIllegalArgument.into());
+            }
+            txouts.push(self.build_op_return_output(extra_data));
+        }
+        Ok(txouts)
+    }
+
+    fn get_address_version(&self, network: Network) -> Result<u8> {
+        let addresses: Vec<Address> = self
+            .outputs
+            .iter()
+            .map(|output| output.address.clone())
+            .collect();
+        let address_version = get_outputs_address_version(network, &addresses)?;
+        self.display_info(address_version)?;
+        Ok(address_version)
+    }
+
+    /**
+    what the device shows for the outputs, base58 recipients are rendered with the single
+    address version it gets. a recipient that renders differently is refused, so is a mix of
+    p2pkh and p2sh outputs. the fee includes a change too small to get an output
+    */
+    pub fn display_info(&self, address_version: u8) -> Result<DisplayInfo> {
+        let mut builder = DisplayInfoBuilder::new();
+        for output in &self.outputs {
+            let hash = match &output.address.payload {
+                Payload::PubkeyHash(hash) => Some(hash.to_vec()),
+                Payload::ScriptHash(hash) => Some(hash.to_vec()),
+                Payload::WitnessProgram { .. } => None,
+            };
+            let shown = match hash {
+                Some(hash) => {
+                    let mut data = vec![address_version];
+                    data.extend(hash);
+                    base58::check_encode_slice(&data)
+                }
+                None => output.address.to_string(),
+            };
+            if shown != output.address.to_string() {
+                return Err(CoinError::AddressTypeMismatch.into());
+            }
+            builder = builder
+                .payment(Amount::new(output.amount as u128, 8, "BTC"))
+                .receiver(&shown);
         }
+
+        let change_amount = self.get_change_amount();
+        let fee = if change_amount > self.dust_policy.change_threshold {
+            self.fee
+        } else {
+            self.fee + change_amount
+        };
+        Ok(builder.fee(Amount::new(fee as u128, 8, "BTC")).build())
     }
 
     pub fn build_op_return_output(&self, extra_data: &Vec<u8>) -> TxOut {
@@ -498,39 +961,66 @@ impl BtcTransaction {
             script_pubkey: opreturn_script,
         }
     }
+}
 
-    pub fn build_lock_script(&self, signed: &str, utxo_public_key: &str) -> Result<Script> {
-        let signed_vec = Vec::from_hex(&signed)?;
-        let mut signnture_obj = Signature::from_compact(signed_vec.as_slice())?;
-        signnture_obj.normalize_s();
-        let mut signed_vec = signnture_obj.serialize_der().to_vec();
-
-        //add hash type
-        signed_vec.push(SigHashType::All.as_u32() as u8);
-        Ok(Builder::new()
-            .push_slice(&signed_vec)
-            .push_slice(Vec::from_hex(utxo_public_key)?.as_slice())
-            .into_script())
-    }
+fn get_outputs_amount(txouts: &[TxOut]) -> i64 {
+    txouts.iter().map(|txout| txout.value as i64).sum()
+}
+
+pub fn build_lock_script(signed: &str, utxo_public_key: &str) -> Result<Script> {
+    let signed_vec = Vec::from_hex(&signed)?;
+    let mut signnture_obj = Signature::from_compact(signed_vec.as_slice())?;
+    signnture_obj.normalize_s();
+    let mut signed_vec = signnture_obj.serialize_der().to_vec();
+
+    //add hash type
+    signed_vec.push(SigHashType::All.as_u32() as u8);
+    Ok(Builder::new()
+        .push_slice(&signed_vec)
+        .push_slice(Vec::from_hex(utxo_public_key)?.as_slice())
+        .into_script())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::transaction::{BtcTransaction, Utxo};
-    use bitcoin::{Address, Network};
+    use crate::address::BtcAddress;
+    use crate::taproot::{parse_address, schnorr_verify, taproot_sighash, SIGHASH_DEFAULT};
+    use crate::transaction::{
+        BtcMultiTransaction, BtcOutput, BtcTransaction, ChangeOutput, DustPolicy, Utxo,
+    };
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin::consensus::deserialize;
+    use bitcoin::secp256k1::{Message, Secp256k1, Signature};
+    use bitcoin::util::bip143::SighashComponents;
+    use bitcoin::{Address, Network, PublicKey, Script, Transaction, TxOut};
+    use bitcoin_hashes::hash160;
+    use bitcoin_hashes::Hash;
     use hex::FromHex;
     use std::str::FromStr;
 
     use common::error::CoinError;
     use device::device_binding::bind_test;
     use device::device_binding::DeviceManage;
-    use transport::hid_api::hid_connect;
+    use transport::Transport;
 
     #[test]
     fn test_sign_transaction() {
-        //binding device
-        bind_test();
+        let transport = bind_test();
+        check_sign_transaction(&transport);
+    }
+
+    #[test]
+    fn test_sign_segwit_transaction() {
+        let transport = bind_test();
+        check_sign_segwit_transaction(&transport);
+    }
+
+    #[test]
+    fn test_sign_transaction_simulator() {
+        check_sign_transaction(&simulator::bind_test());
+    }
 
+    fn check_sign_transaction(transport: &dyn Transport) {
         let extra_data = Vec::from_hex("0200000080a10bc28928f4c17a287318125115c3f098ed20a8237d1e8e4125bc25d1be99752adad0a7b9ceca853768aebb6965eca126a62965f698a0c1bc43d83db632ad7f717276057e6012afa99385").unwrap();
         let utxo = Utxo {
             txhash: "983adf9d813a2b8057454cc6f36c6081948af849966f9b9a33e5b653b02f227a".to_string(),
@@ -582,6 +1072,7 @@ mod tests {
             //            extra_data: extra_data,
         };
         let sign_result = transaction_req_data.sign_transaction(
+            transport,
             Network::Testnet,
             &"m/44'/1'/0'".to_string(),
             53,
@@ -598,10 +1089,11 @@ mod tests {
     }
 
     #[test]
-    fn test_sign_segwit_transaction() {
-        //binding device
-        bind_test();
+    fn test_sign_segwit_transaction_simulator() {
+        check_sign_segwit_transaction(&simulator::bind_test());
+    }
 
+    fn check_sign_segwit_transaction(transport: &dyn Transport) {
         let extra_data = Vec::from_hex("1234").unwrap();
         let utxo = Utxo {
             txhash: "c2ceb5088cf39b677705526065667a3992c68cc18593a9af12607e057672717f".to_string(),
@@ -631,6 +1123,7 @@ mod tests {
             fee: 10000,
         };
         let sign_result = transaction_req_data.sign_segwit_transaction(
+            transport,
             Network::Testnet,
             &"m/49'/1'/0'/".to_string(),
             0,
@@ -649,7 +1142,7 @@ mod tests {
     #[test]
     fn sign_transaction_simple_test() {
         //binding device
-        bind_test();
+        let transport = bind_test();
 
         let extra_data = vec![];
         let utxo = Utxo {
@@ -671,6 +1164,7 @@ mod tests {
             fee: 502130,
         };
         let sign_result = transaction_req_data.sign_transaction(
+            &transport,
             Network::Bitcoin,
             &"m/44'/0'/0'/".to_string(),
             53,
@@ -689,7 +1183,7 @@ mod tests {
     #[test]
     fn insufficient_funds_test() {
         //binding device
-        bind_test();
+        let transport = bind_test();
 
         let extra_data = vec![];
         let utxo = Utxo {
@@ -711,6 +1205,7 @@ mod tests {
             fee: 502130,
         };
         let sign_result = transaction_req_data.sign_transaction(
+            &transport,
             Network::Bitcoin,
             &"m/44'/0'/0'/".to_string(),
             53,
@@ -742,6 +1237,7 @@ mod tests {
             fee: 502130,
         };
         let sign_result = transaction_req_data.sign_segwit_transaction(
+            &transport,
             Network::Bitcoin,
             &"m/49'/0'/0'".to_string(),
             53,
@@ -757,7 +1253,7 @@ mod tests {
     #[test]
     fn btc_extra_data_error() {
         //binding device
-        bind_test();
+        let transport = bind_test();
 
         let extra_data = Vec::from_hex("0200000080a10bc28928f4c17a287318125115c3f098ed20a8237d1e8e4125bc25d1be99752adad0a7b9ceca853768aebb6965eca126a62965f698a0c1bc43d83db632ad7f717276057e6012afa9938500").unwrap();
         //        let extra_data = vec![];
@@ -780,6 +1276,7 @@ mod tests {
             fee: 502130,
         };
         let sign_result = transaction_req_data.sign_transaction(
+            &transport,
             Network::Bitcoin,
             &"m/44'/0'/0'/".to_string(),
             53,
@@ -810,6 +1307,7 @@ mod tests {
             fee: 10000,
         };
         let sign_result = transaction_req_data.sign_segwit_transaction(
+            &transport,
             Network::Testnet,
             &"m/49'/1'/0'/".to_string(),
             0,
@@ -826,7 +1324,7 @@ mod tests {
     #[test]
     fn sign_segwit_transaction_simple_test() {
         //binding device
-        bind_test();
+        let transport = bind_test();
 
         let extra_data = vec![];
         let utxo = Utxo {
@@ -847,6 +1345,7 @@ mod tests {
             fee: 502130,
         };
         let sign_result = transaction_req_data.sign_segwit_transaction(
+            &transport,
             Network::Bitcoin,
             &"m/49'/0'/0'".to_string(),
             53,
@@ -864,7 +1363,7 @@ mod tests {
 
     #[test]
     fn address_error_test() {
-        bind_test();
+        let transport = bind_test();
 
         let extra_data = vec![];
         let utxo = Utxo {
@@ -885,6 +1384,7 @@ mod tests {
             fee: 502130,
         };
         let sign_result = transaction_req_data.sign_segwit_transaction(
+            &transport,
             Network::Bitcoin,
             &"m/49'/0'/0'".to_string(),
             53,
@@ -896,4 +1396,424 @@ mod tests {
             "imkey_address_mismatch_with_path"
         );
     }
+
+    fn simulator_utxos() -> Vec<Utxo> {
+        vec![
+            Utxo {
+                txhash: "983adf9d813a2b8057454cc6f36c6081948af849966f9b9a33e5b653b02f227a"
+                    .to_string(),
+                vout: 0,
+                amount: 200000000,
+                address: Address::from_str("mh7jj2ELSQUvRQELbn9qyA4q5nADhmJmUC").unwrap(),
+                script_pubkey: "76a914118c3123196e030a8a607c22bafc1577af61497d88ac".to_string(),
+                derive_path: "0/22".to_string(),
+                sequence: 4294967295,
+            },
+            Utxo {
+                txhash: "45ef8ac7f78b3d7d5ce71ae7934aea02f4ece1af458773f12af8ca4d79a9b531"
+                    .to_string(),
+                vout: 1,
+                amount: 200000000,
+                address: Address::from_str("mkeNU5nVnozJiaACDELLCsVUc8Wxoh1rQN").unwrap(),
+                script_pubkey: "76a914383fb81cb0a3fc724b5e08cf8bbd404336d711f688ac".to_string(),
+                derive_path: "0/0".to_string(),
+                sequence: 4294967295,
+            },
+        ]
+    }
+
+    #[test]
+    fn sign_multi_transaction_simulator_test() {
+        let transport = simulator::bind_test();
+
+        let to1 = Address::from_str("moLK3tBG86ifpDDTqAQzs4a9cUoNjVLRE3").unwrap();
+        let to2 = Address::from_str("mkeNU5nVnozJiaACDELLCsVUc8Wxoh1rQN").unwrap();
+        let transaction_req_data = BtcMultiTransaction {
+            outputs: vec![
+                BtcOutput {
+                    address: to1.clone(),
+                    amount: 100000000,
+                },
+                BtcOutput {
+                    address: to2.clone(),
+                    amount: 50000000,
+                },
+            ],
+            unspents: simulator_utxos(),
+            fee: 10000,
+            change: ChangeOutput::Path("1/7".to_string()),
+            dust_policy: DustPolicy::default(),
+        };
+        let sign_result = transaction_req_data
+            .sign_transaction(&transport, Network::Testnet, "m/44'/1'/0'", &vec![])
+            .unwrap();
+        let tx: Transaction = deserialize(&hex::decode(&sign_result.signature).unwrap()).unwrap();
+        assert_eq!(tx.txid().to_string(), sign_result.tx_hash);
+        assert_eq!(2, tx.input.len());
+        assert_eq!(3, tx.output.len());
+        assert_eq!(to1.script_pubkey(), tx.output[0].script_pubkey);
+        assert_eq!(100000000, tx.output[0].value);
+        assert_eq!(to2.script_pubkey(), tx.output[1].script_pubkey);
+        assert_eq!(50000000, tx.output[1].value);
+        let change_address =
+            BtcAddress::get_address(&transport, Network::Testnet, "m/44'/1'/0'/1/7").unwrap();
+        assert_eq!(
+            Address::from_str(&change_address).unwrap().script_pubkey(),
+            tx.output[2].script_pubkey
+        );
+        assert_eq!(400000000 - 150000000 - 10000, tx.output[2].value);
+    }
+
+    #[test]
+    fn sign_multi_segwit_transaction_change_address_simulator_test() {
+        let transport = simulator::bind_test();
+
+        let utxos = vec![Utxo {
+            txhash: "c2ceb5088cf39b677705526065667a3992c68cc18593a9af12607e057672717f".to_string(),
+            vout: 0,
+            amount: 50000,
+            address: Address::from_str("2MwN441dq8qudMvtM5eLVwC3u4zfKuGSQAB").unwrap(),
+            script_pubkey: "a9142d2b1ef5ee4cf6c3ebc8cf66a602783798f7875987".to_string(),
+            derive_path: "0/0".to_string(),
+            sequence: 0,
+        }];
+        let change = Address::from_str("mkeNU5nVnozJiaACDELLCsVUc8Wxoh1rQN").unwrap();
+        let mut transaction_req_data = BtcMultiTransaction {
+            outputs: vec![
+                BtcOutput {
+                    address: Address::from_str("2N9wBy6f1KTUF5h2UUeqRdKnBT6oSMh4Whp").unwrap(),
+                    amount: 20000,
+                },
+                BtcOutput {
+                    address: Address::from_str("2MwN441dq8qudMvtM5eLVwC3u4zfKuGSQAB").unwrap(),
+                    amount: 10000,
+                },
+            ],
+            unspents: utxos,
+            fee: 5000,
+            change: ChangeOutput::Address(change.clone()),
+            dust_policy: DustPolicy::default(),
+        };
+        let sign_result = transaction_req_data
+            .sign_segwit_transaction(&transport, Network::Testnet, "m/49'/1'/0'", &vec![])
+            .unwrap();
+        let tx: Transaction = deserialize(&hex::decode(&sign_result.signature).unwrap()).unwrap();
+        assert_eq!(3, tx.output.len());
+        assert_eq!(change.script_pubkey(), tx.output[2].script_pubkey);
+        assert_eq!(15000, tx.output[2].value);
+
+        //change at or below the threshold goes to the fee
+        transaction_req_data.dust_policy.change_threshold = 15000;
+        let sign_result = transaction_req_data
+            .sign_segwit_transaction(&transport, Network::Testnet, "m/49'/1'/0'", &vec![])
+            .unwrap();
+        let tx: Transaction = deserialize(&hex::decode(&sign_result.signature).unwrap()).unwrap();
+        assert_eq!(2, tx.output.len());
+        assert_eq!(2, tx.input[0].witness.len());
+    }
+
+    #[test]
+    fn multi_transaction_error_test() {
+        let transport = simulator::bind_test();
+
+        let mut transaction_req_data = BtcMultiTransaction {
+            outputs: vec![BtcOutput {
+                address: Address::from_str("moLK3tBG86ifpDDTqAQzs4a9cUoNjVLRE3").unwrap(),
+                amount: 545,
+            }],
+            unspents: simulator_utxos(),
+            fee: 10000,
+            change: ChangeOutput::index(0),
+            dust_policy: DustPolicy::default(),
+        };
+        let sign_result = transaction_req_data.sign_transaction(
+            &transport,
+            Network::Testnet,
+            "m/44'/1'/0'",
+            &vec![],
+        );
+        assert_eq!(
+            format!("{}", sign_result.err().unwrap()),
+            "imkey_amount_less_than_minimum"
+        );
+
+        transaction_req_data.outputs[0].amount = 399995000;
+        let sign_result = transaction_req_data.sign_transaction(
+            &transport,
+            Network::Testnet,
+            "m/44'/1'/0'",
+            &vec![],
+        );
+        assert_eq!(
+            format!("{}", sign_result.err().unwrap()),
+            "imkey_insufficient_funds"
+        );
+
+        transaction_req_data.outputs[0].amount = 100000000;
+        transaction_req_data.change =
+            ChangeOutput::Address(Address::from_str("1Fj93kpLwM1KgTN6C75Z5Bokhays4MmJae").unwrap());
+        let sign_result = transaction_req_data.sign_transaction(
+            &transport,
+            Network::Testnet,
+            "m/44'/1'/0'",
+            &vec![],
+        );
+        assert_eq!(
+            format!("{}", sign_result.err().unwrap()),
+            "address_type_mismatch"
+        );
+
+        //the device shows every base58 output with one address version
+        transaction_req_data.change = ChangeOutput::index(0);
+        transaction_req_data.outputs.push(BtcOutput {
+            address: Address::from_str("2N9wBy6f1KTUF5h2UUeqRdKnBT6oSMh4Whp").unwrap(),
+            amount: 50000000,
+        });
+        let sign_result = transaction_req_data.sign_transaction(
+            &transport,
+            Network::Testnet,
+            "m/44'/1'/0'",
+            &vec![],
+        );
+        assert_eq!(
+            format!("{}", sign_result.err().unwrap()),
+            "address_type_mismatch"
+        );
+
+        transaction_req_data.outputs = vec![];
+        let sign_result = transaction_req_data.sign_transaction(
+            &transport,
+            Network::Testnet,
+            "m/44'/1'/0'",
+            &vec![],
+        );
+        assert_eq!(
+            format!("{}", sign_result.err().unwrap()),
+            "imkey_sdk_illegal_argument"
+        );
+    }
+
+    #[test]
+    fn display_info_test() {
+        let transaction_req_data = BtcMultiTransaction {
+            outputs: vec![
+                BtcOutput {
+                    address: Address::from_str("moLK3tBG86ifpDDTqAQzs4a9cUoNjVLRE3").unwrap(),
+                    amount: 100000000,
+                },
+                BtcOutput {
+                    address: Address::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx")
+                        .unwrap(),
+                    amount: 50000,
+                },
+            ],
+            unspents: simulator_utxos(),
+            fee: 10000,
+            change: ChangeOutput::index(0),
+            dust_policy: DustPolicy::default(),
+        };
+        let display = transaction_req_data.display_info(0x6f).unwrap();
+        assert_eq!(display.payment, "1 BTC, 0.0005 BTC");
+        assert_eq!(
+            display.receiver,
+            "moLK3tBG86ifpDDTqAQzs4a9cUoNjVLRE3, tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"
+        );
+        assert_eq!(display.fee, "0.0001 BTC");
+        assert_eq!(
+            format!("{}", transaction_req_data.display_info(0xc4).err().unwrap()),
+            "address_type_mismatch"
+        );
+    }
+
+    fn verify(sighash: &[u8], signature: &[u8], pub_key: &[u8]) -> bool {
+        //the last byte is the sighash type
+        let (signature, sighash_type) = signature.split_at(signature.len() - 1);
+        sighash_type == [0x01]
+            && Secp256k1::verification_only()
+                .verify(
+                    &Message::from_slice(sighash).unwrap(),
+                    &Signature::from_der(signature).unwrap(),
+                    &PublicKey::from_slice(pub_key).unwrap().key,
+                )
+                .is_ok()
+    }
+
+    #[test]
+    fn sign_native_segwit_mixed_transaction_simulator_test() {
+        let transport = simulator::bind_test();
+
+        //a legacy, a nested segwit and a native segwit utxo of the same account
+        let path = "m/44'/1'/0'";
+        let p2pkh =
+            BtcAddress::get_address(&transport, Network::Testnet, "m/44'/1'/0'/0/22").unwrap();
+        let p2sh = BtcAddress::get_segwit_address(&transport, Network::Testnet, "m/44'/1'/0'/0/0")
+            .unwrap();
+        let p2wpkh =
+            BtcAddress::get_native_segwit_address(&transport, Network::Testnet, "m/44'/1'/0'/0/1")
+                .unwrap();
+        let mut utxos = vec![];
+        for (index, (address, derive_path)) in [(p2pkh, "0/22"), (p2sh, "0/0"), (p2wpkh, "0/1")]
+            .iter()
+            .enumerate()
+        {
+            let address = Address::from_str(address).unwrap();
+            utxos.push(Utxo {
+                txhash: "983adf9d813a2b8057454cc6f36c6081948af849966f9b9a33e5b653b02f227a"
+                    .to_string(),
+                vout: index as i32,
+                amount: 100000,
+                script_pubkey: hex::encode(address.script_pubkey().as_bytes()),
+                address,
+                derive_path: derive_path.to_string(),
+                sequence: 4294967295,
+            });
+        }
+
+        let to = Address::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx").unwrap();
+        let transaction_req_data = BtcMultiTransaction {
+            outputs: vec![BtcOutput {
+                address: to.clone(),
+                amount: 200000,
+            }],
+            unspents: utxos.clone(),
+            fee: 5000,
+            change: ChangeOutput::index(3),
+            dust_policy: DustPolicy::default(),
+        };
+        let sign_result = transaction_req_data
+            .sign_native_segwit_transaction(&transport, Network::Testnet, path, &vec![])
+            .unwrap();
+        let tx: Transaction = deserialize(&hex::decode(&sign_result.signature).unwrap()).unwrap();
+        assert_eq!(tx.txid().to_string(), sign_result.tx_hash);
+
+        //change goes to the native segwit address of the change path
+        let change_address =
+            BtcAddress::get_native_segwit_address(&transport, Network::Testnet, "m/44'/1'/0'/1/3")
+                .unwrap();
+        assert_eq!(to.script_pubkey(), tx.output[0].script_pubkey);
+        assert_eq!(
+            Address::from_str(&change_address).unwrap().script_pubkey(),
+            tx.output[1].script_pubkey
+        );
+        assert_eq!(95000, tx.output[1].value);
+
+        //legacy input
+        let script_sig = tx.input[0].script_sig.as_bytes();
+        let signature = &script_sig[1..1 + script_sig[0] as usize];
+        let pub_key = &script_sig[2 + script_sig[0] as usize..];
+        let sighash = tx.signature_hash(0, &utxos[0].address.script_pubkey(), 0x01);
+        assert!(verify(&sighash[..], signature, pub_key));
+        assert!(tx.input[0].witness.is_empty());
+
+        //nested and native segwit inputs sign the same bip143 digest
+        let sighash_components = SighashComponents::new(&tx);
+        for index in 1..3 {
+            let witness = &tx.input[index].witness;
+            let pub_key_hash = hash160::Hash::hash(&witness[1]).into_inner();
+            let script_code = Builder::new()
+                .push_opcode(bitcoin::blockdata::opcodes::all::OP_DUP)
+                .push_opcode(bitcoin::blockdata::opcodes::all::OP_HASH160)
+                .push_slice(&pub_key_hash)
+                .push_opcode(bitcoin::blockdata::opcodes::all::OP_EQUALVERIFY)
+                .push_opcode(bitcoin::blockdata::opcodes::all::OP_CHECKSIG)
+                .into_script();
+            let sighash = sighash_components.sighash_all(&tx.input[index], &script_code, 100000);
+            assert!(verify(&sighash[..], &witness[0], &witness[1]));
+        }
+        //the nested input reveals its p2wpkh redeem script
+        let redeem_script = &tx.input[1].script_sig.as_bytes()[1..];
+        assert_eq!(
+            Script::from(redeem_script.to_vec()).to_p2sh(),
+            utxos[1].address.script_pubkey()
+        );
+        assert!(tx.input[2].script_sig.is_empty());
+    }
+
+    #[test]
+    fn sign_taproot_transaction_simulator_test() {
+        let transport = simulator::bind_test();
+
+        let path = "m/86'/1'/0'";
+        let mut utxos = vec![];
+        for (index, derive_path) in ["0/0", "0/1"].iter().enumerate() {
+            let address = BtcAddress::get_taproot_address(
+                &transport,
+                Network::Testnet,
+                format!("{}/{}", path, derive_path).as_str(),
+            )
+            .unwrap();
+            let address = parse_address(&address).unwrap();
+            utxos.push(Utxo {
+                txhash: "983adf9d813a2b8057454cc6f36c6081948af849966f9b9a33e5b653b02f227a"
+                    .to_string(),
+                vout: index as i32,
+                amount: 100000,
+                script_pubkey: hex::encode(address.script_pubkey().as_bytes()),
+                address,
+                derive_path: derive_path.to_string(),
+                sequence: 4294967295,
+            });
+        }
+
+        let to = Address::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx").unwrap();
+        let transaction_req_data = BtcTransaction {
+            to: to.clone(),
+            amount: 150000,
+            unspents: utxos.clone(),
+            fee: 5000,
+        };
+        let sign_result = transaction_req_data
+            .sign_taproot_transaction(&transport, Network::Testnet, path, 2, &vec![])
+            .unwrap();
+        let tx: Transaction = deserialize(&hex::decode(&sign_result.signature).unwrap()).unwrap();
+        assert_eq!(tx.txid().to_string(), sign_result.tx_hash);
+
+        //change goes to the taproot address of the change path
+        let change_address =
+            BtcAddress::get_taproot_address(&transport, Network::Testnet, "m/86'/1'/0'/1/2")
+                .unwrap();
+        assert!(change_address.starts_with("tb1p"));
+        assert_eq!(to.script_pubkey(), tx.output[0].script_pubkey);
+        assert_eq!(
+            parse_address(&change_address).unwrap().script_pubkey(),
+            tx.output[1].script_pubkey
+        );
+        assert_eq!(45000, tx.output[1].value);
+
+        //every input carries a single bip340 signature over its bip341 digest
+        let spent_outputs: Vec<TxOut> = utxos
+            .iter()
+            .map(|utxo| TxOut {
+                value: utxo.amount as u64,
+                script_pubkey: utxo.address.script_pubkey(),
+            })
+            .collect();
+        for (index, txin) in tx.input.iter().enumerate() {
+            assert_eq!(1, txin.witness.len());
+            assert_eq!(64, txin.witness[0].len());
+            let mut output_key = [0u8; 32];
+            output_key.copy_from_slice(&spent_outputs[index].script_pubkey.as_bytes()[2..]);
+            let sighash = taproot_sighash(&tx, index, &spent_outputs, SIGHASH_DEFAULT).unwrap();
+            assert!(schnorr_verify(&output_key, &sighash, &txin.witness[0]).unwrap());
+        }
+
+        //legacy utxos are not taproot ones
+        let transaction_req_data = BtcTransaction {
+            to,
+            amount: 150000,
+            unspents: simulator_utxos(),
+            fee: 5000,
+        };
+        let result = transaction_req_data.sign_taproot_transaction(
+            &transport,
+            Network::Testnet,
+            "m/44'/1'/0'",
+            2,
+            &vec![],
+        );
+        assert_eq!(
+            format!("{}", result.err().unwrap()),
+            "imkey_address_mismatch_with_path"
+        );
+    }
 }
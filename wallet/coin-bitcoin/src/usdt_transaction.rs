@@ -1,13 +1,12 @@
 use crate::common::{
-    address_verify, get_address_version, get_xpub_data, secp256k1_sign_verify, TransTypeFlg,
-    TxSignResult,
+    address_verify, get_address_version, get_xpub_data, output_data, secp256k1_sign_verify,
+    TransTypeFlg, TxSignResult,
 };
+use crate::omni::OmniPayload;
 use crate::transaction::BtcTransaction;
 use crate::Result;
-use bitcoin::blockdata::{opcodes, script::Builder};
 use bitcoin::consensus::serialize;
 use bitcoin::hashes::hex::FromHex;
-use bitcoin::util::psbt::serialize::Serialize;
 use bitcoin::{BitcoinHash, Network, OutPoint, Script, SigHashType, Transaction, TxIn, TxOut};
 use bitcoin_hashes::hash160;
 use bitcoin_hashes::hex::ToHex;
@@ -17,18 +16,24 @@ use common::apdu::{ApduCheck, BtcApdu};
 use common::constants::{EACH_ROUND_NUMBER, MAX_UTXO_NUMBER, MIN_NONDUST_OUTPUT, TIMEOUT_LONG};
 use common::error::CoinError;
 use common::path::check_path_validity;
-use common::utility::{bigint_to_byte_vec, hex_to_bytes, secp256k1_sign};
-use device::device_binding::KEY_MANAGER;
+use common::utility::{hex_to_bytes, secp256k1_sign};
+use device::device_binding::key_manager;
 use secp256k1::Signature;
-use transport::message::{send_apdu, send_apdu_timeout};
+use transport::Transport;
 
 impl BtcTransaction {
+    /**
+    signs p2pkh utxos for a simple send of amount: the change back to the first utxo, the
+    reference output to the receiver, then the op_return
+    */
     pub fn sign_omni_transaction(
         &self,
+        transport: &dyn Transport,
         network: Network,
         path: &str,
         property_id: i32,
     ) -> Result<TxSignResult> {
+        let payload = self.simple_send(property_id)?;
         //path check
         check_path_validity(path)?;
         let mut path_str = path.to_string();
@@ -41,12 +46,13 @@ impl BtcTransaction {
         }
 
         //check change amount
-        if self.amount - self.fee < MIN_NONDUST_OUTPUT {
+        let change_amount = self.omni_change_amount();
+        if change_amount < MIN_NONDUST_OUTPUT {
             return Err(CoinError::ImkeyAmountLessThanMinimum.into());
         }
 
         //get xpub and sign data
-        let xpub_data = get_xpub_data(path_str.as_str(), true)?;
+        let xpub_data = get_xpub_data(transport, path_str.as_str(), true)?;
         let xpub_data = &xpub_data[..xpub_data.len() - 4].to_string();
         //get xpub data
         let sign_source_val = &xpub_data[..194];
@@ -55,7 +61,8 @@ impl BtcTransaction {
         let chain_code = &sign_source_val[130..];
 
         //use se public key verify sign
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
         let sign_verify_result = secp256k1_sign_verify(
             &key_manager_obj.se_pub_key.as_slice(),
             hex::decode(sign_result).unwrap().as_slice(),
@@ -74,25 +81,8 @@ impl BtcTransaction {
             TransTypeFlg::BTC,
         )?;
 
-        //add change output
-        let mut txouts: Vec<TxOut> = Vec::new();
-        let change_amount = self.get_total_amount() - MIN_NONDUST_OUTPUT - self.fee;
-        let receiver_address = &self.unspents.get(0).unwrap().address;
-        let txout_send_output = TxOut {
-            value: change_amount as u64,
-            script_pubkey: receiver_address.script_pubkey(),
-        };
-        txouts.push(txout_send_output);
-
-        //add send to output
-        let txout_change_output = TxOut {
-            value: MIN_NONDUST_OUTPUT as u64,
-            script_pubkey: self.to.script_pubkey(),
-        };
-        txouts.push(txout_change_output);
-
-        //add omni output
-        txouts.push(self.build_omni_output(property_id, self.amount));
+        //add change, send to and omni outputs
+        let txouts = self.omni_outputs(&payload, change_amount);
 
         //output data serialize
         let mut tx_to_sign = Transaction {
@@ -101,28 +91,9 @@ impl BtcTransaction {
             input: vec![],
             output: txouts,
         };
-        let mut output_serialize_data = serialize(&tx_to_sign);
-
-        output_serialize_data.remove(5);
-        output_serialize_data.remove(5);
-
-        //add sign type
-        output_serialize_data.extend(SigHashType::All.serialize().iter());
-
-        //set input number
-        output_serialize_data.remove(4);
-        output_serialize_data.insert(4, self.unspents.len() as u8);
-
-        //add fee amount
-        output_serialize_data.extend(bigint_to_byte_vec(self.fee));
-
-        //set address version
         let address_version = get_address_version(network, self.to.to_string().as_str())?;
-        output_serialize_data.push(address_version);
-
-        //set 01 tag and length
-        output_serialize_data.insert(0, output_serialize_data.len() as u8);
-        output_serialize_data.insert(0, 0x01);
+        let output_serialize_data =
+            output_data(&tx_to_sign, self.unspents.len(), self.fee, address_version)?;
 
         //use local private key sign data
         let mut output_pareper_data =
@@ -133,7 +104,9 @@ impl BtcTransaction {
 
         //send output prepare command
         let omni_prepare_apdu_str = BtcApdu::omni_prepare_data(0x00, output_pareper_data);
-        ApduCheck::checke_response(&send_apdu_timeout(omni_prepare_apdu_str, TIMEOUT_LONG)?)?;
+        ApduCheck::checke_response(
+            &transport.send_apdu_timeout(omni_prepare_apdu_str, TIMEOUT_LONG)?,
+        )?;
         let mut lock_script_ver: Vec<Script> = vec![];
         let count = (self.unspents.len() - 1) / EACH_ROUND_NUMBER + 1;
         for i in 0..count {
@@ -157,7 +130,7 @@ impl BtcTransaction {
                 input_data_vec.extend_from_slice(serialize(&temp_serialize_txin).as_slice());
                 let btc_perpare_apdu = BtcApdu::btc_perpare_input(0x80, &input_data_vec);
                 //send perpare apdu
-                ApduCheck::checke_response(&send_apdu(btc_perpare_apdu)?)?;
+                ApduCheck::checke_response(&transport.send_apdu(btc_perpare_apdu)?)?;
             }
             for y in i * EACH_ROUND_NUMBER..(i + 1) * EACH_ROUND_NUMBER {
                 if y >= utxo_pub_key_vec.len() {
@@ -169,7 +142,7 @@ impl BtcTransaction {
                     format!("{}{}", path_str, self.unspents.get(y).unwrap().derive_path).as_str(),
                 );
                 //send sign apdu
-                let btc_sign_apdu_return = send_apdu(btc_sign_apdu)?;
+                let btc_sign_apdu_return = transport.send_apdu(btc_sign_apdu)?;
                 ApduCheck::checke_response(&btc_sign_apdu_return)?;
                 let sign_result_str =
                     btc_sign_apdu_return[2..btc_sign_apdu_return.len() - 6].to_string();
@@ -203,12 +176,17 @@ impl BtcTransaction {
         })
     }
 
+    /**
+    signs p2sh-p2wpkh utxos for a simple send of amount, the outputs as for p2pkh utxos
+    */
     pub fn sign_omni_segwit_transaction(
         &self,
+        transport: &dyn Transport,
         network: Network,
         path: &str,
         property_id: i32,
     ) -> Result<TxSignResult> {
+        let payload = self.simple_send(property_id)?;
         //path check
         check_path_validity(path)?;
         let mut path_str = path.to_string();
@@ -219,14 +197,14 @@ impl BtcTransaction {
         if &self.unspents.len() > &MAX_UTXO_NUMBER {
             return Err(CoinError::ImkeyExceededMaxUtxoNumber.into());
         }
-        let change_amount = self.get_total_amount() - self.fee - MIN_NONDUST_OUTPUT;
+        let change_amount = self.omni_change_amount();
         //check change amount
         if change_amount < MIN_NONDUST_OUTPUT {
             return Err(CoinError::ImkeyAmountLessThanMinimum.into());
         }
 
         //get xpub and sign data
-        let xpub_data = get_xpub_data(path_str.as_str(), true)?;
+        let xpub_data = get_xpub_data(transport, path_str.as_str(), true)?;
         let xpub_data = &xpub_data[..xpub_data.len() - 4].to_string();
 
         //get xpub data
@@ -236,7 +214,8 @@ impl BtcTransaction {
         let chain_code = &sign_source_val[130..];
 
         //use se public key verify sign
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
         let sign_verify_result = secp256k1_sign_verify(
             &key_manager_obj.se_pub_key.as_slice(),
             hex::decode(sign_result).unwrap().as_slice(),
@@ -255,24 +234,8 @@ impl BtcTransaction {
             TransTypeFlg::SEGWIT,
         )?;
 
-        //5.add change output
-        let mut txouts: Vec<TxOut> = vec![];
-        let receiver_address = &self.unspents.get(0).unwrap().address;
-        let txout_send_output = TxOut {
-            value: change_amount as u64,
-            script_pubkey: receiver_address.script_pubkey(),
-        };
-        txouts.push(txout_send_output);
-
-        //6.add send to output
-        let txout_change_output = TxOut {
-            value: MIN_NONDUST_OUTPUT as u64,
-            script_pubkey: self.to.script_pubkey(),
-        };
-        txouts.push(txout_change_output);
-
-        //add omni output
-        txouts.push(self.build_omni_output(property_id, self.amount));
+        //5.add change, send to and omni outputs
+        let txouts = self.omni_outputs(&payload, change_amount);
 
         //8.output data serialize
         let mut tx_to_sign = Transaction {
@@ -281,23 +244,9 @@ impl BtcTransaction {
             input: vec![],
             output: txouts,
         };
-        let mut output_serialize_data = serialize(&tx_to_sign);
-
-        output_serialize_data.remove(5);
-        output_serialize_data.remove(5);
-        //add sign type
-        output_serialize_data.extend(SigHashType::All.serialize().iter());
-        //set input number
-        output_serialize_data.remove(4);
-        output_serialize_data.insert(4, self.unspents.len() as u8);
-        //add fee amount
-        output_serialize_data.extend(bigint_to_byte_vec(self.fee));
-        //set address version
         let address_version = get_address_version(network, self.to.to_string().as_str())?;
-        output_serialize_data.push(address_version);
-        //set 01 tag and length
-        output_serialize_data.insert(0, output_serialize_data.len() as u8);
-        output_serialize_data.insert(0, 0x01);
+        let output_serialize_data =
+            output_data(&tx_to_sign, self.unspents.len(), self.fee, address_version)?;
         //use local private key sign data
         let mut output_pareper_data =
             secp256k1_sign(&key_manager_obj.pri_key, &output_serialize_data)?;
@@ -307,7 +256,7 @@ impl BtcTransaction {
 
         let btc_prepare_apdu_vec = BtcApdu::btc_prepare(0x34, 0x00, &output_pareper_data);
         for temp_str in btc_prepare_apdu_vec {
-            ApduCheck::checke_response(&send_apdu_timeout(temp_str, TIMEOUT_LONG)?)?;
+            ApduCheck::checke_response(&transport.send_apdu_timeout(temp_str, TIMEOUT_LONG)?)?;
         }
 
         let mut txinputs: Vec<TxIn> = vec![];
@@ -375,14 +324,14 @@ impl BtcTransaction {
         let mut sequence_prepare_apdu_vec = BtcApdu::btc_prepare(0x34, 0x80, &sequence_vec);
         txhash_vout_prepare_apdu_vec.append(&mut sequence_prepare_apdu_vec);
         for prepare_apdu in txhash_vout_prepare_apdu_vec {
-            ApduCheck::checke_response(&send_apdu(prepare_apdu)?)?;
+            ApduCheck::checke_response(&transport.send_apdu(prepare_apdu)?)?;
         }
 
         //send sign apdu
         let mut witnesses: Vec<(Vec<u8>, Vec<u8>)> = vec![];
         for (index, segwit_sign_apdu) in sign_apdu_vec.iter().enumerate() {
             //send sign apdu
-            let sign_apdu_return_data = send_apdu(segwit_sign_apdu.clone())?;
+            let sign_apdu_return_data = transport.send_apdu(segwit_sign_apdu.clone())?;
             ApduCheck::checke_response(&sign_apdu_return_data)?;
             //build signature obj
             let sign_result_vec =
@@ -430,44 +379,76 @@ impl BtcTransaction {
     }
 
     pub fn build_omni_output(&self, property_id: i32, amount: i64) -> TxOut {
-        let mut property_id_bytes = num_bigint::BigInt::from(property_id).to_signed_bytes_le();
-        while property_id_bytes.len() < 4 {
-            property_id_bytes.push(0x00);
-        }
-        property_id_bytes.reverse();
-        let mut omni_data = hex::decode("6f6d6e6900000000").unwrap();
-        omni_data.extend(property_id_bytes.iter());
-        let mut amount_bytes = num_bigint::BigInt::from(amount).to_signed_bytes_le();
-        while amount_bytes.len() < 8 {
-            amount_bytes.push(0x00);
-        }
-        amount_bytes.reverse();
-        omni_data.extend(amount_bytes.iter());
-        TxOut {
-            value: 0u64,
-            script_pubkey: Builder::new()
-                .push_opcode(opcodes::all::OP_RETURN)
-                .push_slice(&omni_data[..])
-                .into_script(),
+        OmniPayload::SimpleSend {
+            property_id: property_id as u32,
+            amount: amount as u64,
         }
+        .to_output()
+    }
+
+    //the only payload the device shows, the amount of the property to the reference output
+    fn simple_send(&self, property_id: i32) -> Result<OmniPayload> {
+        let payload = OmniPayload::SimpleSend {
+            property_id: property_id as u32,
+            amount: self.amount as u64,
+        };
+        payload.check()?;
+        Ok(payload)
+    }
+
+    //the reference output carries the dust the payload needs
+    fn omni_change_amount(&self) -> i64 {
+        self.get_total_amount() - self.fee - MIN_NONDUST_OUTPUT
+    }
+
+    fn omni_outputs(&self, payload: &OmniPayload, change_amount: i64) -> Vec<TxOut> {
+        let mut txouts: Vec<TxOut> = vec![];
+        //change back to the first utxo
+        let sender_address = &self.unspents.get(0).unwrap().address;
+        txouts.push(TxOut {
+            value: change_amount as u64,
+            script_pubkey: sender_address.script_pubkey(),
+        });
+        txouts.push(TxOut {
+            value: MIN_NONDUST_OUTPUT as u64,
+            script_pubkey: self.to.script_pubkey(),
+        });
+        txouts.push(payload.to_output());
+        txouts
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::omni::OmniPayload;
     use crate::transaction::{BtcTransaction, Utxo};
-    use bitcoin::{Address, Network};
+    use bitcoin::consensus::deserialize;
+    use bitcoin::{Address, Network, Transaction};
     use std::str::FromStr;
+    use transport::hid_api::HidTransport;
+    use transport::Transport;
 
     use device::device_binding::bind_test;
     use device::device_binding::DeviceManage;
-    use transport::hid_api::hid_connect;
 
     #[test]
     fn test_sign_transaction() {
-        //binding device
-        bind_test();
+        let transport = bind_test();
+        check_sign_transaction(&transport);
+    }
+
+    #[test]
+    fn test_sign_segwit_transaction() {
+        let transport = bind_test();
+        check_sign_segwit_transaction(&transport);
+    }
+
+    #[test]
+    fn test_sign_transaction_simulator() {
+        check_sign_transaction(&simulator::bind_test());
+    }
 
+    fn check_sign_transaction(transport: &dyn Transport) {
         let utxo = Utxo {
             txhash: "0dd195c815c5086c5995f43a0c67d28344ae5fa130739a5e03ef40fea54f2031".to_string(),
             vout: 0,
@@ -487,6 +468,7 @@ mod tests {
             fee: 4000,
         };
         let sign_result = transaction_req_data.sign_omni_transaction(
+            transport,
             Network::Testnet,
             &"m/44'/1'/0'".to_string(),
             31,
@@ -502,10 +484,11 @@ mod tests {
     }
 
     #[test]
-    fn test_sign_segwit_transaction() {
-        //binding device
-        bind_test();
+    fn test_sign_segwit_transaction_simulator() {
+        check_sign_segwit_transaction(&simulator::bind_test());
+    }
 
+    fn check_sign_segwit_transaction(transport: &dyn Transport) {
         let utxo = Utxo {
             txhash: "9baf6fd0e560f9f199f4879c23cb73b9c4affb54a1cfdbacb85687efa89f4c78".to_string(),
             vout: 1,
@@ -525,6 +508,7 @@ mod tests {
             fee: 4000,
         };
         let sign_result = transaction_req_data.sign_omni_segwit_transaction(
+            transport,
             Network::Testnet,
             &"m/49'/1'/0'/".to_string(),
             31,
@@ -543,7 +527,7 @@ mod tests {
     #[test]
     fn test_segwit_transaction_8utxo() {
         //binding device
-        bind_test();
+        let transport = bind_test();
 
         let utxo = Utxo {
             txhash: "983adf9d813a2b8057454cc6f36c6081948af849966f9b9a33e5b653b02f227a".to_string(),
@@ -634,6 +618,7 @@ mod tests {
             fee: 502130,
         };
         let sign_result = transaction_req_data.sign_omni_segwit_transaction(
+            &transport,
             Network::Bitcoin,
             &"m/49'/0'/0'/".to_string(),
             31,
@@ -651,7 +636,7 @@ mod tests {
     #[test]
     fn test_sign_segwit_transaction_mainnet() {
         //binding device
-        bind_test();
+        let transport = bind_test();
 
         let utxo = Utxo {
             txhash: "983adf9d813a2b8057454cc6f36c6081948af849966f9b9a33e5b653b02f227a".to_string(),
@@ -671,6 +656,7 @@ mod tests {
             fee: 502130,
         };
         let sign_result = transaction_req_data.sign_omni_segwit_transaction(
+            &transport,
             Network::Bitcoin,
             &"m/49'/0'/0'/".to_string(),
             31,
@@ -687,6 +673,7 @@ mod tests {
 
     #[test]
     fn exceeded_max_utxo_number_test() {
+        let transport = HidTransport::new();
         let utxo = Utxo {
             txhash: "983adf9d813a2b8057454cc6f36c6081948af849966f9b9a33e5b653b02f227a".to_string(),
             vout: 0,
@@ -707,6 +694,7 @@ mod tests {
             fee: 502130,
         };
         let sign_result = transaction_req_data.sign_omni_segwit_transaction(
+            &transport,
             Network::Bitcoin,
             &"m/49'/0'/0'".to_string(),
             31,
@@ -737,6 +725,7 @@ mod tests {
             fee: 4000,
         };
         let sign_result = transaction_req_data.sign_omni_transaction(
+            &transport,
             Network::Testnet,
             &"m/44'/1'/0'".to_string(),
             31,
@@ -748,8 +737,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sign_omni_simulator() {
+        let transport = simulator::bind_test();
+
+        let utxo = Utxo {
+            txhash: "0dd195c815c5086c5995f43a0c67d28344ae5fa130739a5e03ef40fea54f2031".to_string(),
+            vout: 0,
+            amount: 14824854,
+            address: Address::from_str("mkeNU5nVnozJiaACDELLCsVUc8Wxoh1rQN").unwrap(),
+            script_pubkey: "76a914383fb81cb0a3fc724b5e08cf8bbd404336d711f688ac".to_string(),
+            derive_path: "0/0".to_string(),
+            sequence: 4294967295,
+        };
+        let transaction = |amount: i64| BtcTransaction {
+            to: Address::from_str("moLK3tBG86ifpDDTqAQzs4a9cUoNjVLRE3").unwrap(),
+            amount,
+            unspents: vec![utxo.clone()],
+            fee: 4000,
+        };
+
+        let sign_result = transaction(100000000)
+            .sign_omni_transaction(&transport, Network::Testnet, "m/44'/1'/0'", 31)
+            .unwrap();
+        let tx: Transaction = deserialize(&hex::decode(&sign_result.signature).unwrap()).unwrap();
+        assert_eq!(tx.output[0].value, 14824854 - 4000 - 546);
+        assert_eq!(tx.output.len(), 3);
+        assert_eq!(
+            OmniPayload::from_script(&tx.output[2].script_pubkey).unwrap(),
+            Some(OmniPayload::SimpleSend {
+                property_id: 31,
+                amount: 100000000,
+            })
+        );
+
+        //nothing to send
+        let result =
+            transaction(0).sign_omni_transaction(&transport, Network::Testnet, "m/44'/1'/0'", 31);
+        assert_eq!(
+            format!("{}", result.err().unwrap()),
+            "imkey_omni_payload_invalid"
+        );
+    }
+
     #[test]
     fn amount_less_than_minimum_test() {
+        let transport = HidTransport::new();
         let utxo = Utxo {
             txhash: "983adf9d813a2b8057454cc6f36c6081948af849966f9b9a33e5b653b02f227a".to_string(),
             vout: 0,
@@ -769,6 +802,7 @@ mod tests {
             fee: 900,
         };
         let sign_result = transaction_req_data.sign_omni_segwit_transaction(
+            &transport,
             Network::Bitcoin,
             &"m/49'/0'/0'/".to_string(),
             31,
@@ -797,6 +831,7 @@ mod tests {
             fee: 900,
         };
         let sign_result = transaction_req_data.sign_omni_transaction(
+            &transport,
             Network::Testnet,
             &"m/44'/1'/0'".to_string(),
             31,
@@ -1,41 +1,41 @@
 use crate::Result;
 use bech32::bech32::Bech32;
 use bitcoin::bech32::convert_bits;
-use bitcoin_hashes::hex::{FromHex, ToHex};
 use bitcoin_hashes::{hash160, Hash};
 use common::apdu::{ApduCheck, CoinCommonApdu, CosmosApdu};
 use common::error::CoinError;
 use common::path;
 use common::utility;
-use device::device_binding::KEY_MANAGER;
+use device::device_binding::key_manager;
 use hex;
-use transport::message;
+use transport::Transport;
 
 #[derive(Debug)]
 pub struct CosmosAddress {}
 
 impl CosmosAddress {
-    pub fn get_pub_key(path: &str) -> Result<String> {
-        path::check_path_validity(path).unwrap();
+    pub fn get_pub_key(transport: &dyn Transport, path: &str) -> Result<String> {
+        path::check_path_validity(path)?;
 
         let select_apdu = CosmosApdu::select_applet();
-        let select_response = message::send_apdu(select_apdu)?;
+        let select_response = transport.send_apdu(select_apdu)?;
         ApduCheck::checke_response(&select_response)?;
 
         //get public
         let msg_pubkey = CosmosApdu::get_xpub(&path, true);
-        let res_msg_pubkey = message::send_apdu(msg_pubkey)?;
+        let res_msg_pubkey = transport.send_apdu(msg_pubkey)?;
         ApduCheck::checke_response(&res_msg_pubkey)?;
 
         let sign_source_val = &res_msg_pubkey[..194];
         let sign_result = &res_msg_pubkey[194..res_msg_pubkey.len() - 4];
 
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
 
         let sign_verify_result = utility::secp256k1_sign_verify(
             &key_manager_obj.se_pub_key,
-            hex::decode(sign_result).unwrap().as_slice(),
-            hex::decode(sign_source_val).unwrap().as_slice(),
+            hex::decode(sign_result)?.as_slice(),
+            hex::decode(sign_source_val)?.as_slice(),
         )?;
         if !sign_verify_result {
             return Err(CoinError::ImkeySignatureVerifyFail.into());
@@ -50,30 +50,39 @@ impl CosmosAddress {
         Ok(comprs_pubkey)
     }
 
-    pub fn get_address(path: &str) -> Result<String> {
-        let comprs_pubkey = CosmosAddress::get_pub_key(path).unwrap();
-        //hash160
-        let pub_key_bytes = hex::decode(comprs_pubkey).unwrap();
-        let pub_key_hash = hash160::Hash::hash(&pub_key_bytes).to_hex();
-        let hh = Vec::from_hex(&pub_key_hash).unwrap();
+    /**
+     * the bech32 address of the key at path, hrp is the prefix of the chain, "cosmos", "osmo",
+     * "terra" and so on
+     */
+    pub fn get_address(transport: &dyn Transport, hrp: &str, path: &str) -> Result<String> {
+        let comprs_pubkey = CosmosAddress::get_pub_key(transport, path)?;
+        CosmosAddress::address_from_pubkey(&hex::decode(comprs_pubkey)?, hrp)
+    }
+
+    /**
+     * the bech32 of the hash160 of a compressed public key
+     */
+    pub fn address_from_pubkey(pub_key: &[u8], hrp: &str) -> Result<String> {
+        let pub_key_hash = hash160::Hash::hash(pub_key);
 
         //bech32
-        let hash5 = convert_bits(&hh, 8, 5, true);
+        let hash5 = convert_bits(&pub_key_hash[..], 8, 5, true)
+            .map_err(|_| CoinError::ImkeySdkIllegalArgument)?;
         let b32 = Bech32 {
-            hrp: "cosmos".to_string(),
-            data: hash5.unwrap(),
+            hrp: hrp.to_string(),
+            data: hash5,
         }; //todo use bitcoin_hash istead
         let address = match b32.to_string() {
             Ok(s) => s,
-            Err(_e) => return Err(format_err!("AddressError")),
+            Err(_e) => return Err(CoinError::ImkeySdkIllegalArgument.into()),
         };
         Ok(address)
     }
 
-    pub fn display_address(path: &str) -> Result<String> {
-        let address = CosmosAddress::get_address(path).unwrap();
+    pub fn display_address(transport: &dyn Transport, hrp: &str, path: &str) -> Result<String> {
+        let address = CosmosAddress::get_address(transport, hrp, path)?;
         let reg_apdu = CosmosApdu::register_address(address.as_bytes());
-        let res_reg = message::send_apdu(reg_apdu)?;
+        let res_reg = transport.send_apdu(reg_apdu)?;
         ApduCheck::checke_response(&res_reg)?;
         Ok(address)
     }
@@ -85,12 +94,13 @@ mod tests {
     use bech32::bech32::Bech32;
     use common::constants;
     use device::device_binding::bind_test;
+    use transport::Transport;
 
     #[test]
     fn test_get_pub_key() {
-        bind_test();
+        let transport = bind_test();
 
-        let comprs_pubkey = CosmosAddress::get_pub_key(constants::COSMOS_PATH).unwrap();
+        let comprs_pubkey = CosmosAddress::get_pub_key(&transport, constants::COSMOS_PATH).unwrap();
         assert_eq!(
             &comprs_pubkey,
             "0232C1EF21D73C19531B0AA4E863CF397C2B982B2F958F60CDB62969824C096D65"
@@ -99,19 +109,49 @@ mod tests {
 
     #[test]
     fn test_get_address() {
-        bind_test();
+        let transport = bind_test();
 
-        let address = CosmosAddress::get_address(constants::COSMOS_PATH).unwrap();
+        let address =
+            CosmosAddress::get_address(&transport, constants::COSMOS_HRP, constants::COSMOS_PATH)
+                .unwrap();
         assert_eq!(&address, "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992");
     }
 
     #[test]
     fn test_display_address() {
-        bind_test();
-        let address = CosmosAddress::display_address(constants::COSMOS_PATH).unwrap();
+        let transport = bind_test();
+        check_display_address(&transport);
+    }
+
+    #[test]
+    fn test_display_address_simulator() {
+        check_display_address(&simulator::bind_test());
+    }
+
+    fn check_display_address(transport: &dyn Transport) {
+        let address = CosmosAddress::display_address(
+            transport,
+            constants::COSMOS_HRP,
+            constants::COSMOS_PATH,
+        )
+        .unwrap();
         assert_eq!(&address, "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992");
     }
 
+    #[test]
+    fn test_get_address_hrp_simulator() {
+        let transport = simulator::bind_test();
+        for (hrp, expected) in &[
+            ("osmo", "osmo1ajz9y0x3wekez7tz2td2j6l2dftn28v2jk74nc"),
+            ("terra", "terra1ajz9y0x3wekez7tz2td2j6l2dftn28v2ufh982"),
+            ("juno", "juno1ajz9y0x3wekez7tz2td2j6l2dftn28v2vlw7zk"),
+        ] {
+            let address =
+                CosmosAddress::get_address(&transport, hrp, constants::COSMOS_PATH).unwrap();
+            assert_eq!(&address, expected);
+        }
+    }
+
     #[test]
     fn test_bech32() {
         let b32 = Bech32 {
@@ -41,6 +41,9 @@ pub struct CosmosTxReq {
     pub from_dis: std::string::String,
     #[prost(string, tag = "6")]
     pub fee_dis: std::string::String,
+    /// "direct" signs the protobuf sign doc of cosmos sdk 0.40 and later, otherwise amino json
+    #[prost(string, tag = "7")]
+    pub sign_mode: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CosmosTxRes {
@@ -53,6 +56,9 @@ pub struct CosmosTxRes {
 pub struct CosmosAddressReq {
     #[prost(string, tag = "1")]
     pub path: std::string::String,
+    /// bech32 prefix of the chain, "cosmos" if empty
+    #[prost(string, tag = "2")]
+    pub hrp: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CosmosAddressRes {
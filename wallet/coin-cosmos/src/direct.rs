@@ -0,0 +1,397 @@
+use crate::transaction::SignData;
+use crate::Result;
+use common::error::CoinError;
+use prost::Message;
+use prost_types::Any;
+use serde_json::Value;
+
+/**
+the protobuf messages of cosmos sdk 0.40 SIGN_MODE_DIRECT, from cosmos/tx/v1beta1/tx.proto and the
+bank, staking and distribution modules
+*/
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Coin {
+    #[prost(string, tag = "1")]
+    pub denom: std::string::String,
+    #[prost(string, tag = "2")]
+    pub amount: std::string::String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TxBody {
+    #[prost(message, repeated, tag = "1")]
+    pub messages: ::std::vec::Vec<Any>,
+    #[prost(string, tag = "2")]
+    pub memo: std::string::String,
+    #[prost(uint64, tag = "3")]
+    pub timeout_height: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AuthInfo {
+    #[prost(message, repeated, tag = "1")]
+    pub signer_infos: ::std::vec::Vec<SignerInfo>,
+    #[prost(message, optional, tag = "2")]
+    pub fee: ::std::option::Option<Fee>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SignerInfo {
+    #[prost(message, optional, tag = "1")]
+    pub public_key: ::std::option::Option<Any>,
+    #[prost(message, optional, tag = "2")]
+    pub mode_info: ::std::option::Option<ModeInfo>,
+    #[prost(uint64, tag = "3")]
+    pub sequence: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ModeInfo {
+    #[prost(message, optional, tag = "1")]
+    pub single: ::std::option::Option<Single>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Single {
+    #[prost(int32, tag = "1")]
+    pub mode: i32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Fee {
+    #[prost(message, repeated, tag = "1")]
+    pub amount: ::std::vec::Vec<Coin>,
+    #[prost(uint64, tag = "2")]
+    pub gas_limit: u64,
+    #[prost(string, tag = "3")]
+    pub payer: std::string::String,
+    #[prost(string, tag = "4")]
+    pub granter: std::string::String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SignDoc {
+    #[prost(bytes, tag = "1")]
+    pub body_bytes: ::std::vec::Vec<u8>,
+    #[prost(bytes, tag = "2")]
+    pub auth_info_bytes: ::std::vec::Vec<u8>,
+    #[prost(string, tag = "3")]
+    pub chain_id: std::string::String,
+    #[prost(uint64, tag = "4")]
+    pub account_number: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TxRaw {
+    #[prost(bytes, tag = "1")]
+    pub body_bytes: ::std::vec::Vec<u8>,
+    #[prost(bytes, tag = "2")]
+    pub auth_info_bytes: ::std::vec::Vec<u8>,
+    #[prost(bytes, repeated, tag = "3")]
+    pub signatures: ::std::vec::Vec<::std::vec::Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PubKey {
+    #[prost(bytes, tag = "1")]
+    pub key: ::std::vec::Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MsgSend {
+    #[prost(string, tag = "1")]
+    pub from_address: std::string::String,
+    #[prost(string, tag = "2")]
+    pub to_address: std::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub amount: ::std::vec::Vec<Coin>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MsgDelegate {
+    #[prost(string, tag = "1")]
+    pub delegator_address: std::string::String,
+    #[prost(string, tag = "2")]
+    pub validator_address: std::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub amount: ::std::option::Option<Coin>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MsgBeginRedelegate {
+    #[prost(string, tag = "1")]
+    pub delegator_address: std::string::String,
+    #[prost(string, tag = "2")]
+    pub validator_src_address: std::string::String,
+    #[prost(string, tag = "3")]
+    pub validator_dst_address: std::string::String,
+    #[prost(message, optional, tag = "4")]
+    pub amount: ::std::option::Option<Coin>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MsgWithdrawDelegatorReward {
+    #[prost(string, tag = "1")]
+    pub delegator_address: std::string::String,
+    #[prost(string, tag = "2")]
+    pub validator_address: std::string::String,
+}
+
+//signing.v1beta1.SignMode
+const SIGN_MODE_DIRECT: i32 = 1;
+
+fn to_any<M: Message>(type_url: &str, msg: &M) -> Result<Any> {
+    let mut value = vec![];
+    msg.encode(&mut value)?;
+    Ok(Any {
+        type_url: type_url.to_string(),
+        value,
+    })
+}
+
+fn parse_u64(value: &str) -> Result<u64> {
+    let value = value
+        .parse::<u64>()
+        .map_err(|_| CoinError::ImkeySdkIllegalArgument)?;
+    Ok(value)
+}
+
+fn string_field(value: &Value, field: &str) -> Result<String> {
+    let field = value[field]
+        .as_str()
+        .ok_or(CoinError::ImkeySdkIllegalArgument)?;
+    Ok(field.to_string())
+}
+
+fn coins(value: &Value) -> Result<Vec<Coin>> {
+    let coins = match value {
+        Value::Array(coins) => coins.clone(),
+        Value::Null => vec![],
+        coin => vec![coin.clone()],
+    };
+    coins
+        .iter()
+        .map(|coin| {
+            Ok(Coin {
+                denom: string_field(coin, "denom")?,
+                amount: string_field(coin, "amount")?,
+            })
+        })
+        .collect()
+}
+
+//staking messages carry exactly one coin, amino json may wrap it in an array
+fn single_coin(value: &Value) -> Result<Option<Coin>> {
+    let mut coins = coins(value)?;
+    if coins.len() > 1 {
+        return Err(CoinError::ImkeySdkIllegalArgument.into());
+    }
+    Ok(coins.pop())
+}
+
+//MsgUndelegate has the fields of MsgDelegate
+fn delegation(value: &Value) -> Result<MsgDelegate> {
+    Ok(MsgDelegate {
+        delegator_address: string_field(value, "delegator_address")?,
+        validator_address: string_field(value, "validator_address")?,
+        amount: single_coin(&value["amount"])?,
+    })
+}
+
+/**
+the protobuf form of an amino json message, messages without one can not be signed in direct mode
+*/
+pub fn msg_to_any(msg: &Value) -> Result<Any> {
+    let value = &msg["value"];
+    match msg["type"].as_str().unwrap_or_default() {
+        "cosmos-sdk/MsgSend" => to_any(
+            "/cosmos.bank.v1beta1.MsgSend",
+            &MsgSend {
+                from_address: string_field(value, "from_address")?,
+                to_address: string_field(value, "to_address")?,
+                amount: coins(&value["amount"])?,
+            },
+        ),
+        "cosmos-sdk/MsgDelegate" => {
+            to_any("/cosmos.staking.v1beta1.MsgDelegate", &delegation(value)?)
+        }
+        "cosmos-sdk/MsgUndelegate" => {
+            to_any("/cosmos.staking.v1beta1.MsgUndelegate", &delegation(value)?)
+        }
+        "cosmos-sdk/MsgBeginRedelegate" => to_any(
+            "/cosmos.staking.v1beta1.MsgBeginRedelegate",
+            &MsgBeginRedelegate {
+                delegator_address: string_field(value, "delegator_address")?,
+                validator_src_address: string_field(value, "validator_src_address")?,
+                validator_dst_address: string_field(value, "validator_dst_address")?,
+                amount: single_coin(&value["amount"])?,
+            },
+        ),
+        "cosmos-sdk/MsgWithdrawDelegationReward" => to_any(
+            "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward",
+            &MsgWithdrawDelegatorReward {
+                delegator_address: string_field(value, "delegator_address")?,
+                validator_address: string_field(value, "validator_address")?,
+            },
+        ),
+        _ => Err(CoinError::ImkeyCosmosMsgUnsupported.into()),
+    }
+}
+
+impl SignData {
+    pub fn tx_body(&self) -> Result<TxBody> {
+        let messages = match &self.msgs {
+            Value::Array(msgs) => msgs.iter().map(msg_to_any).collect::<Result<Vec<Any>>>()?,
+            msg => vec![msg_to_any(msg)?],
+        };
+        Ok(TxBody {
+            messages,
+            memo: self.memo.clone(),
+            timeout_height: 0,
+        })
+    }
+
+    /**
+    the auth info of a single signer, pub_key is the compressed secp256k1 key
+    */
+    pub fn auth_info(&self, pub_key: &[u8]) -> Result<AuthInfo> {
+        let public_key = to_any(
+            "/cosmos.crypto.secp256k1.PubKey",
+            &PubKey {
+                key: pub_key.to_vec(),
+            },
+        )?;
+        let amount = self
+            .fee
+            .amount
+            .iter()
+            .map(|coin| Coin {
+                denom: coin.denom.clone(),
+                amount: coin.amount.clone(),
+            })
+            .collect();
+        Ok(AuthInfo {
+            signer_infos: vec![SignerInfo {
+                public_key: Some(public_key),
+                mode_info: Some(ModeInfo {
+                    single: Some(Single {
+                        mode: SIGN_MODE_DIRECT,
+                    }),
+                }),
+                sequence: parse_u64(&self.sequence)?,
+            }],
+            fee: Some(Fee {
+                amount,
+                gas_limit: parse_u64(&self.fee.gas)?,
+                payer: "".to_string(),
+                granter: "".to_string(),
+            }),
+        })
+    }
+
+    /**
+    the sign doc and the encoded body and auth info it commits to
+    */
+    pub fn sign_doc(&self, pub_key: &[u8]) -> Result<SignDoc> {
+        let mut body_bytes = vec![];
+        self.tx_body()?.encode(&mut body_bytes)?;
+        let mut auth_info_bytes = vec![];
+        self.auth_info(pub_key)?.encode(&mut auth_info_bytes)?;
+        Ok(SignDoc {
+            body_bytes,
+            auth_info_bytes,
+            chain_id: self.chain_id.clone(),
+            account_number: parse_u64(&self.account_number)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::direct::{msg_to_any, MsgDelegate, MsgSend, TxRaw};
+    use crate::transaction::{Coin, SignData, StdFee};
+    use prost::Message;
+    use serde_json::json;
+
+    fn sign_data() -> SignData {
+        SignData {
+            account_number: "1234567890".to_string(),
+            chain_id: "cosmoshub-4".to_string(),
+            fee: StdFee {
+                amount: vec![Coin {
+                    amount: "750".to_string(),
+                    denom: "uatom".to_string(),
+                }],
+                gas: "30000".to_string(),
+            },
+            memo: "imKey".to_string(),
+            msgs: json!([{
+                "type": "cosmos-sdk/MsgSend",
+                "value": {
+                    "amount": [{"amount": "1500000", "denom": "uatom"}],
+                    "from_address": "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992",
+                    "to_address": "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt"
+                }
+            }]),
+            sequence: "7".to_string(),
+        }
+    }
+
+    #[test]
+    fn msg_to_any_test() {
+        let any = msg_to_any(&sign_data().msgs[0]).unwrap();
+        assert_eq!(any.type_url, "/cosmos.bank.v1beta1.MsgSend");
+        let msg = MsgSend::decode(any.value.as_slice()).unwrap();
+        assert_eq!(
+            msg.to_address,
+            "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt"
+        );
+        assert_eq!(msg.amount[0].amount, "1500000");
+        assert_eq!(msg.amount[0].denom, "uatom");
+
+        let any = msg_to_any(&json!({
+            "type": "cosmos-sdk/MsgDelegate",
+            "value": {
+                "amount": [{"amount": "10", "denom": "uatom"}],
+                "delegator_address": "cosmos1y0a8sc5ayv52f2fm5t7hr2g88qgljzk4jcz78f",
+                "validator_address": "cosmosvaloper1zkupr83hrzkn3up5elktzcq3tuft8nxsmwdqgp"
+            }
+        }))
+        .unwrap();
+        assert_eq!(any.type_url, "/cosmos.staking.v1beta1.MsgDelegate");
+        let msg = MsgDelegate::decode(any.value.as_slice()).unwrap();
+        assert_eq!(msg.amount.unwrap().amount, "10");
+
+        let error = msg_to_any(&json!({"type": "cosmos-sdk/MsgVote", "value": {}}));
+        assert_eq!(
+            format!("{}", error.err().unwrap()),
+            "imkey_cosmos_msg_unsupported"
+        );
+    }
+
+    #[test]
+    fn sign_doc_test() {
+        let pub_key =
+            hex::decode("0232c1ef21d73c19531b0aa4e863cf397c2b982b2f958f60cdb62969824c096d65")
+                .unwrap();
+        let sign_doc = sign_data().sign_doc(&pub_key).unwrap();
+        assert_eq!(sign_doc.chain_id, "cosmoshub-4");
+        assert_eq!(sign_doc.account_number, 1234567890);
+        assert_eq!(
+            hex::encode(&sign_doc.body_bytes),
+            "0a90010a1c2f636f736d6f732e62616e6b2e763162657461312e4d736753656e6412700a2d636f736d6f7331616a7a397930783377656b657a37747a327464326a366c326466746e32387632366464393932122d636f736d6f73317965636b787a377461707a33346b6a776e6a78766d787a7572657271756874726d786d7578741a100a057561746f6d1207313530303030301205696d4b6579"
+        );
+        assert_eq!(
+            hex::encode(&sign_doc.auth_info_bytes),
+            "0a500a460a1f2f636f736d6f732e63727970746f2e736563703235366b312e5075624b657912230a210232c1ef21d73c19531b0aa4e863cf397c2b982b2f958f60cdb62969824c096d6512040a020801180712120a0c0a057561746f6d120337353010b0ea01"
+        );
+        let raw = TxRaw {
+            body_bytes: sign_doc.body_bytes.clone(),
+            auth_info_bytes: sign_doc.auth_info_bytes.clone(),
+            signatures: vec![vec![0; 64]],
+        };
+        let mut raw_bytes = vec![];
+        raw.encode(&mut raw_bytes).unwrap();
+        assert_eq!(TxRaw::decode(raw_bytes.as_slice()).unwrap(), raw);
+    }
+}
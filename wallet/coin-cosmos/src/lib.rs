@@ -1,8 +1,8 @@
 pub mod address;
 pub mod cosmosapi;
+pub mod direct;
 pub mod transaction;
 
-#[macro_use]
 extern crate failure;
 use core::result;
 pub type Result<T> = result::Result<T, failure::Error>;
@@ -1,15 +1,19 @@
 use crate::address::CosmosAddress;
 use crate::cosmosapi::CosmosTxRes;
+use crate::direct::TxRaw;
 use crate::Result;
 use bitcoin_hashes::hex::ToHex;
 use common::apdu::{ApduCheck, CoinCommonApdu, CosmosApdu};
-use common::constants;
+use common::display::{display_tlv, Amount, DisplayInfoBuilder};
+use common::error::CoinError;
 use common::utility::{secp256k1_sign, sha256_hash};
-use device::device_binding::KEY_MANAGER;
-use secp256k1::{self, Signature as SecpSignature};
+use common::{constants, path};
+use device::device_binding::key_manager;
+use prost::Message as ProstMessage;
+use secp256k1::{self, Message, PublicKey, Secp256k1, Signature as SecpSignature};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
-use transport::message::{send_apdu, send_apdu_timeout};
+use transport::Transport;
 
 #[derive(Debug)]
 pub struct CosmosTransaction {
@@ -18,6 +22,8 @@ pub struct CosmosTransaction {
     pub payment_dis: String,
     pub to_dis: String,
     pub fee_dis: String,
+    //the address signing, checked against the key at path along with the signers of the msgs
+    pub from_dis: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -79,62 +85,133 @@ pub struct StdTx {
     pub msg: Value,
 }
 
-impl CosmosTransaction {
-    pub fn sign(self) -> Result<CosmosTxRes> {
-        let json = serde_json::to_vec(&self.sign_data).unwrap();
-        let json_str = String::from_utf8(json.to_owned()).unwrap();
-        let json_hash = sha256_hash(&json_str.as_bytes()).to_hex();
+impl SignData {
+    /**
+     * the bytes signed in amino json mode, the canonical json of the sign doc
+     */
+    pub fn sign_bytes(&self) -> Result<Vec<u8>> {
+        let value = serde_json::to_value(self)?;
+        Ok(canonical_json(&value).into_bytes())
+    }
 
-        let mut sign_pack = "0120".to_string();
-        sign_pack.push_str(&json_hash);
-        if self.payment_dis == "" {
-            sign_pack.push_str("070008000900");
-        } else {
-            sign_pack.push_str("07");
-            sign_pack.push_str(&format!("{:02x}", self.payment_dis.as_bytes().len()));
-            sign_pack.push_str(&hex::encode(&self.payment_dis));
-            sign_pack.push_str("08");
-            sign_pack.push_str(&format!("{:02x}", self.to_dis.as_bytes().len()));
-            sign_pack.push_str(&hex::encode(&self.to_dis));
-            sign_pack.push_str("09");
-            sign_pack.push_str(&format!("{:02x}", self.fee_dis.as_bytes().len()));
-            sign_pack.push_str(&hex::encode(&self.fee_dis));
+    /**
+     * the payments, receivers and fee of the messages. sends, delegations and reward withdrawals
+     * are recognized, any other message is refused as the device could not show what it does
+     */
+    pub fn display_info(&self) -> Result<DisplayInfoBuilder> {
+        let mut builder = DisplayInfoBuilder::new();
+        for coin in &self.fee.amount {
+            builder = builder.fee(coin_amount(&coin.amount, &coin.denom)?);
         }
 
-        let sign_pack_vec = hex::decode(sign_pack).expect("Decoding failed");
-
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
-        let mut prepare_data = secp256k1_sign(&key_manager_obj.pri_key, &sign_pack_vec.as_slice())?;
-        std::mem::drop(key_manager_obj);
-        prepare_data.insert(0, prepare_data.len() as u8);
-        prepare_data.insert(0, 0x00);
-        prepare_data.extend(sign_pack_vec.iter());
-
-        let select_apdu = CosmosApdu::select_applet();
-        let select_response = send_apdu(select_apdu)?;
-        ApduCheck::checke_response(&select_response)?;
+        for msg in &self.msg_list() {
+            let value = &msg["value"];
+            let receiver = match msg["type"].as_str().unwrap_or_default() {
+                "cosmos-sdk/MsgSend" => &value["to_address"],
+                "cosmos-sdk/MsgDelegate"
+                | "cosmos-sdk/MsgUndelegate"
+                | "cosmos-sdk/MsgWithdrawDelegationReward" => &value["validator_address"],
+                "cosmos-sdk/MsgBeginRedelegate" => &value["validator_dst_address"],
+                _ => return Err(CoinError::ImkeyCosmosMsgUnsupported.into()),
+            };
+            let coins = match &value["amount"] {
+                Value::Array(coins) => coins.clone(),
+                Value::Null => vec![],
+                coin => vec![coin.clone()],
+            };
+            for coin in &coins {
+                builder = builder.payment(coin_amount(
+                    coin["amount"].as_str().unwrap_or_default(),
+                    coin["denom"].as_str().unwrap_or_default(),
+                )?);
+            }
+            let receiver = receiver
+                .as_str()
+                .ok_or(CoinError::ImkeySdkIllegalArgument)?;
+            builder = builder.receiver(receiver);
+        }
+        Ok(builder)
+    }
 
-        let prepare_apdus = CosmosApdu::prepare_sign(prepare_data);
+    /**
+     * the addresses the messages are signed for, the sender or the delegator of each
+     */
+    pub fn signers(&self) -> Result<Vec<String>> {
+        let mut signers = vec![];
+        for msg in &self.msg_list() {
+            let value = &msg["value"];
+            let signer = value["from_address"]
+                .as_str()
+                .or_else(|| value["delegator_address"].as_str())
+                .ok_or(CoinError::ImkeySdkIllegalArgument)?;
+            signers.push(signer.to_string());
+        }
+        Ok(signers)
+    }
 
-        for apdu in prepare_apdus {
-            let response = send_apdu_timeout(apdu, constants::TIMEOUT_LONG)?;
-            ApduCheck::checke_response(&response)?;
+    fn msg_list(&self) -> Vec<Value> {
+        match &self.msgs {
+            Value::Array(msgs) => msgs.clone(),
+            msg => vec![msg.clone()],
         }
+    }
+}
 
-        let sign_apdu = CosmosApdu::sign_digest(constants::COSMOS_PATH);
+/**
+ * micro denominations are shown in whole coins, "750uatom" is 0.00075 ATOM
+ */
+fn coin_amount(amount: &str, denom: &str) -> Result<Amount> {
+    let value = amount
+        .parse::<u128>()
+        .map_err(|_| CoinError::ImkeySdkIllegalArgument)?;
+    if denom.len() > 1 && denom.starts_with('u') {
+        Ok(Amount::new(value, 6, &denom[1..].to_uppercase()))
+    } else {
+        Ok(Amount::new(value, 0, denom))
+    }
+}
 
-        let sign_result = send_apdu(sign_apdu)?;
-        ApduCheck::checke_response(&sign_result)?;
+/**
+ * the json cosmos verifies amino signatures against: keys sorted at every level, no whitespace,
+ * and <, >, & escaped the way go's encoding/json does
+ */
+pub fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .iter()
+                .map(|key| format!("{}:{}", json_string(key), canonical_json(&map[*key])))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(canonical_json).collect();
+            format!("[{}]", values.join(","))
+        }
+        Value::String(string) => json_string(string),
+        other => other.to_string(),
+    }
+}
 
-        let sign_compact = hex::decode(&sign_result[2..130]).unwrap();
-        let mut signnture_obj = SecpSignature::from_compact(sign_compact.as_slice()).unwrap();
-        signnture_obj.normalize_s();
-        let normalizes_sig_vec = signnture_obj.serialize_compact();
+fn json_string(string: &str) -> String {
+    Value::String(string.to_string())
+        .to_string()
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029")
+}
 
-        let sign_base64 = base64::encode(&normalizes_sig_vec.as_ref());
+impl CosmosTransaction {
+    pub fn sign(self, transport: &dyn Transport) -> Result<CosmosTxRes> {
+        let pub_key = self.pub_key(transport)?;
+        let json_hash = sha256_hash(&self.sign_data.sign_bytes()?);
 
-        let pub_key = CosmosAddress::get_pub_key(&self.path).unwrap();
-        let pub_key = hex::decode(pub_key).unwrap();
+        let signature = self.sign_digest(transport, &json_hash, &pub_key)?;
+        let sign_base64 = base64::encode(&signature);
         let pub_key = base64::encode(&pub_key);
 
         let std_signature = StdSignature {
@@ -154,24 +231,148 @@ impl CosmosTransaction {
             msg: self.sign_data.msgs,
         };
 
-        let json = serde_json::to_vec(&std_tx).unwrap();
-        let json = String::from_utf8(json.to_owned()).unwrap();
+        let json = serde_json::to_string(&std_tx)?;
 
         let ouput = CosmosTxRes {
-            tx_data: json.to_string(),
+            tx_data: json,
             tx_hash: "".to_string(),
         };
         Ok(ouput)
     }
+
+    /**
+     * SIGN_MODE_DIRECT of cosmos sdk 0.40 and later: the protobuf sign doc is signed and the
+     * TxRaw returned in base64, ready for broadcast. the tx hash is the sha256 of the TxRaw
+     */
+    pub fn sign_direct(self, transport: &dyn Transport) -> Result<CosmosTxRes> {
+        let pub_key = self.pub_key(transport)?;
+
+        let sign_doc = self.sign_data.sign_doc(&pub_key)?;
+        let mut sign_doc_bytes = vec![];
+        sign_doc.encode(&mut sign_doc_bytes)?;
+        let signature = self.sign_digest(transport, &sha256_hash(&sign_doc_bytes), &pub_key)?;
+
+        let tx_raw = TxRaw {
+            body_bytes: sign_doc.body_bytes,
+            auth_info_bytes: sign_doc.auth_info_bytes,
+            signatures: vec![signature],
+        };
+        let mut tx_raw_bytes = vec![];
+        tx_raw.encode(&mut tx_raw_bytes)?;
+
+        Ok(CosmosTxRes {
+            tx_data: base64::encode(&tx_raw_bytes),
+            tx_hash: sha256_hash(&tx_raw_bytes).to_hex().to_uppercase(),
+        })
+    }
+
+    /**
+     * the compressed public key at path, it has to be the one of every signer of the msgs and
+     * of from_dis if that is given
+     */
+    fn pub_key(&self, transport: &dyn Transport) -> Result<Vec<u8>> {
+        let pub_key = hex::decode(CosmosAddress::get_pub_key(transport, &self.path)?)?;
+        let mut signers = self.sign_data.signers()?;
+        if !self.from_dis.is_empty() {
+            signers.push(self.from_dis.clone());
+        }
+        for signer in &signers {
+            let hrp = match signer.rfind('1') {
+                Some(separator) => &signer[..separator],
+                None => return Err(CoinError::ImkeyAddressMismatchWithPath.into()),
+            };
+            if CosmosAddress::address_from_pubkey(&pub_key, hrp)? != *signer {
+                return Err(CoinError::ImkeyAddressMismatchWithPath.into());
+            }
+        }
+        Ok(pub_key)
+    }
+
+    /**
+     * sign a digest with the verified display info at path, the signature is the normalized
+     * compact one and has to verify with pub_key
+     */
+    fn sign_digest(
+        &self,
+        transport: &dyn Transport,
+        digest: &[u8],
+        pub_key: &[u8],
+    ) -> Result<Vec<u8>> {
+        path::check_path_validity(&self.path)?;
+
+        //the device shows the strings derived from the messages, the caller's ones only have to
+        //agree with them
+        let display = self.sign_data.display_info()?.verify(
+            &self.payment_dis,
+            &self.to_dis,
+            &self.fee_dis,
+        )?;
+
+        let mut sign_pack = "0120".to_string();
+        sign_pack.push_str(&digest.to_hex());
+        sign_pack.push_str(&hex::encode(display_tlv(7, &display.payment)?));
+        sign_pack.push_str(&hex::encode(display_tlv(8, &display.receiver)?));
+        sign_pack.push_str(&hex::encode(display_tlv(9, &display.fee)?));
+
+        let sign_pack_vec = hex::decode(sign_pack)?;
+
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
+        let mut prepare_data = secp256k1_sign(&key_manager_obj.pri_key, &sign_pack_vec.as_slice())?;
+        std::mem::drop(key_manager_obj);
+        prepare_data.insert(0, prepare_data.len() as u8);
+        prepare_data.insert(0, 0x00);
+        prepare_data.extend(sign_pack_vec.iter());
+
+        let select_apdu = CosmosApdu::select_applet();
+        let select_response = transport.send_apdu(select_apdu)?;
+        ApduCheck::checke_response(&select_response)?;
+
+        let prepare_apdus = CosmosApdu::prepare_sign(prepare_data);
+
+        for apdu in prepare_apdus {
+            let response = transport.send_apdu_timeout(apdu, constants::TIMEOUT_LONG)?;
+            ApduCheck::checke_response(&response)?;
+        }
+
+        let sign_apdu = CosmosApdu::sign_digest(&self.path);
+
+        let sign_result = transport.send_apdu(sign_apdu)?;
+        ApduCheck::checke_response(&sign_result)?;
+
+        let sign_compact = sign_result
+            .get(2..130)
+            .ok_or(CoinError::ImkeySignatureVerifyFail)?;
+        let mut signnture_obj = SecpSignature::from_compact(&hex::decode(sign_compact)?)?;
+        signnture_obj.normalize_s();
+
+        //a key other than the one at path would give a signature the chain rejects
+        let secp = Secp256k1::verification_only();
+        let verified = secp.verify(
+            &Message::from_slice(digest)?,
+            &signnture_obj,
+            &PublicKey::from_slice(pub_key)?,
+        );
+        if verified.is_err() {
+            return Err(CoinError::ImkeySignatureVerifyFail.into());
+        }
+        Ok(signnture_obj.serialize_compact().to_vec())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::transaction::{Coin, CosmosTransaction, SignData, StdFee};
+    use crate::address::CosmosAddress;
+    use crate::direct::TxRaw;
+    use crate::transaction::{canonical_json, Coin, CosmosTransaction, SignData, StdFee};
+    use bitcoin_hashes::hex::ToHex;
     use common::constants;
-    use common::utility::{hex_to_bytes, secp256k1_sign};
+    use common::utility::{hex_to_bytes, secp256k1_sign, sha256_hash};
     use device::device_binding::bind_test;
+    use prost::Message as ProstMessage;
+    use secp256k1::{Message, PublicKey, Secp256k1, Signature};
     use serde_json::json;
+    use transport::Transport;
 
     #[test]
     fn test_ecsign() {
@@ -201,8 +402,16 @@ mod tests {
 
     #[test]
     fn test_sign_delegate() {
-        bind_test();
+        let transport = bind_test();
+        check_sign_delegate(&transport);
+    }
 
+    #[test]
+    fn test_sign_delegate_simulator() {
+        check_sign_delegate(&simulator::bind_test());
+    }
+
+    fn check_sign_delegate(transport: &dyn Transport) {
         let stdfee = StdFee {
             amount: vec![Coin {
                 amount: "0".to_string(),
@@ -218,7 +427,7 @@ mod tests {
                     "amount": "10",
                     "denom": "atom"
                 }],
-                "delegator_address": "cosmos1y0a8sc5ayv52f2fm5t7hr2g88qgljzk4jcz78f",
+                "delegator_address": "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992",
                 "validator_address": "cosmosvaloper1zkupr83hrzkn3up5elktzcq3tuft8nxsmwdqgp"
             }
         }]);
@@ -228,7 +437,7 @@ mod tests {
             chain_id: "tendermint_test".to_string(),
             fee: stdfee,
             memo: "".to_string(),
-            msgs: msg,
+            msgs: msg.clone(),
             sequence: "1234567890".to_string(),
         };
 
@@ -236,17 +445,39 @@ mod tests {
             sign_data,
             path: constants::COSMOS_PATH.to_string(),
             payment_dis: "".to_string(),
-            to_dis: "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt".to_string(),
-            fee_dis: "0.00075 atom".to_string(),
+            to_dis: "cosmosvaloper1zkupr83hrzkn3up5elktzcq3tuft8nxsmwdqgp".to_string(),
+            fee_dis: "0".to_string(),
+            from_dis: "".to_string(),
         };
-        let cosmos_tx_output = input.sign().unwrap();
-        let expect_result = r#"{"fee":{"amount":[{"amount":"0","denom":""}],"gas":"21906"},"memo":"","signatures":[{"account_number":"1234567890","pub_key":{"type":"tendermint/PubKeySecp256k1","value":"AjLB7yHXPBlTGwqk6GPPOXwrmCsvlY9gzbYpaYJMCW1l"},"sequence":"1234567890","signature":"h4//cOYLTiDYbdw+1NVZufwppIAcEQ1xsWMYcCdcGtsu4xSnYStxyJgIa57445sHnXgWP84VvnQ5geoUZAKxlQ=="}],"msg":[{"type":"cosmos-sdk/MsgDelegate","value":{"amount":[{"amount":"10","denom":"atom"}],"delegator_address":"cosmos1y0a8sc5ayv52f2fm5t7hr2g88qgljzk4jcz78f","validator_address":"cosmosvaloper1zkupr83hrzkn3up5elktzcq3tuft8nxsmwdqgp"}}]}"#;
-        assert_eq!(&expect_result, &cosmos_tx_output.tx_data);
+        let sign_bytes = input.sign_data.sign_bytes().unwrap();
+        let cosmos_tx_output = input.sign(transport).unwrap();
+        check_std_tx(&cosmos_tx_output.tx_data, &sign_bytes, &msg);
+    }
+
+    //the std tx carries the msgs and a signature over sign_bytes by the key at COSMOS_PATH
+    fn check_std_tx(tx_data: &str, sign_bytes: &[u8], msgs: &serde_json::Value) {
+        let std_tx: serde_json::Value = serde_json::from_str(tx_data).unwrap();
+        assert_eq!(&std_tx["msg"], msgs);
+        assert_eq!(
+            std_tx["signatures"][0]["pub_key"]["value"],
+            "AjLB7yHXPBlTGwqk6GPPOXwrmCsvlY9gzbYpaYJMCW1l"
+        );
+        let pub_key = base64::decode("AjLB7yHXPBlTGwqk6GPPOXwrmCsvlY9gzbYpaYJMCW1l").unwrap();
+        let signature =
+            base64::decode(std_tx["signatures"][0]["signature"].as_str().unwrap()).unwrap();
+        let secp = Secp256k1::verification_only();
+        assert!(secp
+            .verify(
+                &Message::from_slice(&sha256_hash(sign_bytes)).unwrap(),
+                &Signature::from_compact(&signature).unwrap(),
+                &PublicKey::from_slice(&pub_key).unwrap(),
+            )
+            .is_ok());
     }
 
     #[test]
     fn test_sign_payment_dis() {
-        bind_test();
+        let transport = bind_test();
 
         let stdfee = StdFee {
             amount: vec![Coin {
@@ -263,7 +494,7 @@ mod tests {
                     "amount": "10",
                     "denom": "atom"
                 }],
-                "delegator_address": "cosmos1y0a8sc5ayv52f2fm5t7hr2g88qgljzk4jcz78f",
+                "delegator_address": "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992",
                 "validator_address": "cosmosvaloper1zkupr83hrzkn3up5elktzcq3tuft8nxsmwdqgp"
             }
         }]);
@@ -273,25 +504,26 @@ mod tests {
             chain_id: "tendermint_test".to_string(),
             fee: stdfee,
             memo: "".to_string(),
-            msgs: msg,
+            msgs: msg.clone(),
             sequence: "1234567890".to_string(),
         };
 
         let input = CosmosTransaction {
             sign_data,
             path: constants::COSMOS_PATH.to_string(),
-            payment_dis: "0.001 ATOM".to_string(),
-            to_dis: "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt".to_string(),
-            fee_dis: "0.00075 atom".to_string(),
+            payment_dis: "10 atom".to_string(),
+            to_dis: "cosmosvaloper1zkupr83hrzkn3up5elktzcq3tuft8nxsmwdqgp".to_string(),
+            fee_dis: "0".to_string(),
+            from_dis: "".to_string(),
         };
-        let cosmos_tx_output = input.sign().unwrap();
-        let expect_result = r#"{"fee":{"amount":[{"amount":"0","denom":""}],"gas":"21906"},"memo":"","signatures":[{"account_number":"1234567890","pub_key":{"type":"tendermint/PubKeySecp256k1","value":"AjLB7yHXPBlTGwqk6GPPOXwrmCsvlY9gzbYpaYJMCW1l"},"sequence":"1234567890","signature":"h4//cOYLTiDYbdw+1NVZufwppIAcEQ1xsWMYcCdcGtsu4xSnYStxyJgIa57445sHnXgWP84VvnQ5geoUZAKxlQ=="}],"msg":[{"type":"cosmos-sdk/MsgDelegate","value":{"amount":[{"amount":"10","denom":"atom"}],"delegator_address":"cosmos1y0a8sc5ayv52f2fm5t7hr2g88qgljzk4jcz78f","validator_address":"cosmosvaloper1zkupr83hrzkn3up5elktzcq3tuft8nxsmwdqgp"}}]}"#;
-        assert_eq!(&expect_result, &cosmos_tx_output.tx_data);
+        let sign_bytes = input.sign_data.sign_bytes().unwrap();
+        let cosmos_tx_output = input.sign(&transport).unwrap();
+        check_std_tx(&cosmos_tx_output.tx_data, &sign_bytes, &msg);
     }
 
     #[test]
     fn test_sign_send() {
-        bind_test();
+        let transport = bind_test();
 
         let stdfee = StdFee {
             amount: vec![Coin {
@@ -327,13 +559,309 @@ mod tests {
             path: constants::COSMOS_PATH.to_string(),
             payment_dis: "".to_string(),
             to_dis: "".to_string(),
-            fee_dis: "0.00075 atom".to_string(),
+            fee_dis: "750 muon".to_string(),
+            from_dis: "".to_string(),
         };
-        let cosmos_tx_output = input.sign().unwrap();
+        let cosmos_tx_output = input.sign(&transport).unwrap();
         let expect_result = r#"{"fee":{"amount":[{"amount":"750","denom":"muon"}],"gas":"30000"},"memo":"","signatures":[{"account_number":"1234567890","pub_key":{"type":"tendermint/PubKeySecp256k1","value":"AjLB7yHXPBlTGwqk6GPPOXwrmCsvlY9gzbYpaYJMCW1l"},"sequence":"1234567890","signature":"Tp8DYyOSghHF2S70I08fodPL0PWPmY6KNu9ZWN+mqoREdHs7UKIox3tZO2K7ytN4LVl9wBqaWstNOfp5Qa44tg=="}],"msg":[{"type":"cosmos-sdk/MsgSend","value":{"amount":[],"from_address":"cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992","to_address":"cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt"}}]}"#;
         assert_eq!(&expect_result, &cosmos_tx_output.tx_data);
     }
 
+    #[test]
+    fn test_sign_direct_simulator() {
+        let transport = simulator::bind_test();
+
+        let sign_data = SignData {
+            account_number: "1234567890".to_string(),
+            chain_id: "cosmoshub-4".to_string(),
+            fee: StdFee {
+                amount: vec![Coin {
+                    amount: "750".to_string(),
+                    denom: "uatom".to_string(),
+                }],
+                gas: "30000".to_string(),
+            },
+            memo: "".to_string(),
+            msgs: json!([{
+                "type": "cosmos-sdk/MsgSend",
+                "value": {
+                    "amount": [{"amount": "1500000", "denom": "uatom"}],
+                    "from_address": "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992",
+                    "to_address": "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt"
+                }
+            }]),
+            sequence: "1".to_string(),
+        };
+        let pub_key =
+            hex::decode(CosmosAddress::get_pub_key(&transport, constants::COSMOS_PATH).unwrap())
+                .unwrap();
+        let sign_doc = sign_data.sign_doc(&pub_key).unwrap();
+
+        let input = CosmosTransaction {
+            sign_data,
+            path: constants::COSMOS_PATH.to_string(),
+            payment_dis: "1.5 ATOM".to_string(),
+            to_dis: "".to_string(),
+            fee_dis: "".to_string(),
+            from_dis: "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992".to_string(),
+        };
+        let output = input.sign_direct(&transport).unwrap();
+
+        let tx_raw_bytes = base64::decode(&output.tx_data).unwrap();
+        assert_eq!(
+            output.tx_hash,
+            sha256_hash(&tx_raw_bytes).to_hex().to_uppercase()
+        );
+        let tx_raw = TxRaw::decode(tx_raw_bytes.as_slice()).unwrap();
+        assert_eq!(tx_raw.body_bytes, sign_doc.body_bytes);
+        assert_eq!(tx_raw.auth_info_bytes, sign_doc.auth_info_bytes);
+
+        let mut sign_doc_bytes = vec![];
+        sign_doc.encode(&mut sign_doc_bytes).unwrap();
+        let secp = Secp256k1::verification_only();
+        assert!(secp
+            .verify(
+                &Message::from_slice(&sha256_hash(&sign_doc_bytes)).unwrap(),
+                &Signature::from_compact(&tx_raw.signatures[0]).unwrap(),
+                &PublicKey::from_slice(&pub_key).unwrap(),
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_sign_account_path_simulator() {
+        let transport = simulator::bind_test();
+
+        let path = "m/44'/118'/1'/0/0";
+        let pub_key = hex::decode(CosmosAddress::get_pub_key(&transport, path).unwrap()).unwrap();
+        let from = CosmosAddress::address_from_pubkey(&pub_key, "cosmos").unwrap();
+        assert_ne!(from, "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992");
+
+        let sign_data = || SignData {
+            account_number: "1234567890".to_string(),
+            chain_id: "cosmoshub-4".to_string(),
+            fee: StdFee {
+                amount: vec![],
+                gas: "30000".to_string(),
+            },
+            memo: "".to_string(),
+            msgs: json!([{
+                "type": "cosmos-sdk/MsgSend",
+                "value": {
+                    "amount": [{"amount": "1500000", "denom": "uatom"}],
+                    "from_address": from,
+                    "to_address": "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt"
+                }
+            }]),
+            sequence: "1".to_string(),
+        };
+        let input = CosmosTransaction {
+            sign_data: sign_data(),
+            path: path.to_string(),
+            payment_dis: "".to_string(),
+            to_dis: "".to_string(),
+            fee_dis: "".to_string(),
+            from_dis: from.clone(),
+        };
+        let sign_bytes = input.sign_data.sign_bytes().unwrap();
+        let output = input.sign(&transport).unwrap();
+        let std_tx: serde_json::Value = serde_json::from_str(&output.tx_data).unwrap();
+        assert_eq!(
+            std_tx["signatures"][0]["pub_key"]["value"],
+            base64::encode(&pub_key)
+        );
+        let signature =
+            base64::decode(std_tx["signatures"][0]["signature"].as_str().unwrap()).unwrap();
+        let secp = Secp256k1::verification_only();
+        assert!(secp
+            .verify(
+                &Message::from_slice(&sha256_hash(&sign_bytes)).unwrap(),
+                &Signature::from_compact(&signature).unwrap(),
+                &PublicKey::from_slice(&pub_key).unwrap(),
+            )
+            .is_ok());
+
+        //the key of the first account does not sign for this one
+        let input = CosmosTransaction {
+            sign_data: sign_data(),
+            path: constants::COSMOS_PATH.to_string(),
+            payment_dis: "".to_string(),
+            to_dis: "".to_string(),
+            fee_dis: "".to_string(),
+            from_dis: from.clone(),
+        };
+        assert_eq!(
+            format!("{}", input.sign_direct(&transport).err().unwrap()),
+            "imkey_address_mismatch_with_path"
+        );
+
+        //nor without from_dis, the sender of the msgs is checked as well
+        let input = CosmosTransaction {
+            sign_data: sign_data(),
+            path: constants::COSMOS_PATH.to_string(),
+            payment_dis: "".to_string(),
+            to_dis: "".to_string(),
+            fee_dis: "".to_string(),
+            from_dis: "".to_string(),
+        };
+        assert_eq!(
+            format!("{}", input.sign(&transport).err().unwrap()),
+            "imkey_address_mismatch_with_path"
+        );
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let value = json!({
+            "value": {"to_address": "cosmos1yeck", "amount": [{"denom": "uatom", "amount": "1"}]},
+            "type": "cosmos-sdk/MsgSend",
+            "memo": "<a&b>",
+            "gas": 200000
+        });
+        assert_eq!(
+            canonical_json(&value),
+            r#"{"gas":200000,"memo":"\u003ca\u0026b\u003e","type":"cosmos-sdk/MsgSend","value":{"amount":[{"amount":"1","denom":"uatom"}],"to_address":"cosmos1yeck"}}"#
+        );
+
+        let sign_data = SignData {
+            account_number: "1".to_string(),
+            chain_id: "osmosis-1".to_string(),
+            fee: StdFee {
+                amount: vec![],
+                gas: "200000".to_string(),
+            },
+            memo: "".to_string(),
+            msgs: json!([{"value": {"b": "2", "a": "1"}, "type": "t"}]),
+            sequence: "0".to_string(),
+        };
+        assert_eq!(
+            String::from_utf8(sign_data.sign_bytes().unwrap()).unwrap(),
+            r#"{"account_number":"1","chain_id":"osmosis-1","fee":{"amount":[],"gas":"200000"},"memo":"","msgs":[{"type":"t","value":{"a":"1","b":"2"}}],"sequence":"0"}"#
+        );
+    }
+
+    #[test]
+    fn test_display_info() {
+        let sign_data = SignData {
+            account_number: "1234567890".to_string(),
+            chain_id: "cosmoshub-3".to_string(),
+            fee: StdFee {
+                amount: vec![Coin {
+                    amount: "750".to_string(),
+                    denom: "uatom".to_string(),
+                }],
+                gas: "30000".to_string(),
+            },
+            memo: "".to_string(),
+            msgs: json!([{
+                "type": "cosmos-sdk/MsgSend",
+                "value": {
+                    "amount": [{"amount": "1500000", "denom": "uatom"}],
+                    "from_address": "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992",
+                    "to_address": "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt"
+                }
+            }]),
+            sequence: "1".to_string(),
+        };
+        let builder = sign_data.display_info().unwrap();
+        let display = builder.build();
+        assert_eq!(display.payment, "1.5 ATOM");
+        assert_eq!(
+            display.receiver,
+            "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt"
+        );
+        assert_eq!(display.fee, "0.00075 ATOM");
+
+        assert!(builder
+            .verify(
+                "1.500000 atom",
+                "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt",
+                "0.00075 atom"
+            )
+            .is_ok());
+        assert_eq!(
+            format!(
+                "{}",
+                builder
+                    .verify(
+                        "0.001 ATOM",
+                        "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt",
+                        ""
+                    )
+                    .err()
+                    .unwrap()
+            ),
+            "imkey_display_mismatch_with_data"
+        );
+        assert!(builder
+            .verify("", "cosmos1y0a8sc5ayv52f2fm5t7hr2g88qgljzk4jcz78f", "")
+            .is_err());
+
+        //a message the device cannot show is not signed blindly
+        let sign_data = SignData {
+            msgs: json!([
+                sign_data.msgs[0],
+                {
+                    "type": "cosmos-sdk/MsgVote",
+                    "value": {
+                        "option": "Yes",
+                        "proposal_id": "1",
+                        "voter": "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992"
+                    }
+                }
+            ]),
+            ..sign_data
+        };
+        assert_eq!(
+            format!("{}", sign_data.display_info().err().unwrap()),
+            "imkey_cosmos_msg_unsupported"
+        );
+    }
+
+    #[test]
+    fn test_signers() {
+        let sign_data = SignData {
+            account_number: "1".to_string(),
+            chain_id: "cosmoshub-4".to_string(),
+            fee: StdFee {
+                amount: vec![],
+                gas: "200000".to_string(),
+            },
+            memo: "".to_string(),
+            msgs: json!([
+                {
+                    "type": "cosmos-sdk/MsgSend",
+                    "value": {
+                        "amount": [],
+                        "from_address": "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992",
+                        "to_address": "cosmos1yeckxz7tapz34kjwnjxvmxzurerquhtrmxmuxt"
+                    }
+                },
+                {
+                    "type": "cosmos-sdk/MsgWithdrawDelegationReward",
+                    "value": {
+                        "delegator_address": "cosmos1y0a8sc5ayv52f2fm5t7hr2g88qgljzk4jcz78f",
+                        "validator_address": "cosmosvaloper1zkupr83hrzkn3up5elktzcq3tuft8nxsmwdqgp"
+                    }
+                }
+            ]),
+            sequence: "0".to_string(),
+        };
+        assert_eq!(
+            sign_data.signers().unwrap(),
+            vec![
+                "cosmos1ajz9y0x3wekez7tz2td2j6l2dftn28v26dd992",
+                "cosmos1y0a8sc5ayv52f2fm5t7hr2g88qgljzk4jcz78f"
+            ]
+        );
+
+        let sign_data = SignData {
+            msgs: json!([{"type": "cosmos-sdk/MsgSend", "value": {"amount": []}}]),
+            ..sign_data
+        };
+        assert!(sign_data.signers().is_err());
+    }
+
     #[test]
     fn test_sort_vec() {
         let mut vec = Vec::new();
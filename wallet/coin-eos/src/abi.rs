@@ -0,0 +1,447 @@
+use crate::serializer::{
+    json_str, json_u64, name_to_string, pack_bytes, pack_symbol, pack_varuint32, string_to_name,
+    Asset, Reader,
+};
+use crate::Result;
+use common::error::CoinError;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+const EOSIO_TOKEN_ABI: &str = r#"{
+    "structs": [
+        {"name": "transfer", "base": "", "fields": [
+            {"name": "from", "type": "name"},
+            {"name": "to", "type": "name"},
+            {"name": "quantity", "type": "asset"},
+            {"name": "memo", "type": "string"}]},
+        {"name": "issue", "base": "", "fields": [
+            {"name": "to", "type": "name"},
+            {"name": "quantity", "type": "asset"},
+            {"name": "memo", "type": "string"}]},
+        {"name": "retire", "base": "", "fields": [
+            {"name": "quantity", "type": "asset"},
+            {"name": "memo", "type": "string"}]},
+        {"name": "open", "base": "", "fields": [
+            {"name": "owner", "type": "name"},
+            {"name": "symbol", "type": "symbol"},
+            {"name": "ram_payer", "type": "name"}]},
+        {"name": "close", "base": "", "fields": [
+            {"name": "owner", "type": "name"},
+            {"name": "symbol", "type": "symbol"}]},
+        {"name": "create", "base": "", "fields": [
+            {"name": "issuer", "type": "name"},
+            {"name": "maximum_supply", "type": "asset"}]}
+    ],
+    "actions": [
+        {"name": "transfer", "type": "transfer"},
+        {"name": "issue", "type": "issue"},
+        {"name": "retire", "type": "retire"},
+        {"name": "open", "type": "open"},
+        {"name": "close", "type": "close"},
+        {"name": "create", "type": "create"}
+    ]
+}"#;
+
+const EOSIO_ABI: &str = r#"{
+    "types": [{"new_type_name": "account_name", "type": "name"}],
+    "structs": [
+        {"name": "delegatebw", "base": "", "fields": [
+            {"name": "from", "type": "account_name"},
+            {"name": "receiver", "type": "account_name"},
+            {"name": "stake_net_quantity", "type": "asset"},
+            {"name": "stake_cpu_quantity", "type": "asset"},
+            {"name": "transfer", "type": "bool"}]},
+        {"name": "undelegatebw", "base": "", "fields": [
+            {"name": "from", "type": "account_name"},
+            {"name": "receiver", "type": "account_name"},
+            {"name": "unstake_net_quantity", "type": "asset"},
+            {"name": "unstake_cpu_quantity", "type": "asset"}]},
+        {"name": "buyram", "base": "", "fields": [
+            {"name": "payer", "type": "account_name"},
+            {"name": "receiver", "type": "account_name"},
+            {"name": "quant", "type": "asset"}]},
+        {"name": "buyrambytes", "base": "", "fields": [
+            {"name": "payer", "type": "account_name"},
+            {"name": "receiver", "type": "account_name"},
+            {"name": "bytes", "type": "uint32"}]},
+        {"name": "sellram", "base": "", "fields": [
+            {"name": "account", "type": "account_name"},
+            {"name": "bytes", "type": "int64"}]},
+        {"name": "voteproducer", "base": "", "fields": [
+            {"name": "voter", "type": "account_name"},
+            {"name": "proxy", "type": "account_name"},
+            {"name": "producers", "type": "account_name[]"}]},
+        {"name": "refund", "base": "", "fields": [
+            {"name": "owner", "type": "account_name"}]},
+        {"name": "claimrewards", "base": "", "fields": [
+            {"name": "owner", "type": "account_name"}]}
+    ],
+    "actions": [
+        {"name": "delegatebw", "type": "delegatebw"},
+        {"name": "undelegatebw", "type": "undelegatebw"},
+        {"name": "buyram", "type": "buyram"},
+        {"name": "buyrambytes", "type": "buyrambytes"},
+        {"name": "sellram", "type": "sellram"},
+        {"name": "voteproducer", "type": "voteproducer"},
+        {"name": "refund", "type": "refund"},
+        {"name": "claimrewards", "type": "claimrewards"}
+    ]
+}"#;
+
+//a struct nests no deeper than this, so a cyclic abi can not overflow the stack
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone)]
+struct Struct {
+    base: String,
+    fields: Vec<(String, String)>,
+}
+
+/**
+the types, structs and actions of a contract abi, enough to pack the json data of an action
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Abi {
+    types: HashMap<String, String>,
+    structs: HashMap<String, Struct>,
+    actions: HashMap<String, String>,
+}
+
+impl Abi {
+    /**
+    an abi in the json form of get_abi, variants, tables and ricardian clauses are ignored
+    */
+    pub fn from_json(abi: &Value) -> Result<Abi> {
+        let list = |key: &str| match &abi[key] {
+            Value::Array(values) => Ok(values.clone()),
+            Value::Null => Ok(vec![]),
+            _ => Err(CoinError::ImkeyEosTransactionInvalid),
+        };
+        let mut parsed = Abi::default();
+        for alias in list("types")? {
+            parsed.types.insert(
+                json_str(&alias["new_type_name"])?.to_string(),
+                json_str(&alias["type"])?.to_string(),
+            );
+        }
+        for definition in list("structs")? {
+            let mut fields = vec![];
+            if let Value::Array(values) = &definition["fields"] {
+                for field in values {
+                    fields.push((
+                        json_str(&field["name"])?.to_string(),
+                        json_str(&field["type"])?.to_string(),
+                    ));
+                }
+            }
+            let base = definition["base"].as_str().unwrap_or_default().to_string();
+            parsed.structs.insert(
+                json_str(&definition["name"])?.to_string(),
+                Struct { base, fields },
+            );
+        }
+        for action in list("actions")? {
+            parsed.actions.insert(
+                json_str(&action["name"])?.to_string(),
+                json_str(&action["type"])?.to_string(),
+            );
+        }
+        Ok(parsed)
+    }
+
+    /**
+    the abi of eosio.token or of the eosio system contract
+    */
+    pub fn builtin(account: &str) -> Option<Abi> {
+        let abi = match account {
+            "eosio.token" => EOSIO_TOKEN_ABI,
+            "eosio" => EOSIO_ABI,
+            _ => return None,
+        };
+        Abi::from_json(&serde_json::from_str(abi).ok()?).ok()
+    }
+
+    fn action_type(&self, action: &str) -> Result<&str> {
+        Ok(self
+            .actions
+            .get(action)
+            .ok_or(CoinError::ImkeyEosActionUnsupported)?)
+    }
+
+    pub fn pack_action(&self, action: &str, data: &Value) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.pack(self.action_type(action)?, data, &mut buf, 0)?;
+        Ok(buf)
+    }
+
+    pub fn unpack_action(&self, action: &str, data: &[u8]) -> Result<Value> {
+        let mut reader = Reader::new(data);
+        let value = self.unpack(self.action_type(action)?, &mut reader, 0)?;
+        if !reader.is_empty() {
+            return Err(CoinError::ImkeyEosTransactionInvalid.into());
+        }
+        Ok(value)
+    }
+
+    fn resolve<'a>(&'a self, type_name: &'a str) -> &'a str {
+        let mut resolved = type_name;
+        for _ in 0..MAX_DEPTH {
+            match self.types.get(resolved) {
+                Some(alias) => resolved = alias,
+                None => break,
+            }
+        }
+        resolved
+    }
+
+    fn pack(&self, type_name: &str, value: &Value, buf: &mut Vec<u8>, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(CoinError::ImkeyEosActionUnsupported.into());
+        }
+        let type_name = self.resolve(type_name);
+        if type_name.ends_with("[]") {
+            let values = value
+                .as_array()
+                .ok_or(CoinError::ImkeyEosTransactionInvalid)?;
+            pack_varuint32(values.len() as u32, buf);
+            for item in values {
+                self.pack(&type_name[..type_name.len() - 2], item, buf, depth + 1)?;
+            }
+            return Ok(());
+        }
+        if type_name.ends_with('?') {
+            if value.is_null() {
+                buf.push(0);
+                return Ok(());
+            }
+            buf.push(1);
+            return self.pack(&type_name[..type_name.len() - 1], value, buf, depth + 1);
+        }
+        let unsigned = |max: u64| -> Result<u64> {
+            let number = json_u64(value)?;
+            if number > max {
+                return Err(CoinError::ImkeyEosTransactionInvalid.into());
+            }
+            Ok(number)
+        };
+        match type_name {
+            "bool" => buf.push(
+                value
+                    .as_bool()
+                    .ok_or(CoinError::ImkeyEosTransactionInvalid)? as u8,
+            ),
+            "uint8" => buf.push(unsigned(u64::from(u8::MAX))? as u8),
+            "uint16" => {
+                buf.extend_from_slice(&(unsigned(u64::from(u16::MAX))? as u16).to_le_bytes())
+            }
+            "uint32" => {
+                buf.extend_from_slice(&(unsigned(u64::from(u32::MAX))? as u32).to_le_bytes())
+            }
+            "uint64" => buf.extend_from_slice(&unsigned(u64::MAX)?.to_le_bytes()),
+            "varuint32" => pack_varuint32(unsigned(u64::from(u32::MAX))? as u32, buf),
+            "int64" => {
+                let number = match value {
+                    Value::Number(number) => number.as_i64(),
+                    Value::String(string) => string.parse::<i64>().ok(),
+                    _ => None,
+                };
+                let number = number.ok_or(CoinError::ImkeyEosTransactionInvalid)?;
+                buf.extend_from_slice(&number.to_le_bytes());
+            }
+            "name" => buf.extend_from_slice(&string_to_name(json_str(value)?)?.to_le_bytes()),
+            "asset" => Asset::parse(json_str(value)?)?.pack(buf)?,
+            "symbol" => {
+                //"4,SYS"
+                let symbol = json_str(value)?;
+                let comma = symbol
+                    .find(',')
+                    .ok_or(CoinError::ImkeyEosTransactionInvalid)?;
+                let precision = symbol[..comma]
+                    .parse::<u8>()
+                    .map_err(|_| CoinError::ImkeyEosTransactionInvalid)?;
+                if precision > 18 {
+                    return Err(CoinError::ImkeyEosTransactionInvalid.into());
+                }
+                pack_symbol(precision, &symbol[comma + 1..], buf)?;
+            }
+            "string" => pack_bytes(json_str(value)?.as_bytes(), buf),
+            "bytes" => pack_bytes(&hex::decode(json_str(value)?)?, buf),
+            "checksum256" => {
+                let checksum = hex::decode(json_str(value)?)?;
+                if checksum.len() != 32 {
+                    return Err(CoinError::ImkeyEosTransactionInvalid.into());
+                }
+                buf.extend_from_slice(&checksum);
+            }
+            _ => {
+                let definition = self
+                    .structs
+                    .get(type_name)
+                    .ok_or(CoinError::ImkeyEosActionUnsupported)?;
+                if !definition.base.is_empty() {
+                    self.pack(&definition.base, value, buf, depth + 1)?;
+                }
+                for (name, field_type) in &definition.fields {
+                    //a missing field packs as null, fine for optionals only
+                    self.pack(field_type, &value[name], buf, depth + 1)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn unpack(&self, type_name: &str, reader: &mut Reader, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(CoinError::ImkeyEosActionUnsupported.into());
+        }
+        let type_name = self.resolve(type_name);
+        if type_name.ends_with("[]") {
+            let mut values = vec![];
+            for _ in 0..reader.read_varuint32()? {
+                values.push(self.unpack(&type_name[..type_name.len() - 2], reader, depth + 1)?);
+            }
+            return Ok(Value::Array(values));
+        }
+        if type_name.ends_with('?') {
+            return match reader.read_u8()? {
+                0 => Ok(Value::Null),
+                1 => self.unpack(&type_name[..type_name.len() - 1], reader, depth + 1),
+                _ => Err(CoinError::ImkeyEosTransactionInvalid.into()),
+            };
+        }
+        let value = match type_name {
+            "bool" => match reader.read_u8()? {
+                0 => Value::Bool(false),
+                1 => Value::Bool(true),
+                _ => return Err(CoinError::ImkeyEosTransactionInvalid.into()),
+            },
+            "uint8" => Value::from(reader.read_u8()?),
+            "uint16" => Value::from(reader.read_u16()?),
+            "uint32" => Value::from(reader.read_u32()?),
+            "uint64" => Value::from(reader.read_u64()?),
+            "varuint32" => Value::from(reader.read_varuint32()?),
+            "int64" => Value::from(reader.read_u64()? as i64),
+            "name" => Value::from(name_to_string(reader.read_u64()?)),
+            "asset" => Value::from(Asset::read(reader)?.to_string()),
+            "symbol" => {
+                let (precision, symbol) = reader.read_symbol()?;
+                Value::from(format!("{},{}", precision, symbol))
+            }
+            "string" => Value::from(reader.read_string()?),
+            "bytes" => Value::from(hex::encode(reader.read_bytes()?)),
+            "checksum256" => Value::from(hex::encode(reader.read(32)?)),
+            _ => {
+                let definition = self
+                    .structs
+                    .get(type_name)
+                    .ok_or(CoinError::ImkeyEosActionUnsupported)?;
+                let mut object = Map::new();
+                if !definition.base.is_empty() {
+                    if let Value::Object(base) = self.unpack(&definition.base, reader, depth + 1)? {
+                        object.extend(base);
+                    }
+                }
+                for (name, field_type) in &definition.fields {
+                    object.insert(name.clone(), self.unpack(field_type, reader, depth + 1)?);
+                }
+                Value::Object(object)
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::abi::Abi;
+    use serde_json::json;
+
+    #[test]
+    fn builtin_test() {
+        let token = Abi::builtin("eosio.token").unwrap();
+        let transfer = json!({
+            "from": "imtoken",
+            "to": "tester222222",
+            "quantity": "1000.0000 SYS",
+            "memo": "memo"
+        });
+        let data = token.pack_action("transfer", &transfer).unwrap();
+        assert_eq!(
+            hex::encode(&data),
+            "000000602a48b374208410425c95b1ca80969800000000000453595300000000046d656d6f"
+        );
+        assert_eq!(token.unpack_action("transfer", &data).unwrap(), transfer);
+
+        let open = json!({"owner": "imtoken", "symbol": "4,EOS", "ram_payer": "imtoken"});
+        let data = token.pack_action("open", &open).unwrap();
+        assert_eq!(token.unpack_action("open", &data).unwrap(), open);
+
+        assert!(Abi::builtin("tethertether").is_none());
+        assert_eq!(
+            format!("{}", token.pack_action("burn", &open).err().unwrap()),
+            "imkey_eos_action_unsupported"
+        );
+    }
+
+    #[test]
+    fn system_test() {
+        let eosio = Abi::builtin("eosio").unwrap();
+        let delegatebw = json!({
+            "from": "imtoken",
+            "receiver": "tester222222",
+            "stake_net_quantity": "0.0100 EOS",
+            "stake_cpu_quantity": "1.0000 EOS",
+            "transfer": false
+        });
+        let data = eosio.pack_action("delegatebw", &delegatebw).unwrap();
+        assert_eq!(data.len(), 8 + 8 + 16 + 16 + 1);
+        assert_eq!(
+            eosio.unpack_action("delegatebw", &data).unwrap(),
+            delegatebw
+        );
+        assert!(eosio
+            .unpack_action("delegatebw", &data[..data.len() - 1])
+            .is_err());
+
+        let vote =
+            json!({"voter": "imtoken", "proxy": "", "producers": ["eoshuobipool", "eosnewyorkio"]});
+        let data = eosio.pack_action("voteproducer", &vote).unwrap();
+        assert_eq!(eosio.unpack_action("voteproducer", &data).unwrap(), vote);
+
+        let sellram = json!({"account": "imtoken", "bytes": -1});
+        let data = eosio.pack_action("sellram", &sellram).unwrap();
+        assert_eq!(hex::encode(&data[8..]), "ffffffffffffffff");
+    }
+
+    #[test]
+    fn from_json_test() {
+        let abi = Abi::from_json(&json!({
+            "types": [{"new_type_name": "amount", "type": "uint64"}],
+            "structs": [
+                {"name": "base", "base": "", "fields": [{"name": "owner", "type": "name"}]},
+                {"name": "pay", "base": "base", "fields": [
+                    {"name": "amounts", "type": "amount[]"},
+                    {"name": "note", "type": "string?"},
+                    {"name": "hash", "type": "checksum256"}]}
+            ],
+            "actions": [{"name": "pay", "type": "pay"}]
+        }))
+        .unwrap();
+        let pay = json!({
+            "owner": "imtoken",
+            "amounts": [1, 2],
+            "note": null,
+            "hash": "00".repeat(32)
+        });
+        let data = abi.pack_action("pay", &pay).unwrap();
+        assert_eq!(data.len(), 8 + 1 + 16 + 1 + 32);
+        assert_eq!(abi.unpack_action("pay", &data).unwrap(), pay);
+
+        let cyclic = Abi::from_json(&json!({
+            "structs": [{"name": "node", "base": "node", "fields": []}],
+            "actions": [{"name": "loop", "type": "node"}]
+        }))
+        .unwrap();
+        assert!(cyclic.pack_action("loop", &json!({})).is_err());
+    }
+}
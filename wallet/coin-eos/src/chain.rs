@@ -0,0 +1,130 @@
+use crate::Result;
+use common::constants;
+use common::error::CoinError;
+
+/**
+what an antelope chain fixes for signing: the chain id the signature commits to, the prefix of
+its legacy public keys and the path of its keys when the caller gives none
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainProfile {
+    pub name: &'static str,
+    pub chain_id: &'static str,
+    pub key_prefix: &'static str,
+    pub path: &'static str,
+}
+
+pub const EOS: ChainProfile = ChainProfile {
+    name: "eos",
+    chain_id: "aca376f206b8fc25a6ed44dbdc66547c36c6c33e3a119ffbeaef943642f0e906",
+    key_prefix: "EOS",
+    path: constants::EOS_PATH,
+};
+
+pub const WAX: ChainProfile = ChainProfile {
+    name: "wax",
+    chain_id: "1064487b3cd1a897ce03ae5b6a865651747e2e152090f99c1d19d44e01aea5a4",
+    key_prefix: "EOS",
+    path: constants::WAX_PATH,
+};
+
+//telos wallets derive on the eos path
+pub const TELOS: ChainProfile = ChainProfile {
+    name: "telos",
+    chain_id: "4667b205c6838ef70ff7988f6e8257e8be0e1284a2f59699054a018f743b1d11",
+    key_prefix: "EOS",
+    path: constants::EOS_PATH,
+};
+
+pub const JUNGLE: ChainProfile = ChainProfile {
+    name: "jungle",
+    chain_id: "73e4385a2708e6d7048834fbc1079f2fabb17b3c125b146af438971e90716c4d",
+    key_prefix: "EOS",
+    path: constants::EOS_PATH,
+};
+
+pub const CHAINS: [&ChainProfile; 4] = [&EOS, &WAX, &TELOS, &JUNGLE];
+
+/**
+the profile of a chain by name, eos when the name is empty
+*/
+pub fn profile(chain: &str) -> Result<&'static ChainProfile> {
+    if chain.is_empty() {
+        return Ok(&EOS);
+    }
+    Ok(CHAINS
+        .iter()
+        .find(|profile| profile.name.eq_ignore_ascii_case(chain))
+        .copied()
+        .ok_or(CoinError::ImkeyEosChainUnsupported)?)
+}
+
+impl ChainProfile {
+    /**
+    the path to sign with, the chain's own when the caller gives none
+    */
+    pub fn path<'a>(&self, path: &'a str) -> &'a str {
+        if path.is_empty() {
+            self.path
+        } else {
+            path
+        }
+    }
+}
+
+/**
+the chain id to sign for: the profile's one when empty, otherwise 32 bytes of hex that have to
+be the profile's one if the caller named a chain
+*/
+pub fn chain_id(chain: &str, chain_id: &str) -> Result<Vec<u8>> {
+    let profile = profile(chain)?;
+    if chain_id.is_empty() {
+        return Ok(hex::decode(profile.chain_id)?);
+    }
+    let bytes = hex::decode(chain_id).map_err(|_| CoinError::ImkeyEosChainIdMismatch)?;
+    if bytes.len() != 32 || (!chain.is_empty() && !chain_id.eq_ignore_ascii_case(profile.chain_id))
+    {
+        return Err(CoinError::ImkeyEosChainIdMismatch.into());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chain::{chain_id, profile, EOS, JUNGLE, WAX};
+    use common::constants;
+
+    #[test]
+    fn profile_test() {
+        assert_eq!(profile("").unwrap(), &EOS);
+        assert_eq!(profile("WAX").unwrap(), &WAX);
+        assert_eq!(profile("jungle").unwrap(), &JUNGLE);
+        assert_eq!(
+            format!("{}", profile("steem").err().unwrap()),
+            "imkey_eos_chain_unsupported"
+        );
+        assert_eq!(WAX.path(""), constants::WAX_PATH);
+        assert_eq!(WAX.path(constants::EOS_PATH), constants::EOS_PATH);
+    }
+
+    #[test]
+    fn chain_id_test() {
+        assert_eq!(hex::encode(chain_id("", "").unwrap()), EOS.chain_id);
+        assert_eq!(hex::encode(chain_id("wax", "").unwrap()), WAX.chain_id);
+        //without a named chain any chain id is signed for
+        assert_eq!(
+            hex::encode(chain_id("", JUNGLE.chain_id).unwrap()),
+            JUNGLE.chain_id
+        );
+        assert_eq!(
+            hex::encode(chain_id("jungle", &JUNGLE.chain_id.to_uppercase()).unwrap()),
+            JUNGLE.chain_id
+        );
+        for (chain, id) in &[("wax", EOS.chain_id), ("", "aca376f2"), ("", "not hex")] {
+            assert_eq!(
+                format!("{}", chain_id(chain, id).err().unwrap()),
+                "imkey_eos_chain_id_mismatch"
+            );
+        }
+    }
+}
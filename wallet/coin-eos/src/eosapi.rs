@@ -4,6 +4,12 @@ pub struct EosTxReq {
     pub path: std::string::String,
     #[prost(message, repeated, tag = "2")]
     pub sign_datas: ::std::vec::Vec<EosSignData>,
+    /// sign apdus to send for a canonical signature, 0 for the default
+    #[prost(uint32, tag = "3")]
+    pub max_sign_attempts: u32,
+    /// eos, wax, telos or jungle, eos when empty
+    #[prost(string, tag = "4")]
+    pub chain: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EosSignData {
@@ -19,6 +25,9 @@ pub struct EosSignData {
     pub from: std::string::String,
     #[prost(string, tag = "6")]
     pub payment: std::string::String,
+    /// an eosio json transaction, packed in place of tx_data when given
+    #[prost(string, tag = "7")]
+    pub transaction: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EosTxRes {
@@ -31,16 +40,25 @@ pub struct EosSignResult {
     pub hash: std::string::String,
     #[prost(string, repeated, tag = "2")]
     pub signs: ::std::vec::Vec<std::string::String>,
+    #[prost(string, tag = "3")]
+    pub packed_trx: std::string::String,
+    #[prost(string, tag = "4")]
+    pub packed_context_free_data: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EosPubkeyReq {
     #[prost(string, tag = "1")]
     pub path: std::string::String,
+    /// eos, wax, telos or jungle, eos when empty
+    #[prost(string, tag = "2")]
+    pub chain: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EosPubkeyRes {
     #[prost(string, tag = "1")]
     pub pubkey: std::string::String,
+    #[prost(string, tag = "2")]
+    pub pub_k1: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EosMessageSignReq {
@@ -52,6 +70,12 @@ pub struct EosMessageSignReq {
     pub is_hex: bool,
     #[prost(string, tag = "4")]
     pub pubkey: std::string::String,
+    /// sign apdus to send for a canonical signature, 0 for the default
+    #[prost(uint32, tag = "5")]
+    pub max_sign_attempts: u32,
+    /// eos, wax, telos or jungle, eos when empty
+    #[prost(string, tag = "6")]
+    pub chain: std::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EosMessageSignRes {
@@ -1,5 +1,9 @@
+pub mod abi;
+pub mod chain;
 pub mod eosapi;
 pub mod pubkey;
+pub mod serializer;
+pub mod signer;
 pub mod transaction;
 #[macro_use]
 extern crate failure;
@@ -1,93 +1,156 @@
+use crate::chain::{self, ChainProfile};
+use crate::eosapi::EosPubkeyRes;
 use crate::Result;
 use bitcoin::util::base58;
 use bitcoin_hashes::{ripemd160, Hash};
 use common::apdu::{ApduCheck, CoinCommonApdu, EosApdu};
 use common::{path, utility};
-use device::device_binding::KEY_MANAGER;
-use transport::message;
+use device::device_binding::key_manager;
+use transport::Transport;
+
+pub const K1_PREFIX: &str = "PUB_K1_";
 
 #[derive(Debug)]
 pub struct EosPubkey {}
 
 impl EosPubkey {
-    pub fn get_pubkey(path: &str) -> Result<String> {
-        path::check_path_validity(path).unwrap();
+    pub fn get_pubkey(transport: &dyn Transport, path: &str) -> Result<String> {
+        Ok(EosPubkey::get_pubkeys(transport, path, &chain::EOS)?.pubkey)
+    }
+
+    /**
+    the key at path in the legacy format of the chain and as PUB_K1_
+    */
+    pub fn get_pubkeys(
+        transport: &dyn Transport,
+        path: &str,
+        profile: &ChainProfile,
+    ) -> Result<EosPubkeyRes> {
+        path::check_path_validity(path)?;
 
         let select_apdu = EosApdu::select_applet();
-        let select_response = message::send_apdu(select_apdu)?;
+        let select_response = transport.send_apdu(select_apdu)?;
         ApduCheck::checke_response(&select_response)?;
 
         //get public key
-        let msg_pubkey = EosApdu::get_xpub(&path, true);
-        let res_msg_pubkey = message::send_apdu(msg_pubkey)?;
+        let msg_pubkey = EosApdu::get_xpub(path, true);
+        let res_msg_pubkey = transport.send_apdu(msg_pubkey)?;
         ApduCheck::checke_response(&res_msg_pubkey)?;
 
         let sign_source_val = &res_msg_pubkey[..194];
         let sign_result = &res_msg_pubkey[194..res_msg_pubkey.len() - 4];
 
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
 
         //use se public key verify sign
         let sign_verify_result = utility::secp256k1_sign_verify(
             &key_manager_obj.se_pub_key,
-            hex::decode(sign_result).unwrap().as_slice(),
-            hex::decode(sign_source_val).unwrap().as_slice(),
+            hex::decode(sign_result)?.as_slice(),
+            hex::decode(sign_source_val)?.as_slice(),
         )?;
         if !sign_verify_result {
             return Err(format_err!("imkey_signature_verify_fail"));
         }
 
-        //compressed key
-        let uncomprs_pubkey: String = res_msg_pubkey
-            .chars()
-            .take(res_msg_pubkey.len() - 4)
-            .collect();
-        let comprs_pubkey = utility::uncompress_pubkey_2_compress(&uncomprs_pubkey);
-
-        //checksum base58
-        let mut comprs_pubkey_slice = hex::decode(comprs_pubkey).expect("Decoding failed");
-        let pubkey_hash = ripemd160::Hash::hash(&comprs_pubkey_slice);
-        let check_sum = &pubkey_hash[0..4];
-        comprs_pubkey_slice.extend(check_sum);
-        let eos_pk = "EOS".to_owned() + base58::encode_slice(&comprs_pubkey_slice).as_ref();
-
-        Ok(eos_pk)
+        let comprs_pubkey = EosPubkey::compressed_from_response(&res_msg_pubkey)?;
+        Ok(EosPubkeyRes {
+            pubkey: encode_pubkey(&comprs_pubkey, profile.key_prefix),
+            pub_k1: encode_k1_pubkey(&comprs_pubkey),
+        })
     }
 
     pub fn pubkey_from_response(response: &str) -> Result<String> {
-        //compressed key
+        let comprs_pubkey = EosPubkey::compressed_from_response(response)?;
+        Ok(encode_pubkey(&comprs_pubkey, chain::EOS.key_prefix))
+    }
+
+    /**
+    the compressed key of a response starting with the uncompressed one
+    */
+    pub fn compressed_from_response(response: &str) -> Result<Vec<u8>> {
         let uncomprs_pubkey: String = response.chars().take(response.len() - 4).collect();
         let comprs_pubkey = utility::uncompress_pubkey_2_compress(&uncomprs_pubkey);
+        Ok(hex::decode(comprs_pubkey)?)
+    }
 
-        //checksum base58
-        let mut comprs_pubkey_slice = hex::decode(comprs_pubkey).expect("Decoding failed");
-        let pubkey_hash = ripemd160::Hash::hash(&comprs_pubkey_slice);
-        let check_sum = &pubkey_hash[0..4];
-        comprs_pubkey_slice.extend(check_sum);
-        let eos_pk = "EOS".to_owned() + base58::encode_slice(&comprs_pubkey_slice).as_ref();
-
-        Ok(eos_pk)
+    pub fn display_pubkey(transport: &dyn Transport, path: &str) -> Result<String> {
+        Ok(EosPubkey::display_pubkeys(transport, path, &chain::EOS)?.pubkey)
     }
-    pub fn display_pubkey(path: &str) -> Result<String> {
-        let pubkey = EosPubkey::get_pubkey(path).unwrap();
-        let reg_apdu = EosApdu::register_address(pubkey.as_bytes());
-        let res_reg = message::send_apdu(reg_apdu)?;
+
+    /**
+    shows the legacy key of the chain on the device
+    */
+    pub fn display_pubkeys(
+        transport: &dyn Transport,
+        path: &str,
+        profile: &ChainProfile,
+    ) -> Result<EosPubkeyRes> {
+        let pubkeys = EosPubkey::get_pubkeys(transport, path, profile)?;
+        let reg_apdu = EosApdu::register_address(pubkeys.pubkey.as_bytes());
+        let res_reg = transport.send_apdu(reg_apdu)?;
         ApduCheck::checke_response(&res_reg)?;
-        Ok(pubkey)
+        Ok(pubkeys)
     }
 }
 
+/**
+the legacy format: the prefix and base58 of the key and ripemd160 of it
+*/
+pub fn encode_pubkey(comprs_pubkey: &[u8], prefix: &str) -> String {
+    let mut pubkey = comprs_pubkey.to_vec();
+    let pubkey_hash = ripemd160::Hash::hash(comprs_pubkey);
+    pubkey.extend_from_slice(&pubkey_hash[0..4]);
+    prefix.to_owned() + &base58::encode_slice(&pubkey)
+}
+
+/**
+the PUB_K1_ format, the checksum also covers the key type
+*/
+pub fn encode_k1_pubkey(comprs_pubkey: &[u8]) -> String {
+    let mut pubkey = comprs_pubkey.to_vec();
+    let mut to_hash = comprs_pubkey.to_vec();
+    to_hash.extend_from_slice(b"K1");
+    let pubkey_hash = ripemd160::Hash::hash(&to_hash);
+    pubkey.extend_from_slice(&pubkey_hash[0..4]);
+    K1_PREFIX.to_owned() + &base58::encode_slice(&pubkey)
+}
+
+/**
+the compressed key of a PUB_K1_ key or a legacy one with the prefix, none if the checksum fails
+*/
+pub fn decode_pubkey(pubkey: &str, prefix: &str) -> Option<Vec<u8>> {
+    let (encoded, key_type): (&str, &[u8]) = if pubkey.starts_with(K1_PREFIX) {
+        (&pubkey[K1_PREFIX.len()..], b"K1")
+    } else if pubkey.starts_with(prefix) {
+        (&pubkey[prefix.len()..], b"")
+    } else {
+        return None;
+    };
+    let decoded = base58::from(encoded).ok()?;
+    if decoded.len() != 37 {
+        return None;
+    }
+    let (comprs_pubkey, check_sum) = decoded.split_at(33);
+    let mut to_hash = comprs_pubkey.to_vec();
+    to_hash.extend_from_slice(key_type);
+    if &ripemd160::Hash::hash(&to_hash)[0..4] != check_sum {
+        return None;
+    }
+    Some(comprs_pubkey.to_vec())
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::pubkey::EosPubkey;
+    use crate::pubkey::{decode_pubkey, encode_k1_pubkey, encode_pubkey, EosPubkey};
     use common::constants;
     use device::device_binding::bind_test;
 
     #[test]
     fn test_get_pubkey() {
-        bind_test();
+        let transport = bind_test();
 
-        let pubkey = EosPubkey::get_pubkey(constants::EOS_PATH);
+        let pubkey = EosPubkey::get_pubkey(&transport, constants::EOS_PATH);
         assert_eq!(
             format!("{}", pubkey.unwrap()),
             "EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF"
@@ -104,11 +167,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn pubkey_format_test() {
+        let comprs_pubkey =
+            hex::decode("03aaf80e479aac0813b17950c390a16438b307aee9a814689d6706be4fb4a4e30a")
+                .unwrap();
+        let legacy = "EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF";
+        let k1 = "PUB_K1_88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAsaXqhu";
+        assert_eq!(encode_pubkey(&comprs_pubkey, "EOS"), legacy);
+        assert_eq!(encode_k1_pubkey(&comprs_pubkey), k1);
+        assert_eq!(decode_pubkey(legacy, "EOS"), Some(comprs_pubkey.clone()));
+        assert_eq!(decode_pubkey(k1, "EOS"), Some(comprs_pubkey.clone()));
+        assert_eq!(
+            decode_pubkey(&encode_pubkey(&comprs_pubkey, "FIO"), "FIO"),
+            Some(comprs_pubkey)
+        );
+
+        //a legacy checksum does not pass for PUB_K1_, nor a prefix of another chain
+        assert_eq!(
+            decode_pubkey(&format!("PUB_K1_{}", &legacy[3..]), "EOS"),
+            None
+        );
+        assert_eq!(decode_pubkey(legacy, "FIO"), None);
+        assert_eq!(decode_pubkey("ERROR PUBKEY", "EOS"), None);
+    }
+
     #[test]
     fn test_display_pubkey() {
-        bind_test();
+        let transport = bind_test();
 
-        let pubkey = EosPubkey::display_pubkey(constants::EOS_PATH);
+        let pubkey = EosPubkey::display_pubkey(&transport, constants::EOS_PATH);
         assert_eq!(
             format!("{}", pubkey.unwrap()),
             "EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF"
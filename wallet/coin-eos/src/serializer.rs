@@ -0,0 +1,810 @@
+use crate::abi::Abi;
+use crate::Result;
+use common::display::{Amount, DisplayInfoBuilder};
+use common::error::CoinError;
+use common::utility::sha256_hash;
+use serde_json::Value;
+use std::fmt;
+
+const NAME_CHARS: &[u8] = b".12345abcdefghijklmnopqrstuvwxyz";
+
+/**
+an action of a packed transaction, the data is still serialized with the contract's abi
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Action {
+    pub account: String,
+    pub name: String,
+    pub authorization: Vec<(String, String)>,
+    pub data: Vec<u8>,
+}
+
+/**
+the token transfer of an eosio.token like contract
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub from: String,
+    pub to: String,
+    pub quantity: Amount,
+    pub memo: String,
+}
+
+/**
+an amount of a token with its precision and symbol, "1000.0000 SYS"
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asset {
+    pub amount: i64,
+    pub precision: u8,
+    pub symbol: String,
+}
+
+impl Asset {
+    pub fn parse(asset: &str) -> Result<Asset> {
+        let mut parts = asset.trim().splitn(2, ' ');
+        let number = parts.next().unwrap_or_default();
+        let symbol = parts.next().unwrap_or_default().trim();
+        let (integer, fraction) = match number.find('.') {
+            Some(dot) => (&number[..dot], &number[dot + 1..]),
+            None => (number, ""),
+        };
+        let digits = format!("{}{}", integer, fraction);
+        if fraction.len() > 18 || integer.trim_start_matches('-').is_empty() {
+            return Err(CoinError::ImkeyEosTransactionInvalid.into());
+        }
+        let amount = digits
+            .parse::<i64>()
+            .map_err(|_| CoinError::ImkeyEosTransactionInvalid)?;
+        check_symbol(symbol)?;
+        Ok(Asset {
+            amount,
+            precision: fraction.len() as u8,
+            symbol: symbol.to_string(),
+        })
+    }
+
+    pub fn pack(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(&self.amount.to_le_bytes());
+        pack_symbol(self.precision, &self.symbol, buf)
+    }
+
+    pub(crate) fn read(reader: &mut Reader) -> Result<Asset> {
+        let amount = reader.read_u64()? as i64;
+        let (precision, symbol) = reader.read_symbol()?;
+        Ok(Asset {
+            amount,
+            precision,
+            symbol,
+        })
+    }
+
+    /**
+    the amount to show, none for a negative one
+    */
+    pub fn to_amount(&self) -> Option<Amount> {
+        if self.amount < 0 {
+            return None;
+        }
+        Some(Amount::new(
+            self.amount as u128,
+            u32::from(self.precision),
+            &self.symbol,
+        ))
+    }
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.amount < 0 { "-" } else { "" };
+        let digits = format!(
+            "{:0>width$}",
+            (i128::from(self.amount)).abs(),
+            width = self.precision as usize + 1
+        );
+        let (integer, fraction) = digits.split_at(digits.len() - self.precision as usize);
+        if fraction.is_empty() {
+            write!(f, "{}{} {}", sign, integer, self.symbol)
+        } else {
+            write!(f, "{}{}.{} {}", sign, integer, fraction, self.symbol)
+        }
+    }
+}
+
+fn check_symbol(symbol: &str) -> Result<()> {
+    if symbol.is_empty() || symbol.len() > 7 || !symbol.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(CoinError::ImkeyEosTransactionInvalid.into());
+    }
+    Ok(())
+}
+
+pub(crate) fn pack_symbol(precision: u8, symbol: &str, buf: &mut Vec<u8>) -> Result<()> {
+    check_symbol(symbol)?;
+    buf.push(precision);
+    let mut code = [0u8; 7];
+    code[..symbol.len()].copy_from_slice(symbol.as_bytes());
+    buf.extend_from_slice(&code);
+    Ok(())
+}
+
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, position: 0 }
+    }
+
+    pub(crate) fn read(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() - self.position < len {
+            return Err(CoinError::ImkeyEosTransactionInvalid.into());
+        }
+        let bytes = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read(1)?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16> {
+        let mut bytes = [0u8; 2];
+        bytes.copy_from_slice(self.read(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.read(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.read(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn read_varuint32(&mut self) -> Result<u32> {
+        let mut value = 0u64;
+        for shift in (0..35).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                if value > u64::from(u32::MAX) {
+                    break;
+                }
+                return Ok(value as u32);
+            }
+        }
+        Err(CoinError::ImkeyEosTransactionInvalid.into())
+    }
+
+    pub(crate) fn read_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_varuint32()? as usize;
+        self.read(len)
+    }
+
+    pub(crate) fn read_string(&mut self) -> Result<String> {
+        let bytes = self.read_bytes()?;
+        let string =
+            String::from_utf8(bytes.to_vec()).map_err(|_| CoinError::ImkeyEosTransactionInvalid)?;
+        Ok(string)
+    }
+
+    pub(crate) fn read_name(&mut self) -> Result<String> {
+        Ok(name_to_string(self.read_u64()?))
+    }
+
+    pub(crate) fn read_symbol(&mut self) -> Result<(u8, String)> {
+        let precision = self.read_u8()?;
+        let code = self.read(7)?;
+        let symbol = String::from_utf8_lossy(code)
+            .trim_end_matches('\u{0}')
+            .to_string();
+        check_symbol(&symbol)?;
+        if precision > 18 {
+            return Err(CoinError::ImkeyEosTransactionInvalid.into());
+        }
+        Ok((precision, symbol))
+    }
+
+    fn read_actions(&mut self) -> Result<Vec<Action>> {
+        let count = self.read_varuint32()?;
+        let mut actions = vec![];
+        for _ in 0..count {
+            let account = self.read_name()?;
+            let name = self.read_name()?;
+            let mut authorization = vec![];
+            for _ in 0..self.read_varuint32()? {
+                authorization.push((self.read_name()?, self.read_name()?));
+            }
+            let data = self.read_bytes()?.to_vec();
+            actions.push(Action {
+                account,
+                name,
+                authorization,
+                data,
+            });
+        }
+        Ok(actions)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.position == self.data.len()
+    }
+}
+
+pub(crate) fn pack_varuint32(value: u32, buf: &mut Vec<u8>) {
+    let mut value = value;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+pub(crate) fn pack_bytes(data: &[u8], buf: &mut Vec<u8>) {
+    pack_varuint32(data.len() as u32, buf);
+    buf.extend_from_slice(data);
+}
+
+/**
+the base32 form of an eos name, trailing dots are dropped
+*/
+pub fn name_to_string(value: u64) -> String {
+    let mut chars = [b'.'; 13];
+    let mut rest = value;
+    for i in 0..13 {
+        let (mask, bits) = if i == 0 { (0x0f, 4) } else { (0x1f, 5) };
+        chars[12 - i] = NAME_CHARS[(rest & mask) as usize];
+        rest >>= bits;
+    }
+    String::from_utf8_lossy(&chars)
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/**
+the value of an eos name, up to 12 characters of a-z, 1-5 and dots and a 13th of a-j, 1-5
+*/
+pub fn string_to_name(name: &str) -> Result<u64> {
+    if name.len() > 13 {
+        return Err(CoinError::ImkeyEosTransactionInvalid.into());
+    }
+    let mut value = 0u64;
+    for (i, c) in name.bytes().enumerate() {
+        let symbol = NAME_CHARS
+            .iter()
+            .position(|known| *known == c)
+            .ok_or(CoinError::ImkeyEosTransactionInvalid)? as u64;
+        if i < 12 {
+            value |= symbol << (64 - 5 * (i + 1));
+        } else if symbol < 16 {
+            value |= symbol;
+        } else {
+            return Err(CoinError::ImkeyEosTransactionInvalid.into());
+        }
+    }
+    //trailing dots have no value of their own
+    if name_to_string(value) != name {
+        return Err(CoinError::ImkeyEosTransactionInvalid.into());
+    }
+    Ok(value)
+}
+
+/**
+the seconds since 1970 of an utc time as "2018-05-24T08:33:09", milliseconds are allowed
+*/
+pub fn parse_time_point_sec(time: &str) -> Result<u32> {
+    let invalid = || CoinError::ImkeyEosTransactionInvalid;
+    let time = time.trim_end_matches('Z');
+    let time = match time.find('.') {
+        Some(dot) => &time[..dot],
+        None => time,
+    };
+    let numbers: Vec<i64> = time
+        .split(&['-', 'T', ':'][..])
+        .map(|number| number.parse::<i64>().map_err(|_| invalid()))
+        .collect::<std::result::Result<Vec<i64>, CoinError>>()?;
+    if numbers.len() != 6 {
+        return Err(invalid().into());
+    }
+    let (year, month, day) = (numbers[0], numbers[1], numbers[2]);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid().into());
+    }
+    //days from civil, the proleptic gregorian calendar
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds = days * 86400 + numbers[3] * 3600 + numbers[4] * 60 + numbers[5];
+    if seconds < 0 || seconds > i64::from(u32::MAX) {
+        return Err(invalid().into());
+    }
+    Ok(seconds as u32)
+}
+
+/**
+an unsigned number of a json transaction, given as number or as string
+*/
+pub(crate) fn json_u64(value: &Value) -> Result<u64> {
+    let number = match value {
+        Value::Number(number) => number.as_u64(),
+        Value::String(string) => string.parse::<u64>().ok(),
+        _ => None,
+    };
+    Ok(number.ok_or(CoinError::ImkeyEosTransactionInvalid)?)
+}
+
+pub(crate) fn json_str(value: &Value) -> Result<&str> {
+    Ok(value
+        .as_str()
+        .ok_or(CoinError::ImkeyEosTransactionInvalid)?)
+}
+
+fn json_array(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Array(values) => Ok(values.clone()),
+        Value::Null => Ok(vec![]),
+        _ => Err(CoinError::ImkeyEosTransactionInvalid.into()),
+    }
+}
+
+fn pack_json_actions(actions: &Value, buf: &mut Vec<u8>) -> Result<()> {
+    let actions = json_array(actions)?;
+    pack_varuint32(actions.len() as u32, buf);
+    for action in &actions {
+        let account = json_str(&action["account"])?;
+        let name = json_str(&action["name"])?;
+        buf.extend_from_slice(&string_to_name(account)?.to_le_bytes());
+        buf.extend_from_slice(&string_to_name(name)?.to_le_bytes());
+        let authorization = json_array(&action["authorization"])?;
+        pack_varuint32(authorization.len() as u32, buf);
+        for level in &authorization {
+            buf.extend_from_slice(&string_to_name(json_str(&level["actor"])?)?.to_le_bytes());
+            buf.extend_from_slice(&string_to_name(json_str(&level["permission"])?)?.to_le_bytes());
+        }
+        //hex data is already packed, json data is packed with the contract's abi
+        let data = match &action["data"] {
+            Value::String(data) => hex::decode(data)?,
+            data => Abi::builtin(account)
+                .ok_or(CoinError::ImkeyEosActionUnsupported)?
+                .pack_action(name, data)?,
+        };
+        pack_bytes(&data, buf);
+    }
+    Ok(())
+}
+
+/**
+the packed_trx of an eosio json transaction: the header, the context free actions, the actions and
+the extensions. the data of an action is hex or json of the eosio.token and eosio system abis
+*/
+pub fn pack_transaction(transaction: &Value) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    let expiration = parse_time_point_sec(json_str(&transaction["expiration"])?)?;
+    buf.extend_from_slice(&expiration.to_le_bytes());
+    let ref_block_num = json_u64(&transaction["ref_block_num"])?;
+    let ref_block_prefix = json_u64(&transaction["ref_block_prefix"])?;
+    if ref_block_num > u64::from(u16::MAX) || ref_block_prefix > u64::from(u32::MAX) {
+        return Err(CoinError::ImkeyEosTransactionInvalid.into());
+    }
+    buf.extend_from_slice(&(ref_block_num as u16).to_le_bytes());
+    buf.extend_from_slice(&(ref_block_prefix as u32).to_le_bytes());
+
+    let optional = |field: &str| match &transaction[field] {
+        Value::Null => Ok(0),
+        value => json_u64(value),
+    };
+    let max_net_usage_words = optional("max_net_usage_words")?;
+    let max_cpu_usage_ms = optional("max_cpu_usage_ms")?;
+    let delay_sec = optional("delay_sec")?;
+    if max_net_usage_words > u64::from(u32::MAX)
+        || max_cpu_usage_ms > u64::from(u8::MAX)
+        || delay_sec > u64::from(u32::MAX)
+    {
+        return Err(CoinError::ImkeyEosTransactionInvalid.into());
+    }
+    pack_varuint32(max_net_usage_words as u32, &mut buf);
+    buf.push(max_cpu_usage_ms as u8);
+    pack_varuint32(delay_sec as u32, &mut buf);
+
+    pack_json_actions(&transaction["context_free_actions"], &mut buf)?;
+    pack_json_actions(&transaction["actions"], &mut buf)?;
+
+    //extensions are pairs of a type and hex data
+    let extensions = json_array(&transaction["transaction_extensions"])?;
+    pack_varuint32(extensions.len() as u32, &mut buf);
+    for extension in &extensions {
+        let extension_type = json_u64(&extension[0])?;
+        if extension_type > u64::from(u16::MAX) {
+            return Err(CoinError::ImkeyEosTransactionInvalid.into());
+        }
+        buf.extend_from_slice(&(extension_type as u16).to_le_bytes());
+        pack_bytes(&hex::decode(json_str(&extension[1])?)?, &mut buf);
+    }
+    Ok(buf)
+}
+
+/**
+the context free data of a json transaction, hex strings
+*/
+pub fn context_free_data(transaction: &Value) -> Result<Vec<Vec<u8>>> {
+    json_array(&transaction["context_free_data"])?
+        .iter()
+        .map(|data| Ok(hex::decode(json_str(data)?)?))
+        .collect()
+}
+
+/**
+the packed_context_free_data to push, empty without context free data
+*/
+pub fn pack_context_free_data(context_free_data: &[Vec<u8>]) -> Vec<u8> {
+    if context_free_data.is_empty() {
+        return vec![];
+    }
+    let mut buf = vec![];
+    pack_varuint32(context_free_data.len() as u32, &mut buf);
+    for data in context_free_data {
+        pack_bytes(data, &mut buf);
+    }
+    buf
+}
+
+/**
+what the signature commits to for the context free data, 32 zero bytes without any
+*/
+pub fn context_free_data_digest(context_free_data: &[Vec<u8>]) -> Vec<u8> {
+    if context_free_data.is_empty() {
+        return vec![0; 32];
+    }
+    sha256_hash(&pack_context_free_data(context_free_data))
+}
+
+/**
+the actions of a packed transaction, context free actions are not signed for by the device
+*/
+pub fn unpack_actions(packed_trx: &[u8]) -> Result<Vec<Action>> {
+    let mut reader = Reader::new(packed_trx);
+    //expiration, ref_block_num and ref_block_prefix
+    reader.read(10)?;
+    //max_net_usage_words, max_cpu_usage_ms and delay_sec
+    reader.read_varuint32()?;
+    reader.read_u8()?;
+    reader.read_varuint32()?;
+    if !reader.read_actions()?.is_empty() {
+        return Err(CoinError::ImkeyEosTransactionInvalid.into());
+    }
+    let actions = reader.read_actions()?;
+    //transaction_extensions
+    for _ in 0..reader.read_varuint32()? {
+        reader.read(2)?;
+        reader.read_bytes()?;
+    }
+    if !reader.is_empty() {
+        return Err(CoinError::ImkeyEosTransactionInvalid.into());
+    }
+    Ok(actions)
+}
+
+impl Action {
+    /**
+    the transfer of a transfer action, none if the data is not a token transfer
+    */
+    pub fn transfer(&self) -> Option<Transfer> {
+        if self.name != "transfer" {
+            return None;
+        }
+        let mut reader = Reader::new(&self.data);
+        let from = reader.read_name().ok()?;
+        let to = reader.read_name().ok()?;
+        let quantity = Asset::read(&mut reader).ok()?.to_amount()?;
+        let memo = reader.read_string().ok()?;
+        if !reader.is_empty() {
+            return None;
+        }
+        Some(Transfer {
+            from,
+            to,
+            quantity,
+            memo,
+        })
+    }
+
+    /**
+    the assets and the receiver of the resource actions of the eosio system contract
+    */
+    fn resources(&self) -> Result<Option<(Vec<Asset>, String)>> {
+        let (assets, receiver): (&[&str], &str) = match (self.account.as_str(), self.name.as_str())
+        {
+            ("eosio", "delegatebw") => (&["stake_net_quantity", "stake_cpu_quantity"], "receiver"),
+            ("eosio", "undelegatebw") => (
+                &["unstake_net_quantity", "unstake_cpu_quantity"],
+                "receiver",
+            ),
+            ("eosio", "buyram") => (&["quant"], "receiver"),
+            _ => return Ok(None),
+        };
+        let abi = Abi::builtin(&self.account).ok_or(CoinError::ImkeyEosActionUnsupported)?;
+        let data = abi.unpack_action(&self.name, &self.data)?;
+        let assets = assets
+            .iter()
+            .map(|field| Asset::parse(json_str(&data[*field])?))
+            .collect::<Result<Vec<Asset>>>()?;
+        Ok(Some((assets, json_str(&data[receiver])?.to_string())))
+    }
+}
+
+/**
+the payments and receivers of the token transfers and the resource actions in a packed transaction.
+a transfer of a token other than eosio.token also shows its contract, any other action is refused
+as the device could not show what it does
+*/
+pub fn display_info(packed_trx: &[u8]) -> Result<DisplayInfoBuilder> {
+    let mut builder = DisplayInfoBuilder::new();
+    for action in unpack_actions(packed_trx)? {
+        if let Some(transfer) = action.transfer() {
+            builder = builder.payment(transfer.quantity).receiver(&transfer.to);
+            if action.account != "eosio.token" {
+                builder = builder.receiver(&action.account);
+            }
+        } else if let Some((assets, receiver)) = action.resources()? {
+            for asset in &assets {
+                let amount = asset
+                    .to_amount()
+                    .ok_or(CoinError::ImkeyEosTransactionInvalid)?;
+                builder = builder.payment(amount);
+            }
+            builder = builder.receiver(&receiver);
+        } else {
+            return Err(CoinError::ImkeyEosActionUnsupported.into());
+        }
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializer::{
+        context_free_data, context_free_data_digest, display_info, name_to_string,
+        pack_context_free_data, pack_transaction, parse_time_point_sec, string_to_name,
+        unpack_actions, Asset,
+    };
+    use common::display::Amount;
+    use serde_json::json;
+
+    const TX_DATA: &str = "c578065b93aec6a7c811000000000100a6823403ea3055000000572d3ccdcd01000000602a48b37400000000a8ed323225000000602a48b374208410425c95b1ca80969800000000000453595300000000046d656d6f00";
+
+    #[test]
+    fn name_to_string_test() {
+        assert_eq!(name_to_string(0x5530ea033482a600), "eosio.token");
+        assert_eq!(name_to_string(0xcdcd3c2d57000000), "transfer");
+        assert_eq!(name_to_string(0), "");
+
+        for name in &[
+            "eosio.token",
+            "transfer",
+            "tester222222",
+            "a.b.c",
+            "zzzzzzzzzzzzj",
+            "",
+        ] {
+            assert_eq!(&name_to_string(string_to_name(name).unwrap()), name);
+        }
+        assert_eq!(string_to_name("eosio.token").unwrap(), 0x5530ea033482a600);
+        for name in &[
+            "Eosio",
+            "eosio6",
+            "eosio.",
+            "zzzzzzzzzzzzz",
+            "aaaaaaaaaaaaaa",
+        ] {
+            assert!(string_to_name(name).is_err());
+        }
+    }
+
+    #[test]
+    fn asset_test() {
+        let asset = Asset::parse("1000.0000 SYS").unwrap();
+        assert_eq!(asset.amount, 10_000_000);
+        assert_eq!(asset.precision, 4);
+        assert_eq!(asset.to_string(), "1000.0000 SYS");
+        let mut buf = vec![];
+        asset.pack(&mut buf).unwrap();
+        assert_eq!(hex::encode(buf), "80969800000000000453595300000000");
+
+        assert_eq!(Asset::parse("-0.01 EOS").unwrap().to_string(), "-0.01 EOS");
+        assert_eq!(Asset::parse("5 WAX").unwrap().to_string(), "5 WAX");
+        for asset in &["1.0 eos", "1.0", ".5 EOS", "1.0 EOSEOSEOS"] {
+            assert!(Asset::parse(asset).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_time_point_sec_test() {
+        assert_eq!(parse_time_point_sec("1970-01-01T00:00:00").unwrap(), 0);
+        assert_eq!(
+            parse_time_point_sec("2018-05-24T08:33:09").unwrap(),
+            0x5b0678c5
+        );
+        assert_eq!(
+            parse_time_point_sec("2018-05-24T08:33:09.500Z").unwrap(),
+            0x5b0678c5
+        );
+        assert_eq!(
+            parse_time_point_sec("2020-02-29T12:00:00").unwrap(),
+            1_582_977_600
+        );
+        assert!(parse_time_point_sec("2018-13-24T08:33:09").is_err());
+        assert!(parse_time_point_sec("yesterday").is_err());
+    }
+
+    #[test]
+    fn pack_transaction_test() {
+        let transaction = json!({
+            "expiration": "2018-05-24T08:33:09",
+            "ref_block_num": 44691,
+            "ref_block_prefix": 298362822,
+            "max_net_usage_words": 0,
+            "max_cpu_usage_ms": 0,
+            "delay_sec": 0,
+            "context_free_actions": [],
+            "actions": [{
+                "account": "eosio.token",
+                "name": "transfer",
+                "authorization": [{"actor": "imtoken", "permission": "active"}],
+                "data": {
+                    "from": "imtoken",
+                    "to": "tester222222",
+                    "quantity": "1000.0000 SYS",
+                    "memo": "memo"
+                }
+            }],
+            "transaction_extensions": []
+        });
+        assert_eq!(
+            hex::encode(pack_transaction(&transaction).unwrap()),
+            TX_DATA
+        );
+
+        //packed data is taken as it is
+        let mut packed = transaction.clone();
+        packed["actions"][0]["data"] =
+            json!("000000602a48b374208410425c95b1ca80969800000000000453595300000000046d656d6f");
+        packed["ref_block_prefix"] = json!("298362822");
+        assert_eq!(hex::encode(pack_transaction(&packed).unwrap()), TX_DATA);
+
+        let mut unknown = transaction.clone();
+        unknown["actions"][0]["account"] = json!("tethertether");
+        assert_eq!(
+            format!("{}", pack_transaction(&unknown).err().unwrap()),
+            "imkey_eos_action_unsupported"
+        );
+        let mut invalid = transaction;
+        invalid["actions"][0]["data"]["quantity"] = json!("1000 sys");
+        assert!(pack_transaction(&invalid).is_err());
+    }
+
+    #[test]
+    fn context_free_data_test() {
+        assert_eq!(context_free_data_digest(&[]), vec![0; 32]);
+        assert!(pack_context_free_data(&[]).is_empty());
+
+        let data = context_free_data(&json!({"context_free_data": ["0102", ""]})).unwrap();
+        assert_eq!(hex::encode(pack_context_free_data(&data)), "0202010200");
+        assert_eq!(
+            hex::encode(context_free_data_digest(&data)),
+            hex::encode(common::utility::sha256_hash(
+                &hex::decode("0202010200").unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn unpack_actions_test() {
+        let actions = unpack_actions(&hex::decode(TX_DATA).unwrap()).unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].account, "eosio.token");
+        assert_eq!(actions[0].name, "transfer");
+        assert_eq!(
+            actions[0].authorization,
+            vec![("imtoken".to_string(), "active".to_string())]
+        );
+
+        let transfer = actions[0].transfer().unwrap();
+        assert_eq!(transfer.from, "imtoken");
+        assert_eq!(transfer.to, "tester222222");
+        assert_eq!(transfer.quantity, Amount::new(10_000_000, 4, "SYS"));
+        assert_eq!(transfer.memo, "memo");
+
+        let display = display_info(&hex::decode(TX_DATA).unwrap())
+            .unwrap()
+            .build();
+        assert_eq!(display.payment, "1000 SYS");
+        assert_eq!(display.receiver, "tester222222");
+    }
+
+    #[test]
+    fn display_info_resources_test() {
+        let transaction = json!({
+            "expiration": "2018-05-24T08:33:09",
+            "ref_block_num": 44691,
+            "ref_block_prefix": 298362822,
+            "actions": [{
+                "account": "eosio",
+                "name": "delegatebw",
+                "authorization": [{"actor": "imtoken", "permission": "active"}],
+                "data": {
+                    "from": "imtoken",
+                    "receiver": "tester222222",
+                    "stake_net_quantity": "0.0100 EOS",
+                    "stake_cpu_quantity": "1.0000 EOS",
+                    "transfer": false
+                }
+            }, {
+                "account": "eosio",
+                "name": "buyram",
+                "authorization": [{"actor": "imtoken", "permission": "active"}],
+                "data": {"payer": "imtoken", "receiver": "imtoken", "quant": "0.5000 EOS"}
+            }]
+        });
+        let packed_trx = pack_transaction(&transaction).unwrap();
+        let display = display_info(&packed_trx).unwrap().build();
+        assert_eq!(display.payment, "0.01 EOS, 1 EOS, 0.5 EOS");
+        assert_eq!(display.receiver, "tester222222, imtoken");
+    }
+
+    #[test]
+    fn display_info_contract_test() {
+        let transaction = |account: &str, name: &str| {
+            json!({
+                "expiration": "2018-05-24T08:33:09",
+                "ref_block_num": 44691,
+                "ref_block_prefix": 298362822,
+                "actions": [{
+                    "account": account,
+                    "name": name,
+                    "authorization": [{"actor": "imtoken", "permission": "active"}],
+                    "data": "000000602a48b374208410425c95b1ca80969800000000000453595300000000046d656d6f"
+                }]
+            })
+        };
+        //anyone can deploy a transfer action, its contract tells the tokens apart
+        let packed_trx = pack_transaction(&transaction("eosiotoken12", "transfer")).unwrap();
+        let display = display_info(&packed_trx).unwrap().build();
+        assert_eq!(display.payment, "1000 SYS");
+        assert_eq!(display.receiver, "tester222222, eosiotoken12");
+
+        //an action the device cannot show is not signed blindly
+        let packed_trx = pack_transaction(&transaction("eosio", "voteproducer")).unwrap();
+        assert_eq!(
+            format!("{}", display_info(&packed_trx).err().unwrap()),
+            "imkey_eos_action_unsupported"
+        );
+    }
+
+    #[test]
+    fn unpack_actions_invalid_test() {
+        let tx_data = hex::decode(TX_DATA).unwrap();
+        for data in &[&tx_data[..tx_data.len() - 1], &tx_data[..20], &[0u8; 4][..]] {
+            assert_eq!(
+                format!("{}", unpack_actions(data).err().unwrap()),
+                "imkey_eos_transaction_invalid"
+            );
+        }
+        let mut extended = tx_data.clone();
+        extended.push(0);
+        assert!(unpack_actions(&extended).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::Result;
+use bitcoin::secp256k1::Signature;
+use bitcoin::util::base58;
+use bitcoin_hashes::{ripemd160, Hash};
+use common::apdu::ApduCheck;
+use common::constants;
+use common::error::CoinError;
+use common::utility::retrieve_recid;
+use transport::Transport;
+
+//the nonce of a sign apdu is two bytes, more attempts would repeat one
+const MAX_NONCES: usize = 0x1_0000;
+
+/**
+the number of sign apdus to send for a canonical signature, the default for 0
+*/
+pub fn sign_attempts(max_sign_attempts: u32) -> usize {
+    if max_sign_attempts == 0 {
+        return constants::EOS_MAX_SIGN_ATTEMPTS;
+    }
+    (max_sign_attempts as usize).min(MAX_NONCES)
+}
+
+/**
+the low s form of a compact signature if it is canonical: r and s both 32 bytes in der,
+no sign bit and no leading zero byte. eosio and the other antelope chains reject the rest
+*/
+pub fn canonical_signature(compact: &[u8]) -> Result<Option<[u8; 64]>> {
+    let mut signature = Signature::from_compact(compact)?;
+    signature.normalize_s();
+    let compact = signature.serialize_compact();
+    let is_canonical =
+        |bytes: &[u8]| bytes[0] & 0x80 == 0 && !(bytes[0] == 0 && bytes[1] & 0x80 == 0);
+    if is_canonical(&compact[..32]) && is_canonical(&compact[32..]) {
+        Ok(Some(compact))
+    } else {
+        Ok(None)
+    }
+}
+
+/**
+the SIG_K1_ string of a signature: the recovery header, r and s, and the first 4 bytes of
+ripemd160 over them and "K1"
+*/
+pub fn k1_signature(rec_id: i32, compact: &[u8]) -> String {
+    let mut signature = vec![(rec_id + 27 + 4) as u8];
+    signature.extend_from_slice(compact);
+    let mut to_hash = signature.clone();
+    to_hash.extend_from_slice(b"K1");
+    let check_sum = ripemd160::Hash::hash(&to_hash);
+    signature.extend_from_slice(&check_sum[0..4]);
+    "SIG_K1_".to_owned() + &base58::encode_slice(&signature)
+}
+
+/**
+asks the device to sign the prepared hash with a new nonce until the signature is canonical,
+at most max_attempts times. the signature has to recover to the uncompressed pub_key
+*/
+pub fn sign_canonical(
+    transport: &dyn Transport,
+    hash: &[u8],
+    pub_key: &[u8],
+    sign_apdu: fn(usize) -> String,
+    max_attempts: usize,
+) -> Result<String> {
+    for nonce in 0..max_attempts.min(MAX_NONCES) {
+        let sign_response = transport.send_apdu(sign_apdu(nonce))?;
+        ApduCheck::checke_response(&sign_response)?;
+        if sign_response.len() < 134 {
+            return Err(CoinError::ImkeySignatureVerifyFail.into());
+        }
+
+        if let Some(compact) = canonical_signature(&hex::decode(&sign_response[2..130])?)? {
+            let rec_id = retrieve_recid(hash, &compact, &pub_key.to_vec())?;
+            return Ok(k1_signature(rec_id.to_i32(), &compact));
+        }
+    }
+    Err(CoinError::ImkeyEosSignAttemptsExceeded.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::signer::{canonical_signature, k1_signature, sign_attempts, sign_canonical};
+    use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+    use bitcoin::util::base58;
+    use common::apdu::EosApdu;
+    use common::constants;
+    use transport::fixture::{parse_fixture, ReplayTransport};
+
+    #[test]
+    fn canonical_signature_test() {
+        let mut compact = [0x11u8; 64];
+        assert_eq!(canonical_signature(&compact).unwrap(), Some(compact));
+        //a sign bit in r
+        compact[0] = 0x80;
+        assert_eq!(canonical_signature(&compact).unwrap(), None);
+        //a leading zero byte that der drops
+        compact[0] = 0x00;
+        compact[1] = 0x7f;
+        assert_eq!(canonical_signature(&compact).unwrap(), None);
+        compact[1] = 0x80;
+        assert!(canonical_signature(&compact).unwrap().is_some());
+        //a high s is normalized before the check
+        let mut high_s = [0x11u8; 64];
+        high_s[32..].copy_from_slice(
+            &hex::decode("eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeda99dcbd59e378f2aaec14d7bbf253030")
+                .unwrap(),
+        );
+        assert_eq!(canonical_signature(&high_s).unwrap(), Some([0x11u8; 64]));
+    }
+
+    #[test]
+    fn k1_signature_test() {
+        let signature = "SIG_K1_K5QYdMqUVUW2phNPpBiqk61xzXnNwKBtRS6NiPP4mPU8HZGH9Dn4YxXcpzVWa3woKMK3VKoPbHGQpqFPVNMJcbLE2ipXBG";
+        let bytes = base58::from(&signature[7..]).unwrap();
+        assert_eq!(
+            k1_signature(i32::from(bytes[0]) - 31, &bytes[1..65]),
+            signature
+        );
+    }
+
+    #[test]
+    fn sign_attempts_test() {
+        assert_eq!(sign_attempts(0), constants::EOS_MAX_SIGN_ATTEMPTS);
+        assert_eq!(sign_attempts(3), 3);
+        assert_eq!(sign_attempts(u32::MAX), 0x1_0000);
+    }
+
+    #[test]
+    fn sign_canonical_test() {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[0x42; 32]).unwrap();
+        let pub_key = PublicKey::from_secret_key(&secp, &secret_key).serialize_uncompressed();
+        //a hash the key signs canonically
+        let (hash, compact) = (1u8..)
+            .map(|i| {
+                let hash = [i; 32];
+                let signature = secp.sign(&Message::from_slice(&hash).unwrap(), &secret_key);
+                (hash, signature.serialize_compact())
+            })
+            .find(|(_, compact)| canonical_signature(compact).unwrap().is_some())
+            .unwrap();
+
+        let non_canonical = format!("00{}{}009000", "80".repeat(32), "11".repeat(32));
+        let canonical = format!("00{}009000", hex::encode(&compact[..]));
+        let fixture = format!(
+            "-->{}\n<--{}\n-->{}\n<--{}\n",
+            EosApdu::sign_tx(0),
+            non_canonical,
+            EosApdu::sign_tx(1),
+            canonical
+        );
+        let transport = ReplayTransport::new(parse_fixture(&fixture).unwrap());
+        let signature = sign_canonical(&transport, &hash, &pub_key, EosApdu::sign_tx, 2).unwrap();
+        assert!(transport.finish().is_ok());
+        let bytes = base58::from(&signature[7..]).unwrap();
+        assert_eq!(&bytes[1..65], &compact[..]);
+
+        //the limit is reached before a canonical signature
+        let transport = ReplayTransport::new(parse_fixture(&fixture).unwrap());
+        let result = sign_canonical(&transport, &hash, &pub_key, EosApdu::sign_tx, 1);
+        assert_eq!(
+            format!("{}", result.err().unwrap()),
+            "imkey_eos_sign_attempts_exceeded"
+        );
+    }
+}
@@ -1,59 +1,76 @@
+use crate::chain;
 use crate::eosapi::{EosMessageSignReq, EosMessageSignRes, EosSignResult, EosTxReq, EosTxRes};
-use crate::pubkey::EosPubkey;
+use crate::pubkey::{decode_pubkey, EosPubkey};
+use crate::serializer;
+use crate::signer;
 use crate::Result;
-use bitcoin::secp256k1::Signature;
-use bitcoin::util::base58;
 use bitcoin_hashes::hex::ToHex;
-use bitcoin_hashes::{ripemd160, Hash};
 use bytes::BufMut;
 use common::apdu::{ApduCheck, CoinCommonApdu, EosApdu};
-use common::utility::{retrieve_recid, secp256k1_sign, sha256_hash};
-use common::{constants, path, utility};
-use device::device_binding::KEY_MANAGER;
-use hex::FromHex;
-use transport::message::{send_apdu, send_apdu_timeout};
+use common::display::display_tlv;
+use common::utility::{secp256k1_sign, sha256_hash};
+use common::{constants, path};
+use device::device_binding::key_manager;
+use serde_json::Value;
+use transport::Transport;
 
 #[derive(Debug)]
 pub struct EosTransaction {}
 
 impl EosTransaction {
-    pub fn sign_tx(tx_input: EosTxReq) -> Result<EosTxRes> {
-        path::check_path_validity(&tx_input.path).unwrap();
+    pub fn sign_tx(transport: &dyn Transport, tx_input: EosTxReq) -> Result<EosTxRes> {
+        let profile = chain::profile(&tx_input.chain)?;
+        let path = profile.path(&tx_input.path);
+        path::check_path_validity(path)?;
 
         let select_apdu = EosApdu::select_applet();
-        let select_response = send_apdu(select_apdu)?;
+        let select_response = transport.send_apdu(select_apdu)?;
         ApduCheck::checke_response(&select_response)?;
 
+        let max_attempts = signer::sign_attempts(tx_input.max_sign_attempts);
         let mut trans_multi_signs: Vec<EosSignResult> = Vec::new();
 
         for sign_data in &tx_input.sign_datas {
+            //a json transaction is packed here, tx_data is packed already
+            let (tx_data_bytes, context_free_data) = if sign_data.transaction.is_empty() {
+                (hex::decode(&sign_data.tx_data)?, vec![])
+            } else {
+                let transaction: Value = serde_json::from_str(&sign_data.transaction)?;
+                (
+                    serializer::pack_transaction(&transaction)?,
+                    serializer::context_free_data(&transaction)?,
+                )
+            };
             let mut sign_result = EosSignResult {
                 hash: "".to_string(),
                 signs: vec![],
+                packed_trx: hex::encode(&tx_data_bytes),
+                packed_context_free_data: hex::encode(serializer::pack_context_free_data(
+                    &context_free_data,
+                )),
             };
             //tx hash
-            let tx_data_bytes = hex::decode(&sign_data.tx_data).unwrap();
             let tx_hash = sha256_hash(&tx_data_bytes).to_hex();
             sign_result.hash = tx_hash;
 
             //pack tx data
             let mut tx_data_pack: Vec<u8> = Vec::new();
-            tx_data_pack.put_slice(hex::decode(&sign_data.chain_id).unwrap().as_slice());
-            tx_data_pack.put_slice(hex::decode(&sign_data.tx_data).unwrap().as_slice());
-            let context_free_actions = [0; 32];
-            tx_data_pack.put_slice(&context_free_actions);
+            tx_data_pack.put_slice(&chain::chain_id(&tx_input.chain, &sign_data.chain_id)?);
+            tx_data_pack.put_slice(&tx_data_bytes);
+            tx_data_pack.put_slice(&serializer::context_free_data_digest(&context_free_data));
 
             //tx data hash
             let tx_data_hash = sha256_hash(&tx_data_pack);
 
-            //view_info
+            //view_info, derived from the transfers and checked against the caller's
+            let display = serializer::display_info(&tx_data_bytes)?.verify(
+                &sign_data.payment,
+                &sign_data.to,
+                "",
+            )?;
             let mut view_info = "".to_string();
-            view_info.push_str("07");
-            view_info.push_str(&format!("{:02x}", &sign_data.payment.as_bytes().len()));
-            view_info.push_str(&hex::encode(&sign_data.payment));
-            view_info.push_str("08");
-            view_info.push_str(&format!("{:02x}", &sign_data.to.as_bytes().len()));
-            view_info.push_str(&hex::encode(&sign_data.to));
+            view_info.push_str(&hex::encode(display_tlv(7, &display.payment)?));
+            view_info.push_str(&hex::encode(display_tlv(8, &display.receiver)?));
 
             //sign
             for pub_key in &sign_data.pub_keys {
@@ -62,12 +79,13 @@ impl EosTransaction {
                 sign_data_pack.push(tx_data_hash.len() as u8); //hash len
                 sign_data_pack.extend(tx_data_hash.iter());
                 sign_data_pack.push(0x02);
-                sign_data_pack.push(tx_input.path.len() as u8); //hash len
-                sign_data_pack.extend(tx_input.path.as_bytes());
+                sign_data_pack.push(path.len() as u8); //hash len
+                sign_data_pack.extend(path.as_bytes());
                 sign_data_pack.extend(hex::decode(&view_info).unwrap().as_slice());
 
                 //bind signature
-                let key_manager_obj = KEY_MANAGER.lock().unwrap();
+                let key_manager = key_manager(transport);
+                let key_manager_obj = key_manager.lock().unwrap();
                 let bind_signature = secp256k1_sign(&key_manager_obj.pri_key, &sign_data_pack)?;
 
                 //send prepare data
@@ -80,71 +98,27 @@ impl EosTransaction {
                 let prepare_apdus = EosApdu::prepare_sign(prepare_apdu_data);
                 let mut prepare_result = "".to_string();
                 for prepare_apdu in prepare_apdus {
-                    prepare_result = send_apdu_timeout(prepare_apdu, constants::TIMEOUT_LONG)?;
+                    prepare_result =
+                        transport.send_apdu_timeout(prepare_apdu, constants::TIMEOUT_LONG)?;
                     ApduCheck::checke_response(&prepare_result)?;
                 }
 
                 //check pub key
-                let mut signature = "".to_string();
-                let uncomprs_pubkey: String = prepare_result
-                    .chars()
-                    .take(prepare_result.len() - 4)
-                    .collect();
-                let comprs_pubkey = utility::uncompress_pubkey_2_compress(&uncomprs_pubkey);
-                let mut comprs_pubkey_slice = hex::decode(comprs_pubkey)?;
-                let pubkey_hash = ripemd160::Hash::hash(&comprs_pubkey_slice);
-                let check_sum = &pubkey_hash[0..4];
-                comprs_pubkey_slice.extend(check_sum);
-                let eos_pk = "EOS".to_owned() + base58::encode_slice(&comprs_pubkey_slice).as_ref();
-                if pub_key != &eos_pk {
+                let uncomprs_pubkey = &prepare_result[..prepare_result.len() - 4];
+                let comprs_pubkey = EosPubkey::compressed_from_response(&prepare_result)?;
+                if decode_pubkey(pub_key, profile.key_prefix) != Some(comprs_pubkey) {
                     return Err(format_err!("imkey_publickey_mismatch_with_path"));
                 }
 
-                //sign
-                let mut nonce = 0;
-                loop {
-                    let sign_apdu = EosApdu::sign_tx(nonce);
-                    let sign_result = send_apdu(sign_apdu)?;
-                    ApduCheck::checke_response(&sign_result)?;
-
-                    let sign_result_vec =
-                        Vec::from_hex(&sign_result[2..sign_result.len() - 6]).unwrap();
-                    let mut signature_obj =
-                        Signature::from_compact(sign_result_vec.as_slice()).unwrap();
-                    //generator der sign data
-                    signature_obj.normalize_s();
-                    let signatrue_der = signature_obj.serialize_der().to_vec();
-                    let normalizes_sig_vec = signature_obj.serialize_compact();
-                    let sig_str = hex::encode(&normalizes_sig_vec.as_ref());
-
-                    let len_r = signatrue_der[3];
-                    let len_s = signatrue_der[5 + len_r as usize];
-                    if len_r == 32 && len_s == 32 {
-                        //calc v
-                        let pub_key_raw = hex::decode(&uncomprs_pubkey).unwrap();
-                        let sign_compact = hex::decode(&sign_result[2..130]).unwrap();
-                        let rec_id = retrieve_recid(&tx_data_hash, &sign_compact, &pub_key_raw)?;
-                        let rec_id = rec_id.to_i32();
-                        let v = rec_id + 27 + 4;
-
-                        signature.push_str(&format!("{:02X}", &v));
-                        signature.push_str(&sig_str);
-                        break;
-                    }
-                    nonce = nonce + 1;
-                }
-
-                //checksum base58
-                let mut to_hash = hex::decode(&signature).unwrap();
-                to_hash.put_slice("K1".as_bytes());
-                let signature_hash = ripemd160::Hash::hash(&to_hash);
-                let check_sum = &signature_hash[0..4];
-
-                let mut signature_slice = hex::decode(&signature).unwrap();
-                signature_slice.extend(check_sum);
-                let sigature_base58 =
-                    "SIG_K1_".to_owned() + base58::encode_slice(&signature_slice).as_ref();
-                sign_result.signs.push(sigature_base58);
+                //sign until the signature is canonical
+                let signature = signer::sign_canonical(
+                    transport,
+                    &tx_data_hash,
+                    &hex::decode(uncomprs_pubkey)?,
+                    EosApdu::sign_tx,
+                    max_attempts,
+                )?;
+                sign_result.signs.push(signature);
 
                 trans_multi_signs.push(sign_result.clone());
             }
@@ -154,7 +128,12 @@ impl EosTransaction {
         Ok(tx_output)
     }
 
-    pub fn sign_message(input: EosMessageSignReq) -> Result<EosMessageSignRes> {
+    pub fn sign_message(
+        transport: &dyn Transport,
+        input: EosMessageSignReq,
+    ) -> Result<EosMessageSignRes> {
+        let profile = chain::profile(&input.chain)?;
+        let path = profile.path(&input.path);
         let hash = if input.is_hex {
             hex::decode(input.data).unwrap()
         } else {
@@ -166,10 +145,11 @@ impl EosTransaction {
         data_pack.push(0x20);
         data_pack.extend(hash.as_slice());
         data_pack.push(0x02);
-        data_pack.push(input.path.as_bytes().len() as u8);
-        data_pack.extend(input.path.as_bytes());
+        data_pack.push(path.as_bytes().len() as u8);
+        data_pack.extend(path.as_bytes());
 
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
         let bind_signature = secp256k1_sign(&key_manager_obj.pri_key, &data_pack).unwrap();
 
         let mut prepare_pack: Vec<u8> = Vec::new();
@@ -179,68 +159,31 @@ impl EosTransaction {
         prepare_pack.extend(data_pack.iter());
 
         let select_apdu = EosApdu::select_applet();
-        let select_response = send_apdu(select_apdu)?;
+        let select_response = transport.send_apdu(select_apdu)?;
         ApduCheck::checke_response(&select_response)?;
 
         let prepare_apdus = EosApdu::prepare_message_sign(prepare_pack);
 
         let mut prepare_response = "".to_string();
         for apdu in prepare_apdus {
-            prepare_response = send_apdu_timeout(apdu, constants::TIMEOUT_LONG)?;
+            prepare_response = transport.send_apdu_timeout(apdu, constants::TIMEOUT_LONG)?;
             ApduCheck::checke_response(&prepare_response)?;
         }
 
-        //todo optmize,calc from prepare response
-        let pubkey = EosPubkey::pubkey_from_response(&prepare_response).unwrap();
-        let mut signature = "".to_string();
-        if &pubkey != &input.pubkey {
+        let comprs_pubkey = EosPubkey::compressed_from_response(&prepare_response)?;
+        if decode_pubkey(&input.pubkey, profile.key_prefix) != Some(comprs_pubkey) {
             return Err(format_err!("imkey_publickey_mismatch_with_path"));
         }
-        //sign
-        let mut nonce = 0;
-        loop {
-            let sign_apdu = EosApdu::sign_message(nonce);
-            let sign_result = send_apdu(sign_apdu)?;
-            ApduCheck::checke_response(&sign_result)?;
-
-            let sign_result_vec = Vec::from_hex(&sign_result[2..sign_result.len() - 6]).unwrap();
-            let mut signature_obj = Signature::from_compact(sign_result_vec.as_slice()).unwrap();
-            //generator der sign data
-            signature_obj.normalize_s();
-            let signatrue_der = signature_obj.serialize_der().to_vec();
-            let normalizes_sig_vec = signature_obj.serialize_compact();
-            let sig_str = hex::encode(&normalizes_sig_vec.as_ref());
-
-            let len_r = signatrue_der[3];
-            let len_s = signatrue_der[5 + len_r as usize];
-            if len_r == 32 && len_s == 32 {
-                //calc v
-                let uncomprs_pubkey: String = prepare_response
-                    .chars()
-                    .take(prepare_response.len() - 4)
-                    .collect();
-                let pub_key_raw = hex::decode(&uncomprs_pubkey).unwrap();
-                let sign_compact = hex::decode(&sign_result[2..130]).unwrap();
-                let rec_id = utility::retrieve_recid(&hash, &sign_compact, &pub_key_raw).unwrap();
-                let rec_id = rec_id.to_i32();
-                let v = rec_id + 27 + 4;
-
-                signature.push_str(&format!("{:02X}", &v));
-                signature.push_str(&sig_str);
-                break;
-            }
-            nonce = nonce + 1;
-        }
 
-        //checksum base58
-        let mut to_hash = hex::decode(&signature).unwrap();
-        to_hash.put_slice("K1".as_bytes());
-        let signature_hash = ripemd160::Hash::hash(&to_hash);
-        let check_sum = &signature_hash[0..4];
-
-        let mut signature_slice = hex::decode(&signature).unwrap();
-        signature_slice.extend(check_sum);
-        let signature = "SIG_K1_".to_owned() + base58::encode_slice(&signature_slice).as_ref();
+        //sign until the signature is canonical
+        let uncomprs_pubkey = &prepare_response[..prepare_response.len() - 4];
+        let signature = signer::sign_canonical(
+            transport,
+            &hash,
+            &hex::decode(uncomprs_pubkey)?,
+            EosApdu::sign_message,
+            signer::sign_attempts(input.max_sign_attempts),
+        )?;
 
         let output = EosMessageSignRes { signature };
         Ok(output)
@@ -249,80 +192,108 @@ impl EosTransaction {
 
 #[cfg(test)]
 mod tests {
+    use crate::chain;
     use crate::eosapi::{EosMessageSignReq, EosSignData, EosTxReq};
     use crate::transaction::EosTransaction;
     use common::constants;
     use device::device_binding::bind_test;
+    use transport::Transport;
 
     #[test]
     fn test_sgin_tx() {
-        bind_test();
+        let transport = bind_test();
+        check_sign_tx(&transport);
+    }
+
+    #[test]
+    fn test_sgin_tx_pubkey_error() {
+        let transport = bind_test();
 
         let eos_sign_data = EosSignData{
             tx_data: "c578065b93aec6a7c811000000000100a6823403ea3055000000572d3ccdcd01000000602a48b37400000000a8ed323225000000602a48b374208410425c95b1ca80969800000000000453595300000000046d656d6f00".to_string(),
-            pub_keys: vec!["EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF".to_string()],
+            pub_keys: vec!["ERROR PUBKEY".to_string()],
             chain_id: "aca376f206b8fc25a6ed44dbdc66547c36c6c33e3a119ffbeaef943642f0e906".to_string(),
-            to: "bbbb5555bbbb".to_string(),
-            from: "liujianmin12".to_string(),
-            payment: "undelegatebw 0.0100 EOS".to_string()
+            to: "tester222222".to_string(),
+            from: "imtoken".to_string(),
+            payment: "1000.0000 SYS".to_string(),
+            transaction: "".to_string()
         };
 
         let eox_tx_input = EosTxReq {
             path: constants::EOS_PATH.to_string(),
             sign_datas: vec![eos_sign_data],
+            max_sign_attempts: 0,
+            chain: "".to_string(),
         };
 
-        let result = EosTransaction::sign_tx(eox_tx_input).unwrap();
+        let result = EosTransaction::sign_tx(&transport, eox_tx_input);
         assert_eq!(
-            result.trans_multi_signs[0].hash,
-            "6af5b3ae9871c25e2de195168ed7423f455a68330955701e327f02276bb34088"
+            format!("{}", result.err().unwrap()),
+            "imkey_publickey_mismatch_with_path"
         );
     }
 
     #[test]
-    fn test_sgin_tx_pubkey_error() {
-        bind_test();
+    fn test_sgin_tx_chainid_is_null() {
+        let transport = bind_test();
 
         let eos_sign_data = EosSignData{
             tx_data: "c578065b93aec6a7c811000000000100a6823403ea3055000000572d3ccdcd01000000602a48b37400000000a8ed323225000000602a48b374208410425c95b1ca80969800000000000453595300000000046d656d6f00".to_string(),
-            pub_keys: vec!["ERROR PUBKEY".to_string()],
-            chain_id: "aca376f206b8fc25a6ed44dbdc66547c36c6c33e3a119ffbeaef943642f0e906".to_string(),
-            to: "bbbb5555bbbb".to_string(),
-            from: "liujianmin12".to_string(),
-            payment: "undelegatebw 0.0100 EOS".to_string()
+            pub_keys: vec!["EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF".to_string()],
+            chain_id: "".to_string(),
+            to: "tester222222".to_string(),
+            from: "imtoken".to_string(),
+            payment: "1000.0000 SYS".to_string(),
+            transaction: "".to_string()
         };
 
         let eox_tx_input = EosTxReq {
             path: constants::EOS_PATH.to_string(),
             sign_datas: vec![eos_sign_data],
+            max_sign_attempts: 0,
+            chain: "".to_string(),
         };
 
-        let result = EosTransaction::sign_tx(eox_tx_input);
+        let result = EosTransaction::sign_tx(&transport, eox_tx_input).unwrap();
         assert_eq!(
-            format!("{}", result.err().unwrap()),
-            "imkey_publickey_mismatch_with_path"
+            result.trans_multi_signs[0].hash,
+            "6af5b3ae9871c25e2de195168ed7423f455a68330955701e327f02276bb34088"
         );
     }
 
     #[test]
-    fn test_sgin_tx_chainid_is_null() {
-        bind_test();
+    fn test_sign_messgage() {
+        let transport = bind_test();
+        check_sign_messgage(
+            &transport,
+            "SIG_K1_K2mrf6ASTK5TCJC6kzZzyQm9uRZm7Jx4fa6gsmWx2sEreokRWmnHQGTRNwKLNF6NVJtXmjmUvR96XYct1DjMJnwRZBbTYR",
+        );
+    }
 
+    #[test]
+    fn test_sgin_tx_simulator() {
+        check_sign_tx(&simulator::bind_test());
+    }
+
+    fn check_sign_tx(transport: &dyn Transport) {
         let eos_sign_data = EosSignData{
             tx_data: "c578065b93aec6a7c811000000000100a6823403ea3055000000572d3ccdcd01000000602a48b37400000000a8ed323225000000602a48b374208410425c95b1ca80969800000000000453595300000000046d656d6f00".to_string(),
             pub_keys: vec!["EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF".to_string()],
-            chain_id: "".to_string(),
-            to: "bbbb5555bbbb".to_string(),
-            from: "liujianmin12".to_string(),
-            payment: "undelegatebw 0.0100 EOS".to_string()
+            chain_id: "aca376f206b8fc25a6ed44dbdc66547c36c6c33e3a119ffbeaef943642f0e906".to_string(),
+            to: "tester222222".to_string(),
+            from: "imtoken".to_string(),
+            payment: "1000.0000 SYS".to_string(),
+            transaction: "".to_string()
         };
 
         let eox_tx_input = EosTxReq {
             path: constants::EOS_PATH.to_string(),
             sign_datas: vec![eos_sign_data],
+            max_sign_attempts: 0,
+            chain: "".to_string(),
         };
 
-        let result = EosTransaction::sign_tx(eox_tx_input).unwrap();
+        let result = EosTransaction::sign_tx(transport, eox_tx_input).unwrap();
         assert_eq!(
             result.trans_multi_signs[0].hash,
             "6af5b3ae9871c25e2de195168ed7423f455a68330955701e327f02276bb34088"
@@ -330,35 +301,226 @@ mod tests {
     }
 
     #[test]
-    fn test_sign_messgage() {
-        bind_test();
+    fn test_sgin_json_tx_simulator() {
+        let transport = simulator::bind_test();
+
+        let transaction = r#"{
+            "expiration": "2018-05-24T08:33:09",
+            "ref_block_num": 44691,
+            "ref_block_prefix": 298362822,
+            "max_net_usage_words": 0,
+            "max_cpu_usage_ms": 0,
+            "delay_sec": 0,
+            "context_free_actions": [],
+            "actions": [{
+                "account": "eosio.token",
+                "name": "transfer",
+                "authorization": [{"actor": "imtoken", "permission": "active"}],
+                "data": {"from": "imtoken", "to": "tester222222", "quantity": "1000.0000 SYS", "memo": "memo"}
+            }],
+            "transaction_extensions": []
+        }"#;
+        let eos_sign_data = EosSignData {
+            tx_data: "".to_string(),
+            pub_keys: vec!["EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF".to_string()],
+            chain_id: "aca376f206b8fc25a6ed44dbdc66547c36c6c33e3a119ffbeaef943642f0e906"
+                .to_string(),
+            to: "".to_string(),
+            from: "imtoken".to_string(),
+            payment: "".to_string(),
+            transaction: transaction.to_string(),
+        };
+        let mut hex_sign_data = eos_sign_data.clone();
+        hex_sign_data.tx_data = "c578065b93aec6a7c811000000000100a6823403ea3055000000572d3ccdcd01000000602a48b37400000000a8ed323225000000602a48b374208410425c95b1ca80969800000000000453595300000000046d656d6f00".to_string();
+        hex_sign_data.transaction = "".to_string();
+
+        let result = EosTransaction::sign_tx(
+            &transport,
+            EosTxReq {
+                path: constants::EOS_PATH.to_string(),
+                sign_datas: vec![eos_sign_data],
+                max_sign_attempts: 0,
+                chain: "".to_string(),
+            },
+        )
+        .unwrap();
+        let hex_result = EosTransaction::sign_tx(
+            &transport,
+            EosTxReq {
+                path: constants::EOS_PATH.to_string(),
+                sign_datas: vec![hex_sign_data],
+                max_sign_attempts: 0,
+                chain: "".to_string(),
+            },
+        )
+        .unwrap();
+        let sign_result = &result.trans_multi_signs[0];
+        assert_eq!(
+            sign_result.hash,
+            "6af5b3ae9871c25e2de195168ed7423f455a68330955701e327f02276bb34088"
+        );
+        assert_eq!(
+            sign_result.packed_trx,
+            hex_result.trans_multi_signs[0].packed_trx
+        );
+        assert_eq!(sign_result.packed_context_free_data, "");
+        assert_eq!(sign_result.signs, hex_result.trans_multi_signs[0].signs);
+        assert!(sign_result.signs[0].starts_with("SIG_K1_"));
+    }
+
+    #[test]
+    fn test_sgin_tx_chain_simulator() {
+        let transport = simulator::bind_test();
+
+        let tx_data = "c578065b93aec6a7c811000000000100a6823403ea3055000000572d3ccdcd01000000602a48b37400000000a8ed323225000000602a48b374208410425c95b1ca80969800000000000453595300000000046d656d6f00";
+        let sign = |chain: &str, chain_id: &str, pub_key: &str| {
+            let eos_sign_data = EosSignData {
+                tx_data: tx_data.to_string(),
+                pub_keys: vec![pub_key.to_string()],
+                chain_id: chain_id.to_string(),
+                to: "tester222222".to_string(),
+                from: "imtoken".to_string(),
+                payment: "1000.0000 SYS".to_string(),
+                transaction: "".to_string(),
+            };
+            EosTransaction::sign_tx(
+                &transport,
+                EosTxReq {
+                    path: "".to_string(),
+                    sign_datas: vec![eos_sign_data],
+                    max_sign_attempts: 0,
+                    chain: chain.to_string(),
+                },
+            )
+        };
+
+        //a PUB_K1_ key signs like the legacy one, the chain id comes from the profile
+        let legacy = sign(
+            "",
+            chain::EOS.chain_id,
+            "EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF",
+        )
+        .unwrap();
+        let k1 = sign(
+            "eos",
+            "",
+            "PUB_K1_88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAsaXqhu",
+        )
+        .unwrap();
+        assert_eq!(
+            legacy.trans_multi_signs[0].signs,
+            k1.trans_multi_signs[0].signs
+        );
+
+        let jungle = sign(
+            "jungle",
+            "",
+            "PUB_K1_88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAsaXqhu",
+        )
+        .unwrap();
+        assert_ne!(
+            legacy.trans_multi_signs[0].signs,
+            jungle.trans_multi_signs[0].signs
+        );
+
+        let result = sign(
+            "telos",
+            chain::EOS.chain_id,
+            "EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF",
+        );
+        assert_eq!(
+            format!("{}", result.err().unwrap()),
+            "imkey_eos_chain_id_mismatch"
+        );
+    }
+
+    #[test]
+    fn test_sgin_tx_display_mismatch_simulator() {
+        let transport = simulator::bind_test();
+
+        for (payment, to) in &[("undelegatebw 0.0100 EOS", ""), ("", "bbbb5555bbbb")] {
+            let eos_sign_data = EosSignData{
+                tx_data: "c578065b93aec6a7c811000000000100a6823403ea3055000000572d3ccdcd01000000602a48b37400000000a8ed323225000000602a48b374208410425c95b1ca80969800000000000453595300000000046d656d6f00".to_string(),
+                pub_keys: vec!["EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF".to_string()],
+                chain_id: "aca376f206b8fc25a6ed44dbdc66547c36c6c33e3a119ffbeaef943642f0e906".to_string(),
+                to: to.to_string(),
+                from: "imtoken".to_string(),
+                payment: payment.to_string(),
+                transaction: "".to_string()
+            };
+
+            let eox_tx_input = EosTxReq {
+                path: constants::EOS_PATH.to_string(),
+                sign_datas: vec![eos_sign_data],
+                max_sign_attempts: 0,
+                chain: "".to_string(),
+            };
+
+            let result = EosTransaction::sign_tx(&transport, eox_tx_input);
+            assert_eq!(
+                format!("{}", result.err().unwrap()),
+                "imkey_display_mismatch_with_data"
+            );
+        }
+    }
+
+    #[test]
+    fn test_sign_messgage_simulator() {
+        //the simulator grinds with its own nonce entropy, so the signature differs from the device one
+        check_sign_messgage(
+            &simulator::bind_test(),
+            "SIG_K1_K5QYdMqUVUW2phNPpBiqk61xzXnNwKBtRS6NiPP4mPU8HZGH9Dn4YxXcpzVWa3woKMK3VKoPbHGQpqFPVNMJcbLE2ipXBG",
+        );
+    }
 
+    fn check_sign_messgage(transport: &dyn Transport, signature: &str) {
         let input = EosMessageSignReq {
             path: constants::EOS_PATH.to_string(),
             data: "imKey2019".to_string(),
             is_hex: false,
             pubkey: "EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF".to_string(),
+            max_sign_attempts: 0,
+            chain: "".to_string(),
+        };
+
+        let output = EosTransaction::sign_message(transport, input);
+        assert_eq!(output.unwrap().signature, signature);
+    }
+
+    #[test]
+    fn test_sign_messgage_k1_simulator() {
+        let transport = simulator::bind_test();
+
+        let input = EosMessageSignReq {
+            path: "".to_string(),
+            data: "imKey2019".to_string(),
+            is_hex: false,
+            pubkey: "PUB_K1_88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAsaXqhu".to_string(),
+            max_sign_attempts: 0,
+            chain: "".to_string(),
         };
 
-        let output = EosTransaction::sign_message(input);
+        let output = EosTransaction::sign_message(&transport, input);
         assert_eq!(
             output.unwrap().signature,
-            "SIG_K1_K2mrf6ASTK5TCJC6kzZzyQm9uRZm7Jx4fa6gsmWx2sEreokRWmnHQGTRNwKLNF6NVJtXmjmUvR96XYct1DjMJnwRZBbTYR"
+            "SIG_K1_K5QYdMqUVUW2phNPpBiqk61xzXnNwKBtRS6NiPP4mPU8HZGH9Dn4YxXcpzVWa3woKMK3VKoPbHGQpqFPVNMJcbLE2ipXBG"
         );
     }
 
     #[test]
     fn test_sign_messgage_hex() {
-        bind_test();
+        let transport = bind_test();
 
         let input = EosMessageSignReq {
             path: constants::EOS_PATH.to_string(),
             data: "1122334455667788990011223344556677889900112233445566778899001122".to_string(),
             is_hex: true,
             pubkey: "EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF".to_string(),
+            max_sign_attempts: 0,
+            chain: "".to_string(),
         };
 
-        let output = EosTransaction::sign_message(input);
+        let output = EosTransaction::sign_message(&transport, input);
         assert_eq!(
             output.unwrap().signature,
             "SIG_K1_Jy1w6cs58tXkFVVBoku9uAVUuiknttFtwQEjXzpZvXY85EsBi6dU27RPf8KQRRh25jewnpdeVgqZDrj6RiYkdJk5fktZyw"
@@ -367,16 +529,18 @@ mod tests {
 
     #[test]
     fn sign_messgage_wrong_pubkey_test() {
-        bind_test();
+        let transport = bind_test();
 
         let input = EosMessageSignReq {
             path: constants::EOS_PATH.to_string(),
             data: "imKey2019".to_string(),
             is_hex: false,
             pubkey: "wrong pubkey".to_string(),
+            max_sign_attempts: 0,
+            chain: "".to_string(),
         };
 
-        let output = EosTransaction::sign_message(input);
+        let output = EosTransaction::sign_message(&transport, input);
         assert_eq!(
             format!("{}", output.err().unwrap()),
             "imkey_publickey_mismatch_with_path"
@@ -385,16 +549,18 @@ mod tests {
 
     #[test]
     fn sign_messgage_data_is_null_test() {
-        bind_test();
+        let transport = bind_test();
 
         let input = EosMessageSignReq {
             path: constants::EOS_PATH.to_string(),
             data: "".to_string(),
             is_hex: false,
             pubkey: "EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF".to_string(),
+            max_sign_attempts: 0,
+            chain: "".to_string(),
         };
 
-        let output = EosTransaction::sign_message(input);
+        let output = EosTransaction::sign_message(&transport, input);
         assert_eq!(
             output.unwrap().signature,
             "SIG_K1_Kij4tk3eM3UtB5Z1Gz6B5jC9JAtPDj7PQA8kkNPAX97U7JQWePVNCUg4WGso6m91Bz8rWzFoXo3SNhehpYrmJfYtNc4dxJ"
@@ -403,16 +569,18 @@ mod tests {
 
     #[test]
     fn sign_messgage_wrong_path_test() {
-        bind_test();
+        let transport = bind_test();
 
         let input = EosMessageSignReq {
             path: "m/44'".to_string(),
             data: "imKey2019".to_string(),
             is_hex: false,
             pubkey: "EOS88XhiiP7Cu5TmAUJqHbyuhyYgd6sei68AU266PyetDDAtjmYWF".to_string(),
+            max_sign_attempts: 0,
+            chain: "".to_string(),
         };
 
-        let output = EosTransaction::sign_message(input);
+        let output = EosTransaction::sign_message(&transport, input);
         assert_eq!(
             format!("{}", output.err().unwrap()),
             "imkey_command_data_error"
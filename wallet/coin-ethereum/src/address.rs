@@ -5,7 +5,7 @@ use common::utility::hex_to_bytes;
 use hex;
 use keccak_hash::keccak;
 use regex::Regex;
-use transport::message::send_apdu;
+use transport::Transport;
 
 #[derive(Debug)]
 pub struct EthAddress {}
@@ -42,16 +42,16 @@ impl EthAddress {
         return checksum_address;
     }
 
-    pub fn get_address(path: &str) -> Result<String> {
+    pub fn get_address(transport: &dyn Transport, path: &str) -> Result<String> {
         check_path_validity(path).unwrap();
 
         let select_apdu = EthApdu::select_applet();
-        let select_response = send_apdu(select_apdu)?;
+        let select_response = transport.send_apdu(select_apdu)?;
         ApduCheck::checke_response(&select_response)?;
 
         //get public
         let msg_pubkey = EthApdu::get_xpub(&path, false);
-        let res_msg_pubkey = send_apdu(msg_pubkey)?;
+        let res_msg_pubkey = transport.send_apdu(msg_pubkey)?;
         ApduCheck::checke_response(&res_msg_pubkey)?;
 
         let pubkey_raw = hex_to_bytes(&res_msg_pubkey[..130]).unwrap();
@@ -61,10 +61,10 @@ impl EthAddress {
         Ok(address_checksum)
     }
 
-    pub fn display_address(path: &str) -> Result<String> {
-        let address = EthAddress::get_address(path).unwrap();
+    pub fn display_address(transport: &dyn Transport, path: &str) -> Result<String> {
+        let address = EthAddress::get_address(transport, path).unwrap();
         let reg_apdu = EthApdu::register_address(address.as_bytes());
-        let res_reg = send_apdu(reg_apdu)?;
+        let res_reg = transport.send_apdu(reg_apdu)?;
         ApduCheck::checke_response(&res_reg)?;
         Ok(address)
     }
@@ -75,6 +75,7 @@ mod test {
     use crate::address::EthAddress;
     use common::constants;
     use device::device_binding::bind_test;
+    use transport::Transport;
 
     #[test]
     fn test_pubkey_to_address() {
@@ -102,17 +103,28 @@ mod test {
 
     #[test]
     fn test_get_address() {
-        bind_test();
-        let address = EthAddress::get_address(constants::ETH_PATH).unwrap();
+        let transport = bind_test();
+        let address = EthAddress::get_address(&transport, constants::ETH_PATH).unwrap();
         println!("address:{}", &address);
         assert_eq!(&address, "0x6031564e7b2F5cc33737807b2E58DaFF870B590b");
     }
 
     #[test]
     fn test_display_address() {
-        bind_test();
-        let address = EthAddress::display_address(constants::ETH_PATH).unwrap();
-        println!("address:{}", &address);
+        let transport = bind_test();
+        check_display_address(&transport);
+    }
+
+    #[test]
+    fn test_get_address_simulator() {
+        let transport = simulator::bind_test();
+        let address = check_display_address(&transport);
+        assert_eq!(transport.registered_addresses(), vec![address]);
+    }
+
+    fn check_display_address(transport: &dyn Transport) -> String {
+        let address = EthAddress::display_address(transport, constants::ETH_PATH).unwrap();
         assert_eq!(&address, "0x6031564e7b2F5cc33737807b2E58DaFF870B590b");
+        address
     }
 }
@@ -0,0 +1,555 @@
+use crate::address::EthAddress;
+use crate::Result;
+use common::display::{numbers, Amount, DisplayInfoBuilder};
+use common::error::CoinError;
+use ethereum_types::{Address, U256};
+use std::str::FromStr;
+
+const TRANSFER: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+const APPROVE: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+const TRANSFER_FROM: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+const SAFE_TRANSFER_FROM: [u8; 4] = [0x42, 0x84, 0x2e, 0x0e];
+const SAFE_TRANSFER_FROM_WITH_DATA: [u8; 4] = [0xb8, 0x8d, 0x4f, 0xde];
+const MULTI_TOKEN_SAFE_TRANSFER_FROM: [u8; 4] = [0xf2, 0x42, 0x43, 0x2a];
+const SET_APPROVAL_FOR_ALL: [u8; 4] = [0xa2, 0x2c, 0xb4, 0x65];
+
+//the digits of the largest uint256, more decimals have no integer part to show
+const MAX_DECIMALS: u32 = 77;
+
+/// ERC-20 contracts whose amounts are shown in their decimals, by chain id and contract.
+const KNOWN_TOKENS: [(u64, &str, &str, u32); 7] = [
+    (1, "dac17f958d2ee523a2206206994597c13d831ec7", "USDT", 6),
+    (1, "a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", "USDC", 6),
+    (1, "6b175474e89094c44da98b954eedeac495271d0f", "DAI", 18),
+    (1, "c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", "WETH", 18),
+    (1, "2260fac5e5542a773aa44fbcfedf7c193bc2c599", "WBTC", 8),
+    (1, "514910771af9ca656af840dff83e8264ecf986ca", "LINK", 18),
+    (1, "1f9840a85d5af5bf1d1762f925bdaddc4201f984", "UNI", 18),
+];
+
+/// The symbol and decimals of an ERC-20 contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub symbol: &'static str,
+    pub decimals: u32,
+}
+
+impl Token {
+    /// The built-in token at `contract`, none for any other contract.
+    pub fn find(chain_id: u64, contract: &Address) -> Option<Token> {
+        let contract = hex::encode(contract);
+        KNOWN_TOKENS
+            .iter()
+            .find(|token| token.0 == chain_id && token.1 == contract)
+            .map(|token| Token {
+                symbol: token.2,
+                decimals: token.3,
+            })
+    }
+}
+
+/// Standard token methods recognized in transaction calldata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenCall {
+    /// ERC-20 `transfer(address,uint256)`
+    Transfer { to: Address, amount: U256 },
+    /// ERC-20 `approve(address,uint256)`
+    Approve { spender: Address, amount: U256 },
+    /// `transferFrom(address,address,uint256)`, an ERC-20 amount or an ERC-721 token id
+    TransferFrom {
+        from: Address,
+        to: Address,
+        amount: U256,
+    },
+    /// ERC-721 `safeTransferFrom(address,address,uint256[,bytes])`
+    SafeTransferFrom {
+        from: Address,
+        to: Address,
+        token_id: U256,
+    },
+    /// ERC-1155 `safeTransferFrom(address,address,uint256,uint256,bytes)`
+    MultiTokenTransferFrom {
+        from: Address,
+        to: Address,
+        token_id: U256,
+        amount: U256,
+    },
+    /// ERC-721 and ERC-1155 `setApprovalForAll(address,bool)`
+    SetApprovalForAll { operator: Address, approved: bool },
+}
+
+impl TokenCall {
+    /// Decodes the calldata of a standard token method, none for any other calldata.
+    pub fn decode(data: &[u8]) -> Result<Option<TokenCall>> {
+        if data.len() < 4 {
+            return Ok(None);
+        }
+        let mut selector = [0u8; 4];
+        selector.copy_from_slice(&data[..4]);
+        let args = Arguments(&data[4..]);
+
+        let call = match selector {
+            TRANSFER => TokenCall::Transfer {
+                to: args.address(0)?,
+                amount: args.uint(1)?,
+            },
+            APPROVE => TokenCall::Approve {
+                spender: args.address(0)?,
+                amount: args.uint(1)?,
+            },
+            TRANSFER_FROM => TokenCall::TransferFrom {
+                from: args.address(0)?,
+                to: args.address(1)?,
+                amount: args.uint(2)?,
+            },
+            SAFE_TRANSFER_FROM | SAFE_TRANSFER_FROM_WITH_DATA => TokenCall::SafeTransferFrom {
+                from: args.address(0)?,
+                to: args.address(1)?,
+                token_id: args.uint(2)?,
+            },
+            MULTI_TOKEN_SAFE_TRANSFER_FROM => TokenCall::MultiTokenTransferFrom {
+                from: args.address(0)?,
+                to: args.address(1)?,
+                token_id: args.uint(2)?,
+                amount: args.uint(3)?,
+            },
+            SET_APPROVAL_FOR_ALL => TokenCall::SetApprovalForAll {
+                operator: args.address(0)?,
+                approved: args.bool(1)?,
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(call))
+    }
+
+    /// The account receiving the tokens or the allowance.
+    pub fn receiver(&self) -> Address {
+        match *self {
+            TokenCall::Transfer { to, .. }
+            | TokenCall::TransferFrom { to, .. }
+            | TokenCall::SafeTransferFrom { to, .. }
+            | TokenCall::MultiTokenTransferFrom { to, .. } => to,
+            TokenCall::Approve { spender, .. } => spender,
+            TokenCall::SetApprovalForAll { operator, .. } => operator,
+        }
+    }
+
+    /// The method called, shown ahead of the payment.
+    pub fn method(&self) -> &'static str {
+        match self {
+            TokenCall::Transfer { .. } => "transfer",
+            TokenCall::Approve { .. } => "approve",
+            TokenCall::TransferFrom { .. } => "transferFrom",
+            TokenCall::SafeTransferFrom { .. } | TokenCall::MultiTokenTransferFrom { .. } => {
+                "safeTransferFrom"
+            }
+            TokenCall::SetApprovalForAll { .. } => "setApprovalForAll",
+        }
+    }
+
+    /// The payment derived from the calldata, amounts are in the token's smallest unit.
+    pub fn payment(&self) -> String {
+        format!("{} {}", self.method(), self.value(None))
+    }
+
+    /**
+     * The payment and receiver to show on the device, both derived from the calldata.
+     * A given payment has to name the method and agree with the calldata, a given receiver
+     * has to list the same accounts. The receiver is followed by the token contract, so the
+     * device tells which token moves. An ERC-20 amount is compared exactly in the decimals of
+     * a known token, for any other contract it is the raw integer amount.
+     */
+    pub fn display(
+        &self,
+        contract: &Address,
+        token: Option<&Token>,
+        payment: &str,
+        receiver: &str,
+    ) -> Result<(String, String)> {
+        let checksummed =
+            |address: &Address| EthAddress::address_checksummed(&hex::encode(address));
+        let receiver = DisplayInfoBuilder::new()
+            .receiver(&checksummed(&self.receiver()))
+            .receiver(&checksummed(contract))
+            .verify("", receiver, "")?
+            .receiver;
+
+        let mut words = payment.trim_start().splitn(2, char::is_whitespace);
+        let shown = match (words.next(), words.next()) {
+            (Some(""), _) => "",
+            (Some(method), Some(shown)) if method == self.method() => shown,
+            _ => return Err(CoinError::ImkeyDisplayMismatchWithData.into()),
+        };
+        let value = match self {
+            TokenCall::Approve { amount, .. } if *amount == U256::max_value() => {
+                self.verify_words(shown, token)?
+            }
+            TokenCall::Transfer { amount, .. }
+            | TokenCall::Approve { amount, .. }
+            | TokenCall::TransferFrom { amount, .. } => {
+                match (token, u128::from_str(&amount.to_string())) {
+                    (Some(token), Ok(value)) => {
+                        if token.decimals > MAX_DECIMALS {
+                            return Err(CoinError::ImkeySdkIllegalArgument.into());
+                        }
+                        DisplayInfoBuilder::new()
+                            .payment(Amount::new(value, token.decimals, token.symbol))
+                            .verify(shown, "", "")?
+                            .payment
+                    }
+                    //without a known token or too large for the display amounts, only the
+                    //raw integer is shown
+                    _ => self.verify_integers(shown, &[*amount])?,
+                }
+            }
+            TokenCall::SafeTransferFrom { token_id, .. } => {
+                self.verify_integers(shown, &[*token_id])?
+            }
+            TokenCall::MultiTokenTransferFrom {
+                token_id, amount, ..
+            } => self.verify_integers(shown, &[*amount, *token_id])?,
+            TokenCall::SetApprovalForAll { .. } => self.verify_words(shown, None)?,
+        };
+        Ok((format!("{} {}", self.method(), value), receiver))
+    }
+
+    //what follows the method, amounts are in the token's smallest unit
+    fn value(&self, token: Option<&Token>) -> String {
+        match self {
+            TokenCall::Approve { amount, .. } if *amount == U256::max_value() => match token {
+                Some(token) => format!("unlimited {}", token.symbol),
+                None => "unlimited".to_string(),
+            },
+            TokenCall::Transfer { amount, .. }
+            | TokenCall::Approve { amount, .. }
+            | TokenCall::TransferFrom { amount, .. } => amount.to_string(),
+            TokenCall::SafeTransferFrom { token_id, .. } => format!("#{}", token_id),
+            TokenCall::MultiTokenTransferFrom {
+                token_id, amount, ..
+            } => format!("{} of #{}", amount, token_id),
+            TokenCall::SetApprovalForAll { approved: true, .. } => "approve all".to_string(),
+            TokenCall::SetApprovalForAll {
+                approved: false, ..
+            } => "revoke all".to_string(),
+        }
+    }
+
+    //the derived value, once a given one has the same words ignoring case
+    fn verify_words(&self, shown: &str, token: Option<&Token>) -> Result<String> {
+        let value = self.value(token);
+        if !shown.is_empty()
+            && !shown
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .eq(value.split_whitespace().map(str::to_lowercase))
+        {
+            return Err(CoinError::ImkeyDisplayMismatchWithData.into());
+        }
+        Ok(value)
+    }
+
+    //the derived value, once the numbers of a given one are the values in order
+    fn verify_integers(&self, shown: &str, values: &[U256]) -> Result<String> {
+        if !shown.is_empty() {
+            let numbers = numbers(shown);
+            let matched = numbers.len() == values.len()
+                && numbers
+                    .iter()
+                    .zip(values)
+                    .all(|(number, value)| same_integer(number, value));
+            if !matched {
+                return Err(CoinError::ImkeyDisplayMismatchWithData.into());
+            }
+        }
+        Ok(self.value(None))
+    }
+}
+
+/// The 32 bytes words following the selector.
+struct Arguments<'a>(&'a [u8]);
+
+impl<'a> Arguments<'a> {
+    fn word(&self, index: usize) -> Result<&'a [u8]> {
+        self.0
+            .get(index * 32..(index + 1) * 32)
+            .ok_or_else(|| CoinError::ImkeyCalldataInvalid.into())
+    }
+
+    fn address(&self, index: usize) -> Result<Address> {
+        let word = self.word(index)?;
+        if word[..12].iter().any(|byte| *byte != 0) {
+            return Err(CoinError::ImkeyCalldataInvalid.into());
+        }
+        Ok(Address::from_slice(&word[12..]))
+    }
+
+    fn uint(&self, index: usize) -> Result<U256> {
+        Ok(U256::from_big_endian(self.word(index)?))
+    }
+
+    fn bool(&self, index: usize) -> Result<bool> {
+        let flag = self.uint(index)?;
+        if flag > U256::one() {
+            return Err(CoinError::ImkeyCalldataInvalid.into());
+        }
+        Ok(flag == U256::one())
+    }
+}
+
+fn same_integer(number: &str, value: &U256) -> bool {
+    U256::from_dec_str(number).map_or(false, |number| number == *value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calldata::{Token, TokenCall};
+    use ethereum_types::{Address, U256};
+    use std::str::FromStr;
+    use tiny_keccak::keccak256;
+
+    fn address_word(address: &str) -> String {
+        format!("{:0>64}", address)
+    }
+
+    fn uint_word(value: u64) -> String {
+        format!("{:064x}", value)
+    }
+
+    #[test]
+    fn selector_test() {
+        for (selector, signature) in &[
+            ("a9059cbb", "transfer(address,uint256)"),
+            ("095ea7b3", "approve(address,uint256)"),
+            ("23b872dd", "transferFrom(address,address,uint256)"),
+            ("42842e0e", "safeTransferFrom(address,address,uint256)"),
+            (
+                "b88d4fde",
+                "safeTransferFrom(address,address,uint256,bytes)",
+            ),
+            (
+                "f242432a",
+                "safeTransferFrom(address,address,uint256,uint256,bytes)",
+            ),
+            ("a22cb465", "setApprovalForAll(address,bool)"),
+        ] {
+            assert_eq!(
+                *selector,
+                hex::encode(&keccak256(signature.as_bytes())[..4])
+            );
+        }
+    }
+
+    #[test]
+    fn decode_test() {
+        let receiver = "3535353535353535353535353535353535353535";
+        let sender = "6031564e7b2f5cc33737807b2e58daff870b590b";
+
+        let data = format!("a9059cbb{}{}", address_word(receiver), uint_word(10000));
+        let call = TokenCall::decode(&hex::decode(data).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            call,
+            TokenCall::Transfer {
+                to: Address::from_str(receiver).unwrap(),
+                amount: U256::from(10000),
+            }
+        );
+        assert_eq!(call.payment(), "transfer 10000");
+
+        let data = format!(
+            "f242432a{}{}{}{}{}{}",
+            address_word(sender),
+            address_word(receiver),
+            uint_word(7),
+            uint_word(3),
+            uint_word(0xa0),
+            uint_word(0)
+        );
+        let call = TokenCall::decode(&hex::decode(data).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(call.receiver(), Address::from_str(receiver).unwrap());
+        assert_eq!(call.payment(), "safeTransferFrom 3 of #7");
+
+        let data = format!("a22cb465{}{}", address_word(receiver), uint_word(0));
+        let call = TokenCall::decode(&hex::decode(data).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(call.payment(), "setApprovalForAll revoke all");
+
+        let data = format!("095ea7b3{}{}", address_word(receiver), "f".repeat(64));
+        let call = TokenCall::decode(&hex::decode(data).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(call.payment(), "approve unlimited");
+
+        //other calldata is not decoded
+        assert_eq!(TokenCall::decode(&[]).unwrap(), None);
+        assert_eq!(TokenCall::decode(&[0u8; 36]).unwrap(), None);
+    }
+
+    #[test]
+    fn decode_invalid_test() {
+        let receiver = "3535353535353535353535353535353535353535";
+        //missing amount
+        let data = format!("a9059cbb{}", address_word(receiver));
+        assert_eq!(
+            format!(
+                "{}",
+                TokenCall::decode(&hex::decode(data).unwrap())
+                    .err()
+                    .unwrap()
+            ),
+            "imkey_calldata_invalid"
+        );
+        //dirty address word
+        let data = format!("a9059cbb{}{}", "f".repeat(64), uint_word(1));
+        assert!(TokenCall::decode(&hex::decode(data).unwrap()).is_err());
+        //bool out of range
+        let data = format!("a22cb465{}{}", address_word(receiver), uint_word(2));
+        assert!(TokenCall::decode(&hex::decode(data).unwrap()).is_err());
+    }
+
+    #[test]
+    fn display_test() {
+        let contract = Address::from_str("dac17f958d2ee523a2206206994597c13d831ec7").unwrap();
+        let usdt = Token::find(1, &contract).unwrap();
+        assert_eq!(
+            usdt,
+            Token {
+                symbol: "USDT",
+                decimals: 6,
+            }
+        );
+        assert_eq!(Token::find(3, &contract), None);
+
+        let receivers = "0x3535353535353535353535353535353535353535, 0xdAC17F958D2ee523a2206206994597C13D831ec7";
+        let call = TokenCall::Transfer {
+            to: Address::from_str("3535353535353535353535353535353535353535").unwrap(),
+            amount: U256::from(10000),
+        };
+        assert_eq!(
+            call.display(&contract, Some(&usdt), "", "").unwrap(),
+            ("transfer 0.01 USDT".to_string(), receivers.to_string())
+        );
+        assert_eq!(
+            call.display(&contract, None, "", "").unwrap(),
+            ("transfer 10000".to_string(), receivers.to_string())
+        );
+        assert_eq!(
+            call.display(&contract, Some(&usdt), "transfer 0.010 usdt", receivers)
+                .unwrap()
+                .0,
+            "transfer 0.01 USDT"
+        );
+        assert!(call.display(&contract, None, "transfer 10,000", "").is_ok());
+        assert!(call
+            .display(&contract, None, "transfer 10,000 TKN", "")
+            .is_err());
+        //the amount is compared in the token's decimals, not by its digits
+        assert_eq!(
+            format!(
+                "{}",
+                call.display(&contract, Some(&usdt), "transfer 0.1 USDT", "")
+                    .err()
+                    .unwrap()
+            ),
+            "imkey_display_mismatch_with_data"
+        );
+        assert!(call
+            .display(&contract, Some(&usdt), "transfer 10,000 USDT", "")
+            .is_err());
+        assert!(call
+            .display(&contract, None, "transfer 0.01 USDT", "")
+            .is_err());
+        assert!(call
+            .display(&contract, Some(&usdt), "transfer USDT", "")
+            .is_err());
+        //the method has to be named
+        assert!(call
+            .display(&contract, Some(&usdt), "0.01 USDT", "")
+            .is_err());
+        assert!(call
+            .display(&contract, Some(&usdt), "approve 0.01 USDT", "")
+            .is_err());
+        //the contract has to be shown along with the receiver
+        assert!(call
+            .display(
+                &contract,
+                Some(&usdt),
+                "",
+                "0x3535353535353535353535353535353535353535"
+            )
+            .is_err());
+        assert!(call
+            .display(
+                &contract,
+                Some(&usdt),
+                "",
+                "0x6031564e7b2F5cc33737807b2E58DaFF870B590b, 0xdAC17F958D2ee523a2206206994597C13D831ec7"
+            )
+            .is_err());
+        //decimals the amounts cannot be shown in are refused
+        let token = Token {
+            symbol: "TKN",
+            decimals: 78,
+        };
+        assert!(call.display(&contract, Some(&token), "", "").is_err());
+
+        let call = TokenCall::Approve {
+            spender: Address::from_str("3535353535353535353535353535353535353535").unwrap(),
+            amount: U256::max_value(),
+        };
+        assert_eq!(
+            call.display(&contract, Some(&usdt), "", "").unwrap().0,
+            "approve unlimited USDT"
+        );
+        assert!(call
+            .display(&contract, Some(&usdt), "approve unlimited usdt", "")
+            .is_ok());
+        assert!(call
+            .display(&contract, Some(&usdt), "approve 1 USDT", "")
+            .is_err());
+
+        let call = TokenCall::SafeTransferFrom {
+            from: Address::from_str("6031564e7b2f5cc33737807b2e58daff870b590b").unwrap(),
+            to: Address::from_str("3535353535353535353535353535353535353535").unwrap(),
+            token_id: U256::from(10),
+        };
+        assert!(call
+            .display(&contract, None, "safeTransferFrom CryptoKitty #10", "")
+            .is_ok());
+        assert!(call
+            .display(&contract, None, "safeTransferFrom CryptoKitty #1", "")
+            .is_err());
+
+        let call = TokenCall::MultiTokenTransferFrom {
+            from: Address::from_str("6031564e7b2f5cc33737807b2e58daff870b590b").unwrap(),
+            to: Address::from_str("3535353535353535353535353535353535353535").unwrap(),
+            token_id: U256::from(7),
+            amount: U256::from(3),
+        };
+        assert!(call
+            .display(&contract, None, "safeTransferFrom 3 of #7", "")
+            .is_ok());
+        assert!(call
+            .display(&contract, None, "safeTransferFrom 3", "")
+            .is_err());
+
+        let call = TokenCall::SetApprovalForAll {
+            operator: Address::from_str("3535353535353535353535353535353535353535").unwrap(),
+            approved: true,
+        };
+        assert_eq!(
+            call.display(&contract, None, "setApprovalForAll approve all", "")
+                .unwrap()
+                .0,
+            "setApprovalForAll approve all"
+        );
+        assert!(call
+            .display(&contract, None, "setApprovalForAll revoke all", "")
+            .is_err());
+        assert!(call.display(&contract, None, "0 ETH", "").is_err());
+    }
+}
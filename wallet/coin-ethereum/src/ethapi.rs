@@ -24,6 +24,22 @@ pub struct EthTxReq {
     pub sender: std::string::String,
     #[prost(string, tag = "12")]
     pub fee: std::string::String,
+    /// "" or "0" legacy, "1" eip-2930 access list, "2" eip-1559 dynamic fee
+    #[prost(string, tag = "13")]
+    pub tx_type: std::string::String,
+    #[prost(string, tag = "14")]
+    pub max_fee_per_gas: std::string::String,
+    #[prost(string, tag = "15")]
+    pub max_priority_fee_per_gas: std::string::String,
+    #[prost(message, repeated, tag = "16")]
+    pub access_list: ::std::vec::Vec<AccessListItem>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AccessListItem {
+    #[prost(string, tag = "1")]
+    pub address: std::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub storage_keys: ::std::vec::Vec<std::string::String>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EthTxRes {
@@ -56,3 +72,15 @@ pub struct EthMessageSignRes {
     #[prost(string, tag = "1")]
     pub signature: std::string::String,
 }
+/// data is the json payload, version one of V0, V1, V3 and V4 (default)
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EthTypedDataSignReq {
+    #[prost(string, tag = "1")]
+    pub path: std::string::String,
+    #[prost(string, tag = "2")]
+    pub data: std::string::String,
+    #[prost(string, tag = "3")]
+    pub version: std::string::String,
+    #[prost(string, tag = "4")]
+    pub sender: std::string::String,
+}
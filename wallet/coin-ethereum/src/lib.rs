@@ -1,6 +1,8 @@
 pub mod address;
+pub mod calldata;
 pub mod ethapi;
 pub mod transaction;
+pub mod typed_data;
 pub mod types;
 
 #[macro_use]
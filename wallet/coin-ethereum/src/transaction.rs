@@ -1,20 +1,23 @@
 use crate::address::EthAddress;
-use crate::ethapi::{EthMessageSignReq, EthMessageSignRes, EthTxRes};
-use crate::types::{Action, Signature};
+use crate::calldata::{Token, TokenCall};
+use crate::ethapi::{EthMessageSignReq, EthMessageSignRes, EthTxRes, EthTypedDataSignReq};
+use crate::typed_data::{hash_typed_data, typed_data_summary, TypedDataVersion};
+use crate::types::{AccessListItem, Action, Signature, TransactionType};
 use crate::Result as EthResult;
 use common::apdu::{ApduCheck, CoinCommonApdu, EthApdu};
+use common::display::{display_tlv, Amount, DisplayInfo, DisplayInfoBuilder};
 use common::error::CoinError;
 use common::path::check_path_validity;
 use common::utility::{hex_to_bytes, is_valid_hex, secp256k1_sign};
 use common::{constants, utility};
-use device::device_binding::KEY_MANAGER;
+use device::device_binding::key_manager;
 use ethereum_types::{H256, U256};
 use keccak_hash::keccak;
 use lazy_static::lazy_static;
 use rlp::{self, DecoderError, Encodable, Rlp, RlpStream};
 use secp256k1::recovery::{RecoverableSignature, RecoveryId};
 use secp256k1::{self, Message as SecpMessage, Signature as SecpSignature};
-use transport::message::{send_apdu, send_apdu_timeout};
+use transport::Transport;
 
 lazy_static! {
     pub static ref SECP256K1: secp256k1::Secp256k1<secp256k1::All> = secp256k1::Secp256k1::new();
@@ -28,12 +31,19 @@ pub struct Transaction {
     pub to: Action,
     pub value: U256,
     pub data: Vec<u8>,
+    pub tx_type: TransactionType,
+    /// EIP-1559 fee cap and tip, dynamic fee transactions ignore gas_price
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+    /// EIP-2930 access list of typed transactions
+    pub access_list: Vec<AccessListItem>,
 }
 
 impl Transaction {
     /// Signs the transaction as coming from `sender`.
     pub fn sign(
         &self,
+        transport: &dyn Transport,
         chain_id: Option<u64>,
         path: &str,
         payment: &str,
@@ -44,6 +54,14 @@ impl Transaction {
         // ) {
         //check path
         check_path_validity(path)?;
+        //typed transactions always commit to a chain id
+        if self.tx_type != TransactionType::Legacy && chain_id.is_none() {
+            return Err(CoinError::ImkeySdkIllegalArgument.into());
+        }
+
+        //the device shows the strings derived from the transaction, the caller's ones only
+        //have to agree with them
+        let display = self.display_info(chain_id, payment, receiver, fee)?;
 
         //organize data
         let mut data_pack: Vec<u8> = Vec::new();
@@ -59,17 +77,13 @@ impl Transaction {
             .iter(),
         );
         data_pack.extend(encode_tx.iter());
-        //payment info in TLV format
-        data_pack.extend([7, payment.as_bytes().len() as u8].iter());
-        data_pack.extend(payment.as_bytes().iter());
-        //receiver info in TLV format
-        data_pack.extend([8, receiver.as_bytes().len() as u8].iter());
-        data_pack.extend(receiver.as_bytes().iter());
-        //fee info in TLV format
-        data_pack.extend([9, fee.as_bytes().len() as u8].iter());
-        data_pack.extend(fee.as_bytes().iter());
-
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
+        //payment, receiver and fee info in TLV format
+        data_pack.extend(display_tlv(7, &display.payment)?);
+        data_pack.extend(display_tlv(8, &display.receiver)?);
+        data_pack.extend(display_tlv(9, &display.fee)?);
+
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
         let bind_signature = secp256k1_sign(&key_manager_obj.pri_key, &data_pack).unwrap();
 
         let mut apdu_pack: Vec<u8> = Vec::new();
@@ -80,19 +94,19 @@ impl Transaction {
 
         //select applet
         let select_apdu = EthApdu::select_applet();
-        let select_result = send_apdu(select_apdu)?;
+        let select_result = transport.send_apdu(select_apdu)?;
         ApduCheck::checke_response(&select_result)?;
 
         //prepare apdu
         let msg_prepare = EthApdu::prepare_sign(apdu_pack);
         for msg in msg_prepare {
-            let res = send_apdu_timeout(msg, constants::TIMEOUT_LONG)?;
+            let res = transport.send_apdu_timeout(msg, constants::TIMEOUT_LONG)?;
             ApduCheck::checke_response(&res)?;
         }
 
         //get public
         let msg_pubkey = EthApdu::get_xpub(path, false);
-        let res_msg_pubkey = send_apdu(msg_pubkey)?;
+        let res_msg_pubkey = transport.send_apdu(msg_pubkey)?;
         ApduCheck::checke_response(&res_msg_pubkey)?;
 
         let pubkey_raw = hex_to_bytes(&res_msg_pubkey[..130]).unwrap();
@@ -105,7 +119,7 @@ impl Transaction {
         }
         //sign
         let msg_sign = EthApdu::sign_digest(path);
-        let res_msg_sign = send_apdu(msg_sign)?;
+        let res_msg_sign = transport.send_apdu(msg_sign)?;
         ApduCheck::checke_response(&res_msg_sign)?;
 
         let sign_compact = &res_msg_sign[2..130];
@@ -140,20 +154,24 @@ impl Transaction {
         Ok(tx_sign_result)
     }
 
+    /// The payload the device signs, typed transactions are prefixed with their type byte.
     pub fn rlp_encode_tx(&self, chain_id: Option<u64>) -> Vec<u8> {
         let mut stream = RlpStream::new();
         self.rlp_append_unsigned_transaction(&mut stream, chain_id);
-        stream.as_raw().to_vec()
+        with_type_byte(self.tx_type, stream.as_raw())
     }
 
     /// The message hash of the transaction.
     pub fn hash(&self, chain_id: Option<u64>) -> H256 {
-        let mut stream = RlpStream::new();
-        self.rlp_append_unsigned_transaction(&mut stream, chain_id);
-        keccak(stream.as_raw())
+        keccak(self.rlp_encode_tx(chain_id))
     }
 
     pub fn rlp_append_unsigned_transaction(&self, s: &mut RlpStream, chain_id: Option<u64>) {
+        if self.tx_type != TransactionType::Legacy {
+            s.begin_list(self.typed_field_count());
+            self.rlp_append_typed_fields(s, chain_id.unwrap_or_default());
+            return;
+        }
         s.begin_list(if chain_id.is_none() { 6 } else { 9 });
         s.append(&self.nonce);
         s.append(&self.gas_price);
@@ -168,20 +186,50 @@ impl Transaction {
         }
     }
 
+    fn typed_field_count(&self) -> usize {
+        match self.tx_type {
+            TransactionType::DynamicFee => 9,
+            _ => 8,
+        }
+    }
+
+    /// Fields of EIP-2930 and EIP-1559 payloads, without the signature.
+    fn rlp_append_typed_fields(&self, s: &mut RlpStream, chain_id: u64) {
+        s.append(&chain_id);
+        s.append(&self.nonce);
+        if self.tx_type == TransactionType::DynamicFee {
+            s.append(&self.max_priority_fee_per_gas);
+            s.append(&self.max_fee_per_gas);
+        } else {
+            s.append(&self.gas_price);
+        }
+        s.append(&self.gas_limit);
+        s.append(&self.to);
+        s.append(&self.value);
+        s.append(&self.data);
+        s.append_list(&self.access_list);
+    }
+
     pub fn with_signature(
         &self,
         sig: Signature,
         chain_id: Option<u64>,
     ) -> (Vec<u8>, UnverifiedTransaction) {
+        //typed transactions carry the y parity of the signature
+        let v = match self.tx_type {
+            TransactionType::Legacy => self.add_chain_replay_protection(sig.v() as u64, chain_id),
+            _ => sig.v() as u64,
+        };
         let unverified = UnverifiedTransaction {
             unsigned: self.clone(),
+            chain_id,
             r: sig.r().into(),
             s: sig.s().into(),
-            v: self.add_chain_replay_protection(sig.v() as u64, chain_id),
+            v,
             hash: H256::zero(),
         };
 
-        (unverified.rlp_bytes(), unverified.compute_hash())
+        (unverified.encode(), unverified.compute_hash())
     }
 
     pub fn add_chain_replay_protection(&self, v: u64, chain_id: Option<u64>) -> u64 {
@@ -192,7 +240,10 @@ impl Transaction {
         }
     }
 
-    pub fn sign_persional_message(input: EthMessageSignReq) -> EthResult<EthMessageSignRes> {
+    pub fn sign_persional_message(
+        transport: &dyn Transport,
+        input: EthMessageSignReq,
+    ) -> EthResult<EthMessageSignRes> {
         check_path_validity(&input.path).unwrap();
 
         let message = match is_valid_hex(&input.message){
@@ -207,22 +258,30 @@ impl Transaction {
         data.extend(header.as_bytes());
         data.extend(message);
 
-        Transaction::sign_message(&input.path,&data,&input.sender)
+        Transaction::sign_message(transport, &input.path,&data,&input.sender)
     }
 
 
-    pub fn ec_sign(input: EthMessageSignReq) -> EthResult<EthMessageSignRes> {
+    pub fn ec_sign(
+        transport: &dyn Transport,
+        input: EthMessageSignReq,
+    ) -> EthResult<EthMessageSignRes> {
         check_path_validity(&input.path).unwrap();
         let message = match is_valid_hex(&input.message){
             true => {let value = &input.message[2..];
             hex::decode(value).unwrap()}
             false => input.message.into_bytes()
         };
-        Transaction::sign_message(&input.path,&message,&input.sender)
+        Transaction::sign_message(transport, &input.path,&message,&input.sender)
     }
 
-    pub fn sign_message(path:&str,message:&[u8],sender:&str) -> EthResult<EthMessageSignRes> {
-        let mut data = message.to_vec();
+    pub fn sign_message(
+        transport: &dyn Transport,
+        path: &str,
+        message: &[u8],
+        sender: &str,
+    ) -> EthResult<EthMessageSignRes> {
+        let data = message.to_vec();
 
         let mut data_to_sign: Vec<u8> = Vec::new();
         data_to_sign.push(0x01);
@@ -230,21 +289,126 @@ impl Transaction {
         data_to_sign.push((data.len() & 0x00FF) as u8);
         data_to_sign.extend(data.as_slice());
 
-        let key_manager_obj = KEY_MANAGER.lock().unwrap();
-        let bind_signature = secp256k1_sign(&key_manager_obj.pri_key, &data_to_sign)?;
+        let data_hash = tiny_keccak::keccak256(&data);
+        Transaction::sign_prepared_digest(
+            transport,
+            path,
+            sender,
+            &data_to_sign,
+            &data_hash,
+            EthApdu::prepare_personal_sign,
+            EthApdu::personal_sign(path),
+        )
+    }
+
+    /// What the device shows, checked against the transaction: token calls against their
+    /// calldata, plain transfers against value and receiver, and the fee against the gas.
+    /// Token amounts are shown in their decimals only for the built-in tokens of the chain.
+    fn display_info(
+        &self,
+        chain_id: Option<u64>,
+        payment: &str,
+        receiver: &str,
+        fee: &str,
+    ) -> EthResult<DisplayInfo> {
+        let fee_info = DisplayInfoBuilder::new()
+            .fee(Amount::new(to_wei(self.max_fee()?)?, 18, "ETH"))
+            .verify("", "", fee)?;
+
+        let call = match self.to {
+            Action::Call(contract) => TokenCall::decode(&self.data)?.map(|call| (call, contract)),
+            Action::Create => None,
+        };
+        let (payment, receiver) = match call {
+            //the device would show the token side only
+            Some(_) if !self.value.is_zero() => {
+                return Err(CoinError::ImkeyDisplayMismatchWithData.into())
+            }
+            Some((call, contract)) => {
+                let token = chain_id.and_then(|chain_id| Token::find(chain_id, &contract));
+                call.display(&contract, token.as_ref(), payment, receiver)?
+            }
+            None => {
+                let mut builder =
+                    DisplayInfoBuilder::new().payment(Amount::new(to_wei(self.value)?, 18, "ETH"));
+                if let Action::Call(ref to) = self.to {
+                    builder =
+                        builder.receiver(&EthAddress::address_checksummed(&hex::encode(to)));
+                }
+                let display = builder.verify(payment, receiver, "")?;
+                (display.payment, display.receiver)
+            }
+        };
+        Ok(DisplayInfo {
+            payment,
+            receiver,
+            ..fee_info
+        })
+    }
+
+    /// The most the transaction can pay for gas.
+    fn max_fee(&self) -> EthResult<U256> {
+        let gas_price = match self.tx_type {
+            TransactionType::DynamicFee => self.max_fee_per_gas,
+            _ => self.gas_price,
+        };
+        match self.gas_limit.overflowing_mul(gas_price) {
+            (fee, false) => Ok(fee),
+            (_, true) => Err(CoinError::ImkeySdkIllegalArgument.into()),
+        }
+    }
+
+    /// Signs EIP-712 typed data, the device gets the digest and a summary to show.
+    pub fn sign_typed_data(
+        transport: &dyn Transport,
+        input: EthTypedDataSignReq,
+    ) -> EthResult<EthMessageSignRes> {
+        check_path_validity(&input.path)?;
+        let version = TypedDataVersion::from_name(&input.version)?;
+        let digest = hash_typed_data(&input.data, version)?;
+        let summary = typed_data_summary(&input.data, version)?;
+
+        //01, the 32 bytes digest, then 0A, length and the summary
+        let mut data_to_sign: Vec<u8> = vec![0x01, 0x00, 0x20];
+        data_to_sign.extend(&digest);
+        data_to_sign.extend(display_tlv(0x0A, &summary)?);
+
+        Transaction::sign_prepared_digest(
+            transport,
+            &input.path,
+            &input.sender,
+            &data_to_sign,
+            &digest,
+            EthApdu::prepare_typed_data_sign,
+            EthApdu::typed_data_sign(&input.path),
+        )
+    }
+
+    fn sign_prepared_digest(
+        transport: &dyn Transport,
+        path: &str,
+        sender: &str,
+        data_to_sign: &[u8],
+        digest: &[u8],
+        prepare_sign: fn(Vec<u8>) -> Vec<String>,
+        sign_apdu: String,
+    ) -> EthResult<EthMessageSignRes> {
+        let key_manager = key_manager(transport);
+        let key_manager_obj = key_manager.lock().unwrap();
+        let bind_signature = secp256k1_sign(&key_manager_obj.pri_key, data_to_sign)?;
 
         let mut apdu_pack: Vec<u8> = vec![];
         apdu_pack.push(0x00);
         apdu_pack.push(bind_signature.len() as u8);
         apdu_pack.extend(bind_signature.as_slice());
-        apdu_pack.extend(data_to_sign.as_slice());
+        apdu_pack.extend(data_to_sign);
 
         let select_apdu = EthApdu::select_applet();
-        let select_result = send_apdu(select_apdu)?;
+        let select_result = transport.send_apdu(select_apdu)?;
         ApduCheck::checke_response(&select_result)?;
 
         let msg_pubkey = EthApdu::get_xpub(path, false);
-        let res_msg_pubkey = send_apdu(msg_pubkey)?;
+        let res_msg_pubkey = transport.send_apdu(msg_pubkey)?;
         let pubkey_raw = hex_to_bytes(&res_msg_pubkey[..130]).unwrap();
         let address_main = EthAddress::address_from_pubkey(pubkey_raw.clone()).unwrap();
         let address_checksummed = EthAddress::address_checksummed(&address_main);
@@ -253,14 +417,13 @@ impl Transaction {
             return Err(CoinError::ImkeyAddressMismatchWithPath.into());
         }
 
-        let prepare_apdus = EthApdu::prepare_personal_sign(apdu_pack);
+        let prepare_apdus = prepare_sign(apdu_pack);
         for apdu in prepare_apdus {
-            let res = send_apdu_timeout(apdu, constants::TIMEOUT_LONG)?;
+            let res = transport.send_apdu_timeout(apdu, constants::TIMEOUT_LONG)?;
             ApduCheck::checke_response(&res)?;
         }
 
-        let sign_apdu = EthApdu::personal_sign(path);
-        let sign_response = send_apdu(sign_apdu)?;
+        let sign_response = transport.send_apdu(sign_apdu)?;
         ApduCheck::checke_response(&sign_response)?;
 
         let sign_compact = hex::decode(&sign_response[2..130]).unwrap();
@@ -268,8 +431,7 @@ impl Transaction {
         signnture_obj.normalize_s();
         let normalizes_sig_vec = signnture_obj.serialize_compact();
 
-        let data_hash = tiny_keccak::keccak256(&data);
-        let rec_id = utility::retrieve_recid(&data_hash, &normalizes_sig_vec, &pubkey_raw).unwrap();
+        let rec_id = utility::retrieve_recid(digest, &normalizes_sig_vec, &pubkey_raw).unwrap();
         let rec_id = rec_id.to_i32();
         let v = rec_id + 27;
 
@@ -284,6 +446,8 @@ impl Transaction {
 pub struct UnverifiedTransaction {
     /// Plain Transaction.
     unsigned: Transaction,
+    /// Chain id the transaction was signed for, part of typed payloads.
+    chain_id: Option<u64>,
     /// The V field of the signature; the LS bit described which half of the curve our point falls
     /// in. The MS bits describe which chain this transaction is for. If 27/28, its for all chains.
     v: u64,
@@ -302,9 +466,14 @@ impl rlp::Encodable for UnverifiedTransaction {
 }
 
 impl UnverifiedTransaction {
+    /// Signed transaction bytes, typed transactions are prefixed with their type byte.
+    pub fn encode(&self) -> Vec<u8> {
+        with_type_byte(self.unsigned.tx_type, &self.rlp_bytes())
+    }
+
     /// Used to compute hash of created transactions
     fn compute_hash(mut self) -> UnverifiedTransaction {
-        let hash = keccak(&*self.rlp_bytes());
+        let hash = keccak(self.encode());
         self.hash = hash;
         println!("hash:{}", &hex::encode(&hash));
         self
@@ -312,6 +481,15 @@ impl UnverifiedTransaction {
 
     /// Append object with a signature into RLP stream
     fn rlp_append_sealed_transaction(&self, s: &mut RlpStream) {
+        if self.unsigned.tx_type != TransactionType::Legacy {
+            s.begin_list(self.unsigned.typed_field_count() + 3);
+            self.unsigned
+                .rlp_append_typed_fields(s, self.chain_id.unwrap_or_default());
+            s.append(&self.v);
+            s.append(&self.r);
+            s.append(&self.s);
+            return;
+        }
         s.begin_list(9);
         s.append(&self.unsigned.nonce);
         s.append(&self.unsigned.gas_price);
@@ -325,6 +503,23 @@ impl UnverifiedTransaction {
     }
 }
 
+//wei amounts beyond u128 are not shown
+fn to_wei(amount: U256) -> EthResult<u128> {
+    if amount.bits() > 128 {
+        return Err(CoinError::ImkeySdkIllegalArgument.into());
+    }
+    Ok(amount.low_u128())
+}
+
+fn with_type_byte(tx_type: TransactionType, rlp: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![];
+    if let Some(type_byte) = tx_type.type_byte() {
+        encoded.push(type_byte);
+    }
+    encoded.extend_from_slice(rlp);
+    encoded
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -336,8 +531,16 @@ mod tests {
 
     #[test]
     fn test_sign_trans() {
-        bind_test();
+        let transport = bind_test();
+        check_sign_trans(&transport);
+    }
 
+    #[test]
+    fn test_sign_trans_simulator() {
+        check_sign_trans(&simulator::bind_test());
+    }
+
+    fn check_sign_trans(transport: &dyn Transport) {
         let tx = Transaction {
             nonce: U256::from(8),
             gas_price: U256::from(20000000008 as usize),
@@ -347,16 +550,17 @@ mod tests {
             ),
             value: U256::from(512 as usize),
             data: Vec::new(),
+            ..Default::default()
         };
 
         let path = "m/44'/60'/0'/0/0".to_string();
-        let payment = "0.01 ETH".to_string();
-        let receiver = "0xE6F4142dfFA574D1d9f18770BF73814df07931F3".to_string();
+        let payment = "0.000000000000000512 ETH".to_string();
+        let receiver = "0x3535353535353535353535353535353535353535".to_string();
         let sender = "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string();
-        let fee = "0.0032 ether".to_string();
+        let fee = "0.00378 ETH".to_string();
 
         let tx_result = tx
-            .sign(Some(28), &path, &payment, &receiver, &sender, &fee)
+            .sign(transport, Some(28), &path, &payment, &receiver, &sender, &fee)
             .unwrap();
         assert_eq!(
             tx_result.tx_data,
@@ -366,11 +570,30 @@ mod tests {
             tx_result.tx_hash,
             "0x09fa41c4d6b92482506c8c56f65b217cc3398821caec7695683110997426db01".to_string()
         );
+
+        //the device must not show what is not signed
+        let tx_result = tx.sign(transport, Some(28), &path, "0.01 ETH", &receiver, &sender, &fee);
+        assert_eq!(
+            format!("{}", tx_result.err().unwrap()),
+            "imkey_display_mismatch_with_data"
+        );
+        let tx_result = tx.sign(
+            transport,
+            Some(28),
+            &path,
+            &payment,
+            "0xE6F4142dfFA574D1d9f18770BF73814df07931F3",
+            &sender,
+            &fee,
+        );
+        assert!(tx_result.is_err());
+        let tx_result = tx.sign(transport, Some(28), &path, &payment, &receiver, &sender, "0.0001 ETH");
+        assert!(tx_result.is_err());
     }
 
     #[test]
     fn test_data_is_null() {
-        bind_test();
+        let transport = bind_test();
 
         let tx = Transaction {
             nonce: U256::from_dec_str("13").unwrap(),
@@ -381,15 +604,16 @@ mod tests {
             ),
             value: U256::from_dec_str("10000000000000000").unwrap(),
             data: Vec::new(),
+            ..Default::default()
         };
         let path = "m/44'/60'/0'/0/0".to_string();
         let payment = "0.01 ETH".to_string();
         let receiver = "0x7c47ef93268a311f4cad0c750724299e9b72c268".to_string();
         let sender = "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string();
-        let fee = "0.0032 ether".to_string();
+        let fee = "0.0032 ETH".to_string();
 
         let tx_result = tx
-            .sign(Some(28), &path, &payment, &receiver, &sender, &fee)
+            .sign(&transport, Some(28), &path, &payment, &receiver, &sender, &fee)
             .unwrap();
         assert_eq!(
             tx_result.tx_hash,
@@ -399,7 +623,7 @@ mod tests {
 
     #[test]
     fn test_data_is_long() {
-        bind_test();
+        let transport = bind_test();
 
         let data = "0x60056013565b6101918061001d6000396000f35b3360008190555056006001600060e060020a6000350480630a874df61461003a57806341c0e1b514610058578063a02b161e14610066578063dbbdf0831461007757005b610045600435610149565b80600160a060020a031660005260206000f35b610060610161565b60006000f35b6100716004356100d4565b60006000f35b61008560043560243561008b565b60006000f35b600054600160a060020a031632600160a060020a031614156100ac576100b1565b6100d0565b8060018360005260205260406000208190555081600060005260206000a15b5050565b600054600160a060020a031633600160a060020a031614158015610118575033600160a060020a0316600182600052602052604060002054600160a060020a031614155b61012157610126565b610146565b600060018260005260205260406000208190555080600060005260206000a15b50565b60006001826000526020526040600020549050919050565b600054600160a060020a031633600160a060020a0316146101815761018f565b600054600160a060020a0316ff5b56".to_string();
         let data_vec;
@@ -419,15 +643,16 @@ mod tests {
             ),
             value: U256::from_dec_str("10000000000000000").unwrap(),
             data: Vec::from(data_vec.as_slice()),
+            ..Default::default()
         };
         let path = "m/44'/60'/0'/0/0".to_string();
         let payment = "0.01 ETH".to_string();
         let receiver = "0x7c47ef93268a311f4cad0c750724299e9b72c268".to_string();
         let sender = "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string();
-        let fee = "0.0032 ether".to_string();
+        let fee = "0.0032 ETH".to_string();
 
         let tx_result = tx
-            .sign(Some(28), &path, &payment, &receiver, &sender, &fee)
+            .sign(&transport, Some(28), &path, &payment, &receiver, &sender, &fee)
             .unwrap();
         assert_eq!(
             tx_result.tx_hash,
@@ -437,7 +662,7 @@ mod tests {
 
     #[test]
     fn test_zero_bytes() {
-        bind_test();
+        let transport = bind_test();
 
         let data = "0x0000000000000000000000000000000000000000000000000000000000".to_string();
         let data_vec;
@@ -457,15 +682,16 @@ mod tests {
             ),
             value: U256::from_dec_str("10000000000000000").unwrap(),
             data: Vec::from(data_vec.as_slice()),
+            ..Default::default()
         };
         let path = "m/44'/60'/0'/0/0".to_string();
         let payment = "0.01 ETH".to_string();
         let receiver = "0x7c47ef93268a311f4cad0c750724299e9b72c268".to_string();
         let sender = "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string();
-        let fee = "0.0032 ether".to_string();
+        let fee = "0.0032 ETH".to_string();
 
         let tx_result = tx
-            .sign(Some(28), &path, &payment, &receiver, &sender, &fee)
+            .sign(&transport, Some(28), &path, &payment, &receiver, &sender, &fee)
             .unwrap();
         assert_eq!(
             tx_result.tx_hash,
@@ -475,14 +701,14 @@ mod tests {
 
     #[test]
     fn test_sign_personal_message() {
-        bind_test();
+        let transport = bind_test();
 
         let input = EthMessageSignReq {
             path: constants::ETH_PATH.to_string(),
             message: "Hello imKey".to_string(),
             sender: "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string(),
         };
-        let output = Transaction::sign_persional_message(input).unwrap();
+        let output = Transaction::sign_persional_message(&transport, input).unwrap();
         assert_eq!(
             output.signature,
             "d928f76ad80d63003c189b095078d94ae068dc2f18a5cafd97b3a630d7bc47465bd6f1e74de2e88c05b271e1c5a8b93564d9d8842c207482b20634d68f2d54e51b".to_string()
@@ -493,7 +719,7 @@ mod tests {
             message: "0x8d61d40bb0761526fe24d84199321d5e9f6542e56c52018c401b963d64ef21678c18563a3eba889229ab078a8a1baed22226913f".to_string(),
             sender: "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string(),
         };
-        let output = Transaction::sign_persional_message(input).unwrap();
+        let output = Transaction::sign_persional_message(&transport, input).unwrap();
         assert_eq!(
             output.signature,
             "35a94616ce12ddb79f6d351c2644c0fa2f496bd152b17102a5672359f583373b6dd5d2a60f5d9909cf84e6af7dc40176179c819a7cbd9b199f4c2e868530293f1b".to_string()
@@ -502,14 +728,14 @@ mod tests {
 
     #[test]
     fn test_ec_sign() {
-        bind_test();
+        let transport = bind_test();
 
         let input = EthMessageSignReq {
             path: constants::ETH_PATH.to_string(),
             message: "Hello imKey".to_string(),
             sender: "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string(),
         };
-        let output = Transaction::ec_sign(input).unwrap();
+        let output = Transaction::ec_sign(&transport, input).unwrap();
         assert_eq!(
             output.signature,
             "57c976d1fa15c7e833fd340bcb3a96974060ed555369d443449ac4429c1933433afa5304d1cfcb6799403f2b97a1e83309b98fae8ad5fade62335664d90e819f1b".to_string()
@@ -520,7 +746,7 @@ mod tests {
             message: "0x8d61d40bb0761526fe24d84199321d5e9f6542e56c52018c401b963d64ef21678c18563a3eba889229ab078a8a1baed22226913f".to_string(),
             sender: "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string(),
         };
-        let output = Transaction::ec_sign(input).unwrap();
+        let output = Transaction::ec_sign(&transport, input).unwrap();
         assert_eq!(
             output.signature,
             "3d8ba5e7375900476d715b479938e48a2e46e59f8e2e12673adb5e3df78a622050053ae0183f5e555e5db34ff43293de255f384709bd3fe6e00b8239c7f1a3561c".to_string()
@@ -529,17 +755,17 @@ mod tests {
 
     #[test]
     fn test_sign_message() {
-        bind_test();
+        let transport = bind_test();
 
         let message = b"Hello imKey";
-        let output = Transaction::sign_message(constants::ETH_PATH,message,"0x6031564e7b2F5cc33737807b2E58DaFF870B590b").unwrap();
+        let output = Transaction::sign_message(&transport, constants::ETH_PATH,message,"0x6031564e7b2F5cc33737807b2E58DaFF870B590b").unwrap();
         assert_eq!(
             output.signature,
             "57c976d1fa15c7e833fd340bcb3a96974060ed555369d443449ac4429c1933433afa5304d1cfcb6799403f2b97a1e83309b98fae8ad5fade62335664d90e819f1b".to_string()
         );
 
         let message = hex::decode("8d61d40bb0761526fe24d84199321d5e9f6542e56c52018c401b963d64ef21678c18563a3eba889229ab078a8a1baed22226913f").unwrap();
-        let output = Transaction::sign_message(constants::ETH_PATH,&message,"0x6031564e7b2F5cc33737807b2E58DaFF870B590b").unwrap();
+        let output = Transaction::sign_message(&transport, constants::ETH_PATH,&message,"0x6031564e7b2F5cc33737807b2E58DaFF870B590b").unwrap();
         assert_eq!(
             output.signature,
             "3d8ba5e7375900476d715b479938e48a2e46e59f8e2e12673adb5e3df78a622050053ae0183f5e555e5db34ff43293de255f384709bd3fe6e00b8239c7f1a3561c".to_string()
@@ -564,7 +790,7 @@ mod tests {
 
     #[test]
     fn test_address_mismatch() {
-        bind_test();
+        let transport = bind_test();
 
         let tx = Transaction {
             nonce: U256::from_dec_str("13").unwrap(),
@@ -575,14 +801,23 @@ mod tests {
             ),
             value: U256::from_dec_str("10000000000000000").unwrap(),
             data: Vec::new(),
+            ..Default::default()
         };
         let path = "m/44'/60'/0'/0/1".to_string();
         let payment = "0.01 ETH".to_string();
         let receiver = "0x7c47ef93268a311f4cad0c750724299e9b72c268".to_string();
         let sender = "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string();
-        let fee = "0.0032 ether".to_string();
-
-        let tx_result = tx.sign(Some(28), &path, &payment, &receiver, &sender, &fee);
+        let fee = "0.0032 ETH".to_string();
+
+        let tx_result = tx.sign(
+            &transport,
+            Some(28),
+            &path,
+            &payment,
+            &receiver,
+            &sender,
+            &fee,
+        );
         assert_eq!(
             format!("{}", tx_result.err().unwrap()),
             "imkey_address_mismatch_with_path"
@@ -593,10 +828,251 @@ mod tests {
             message: "Hello imKey".to_string(),
             sender: "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string(),
         };
-        let output = Transaction::sign_persional_message(input);
+        let output = Transaction::sign_persional_message(&transport, input);
         assert_eq!(
             format!("{}", output.err().unwrap()),
             "imkey_address_mismatch_with_path"
         );
     }
+
+    #[test]
+    fn test_typed_transaction_encode() {
+        let to =
+            Action::Call(Address::from_str("3535353535353535353535353535353535353535").unwrap());
+        let tx = Transaction {
+            nonce: U256::zero(),
+            gas_limit: U256::from(21000),
+            to: to.clone(),
+            tx_type: TransactionType::DynamicFee,
+            max_fee_per_gas: U256::from(2),
+            max_priority_fee_per_gas: U256::from(1),
+            ..Default::default()
+        };
+        //0x02 || rlp([chain_id, nonce, tip, fee cap, gas limit, to, value, data, access_list])
+        assert_eq!(
+            hex::encode(tx.rlp_encode_tx(Some(1))),
+            "02df018001028252089435353535353535353535353535353535353535358080c0"
+        );
+
+        let tx = Transaction {
+            nonce: U256::zero(),
+            gas_price: U256::from(1),
+            gas_limit: U256::from(21000),
+            to,
+            tx_type: TransactionType::AccessList,
+            access_list: vec![AccessListItem {
+                address: Address::from_str("3535353535353535353535353535353535353535").unwrap(),
+                storage_keys: vec![H256::zero()],
+            }],
+            ..Default::default()
+        };
+        //0x01 || rlp([chain_id, nonce, gas price, gas limit, to, value, data, access_list])
+        assert_eq!(
+            hex::encode(tx.rlp_encode_tx(Some(1))),
+            format!(
+                "01f85701800182520894{}8080f838f794{}e1a0{}",
+                "35".repeat(20),
+                "35".repeat(20),
+                "00".repeat(32)
+            )
+        );
+        assert_eq!(tx.hash(Some(1)), keccak(tx.rlp_encode_tx(Some(1))));
+    }
+
+    #[test]
+    fn test_sign_dynamic_fee_trans_simulator() {
+        let transport = simulator::bind_test();
+
+        let tx = Transaction {
+            nonce: U256::from(8),
+            gas_limit: U256::from(21000),
+            to: Action::Call(
+                Address::from_str("3535353535353535353535353535353535353535").unwrap(),
+            ),
+            value: U256::from(512 as usize),
+            tx_type: TransactionType::DynamicFee,
+            max_fee_per_gas: U256::from(40000000000 as u64),
+            max_priority_fee_per_gas: U256::from(1500000000),
+            ..Default::default()
+        };
+
+        let path = "m/44'/60'/0'/0/0".to_string();
+        let payment = "".to_string();
+        let receiver = "0x3535353535353535353535353535353535353535".to_string();
+        let sender = "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string();
+        let fee = "0.00084 ETH".to_string();
+
+        let tx_result = tx
+            .sign(&transport, Some(1), &path, &payment, &receiver, &sender, &fee)
+            .unwrap();
+        let tx_data = hex::decode(&tx_result.tx_data).unwrap();
+        assert_eq!(tx_data[0], 0x02);
+        assert_eq!(
+            tx_result.tx_hash,
+            format!("0x{}", hex::encode(keccak(&tx_data)))
+        );
+
+        //the unsigned fields followed by y parity, r and s
+        let rlp = Rlp::new(&tx_data[1..]);
+        assert_eq!(rlp.item_count().unwrap(), 12);
+        let y_parity: u8 = rlp.val_at(9).unwrap();
+        assert!(y_parity <= 1);
+        let r: U256 = rlp.val_at(10).unwrap();
+        let s: U256 = rlp.val_at(11).unwrap();
+        let mut compact = [0u8; 64];
+        r.to_big_endian(&mut compact[..32]);
+        s.to_big_endian(&mut compact[32..]);
+
+        let signature = RecoverableSignature::from_compact(
+            &compact,
+            RecoveryId::from_i32(y_parity as i32).unwrap(),
+        )
+        .unwrap();
+        let message = SecpMessage::from_slice(&tx.hash(Some(1))[..]).unwrap();
+        let public_key = SECP256K1.recover(&message, &signature).unwrap();
+        let address =
+            EthAddress::address_from_pubkey(public_key.serialize_uncompressed().to_vec()).unwrap();
+        assert_eq!(EthAddress::address_checksummed(&address), sender);
+
+        //a typed transaction needs a chain id
+        let tx_result = tx.sign(&transport, None, &path, &payment, &receiver, &sender, &fee);
+        assert_eq!(
+            format!("{}", tx_result.err().unwrap()),
+            "imkey_sdk_illegal_argument"
+        );
+    }
+
+    #[test]
+    fn test_sign_token_transfer_simulator() {
+        let transport = simulator::bind_test();
+
+        //transfer(0x3535..., 10000)
+        let data = hex::decode("a9059cbb00000000000000000000000035353535353535353535353535353535353535350000000000000000000000000000000000000000000000000000000000002710").unwrap();
+        let tx = Transaction {
+            nonce: U256::from(8),
+            gas_price: U256::from(20000000008 as u64),
+            gas_limit: U256::from(60000),
+            to: Action::Call(
+                Address::from_str("dac17f958d2ee523a2206206994597c13d831ec7").unwrap(),
+            ),
+            data,
+            ..Default::default()
+        };
+        let path = "m/44'/60'/0'/0/0".to_string();
+        //the contract is shown after the receiver
+        let receiver =
+            "0x3535353535353535353535353535353535353535, 0xdAC17F958D2ee523a2206206994597C13D831ec7"
+                .to_string();
+        let sender = "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string();
+        let fee = "0.0012 ETH".to_string();
+        let sign = |chain_id: u64, payment: &str, receiver: &str| {
+            tx.sign(
+                &transport,
+                Some(chain_id),
+                &path,
+                payment,
+                receiver,
+                &sender,
+                &fee,
+            )
+        };
+
+        assert!(sign(1, "transfer 0.01 USDT", &receiver).is_ok());
+        assert!(sign(1, "", "").is_ok());
+        //the contract is not a known token on other chains, only the raw amount can be shown
+        assert!(sign(3, "transfer 10000", &receiver).is_ok());
+
+        //the display strings must agree with the calldata
+        for (chain_id, payment, receiver) in &[
+            (1, "transfer 0.01 USDT", sender.as_str()),
+            (
+                1,
+                "transfer 0.01 USDT",
+                "0x3535353535353535353535353535353535353535",
+            ),
+            (1, "transfer 0.02 USDT", receiver.as_str()),
+            (1, "transfer 0.1 USDT", receiver.as_str()),
+            (1, "0.01 USDT", receiver.as_str()),
+            (1, "approve 0.01 USDT", receiver.as_str()),
+            (1, "transfer 0.01 DAI", receiver.as_str()),
+            (3, "transfer 0.01 USDT", receiver.as_str()),
+        ] {
+            assert_eq!(
+                format!("{}", sign(*chain_id, *payment, *receiver).err().unwrap()),
+                "imkey_display_mismatch_with_data"
+            );
+        }
+        let tx_result = Transaction {
+            value: U256::from(1),
+            ..tx.clone()
+        }
+        .sign(
+            &transport,
+            Some(1),
+            &path,
+            "transfer 0.01 USDT",
+            &receiver,
+            &sender,
+            &fee,
+        );
+        assert_eq!(
+            format!("{}", tx_result.err().unwrap()),
+            "imkey_display_mismatch_with_data"
+        );
+    }
+
+    #[test]
+    fn test_sign_typed_data_simulator() {
+        let transport = simulator::bind_test();
+        let sender = "0x6031564e7b2F5cc33737807b2E58DaFF870B590b".to_string();
+        let data = r#"{
+            "types": {
+                "EIP712Domain": [
+                    {"name": "name", "type": "string"},
+                    {"name": "chainId", "type": "uint256"}
+                ],
+                "Permit": [
+                    {"name": "spender", "type": "address"},
+                    {"name": "value", "type": "uint256"}
+                ]
+            },
+            "primaryType": "Permit",
+            "domain": {"name": "Token", "chainId": 1},
+            "message": {"spender": "0x3535353535353535353535353535353535353535", "value": "0x2710"}
+        }"#;
+        let input = EthTypedDataSignReq {
+            path: "m/44'/60'/0'/0/0".to_string(),
+            data: data.to_string(),
+            version: "V4".to_string(),
+            sender: sender.clone(),
+        };
+        let sign_result = Transaction::sign_typed_data(&transport, input.clone()).unwrap();
+
+        //rsv with v of 27 or 28, recovering the sender from the eip-712 digest
+        let signature = hex::decode(&sign_result.signature).unwrap();
+        assert_eq!(signature.len(), 65);
+        let signature = RecoverableSignature::from_compact(
+            &signature[..64],
+            RecoveryId::from_i32(signature[64] as i32 - 27).unwrap(),
+        )
+        .unwrap();
+        let digest = hash_typed_data(data, TypedDataVersion::V4).unwrap();
+        let message = SecpMessage::from_slice(&digest).unwrap();
+        let public_key = SECP256K1.recover(&message, &signature).unwrap();
+        let address =
+            EthAddress::address_from_pubkey(public_key.serialize_uncompressed().to_vec()).unwrap();
+        assert_eq!(EthAddress::address_checksummed(&address), sender);
+
+        let sign_result = Transaction::sign_typed_data(
+            &transport,
+            EthTypedDataSignReq {
+                sender: "0x3535353535353535353535353535353535353535".to_string(),
+                ..input
+            },
+        );
+        assert_eq!(
+            format!("{}", sign_result.err().unwrap()),
+            "imkey_address_mismatch_with_path"
+        );
+    }
 }
@@ -0,0 +1,598 @@
+use crate::Result;
+use common::error::CoinError;
+use ethereum_types::U256;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+use tiny_keccak::keccak256;
+
+//the device shows at most this many bytes of the summary
+const MAX_SUMMARY_LENGTH: usize = 255;
+//what an eip-2612 permit grants, its owner is the signer and its nonce tells the user nothing
+const PERMIT_FIELDS: [&str; 3] = ["spender", "value", "deadline"];
+
+/// Typed data flavours of eth_signTypedData.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypedDataVersion {
+    /// Legacy array of `{type, name, value}`, also what the unversioned V0 call signs.
+    V1,
+    /// EIP-712 without arrays, fields missing from the message are skipped.
+    V3,
+    /// EIP-712 with arrays and recursive structs.
+    V4,
+}
+
+impl TypedDataVersion {
+    pub fn from_name(version: &str) -> Result<TypedDataVersion> {
+        match version.to_uppercase().as_str() {
+            "V0" | "V1" => Ok(TypedDataVersion::V1),
+            "V3" => Ok(TypedDataVersion::V3),
+            "" | "V4" => Ok(TypedDataVersion::V4),
+            _ => Err(CoinError::ImkeyTypedDataVersionUnsupported.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    field_type: String,
+}
+
+type Types = BTreeMap<String, Vec<Field>>;
+
+/// The digest signed for typed data:
+/// V1 hashes the schema and the packed values, V3 and V4 follow EIP-712
+/// `keccak256(0x19 0x01 || domainSeparator || hashStruct(message))`.
+pub fn hash_typed_data(data: &str, version: TypedDataVersion) -> Result<[u8; 32]> {
+    let value: Value = serde_json::from_str(data).map_err(|_| CoinError::ImkeyTypedDataInvalid)?;
+    if version == TypedDataVersion::V1 {
+        return hash_legacy_typed_data(&value);
+    }
+
+    let types = parse_types(&value["types"])?;
+    let primary_type = value["primaryType"]
+        .as_str()
+        .ok_or(CoinError::ImkeyTypedDataInvalid)?;
+    let mut preimage = vec![0x19, 0x01];
+    preimage.extend(&hash_struct(
+        "EIP712Domain",
+        &value["domain"],
+        &types,
+        version,
+    )?);
+    if primary_type != "EIP712Domain" {
+        preimage.extend(&hash_struct(
+            primary_type,
+            &value["message"],
+            &types,
+            version,
+        )?);
+    }
+    Ok(keccak256(&preimage))
+}
+
+/// What the device shows before signing typed data: the domain, the primary type and the
+/// top level fields of the message, only the spender, value and deadline of a permit,
+/// or the named values of legacy typed data. A summary too long for the device is refused.
+pub fn typed_data_summary(data: &str, version: TypedDataVersion) -> Result<String> {
+    let value: Value = serde_json::from_str(data).map_err(|_| CoinError::ImkeyTypedDataInvalid)?;
+    let mut lines = vec![];
+    if version == TypedDataVersion::V1 {
+        for item in value.as_array().ok_or(CoinError::ImkeyTypedDataInvalid)? {
+            lines.push(format!(
+                "{}: {}",
+                item["name"].as_str().unwrap_or_default(),
+                display_value(&item["value"])
+            ));
+        }
+    } else {
+        for key in &["name", "version", "chainId", "verifyingContract"] {
+            if let Some(field) = value["domain"].get(key) {
+                lines.push(format!("{}: {}", key, display_value(field)));
+            }
+        }
+        let primary_type = value["primaryType"]
+            .as_str()
+            .ok_or(CoinError::ImkeyTypedDataInvalid)?;
+        lines.push(format!("primaryType: {}", primary_type));
+
+        let types = parse_types(&value["types"])?;
+        let message = &value["message"];
+        let is_permit = primary_type == "Permit" && message.get("spender").is_some();
+        if primary_type != "EIP712Domain" {
+            for field in types
+                .get(primary_type)
+                .ok_or(CoinError::ImkeyTypedDataInvalid)?
+            {
+                if is_permit && !PERMIT_FIELDS.contains(&field.name.as_str()) {
+                    continue;
+                }
+                if let Some(field_value) = message.get(&field.name) {
+                    lines.push(format!(
+                        "{}: {}",
+                        field.name,
+                        display_field(&field.field_type, field_value, &types)?
+                    ));
+                }
+            }
+        }
+    }
+
+    let summary = lines.join("\n");
+    if summary.len() > MAX_SUMMARY_LENGTH {
+        return Err(CoinError::ImkeyDisplayTooLong.into());
+    }
+    Ok(summary)
+}
+
+/// A message value as shown: structs as their values in parentheses, arrays in brackets and
+/// unsigned numbers in decimal.
+fn display_field(field_type: &str, value: &Value, types: &Types) -> Result<String> {
+    if let Some(fields) = types.get(field_type) {
+        let mut values = vec![];
+        for field in fields {
+            if let Some(field_value) = value.get(&field.name) {
+                values.push(display_field(&field.field_type, field_value, types)?);
+            }
+        }
+        return Ok(format!("({})", values.join(", ")));
+    }
+    if field_type.ends_with(']') {
+        let item_type = &field_type[..field_type.rfind('[').unwrap_or(0)];
+        let mut items = vec![];
+        for item in value.as_array().ok_or(CoinError::ImkeyTypedDataInvalid)? {
+            items.push(display_field(item_type, item, types)?);
+        }
+        return Ok(format!("[{}]", items.join(", ")));
+    }
+    if field_type.starts_with("uint") {
+        return Ok(parse_number(value, false)?.to_string());
+    }
+    Ok(display_value(value))
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        _ => value.to_string(),
+    }
+}
+
+fn parse_types(value: &Value) -> Result<Types> {
+    let mut types = Types::new();
+    for (name, fields) in value.as_object().ok_or(CoinError::ImkeyTypedDataInvalid)? {
+        let mut parsed_fields = vec![];
+        for field in fields.as_array().ok_or(CoinError::ImkeyTypedDataInvalid)? {
+            parsed_fields.push(Field {
+                name: field["name"]
+                    .as_str()
+                    .ok_or(CoinError::ImkeyTypedDataInvalid)?
+                    .to_string(),
+                field_type: field["type"]
+                    .as_str()
+                    .ok_or(CoinError::ImkeyTypedDataInvalid)?
+                    .to_string(),
+            });
+        }
+        types.insert(name.clone(), parsed_fields);
+    }
+    //a domain without fields still has a type
+    types.entry("EIP712Domain".to_string()).or_default();
+    Ok(types)
+}
+
+/// `Name(type name,...)` of the type followed by its dependencies in alphabetical order.
+fn encode_type(type_name: &str, types: &Types) -> Result<String> {
+    let mut dependencies = BTreeSet::new();
+    find_dependencies(type_name, types, &mut dependencies);
+    dependencies.remove(type_name);
+
+    let mut encoded = String::new();
+    for name in std::iter::once(type_name).chain(dependencies.iter().map(String::as_str)) {
+        let fields = types.get(name).ok_or(CoinError::ImkeyTypedDataInvalid)?;
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|field| format!("{} {}", field.field_type, field.name))
+            .collect();
+        encoded.push_str(&format!("{}({})", name, fields.join(",")));
+    }
+    Ok(encoded)
+}
+
+fn find_dependencies(type_name: &str, types: &Types, dependencies: &mut BTreeSet<String>) {
+    let base_type = type_name.split('[').next().unwrap_or(type_name);
+    if dependencies.contains(base_type) {
+        return;
+    }
+    if let Some(fields) = types.get(base_type) {
+        dependencies.insert(base_type.to_string());
+        for field in fields {
+            find_dependencies(&field.field_type, types, dependencies);
+        }
+    }
+}
+
+fn hash_struct(
+    type_name: &str,
+    data: &Value,
+    types: &Types,
+    version: TypedDataVersion,
+) -> Result<[u8; 32]> {
+    Ok(keccak256(&encode_data(type_name, data, types, version)?))
+}
+
+fn encode_data(
+    type_name: &str,
+    data: &Value,
+    types: &Types,
+    version: TypedDataVersion,
+) -> Result<Vec<u8>> {
+    let mut encoded = keccak256(encode_type(type_name, types)?.as_bytes()).to_vec();
+    for field in types
+        .get(type_name)
+        .ok_or(CoinError::ImkeyTypedDataInvalid)?
+    {
+        match data.get(&field.name) {
+            None if version == TypedDataVersion::V3 => continue,
+            value => encoded.extend(&encode_field(
+                &field.field_type,
+                value.unwrap_or(&Value::Null),
+                types,
+                version,
+            )?),
+        }
+    }
+    Ok(encoded)
+}
+
+fn encode_field(
+    field_type: &str,
+    value: &Value,
+    types: &Types,
+    version: TypedDataVersion,
+) -> Result<[u8; 32]> {
+    if types.contains_key(field_type) {
+        if value.is_null() {
+            return Ok([0u8; 32]);
+        }
+        return hash_struct(field_type, value, types, version);
+    }
+
+    if field_type.ends_with(']') {
+        if version != TypedDataVersion::V4 {
+            return Err(CoinError::ImkeyTypedDataVersionUnsupported.into());
+        }
+        let item_type = &field_type[..field_type.rfind('[').unwrap_or(0)];
+        let mut encoded = vec![];
+        for item in value.as_array().ok_or(CoinError::ImkeyTypedDataInvalid)? {
+            encoded.extend(&encode_field(item_type, item, types, version)?);
+        }
+        return Ok(keccak256(&encoded));
+    }
+
+    match field_type {
+        "string" => Ok(keccak256(
+            value
+                .as_str()
+                .ok_or(CoinError::ImkeyTypedDataInvalid)?
+                .as_bytes(),
+        )),
+        "bytes" => Ok(keccak256(&parse_bytes(value)?)),
+        _ => encode_atomic(field_type, value),
+    }
+}
+
+/// Abi word of an atomic value: numbers, addresses and bools are left padded, bytesN right padded.
+fn encode_atomic(field_type: &str, value: &Value) -> Result<[u8; 32]> {
+    let mut word = [0u8; 32];
+    if field_type == "address" {
+        let address = parse_bytes(value)?;
+        if address.len() != 20 {
+            return Err(CoinError::ImkeyTypedDataInvalid.into());
+        }
+        word[12..].copy_from_slice(&address);
+    } else if field_type == "bool" {
+        let flag = match value {
+            Value::Bool(flag) => *flag,
+            Value::String(flag) if flag == "true" || flag == "false" => flag == "true",
+            _ => return Err(CoinError::ImkeyTypedDataInvalid.into()),
+        };
+        word[31] = flag as u8;
+    } else if field_type.starts_with("bytes") {
+        let bytes = parse_bytes(value)?;
+        if bytes.len() > atomic_width(field_type)? {
+            return Err(CoinError::ImkeyTypedDataInvalid.into());
+        }
+        word[..bytes.len()].copy_from_slice(&bytes);
+    } else if field_type.starts_with("uint") {
+        atomic_width(field_type)?;
+        parse_number(value, false)?.to_big_endian(&mut word);
+    } else if field_type.starts_with("int") {
+        atomic_width(field_type)?;
+        parse_number(value, true)?.to_big_endian(&mut word);
+    } else {
+        return Err(CoinError::ImkeyTypedDataInvalid.into());
+    }
+    Ok(word)
+}
+
+/// Packed encoding of legacy typed data values, every atomic value takes its natural width.
+fn encode_packed(field_type: &str, value: &Value) -> Result<Vec<u8>> {
+    match field_type {
+        "string" => Ok(value
+            .as_str()
+            .ok_or(CoinError::ImkeyTypedDataInvalid)?
+            .as_bytes()
+            .to_vec()),
+        "bytes" => parse_bytes(value),
+        _ => {
+            let word = encode_atomic(field_type, value)?;
+            let width = atomic_width(field_type)?;
+            if field_type.starts_with("bytes") {
+                Ok(word[..width].to_vec())
+            } else {
+                Ok(word[32 - width..].to_vec())
+            }
+        }
+    }
+}
+
+fn atomic_width(field_type: &str) -> Result<usize> {
+    let (prefix, unit) = match field_type {
+        "address" => return Ok(20),
+        "bool" => return Ok(1),
+        _ if field_type.starts_with("bytes") => ("bytes", 1),
+        _ if field_type.starts_with("uint") => ("uint", 8),
+        _ if field_type.starts_with("int") => ("int", 8),
+        _ => return Err(CoinError::ImkeyTypedDataInvalid.into()),
+    };
+    let size = match &field_type[prefix.len()..] {
+        "" if unit == 8 => 256,
+        size => size
+            .parse::<usize>()
+            .map_err(|_| CoinError::ImkeyTypedDataInvalid)?,
+    };
+    if size == 0 || size % unit != 0 || size / unit > 32 {
+        return Err(CoinError::ImkeyTypedDataInvalid.into());
+    }
+    Ok(size / unit)
+}
+
+/// Numbers come as json numbers, decimal strings or 0x prefixed hex strings,
+/// negative values of signed types are returned in two's complement.
+fn parse_number(value: &Value, signed: bool) -> Result<U256> {
+    let (negative, magnitude) = match value {
+        Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(number), _) => (false, U256::from(number)),
+            (None, Some(number)) => (true, U256::from(number.wrapping_neg() as u64)),
+            _ => return Err(CoinError::ImkeyTypedDataInvalid.into()),
+        },
+        Value::String(number) => {
+            let negative = number.starts_with('-');
+            let digits = if negative { &number[1..] } else { &number[..] };
+            let hex_digits = digits.trim_start_matches("0x");
+            let magnitude = if hex_digits.len() != digits.len() {
+                U256::from_str(hex_digits).map_err(|_| CoinError::ImkeyTypedDataInvalid)?
+            } else {
+                U256::from_dec_str(digits).map_err(|_| CoinError::ImkeyTypedDataInvalid)?
+            };
+            (negative, magnitude)
+        }
+        _ => return Err(CoinError::ImkeyTypedDataInvalid.into()),
+    };
+    if !negative {
+        return Ok(magnitude);
+    }
+    if !signed {
+        return Err(CoinError::ImkeyTypedDataInvalid.into());
+    }
+    Ok((!magnitude).overflowing_add(U256::one()).0)
+}
+
+fn parse_bytes(value: &Value) -> Result<Vec<u8>> {
+    let hex_data = value.as_str().ok_or(CoinError::ImkeyTypedDataInvalid)?;
+    let hex_data = hex_data.trim_start_matches("0x");
+    Ok(hex::decode(hex_data).map_err(|_| CoinError::ImkeyTypedDataInvalid)?)
+}
+
+/// Legacy typed data: keccak256(keccak256(packed "type name" strings) || keccak256(packed values)).
+fn hash_legacy_typed_data(value: &Value) -> Result<[u8; 32]> {
+    let mut schema = vec![];
+    let mut data = vec![];
+    for item in value.as_array().ok_or(CoinError::ImkeyTypedDataInvalid)? {
+        let field_type = item["type"]
+            .as_str()
+            .ok_or(CoinError::ImkeyTypedDataInvalid)?;
+        let name = item["name"]
+            .as_str()
+            .ok_or(CoinError::ImkeyTypedDataInvalid)?;
+        schema.extend(format!("{} {}", field_type, name).as_bytes());
+        data.extend(encode_packed(field_type, &item["value"])?);
+    }
+    let mut hashes = keccak256(&schema).to_vec();
+    hashes.extend(&keccak256(&data));
+    Ok(keccak256(&hashes))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::typed_data::{hash_typed_data, typed_data_summary, TypedDataVersion};
+    use tiny_keccak::keccak256;
+
+    const MAIL_TYPED_DATA: &str = r#"{
+        "types": {
+            "EIP712Domain": [
+                {"name": "name", "type": "string"},
+                {"name": "version", "type": "string"},
+                {"name": "chainId", "type": "uint256"},
+                {"name": "verifyingContract", "type": "address"}
+            ],
+            "Person": [
+                {"name": "name", "type": "string"},
+                {"name": "wallet", "type": "address"}
+            ],
+            "Mail": [
+                {"name": "from", "type": "Person"},
+                {"name": "to", "type": "Person"},
+                {"name": "contents", "type": "string"}
+            ]
+        },
+        "primaryType": "Mail",
+        "domain": {
+            "name": "Ether Mail",
+            "version": "1",
+            "chainId": 1,
+            "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+        },
+        "message": {
+            "from": {"name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"},
+            "to": {"name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"},
+            "contents": "Hello, Bob!"
+        }
+    }"#;
+
+    #[test]
+    fn hash_typed_data_test() {
+        //the example of eip-712
+        let digest = hash_typed_data(MAIL_TYPED_DATA, TypedDataVersion::V4).unwrap();
+        assert_eq!(
+            hex::encode(digest),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+        //without arrays or missing fields v3 agrees with v4
+        let digest = hash_typed_data(MAIL_TYPED_DATA, TypedDataVersion::V3).unwrap();
+        assert_eq!(
+            hex::encode(digest),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+    }
+
+    #[test]
+    fn hash_typed_data_array_test() {
+        let typed_data = MAIL_TYPED_DATA
+            .replace(
+                r#"{"name": "to", "type": "Person"}"#,
+                r#"{"name": "to", "type": "Person[]"}"#,
+            )
+            .replace(
+                r#""to": {"name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"}"#,
+                r#""to": [{"name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"}]"#,
+            );
+        let digest = hash_typed_data(&typed_data, TypedDataVersion::V4).unwrap();
+        assert_ne!(
+            hex::encode(digest),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                hash_typed_data(&typed_data, TypedDataVersion::V3)
+                    .err()
+                    .unwrap()
+            ),
+            "imkey_typed_data_version_unsupported"
+        );
+    }
+
+    #[test]
+    fn hash_legacy_typed_data_test() {
+        let typed_data = r#"[
+            {"type": "string", "name": "message", "value": "Hi, Alice!"},
+            {"type": "uint8", "name": "value", "value": 10},
+            {"type": "int16", "name": "offset", "value": -2}
+        ]"#;
+        let schema_hash = keccak256(b"string messageuint8 valueint16 offset");
+        let data_hash = keccak256(b"Hi, Alice!\x0a\xff\xfe");
+        let digest = hash_typed_data(typed_data, TypedDataVersion::V1).unwrap();
+        assert_eq!(digest, keccak256(&[schema_hash, data_hash].concat()));
+        assert_eq!(
+            digest,
+            hash_typed_data(typed_data, TypedDataVersion::from_name("V0").unwrap()).unwrap()
+        );
+        assert_eq!(
+            "message: Hi, Alice!\nvalue: 10\noffset: -2",
+            typed_data_summary(typed_data, TypedDataVersion::V1).unwrap()
+        );
+    }
+
+    #[test]
+    fn typed_data_error_test() {
+        assert_eq!(
+            format!("{}", TypedDataVersion::from_name("V2").err().unwrap()),
+            "imkey_typed_data_version_unsupported"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                hash_typed_data("{}", TypedDataVersion::V4).err().unwrap()
+            ),
+            "imkey_typed_data_invalid"
+        );
+        let typed_data = MAIL_TYPED_DATA.replace(r#""chainId": 1"#, r#""chainId": "one""#);
+        assert!(hash_typed_data(&typed_data, TypedDataVersion::V4).is_err());
+    }
+
+    #[test]
+    fn typed_data_summary_test() {
+        assert_eq!(
+            "name: Ether Mail\nversion: 1\nchainId: 1\nverifyingContract: 0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC\nprimaryType: Mail\n\
+             from: (Cow, 0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826)\n\
+             to: (Bob, 0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB)\ncontents: Hello, Bob!",
+            typed_data_summary(MAIL_TYPED_DATA, TypedDataVersion::V4).unwrap()
+        );
+
+        //a longer message is refused rather than shown in part
+        let typed_data = MAIL_TYPED_DATA.replace("Hello, Bob!", "Hello, Bob! Hello, Bob!");
+        assert!(hash_typed_data(&typed_data, TypedDataVersion::V4).is_ok());
+        assert_eq!(
+            format!(
+                "{}",
+                typed_data_summary(&typed_data, TypedDataVersion::V4)
+                    .err()
+                    .unwrap()
+            ),
+            "imkey_display_too_long"
+        );
+    }
+
+    #[test]
+    fn permit_summary_test() {
+        let typed_data = r#"{
+            "types": {
+                "EIP712Domain": [
+                    {"name": "name", "type": "string"},
+                    {"name": "version", "type": "string"},
+                    {"name": "chainId", "type": "uint256"},
+                    {"name": "verifyingContract", "type": "address"}
+                ],
+                "Permit": [
+                    {"name": "owner", "type": "address"},
+                    {"name": "spender", "type": "address"},
+                    {"name": "value", "type": "uint256"},
+                    {"name": "nonce", "type": "uint256"},
+                    {"name": "deadline", "type": "uint256"}
+                ]
+            },
+            "primaryType": "Permit",
+            "domain": {
+                "name": "USD Coin",
+                "version": "2",
+                "chainId": 1,
+                "verifyingContract": "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"
+            },
+            "message": {
+                "owner": "0x6031564e7b2F5cc33737807b2E58DaFF870B590b",
+                "spender": "0x3535353535353535353535353535353535353535",
+                "value": "0xf4240",
+                "nonce": 0,
+                "deadline": "1893456000"
+            }
+        }"#;
+        assert_eq!(
+            "name: USD Coin\nversion: 2\nchainId: 1\nverifyingContract: 0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48\nprimaryType: Permit\n\
+             spender: 0x3535353535353535353535353535353535353535\nvalue: 1000000\ndeadline: 1893456000",
+            typed_data_summary(typed_data, TypedDataVersion::V4).unwrap()
+        );
+    }
+}
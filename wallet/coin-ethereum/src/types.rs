@@ -1,4 +1,4 @@
-use ethereum_types::Address;
+use ethereum_types::{Address, H256};
 use rlp::{self, DecoderError, Rlp, RlpStream};
 
 /// Transaction action type.
@@ -26,6 +26,49 @@ impl rlp::Encodable for Action {
     }
 }
 
+/// EIP-2718 transaction type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionType {
+    /// Pre EIP-2718 transaction, replay protected by EIP-155.
+    Legacy,
+    /// EIP-2930 transaction carrying an access list.
+    AccessList,
+    /// EIP-1559 transaction paying a base fee and a priority fee.
+    DynamicFee,
+}
+
+impl TransactionType {
+    /// The byte in front of the rlp payload, none for legacy transactions.
+    pub fn type_byte(&self) -> Option<u8> {
+        match *self {
+            TransactionType::Legacy => None,
+            TransactionType::AccessList => Some(0x01),
+            TransactionType::DynamicFee => Some(0x02),
+        }
+    }
+}
+
+impl Default for TransactionType {
+    fn default() -> TransactionType {
+        TransactionType::Legacy
+    }
+}
+
+/// EIP-2930 access list entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<H256>,
+}
+
+impl rlp::Encodable for AccessListItem {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2);
+        s.append(&self.address);
+        s.append_list(&self.storage_keys);
+    }
+}
+
 pub struct Signature(pub [u8; 65]);
 
 impl Signature {